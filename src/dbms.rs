@@ -1,3 +1,4 @@
 pub mod buffer;
+pub mod container;
 pub mod storage;
 pub mod types;
@@ -1,13 +1,14 @@
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::{Arc, RwLock, RwLockWriteGuard};
 
 use crate::dbms::buffer::replacer::BufferPoolReplacerError;
 use crate::dbms::buffer::types::{
     DiskManagerGeneric, PageGeneric, ReadOnlyPage, ReplacerGeneric, WritablePage,
 };
-use crate::dbms::storage::disk::DiskManagerError;
+use crate::dbms::storage::disk::{DiskManagerError, DiskScheduler, DEFAULT_DISK_WORKER_COUNT};
 use crate::dbms::storage::page::{Page, PageError};
-use crate::dbms::types::{PageId, PAGE_SIZE};
+use crate::dbms::types::{page_file_id, FileId, PageId, SYSTEM_FILE_ID};
 
 #[derive(Debug)]
 pub enum BufferPoolManagerError {
@@ -17,6 +18,8 @@ pub enum BufferPoolManagerError {
     PageNotInPool,
     /// A page is in use, e.g. when it's trying to be deleted
     PageInUse,
+    /// A page is being unpinned more times than it was pinned
+    PageNotPinned,
     ReplacerError(BufferPoolReplacerError),
     PageError(PageError),
     DiskManagerError(DiskManagerError),
@@ -42,25 +45,55 @@ impl From<DiskManagerError> for BufferPoolManagerError {
 
 pub trait IBufferPoolManager {
     /// Fetch the requested page as readable from the buffer pool.
-    fn fetch_page(&self, page_id: PageId) -> Result<ReadOnlyPage, BufferPoolManagerError>;
+    fn fetch_page(&self, page_id: PageId) -> Result<ReadOnlyPage<'_>, BufferPoolManagerError>;
     /// Fetch the requested page as writable from the buffer pool.
-    fn fetch_page_writable(&self, page_id: PageId) -> Result<WritablePage, BufferPoolManagerError>;
+    fn fetch_page_writable(
+        &self,
+        page_id: PageId,
+    ) -> Result<WritablePage<'_>, BufferPoolManagerError>;
     /// Creates a new page in the buffer pool, returning it as writable.
-    fn new_page(&self) -> Result<WritablePage, BufferPoolManagerError>;
-    /// Unpin the target page from the buffer pool.
+    fn new_page(&self) -> Result<WritablePage<'_>, BufferPoolManagerError>;
+    /// Creates a new page in the given file, returning it as writable.
+    fn new_page_in(&self, file_id: FileId) -> Result<WritablePage<'_>, BufferPoolManagerError>;
+    /// Unpin the target page from the buffer pool. Unpinning a page more
+    /// times than it's been pinned is an error.
     fn unpin_page(&self, page_id: PageId, mark_dirty: bool) -> Result<(), BufferPoolManagerError>;
     /// Flushes the target page to disk.
     fn flush_page(&self, page_id: PageId) -> Result<(), BufferPoolManagerError>;
     /// Deletes a page from the buffer pool.
     fn delete_page(&self, page_id: PageId) -> Result<(), BufferPoolManagerError>;
-    /// Flushes all the pages in the buffer pool to disk.
+    /// Flushes all the pages in the buffer pool to disk, and makes them
+    /// durable.
     fn flush_all_pages(&self) -> Result<(), BufferPoolManagerError>;
+    /// Adds a new, empty file to the database, returning its ID.
+    fn create_file(&self) -> Result<FileId, BufferPoolManagerError>;
+    /// Drops a file and every page in it. None of its pages can be in use.
+    fn drop_file(&self, file_id: FileId) -> Result<(), BufferPoolManagerError>;
+    /// Size of every page in the pool, chosen when the database was created.
+    fn page_size(&self) -> usize;
+}
+
+/// Bookkeeping for a frame that's kept outside of the page latch, so pinning
+/// and unpinning a page never has to wait for whoever is using it.
+#[derive(Default)]
+struct FrameMetadata {
+    pin_count: AtomicUsize,
+    is_dirty: AtomicBool,
+}
+
+/// A frame that's been picked to load a page into, along with the locks
+/// that have to be held to change which page is in it.
+struct ReservedFrame<'a> {
+    frame_id: usize,
+    page_table: RwLockWriteGuard<'a, HashMap<PageId, usize>>,
+    replacer: RwLockWriteGuard<'a, ReplacerGeneric>,
+    page: WritablePage<'a>,
 }
 
 #[derive(Clone)]
 pub struct BufferPoolManager {
     replacer: Arc<RwLock<ReplacerGeneric>>,
-    disk_manager: Arc<RwLock<DiskManagerGeneric>>,
+    disk_scheduler: Arc<DiskScheduler>,
     /// page_id -> frame_id
     // Latch on the whole hashmap
     page_table: Arc<RwLock<HashMap<PageId, usize>>>,
@@ -68,8 +101,23 @@ pub struct BufferPoolManager {
     free_frames: Arc<RwLock<Vec<usize>>>,
     // N.B. Latch on each individual page, not the array itself
     pages: Arc<Vec<RwLock<PageGeneric>>>,
+    // Pin counts and dirty flags for each frame
+    frames: Arc<Vec<FrameMetadata>>,
+    page_size: usize,
 }
 
+// Lock ordering, to avoid deadlocks between threads:
+//   page_table -> replacer -> page latch -> free_frames
+// A page's latch is only taken while holding `page_table` if the page is
+// unpinned, so nobody else should be holding it. Anything that holds a latch
+// while waiting on the disk keeps the page pinned for it, e.g. a flush. The
+// reverse is only done for a frame nobody else can reach: one that's pinned,
+// or that's out of the page table, the replacer and the free list.
+//
+// Disk I/O goes through the disk scheduler, and is only waited on while
+// holding the latch of the page being read or written, so the rest of the
+// pool carries on in the meantime.
+
 impl BufferPoolManager {
     #[allow(dead_code)]
     pub fn new(
@@ -77,18 +125,21 @@ impl BufferPoolManager {
         replacer: ReplacerGeneric,
         disk_manager: DiskManagerGeneric,
     ) -> BufferPoolManager {
+        let page_size = disk_manager.page_size();
         BufferPoolManager {
             replacer: Arc::new(RwLock::new(replacer)),
-            disk_manager: Arc::new(RwLock::new(disk_manager)),
+            disk_scheduler: Arc::new(DiskScheduler::new(disk_manager, DEFAULT_DISK_WORKER_COUNT)),
             page_table: Arc::new(RwLock::new(HashMap::new())),
             // All frames are free
             free_frames: Arc::new(RwLock::new((0..pool_size).collect())),
             // Fill frames with uninitialized pages with no page IDs
             pages: Arc::new(
                 (0..pool_size)
-                    .map(|_| RwLock::new(Box::new(Page::new(None)) as PageGeneric))
+                    .map(|_| RwLock::new(Box::new(Page::new(None, page_size)) as PageGeneric))
                     .collect(),
             ),
+            frames: Arc::new((0..pool_size).map(|_| FrameMetadata::default()).collect()),
+            page_size,
         }
     }
 
@@ -107,11 +158,12 @@ impl BufferPoolManager {
         }
     }
 
-    /// Write a page to disk
+    /// Write a page to disk, waiting for the write to finish. The page is
+    /// left dirty if it fails.
     fn write_page(
         &self,
+        frame_id: usize,
         page: &mut RwLockWriteGuard<PageGeneric>,
-        disk_manager: &mut RwLockWriteGuard<DiskManagerGeneric>,
     ) -> Result<(), BufferPoolManagerError> {
         let page_id = match page.get_page_id() {
             Ok(Some(id)) => id,
@@ -120,161 +172,351 @@ impl BufferPoolManager {
         };
 
         let page_data = page.get_data()?;
-        disk_manager.write_page(page_id, &page_data)?;
+        self.disk_scheduler
+            .schedule_write(page_id, &page_data)
+            .wait()?;
         page.set_clean()?;
+        self.frames[frame_id]
+            .is_dirty
+            .store(false, Ordering::SeqCst);
 
         Ok(())
     }
 
-    /// Write a page to disk if it's dirty
-    fn write_if_dirty(
+    fn is_dirty(
         &self,
-        page: &mut RwLockWriteGuard<PageGeneric>,
-        disk_manager: &mut RwLockWriteGuard<DiskManagerGeneric>,
+        frame_id: usize,
+        page: &RwLockWriteGuard<PageGeneric>,
+    ) -> Result<bool, BufferPoolManagerError> {
+        Ok(page.is_dirty()? || self.frames[frame_id].is_dirty.load(Ordering::SeqCst))
+    }
+
+    /// Pick a frame to load a page into, from the free list or the replacer,
+    /// and return it with the page table and replacer locked and the frame's
+    /// page latched. Whatever page the frame holds is clean, and still in
+    /// the page table.
+    ///
+    /// A dirty page is written back without holding the page table or
+    /// replacer, so the rest of the pool isn't held up by the write. If it's
+    /// been used again by the time the write's done, it's handed back to the
+    /// replacer and another frame is picked. If it can't be written back it
+    /// stays in the pool, still dirty.
+    fn reserve_frame(&self) -> Result<ReservedFrame<'_>, BufferPoolManagerError> {
+        let mut page_table = self.page_table.write().unwrap();
+        let mut replacer = self.replacer.write().unwrap();
+        loop {
+            let frame_id = self.get_freeable_frame_id(&mut replacer)?;
+            let mut page = self.pages[frame_id].write().unwrap();
+
+            let page_id = match page.get_page_id()? {
+                Some(page_id) if self.is_dirty(frame_id, &page)? => page_id,
+                _ => {
+                    return Ok(ReservedFrame {
+                        frame_id,
+                        page_table,
+                        replacer,
+                        page,
+                    })
+                }
+            };
+
+            // The frame's out of the replacer, so nobody else can pick it,
+            // and anyone fetching its page waits on the latch for the write
+            drop(replacer);
+            drop(page_table);
+            let written = self.write_page(frame_id, &mut page);
+            drop(page);
+
+            page_table = self.page_table.write().unwrap();
+            replacer = self.replacer.write().unwrap();
+            let unused = page_table.get(&page_id) == Some(&frame_id)
+                && self.frames[frame_id].pin_count.load(Ordering::SeqCst) == 0;
+            if let Err(e) = written {
+                if unused {
+                    replacer.unpin(frame_id)?;
+                }
+                return Err(e);
+            }
+            if unused {
+                let page = self.pages[frame_id].write().unwrap();
+                if !self.is_dirty(frame_id, &page)? {
+                    // It could have been pinned and unpinned in the meantime,
+                    // putting it back in the replacer
+                    replacer.pin(frame_id)?;
+                    return Ok(ReservedFrame {
+                        frame_id,
+                        page_table,
+                        replacer,
+                        page,
+                    });
+                }
+                replacer.unpin(frame_id)?;
+            }
+        }
+    }
+
+    /// Give a reserved frame back, without having used it
+    fn release_frame(
+        &self,
+        frame_id: usize,
+        page: &RwLockWriteGuard<PageGeneric>,
+        replacer: &mut RwLockWriteGuard<ReplacerGeneric>,
     ) -> Result<(), BufferPoolManagerError> {
-        let page_dirty = page.is_dirty()?;
+        if page.get_page_id()?.is_some() {
+            replacer.unpin(frame_id)?;
+        } else {
+            self.free_frames.write().unwrap().push(frame_id);
+        }
+        Ok(())
+    }
 
-        if page_dirty {
-            self.write_page(page, disk_manager)?;
+    /// Remove a frame's current page, which must be clean, from the page
+    /// table, leaving the frame empty.
+    fn evict_frame(
+        &self,
+        page_table: &mut RwLockWriteGuard<HashMap<PageId, usize>>,
+        page: &mut RwLockWriteGuard<PageGeneric>,
+    ) -> Result<(), BufferPoolManagerError> {
+        if let Some(old_page_id) = page.get_page_id()? {
+            page_table.remove(&old_page_id);
         }
 
+        page.clear()?;
+        Ok(())
+    }
+
+    /// Hand an empty frame back to the free list, e.g. when loading a page
+    /// into it failed.
+    fn free_frame(
+        &self,
+        frame_id: usize,
+        page: &mut RwLockWriteGuard<PageGeneric>,
+    ) -> Result<(), BufferPoolManagerError> {
+        page.clear()?;
+        self.frames[frame_id]
+            .is_dirty
+            .store(false, Ordering::SeqCst);
+        self.free_frames.write().unwrap().push(frame_id);
         Ok(())
     }
 
-    /// Flush a frame to disk and prep it for a new page
-    fn swap_frame(
+    /// Record a freshly loaded page in the page table, pinned once.
+    fn pin_new_frame(
         &self,
         frame_id: usize,
-        new_page_id: PageId,
-        disk_manager: &mut RwLockWriteGuard<DiskManagerGeneric>,
+        page_id: PageId,
         replacer: &mut RwLockWriteGuard<ReplacerGeneric>,
         page_table: &mut RwLockWriteGuard<HashMap<PageId, usize>>,
-        page: &mut RwLockWriteGuard<PageGeneric>,
     ) -> Result<(), BufferPoolManagerError> {
-        if let Some(old_page_id) = page.get_page_id().unwrap() {
-            self.write_if_dirty(page, disk_manager)?;
+        page_table.insert(page_id, frame_id);
+        self.frames[frame_id].pin_count.store(1, Ordering::SeqCst);
+        self.frames[frame_id]
+            .is_dirty
+            .store(false, Ordering::SeqCst);
+        replacer.pin(frame_id)?;
+        replacer.record_access(frame_id, page_id)?;
+        Ok(())
+    }
 
-            page_table.remove(&old_page_id);
+    /// Pin a page that's already in the pool, if it is
+    fn pin_if_present(
+        &self,
+        page_id: PageId,
+        page_table: &RwLockWriteGuard<HashMap<PageId, usize>>,
+        replacer: &mut RwLockWriteGuard<ReplacerGeneric>,
+    ) -> Result<Option<usize>, BufferPoolManagerError> {
+        if let Some(&frame_id) = page_table.get(&page_id) {
+            self.frames[frame_id]
+                .pin_count
+                .fetch_add(1, Ordering::SeqCst);
+            replacer.pin(frame_id)?;
+            replacer.record_access(frame_id, page_id)?;
+            return Ok(Some(frame_id));
         }
+        Ok(None)
+    }
 
-        page_table.insert(new_page_id, frame_id);
-        replacer.pin(frame_id)?;
+    /// Pin a page that's in the pool without counting it as an access, so it
+    /// can't be evicted or deleted while it's being flushed
+    fn pin_for_flush(&self, page_id: PageId) -> Result<Option<usize>, BufferPoolManagerError> {
+        let page_table = self.page_table.write().unwrap();
+        let Some(&frame_id) = page_table.get(&page_id) else {
+            return Ok(None);
+        };
+        self.frames[frame_id]
+            .pin_count
+            .fetch_add(1, Ordering::SeqCst);
+        self.replacer.write().unwrap().pin(frame_id)?;
+        Ok(Some(frame_id))
+    }
 
-        Ok(())
+    /// Drop a pin on a frame a page couldn't be read into. The page is taken
+    /// out of the page table, and whoever drops the last pin frees the frame.
+    fn abandon_frame(&self, frame_id: usize, page_id: PageId) {
+        let mut page_table = self.page_table.write().unwrap();
+        if page_table.get(&page_id) == Some(&frame_id) {
+            page_table.remove(&page_id);
+        }
+
+        let pin_count = self.frames[frame_id]
+            .pin_count
+            .fetch_sub(1, Ordering::SeqCst)
+            - 1;
+        if pin_count == 0 {
+            // Still pinned in the replacer, as frames on the free list must be
+            self.frames[frame_id]
+                .is_dirty
+                .store(false, Ordering::SeqCst);
+            self.free_frames.write().unwrap().push(frame_id);
+        }
     }
 
-    /// Fetch a page, from disk if needed, and return its frame ID
+    /// Fetch a page, from disk if needed, and return its frame ID.
+    ///
+    /// A page being read in is in the page table from the start, with its
+    /// frame latched until the read finishes, so anyone else fetching it
+    /// waits on the latch rather than reading it in again. If the read fails
+    /// the frame is left empty, and they have to try again.
     fn fetch_page_frame(&self, page_id: PageId) -> Result<usize, BufferPoolManagerError> {
         // 1.     Search the page table for the requested page (P).
-        let mut page_table = self.page_table.write().unwrap();
-        let mut replacer = self.replacer.write().unwrap();
-
-        if let Some(&frame_id) = page_table.get(&page_id) {
+        {
+            let page_table = self.page_table.write().unwrap();
+            let mut replacer = self.replacer.write().unwrap();
             // 1.1    If P exists, pin it and return it immediately.
-            {
-                let mut page = self.pages[frame_id].write().unwrap();
-                page.increase_pin_count().unwrap();
-                replacer.pin(frame_id).unwrap();
+            if let Some(frame_id) = self.pin_if_present(page_id, &page_table, &mut replacer)? {
+                return Ok(frame_id);
             }
-            return Ok(frame_id);
         }
 
         // 1.2    If P does not exist, find a replacement page (R) from either the free list or the replacer.
         //        Note that pages are always found from the free list first.
-        let new_frame_id = self.get_freeable_frame_id(&mut replacer).unwrap();
-
-        {
-            let mut disk_manager = self.disk_manager.write().unwrap();
-            let mut page_to_overwrite = self.pages[new_frame_id].write().unwrap();
-
-            // 2.     If R is dirty, write it back to the disk.
-            // 3.     Delete R from the page table and insert P.
-            // 4.     Update P's metadata, read in the page content from disk, and then return a pointer to P.
-            self.swap_frame(
-                new_frame_id,
-                page_id,
-                &mut disk_manager,
-                &mut replacer,
-                &mut page_table,
-                &mut page_to_overwrite,
-            )?;
+        // 2.     If R is dirty, write it back to the disk.
+        let ReservedFrame {
+            frame_id,
+            mut page_table,
+            mut replacer,
+            mut page,
+        } = self.reserve_frame()?;
+
+        // P could have been read in by someone else while R was written back
+        if let Some(existing_frame_id) = self.pin_if_present(page_id, &page_table, &mut replacer)? {
+            self.release_frame(frame_id, &page, &mut replacer)?;
+            return Ok(existing_frame_id);
+        }
 
-            let new_page_data = disk_manager.read_page(page_id)?;
-            page_to_overwrite.overwrite(Some(page_id), new_page_data)?;
+        // 3.     Delete R from the page table and insert P.
+        self.evict_frame(&mut page_table, &mut page)?;
+        self.pin_new_frame(frame_id, page_id, &mut replacer, &mut page_table)?;
+        drop(replacer);
+        drop(page_table);
+
+        // 4.     Update P's metadata, read in the page content from disk, and then return a pointer to P.
+        match self.disk_scheduler.schedule_read(page_id).wait() {
+            Ok(data) => {
+                page.overwrite(Some(page_id), data)?;
+                Ok(frame_id)
+            }
+            Err(e) => {
+                drop(page);
+                self.abandon_frame(frame_id, page_id);
+                Err(e.into())
+            }
         }
+    }
 
-        Ok(new_frame_id)
+    /// Fetch a page and latch it with `latch`, reading it in again if someone
+    /// else's attempt to read it in failed while waiting for the latch.
+    fn fetch_latched_page<'a, G>(
+        &'a self,
+        page_id: PageId,
+        latch: impl Fn(&'a RwLock<PageGeneric>) -> G,
+    ) -> Result<G, BufferPoolManagerError>
+    where
+        G: std::ops::Deref<Target = PageGeneric>,
+    {
+        loop {
+            let frame_id = self.fetch_page_frame(page_id)?;
+            let page = latch(&self.pages[frame_id]);
+            if page.get_page_id()? == Some(page_id) {
+                return Ok(page);
+            }
+            drop(page);
+            self.abandon_frame(frame_id, page_id);
+        }
     }
 }
 
 impl IBufferPoolManager for BufferPoolManager {
-    fn fetch_page(&self, page_id: PageId) -> Result<ReadOnlyPage, BufferPoolManagerError> {
-        let frame_id = self.fetch_page_frame(page_id)?;
-        Ok(self.pages[frame_id].read().unwrap())
+    fn fetch_page(&self, page_id: PageId) -> Result<ReadOnlyPage<'_>, BufferPoolManagerError> {
+        self.fetch_latched_page(page_id, |page| page.read().unwrap())
     }
 
-    fn fetch_page_writable(&self, page_id: PageId) -> Result<WritablePage, BufferPoolManagerError> {
-        let frame_id = self.fetch_page_frame(page_id)?;
-        Ok(self.pages[frame_id].write().unwrap())
+    fn fetch_page_writable(
+        &self,
+        page_id: PageId,
+    ) -> Result<WritablePage<'_>, BufferPoolManagerError> {
+        self.fetch_latched_page(page_id, |page| page.write().unwrap())
     }
 
-    fn new_page(&self) -> Result<WritablePage, BufferPoolManagerError> {
-        let mut disk_manager = self.disk_manager.write().unwrap();
-        let mut page_table = self.page_table.write().unwrap();
-        let mut replacer = self.replacer.write().unwrap();
+    fn new_page(&self) -> Result<WritablePage<'_>, BufferPoolManagerError> {
+        self.new_page_in(SYSTEM_FILE_ID)
+    }
 
+    fn new_page_in(&self, file_id: FileId) -> Result<WritablePage<'_>, BufferPoolManagerError> {
         // 1.   If all the pages in the buffer pool are pinned, return nullptr.
-        {
-            let pinless_page = self
-                .pages
-                .iter()
-                .find(|page| page.read().unwrap().get_pin_count().unwrap() == 0);
-            match pinless_page {
-                Some(_) => {}
-                None => {
-                    return Err(BufferPoolManagerError::NoFrameAvailable);
-                }
-            };
-        };
-
-        // 0.   Make sure you call DiskManager::AllocatePage!
-        let new_page_id = disk_manager.allocate_page()?;
-
         // 2.   Pick a victim page P from either the free list or the replacer. Always pick from the free list first.
-        let frame_id = self.get_freeable_frame_id(&mut replacer)?;
-        let mut page_to_overwrite = self.pages[frame_id].write().unwrap();
-        self.swap_frame(
+        let ReservedFrame {
             frame_id,
-            new_page_id,
-            &mut disk_manager,
-            &mut replacer,
-            &mut page_table,
-            &mut page_to_overwrite,
-        )?;
+            mut page_table,
+            replacer,
+            mut page,
+        } = self.reserve_frame()?;
+        self.evict_frame(&mut page_table, &mut page)?;
+        drop(replacer);
+        drop(page_table);
+
+        // 0.   Make sure you call DiskManager::AllocatePage!
+        // The frame is out of the page table, replacer and free list while
+        // the page is allocated, so nobody else can get at it
+        let new_page_id = match self.disk_scheduler.schedule_allocate(file_id).wait() {
+            Ok(page_id) => page_id,
+            Err(e) => {
+                self.free_frame(frame_id, &mut page)?;
+                return Err(e.into());
+            }
+        };
 
         // 3.   Update P's metadata, zero out memory and add P to the page table.
-        page_to_overwrite.overwrite(Some(new_page_id), [0; PAGE_SIZE])?;
-        page_to_overwrite.increase_pin_count()?;
-        replacer.pin(frame_id)?;
+        page.overwrite(Some(new_page_id), vec![0; self.page_size])?;
+        let mut page_table = self.page_table.write().unwrap();
+        let mut replacer = self.replacer.write().unwrap();
+        self.pin_new_frame(frame_id, new_page_id, &mut replacer, &mut page_table)?;
 
         // 4.   Set the page ID output parameter. Return a pointer to P.
-        Ok(page_to_overwrite)
+        Ok(page)
     }
 
     fn unpin_page(&self, page_id: PageId, mark_dirty: bool) -> Result<(), BufferPoolManagerError> {
         let page_table = self.page_table.read().unwrap();
-        let mut replacer = self.replacer.write().unwrap();
 
         if let Some(&frame_id) = page_table.get(&page_id) {
-            let mut page = self.pages[frame_id].write().unwrap();
-
-            page.decrease_pin_count()?;
+            let frame = &self.frames[frame_id];
+            let pin_count = frame
+                .pin_count
+                .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |count| {
+                    count.checked_sub(1)
+                })
+                .map_err(|_| BufferPoolManagerError::PageNotPinned)?
+                - 1;
+
+            // Still out of the replacer, so it can't be evicted before it's
+            // marked dirty
             if mark_dirty {
-                page.set_dirty()?;
+                frame.is_dirty.store(true, Ordering::SeqCst);
             }
 
-            if page.get_pin_count()? == 0 {
-                replacer.unpin(frame_id)?;
+            if pin_count == 0 {
+                self.replacer.write().unwrap().unpin(frame_id)?;
             }
 
             Ok(())
@@ -284,72 +526,148 @@ impl IBufferPoolManager for BufferPoolManager {
     }
 
     fn flush_page(&self, page_id: PageId) -> Result<(), BufferPoolManagerError> {
-        let mut disk_manager = self.disk_manager.write().unwrap();
-        let page_table = self.page_table.read().unwrap();
-
-        if let Some(&frame_id) = page_table.get(&page_id) {
-            let mut page = self.pages[frame_id].write().unwrap();
+        // Pinned while it's written, so nobody picks the frame to evict and
+        // then waits on its latch with the page table held
+        let Some(frame_id) = self.pin_for_flush(page_id)? else {
+            return Err(BufferPoolManagerError::PageNotInPool);
+        };
 
-            self.write_page(&mut page, &mut disk_manager)
-        } else {
-            Err(BufferPoolManagerError::PageNotInPool)
+        let mut page = self.pages[frame_id].write().unwrap();
+        if page.get_page_id()? != Some(page_id) {
+            // It was still being read in, and the read failed
+            drop(page);
+            self.abandon_frame(frame_id, page_id);
+            return Err(BufferPoolManagerError::PageNotInPool);
         }
+        let written = self.write_page(frame_id, &mut page);
+        drop(page);
+
+        self.unpin_page(page_id, false)?;
+        written
     }
 
     fn delete_page(&self, page_id: PageId) -> Result<(), BufferPoolManagerError> {
-        let mut disk_manager = self.disk_manager.write().unwrap();
-        let mut free_frames = self.free_frames.write().unwrap();
         let mut page_table = self.page_table.write().unwrap();
+        let mut replacer = self.replacer.write().unwrap();
 
         // 1.   Search the page table for the requested page (P).
-        if let Some(&frame_id) = page_table.get(&page_id) {
-            let mut page = self.pages[frame_id].write().unwrap();
-
+        let frame_id = page_table.get(&page_id).copied();
+        if let Some(frame_id) = frame_id {
             // 2.   If P exists, but has a non-zero pin-count, return false. Someone is using the page.
-            match page.get_pin_count() {
-                Ok(0) => {}
-                Ok(_) => return Err(BufferPoolManagerError::PageInUse),
-                Err(e) => return Err(BufferPoolManagerError::PageError(e)),
-            };
+            if self.frames[frame_id].pin_count.load(Ordering::SeqCst) > 0 {
+                return Err(BufferPoolManagerError::PageInUse);
+            }
 
             // 3.   Otherwise, P can be deleted. Remove P from the page table, reset its metadata and return it to the free list.
-            self.write_if_dirty(&mut page, &mut disk_manager)?;
-
+            let mut page = self.pages[frame_id].write().unwrap();
             page_table.remove(&page_id);
+            // Frames on the free list mustn't also be victims in the replacer
+            replacer.pin(frame_id)?;
+            self.free_frame(frame_id, &mut page)?;
+        }
+        // The page's ID can be handed out again, so the replacer mustn't
+        // take a new page with it for this one coming back
+        replacer.remove(frame_id, page_id)?;
+
+        // 0.   Make sure you call DiskManager::DeallocatePage!
+        // Scheduled before letting go of the page table, so anyone fetching
+        // the page from now on reads it after it's been deallocated
+        let deallocated = self.disk_scheduler.schedule_deallocate(page_id);
+        drop(replacer);
+        drop(page_table);
+        deallocated.wait()?;
 
-            // 0.   Make sure you call DiskManager::DeallocatePage!
-            disk_manager.deallocate_page(page_id)?;
-
-            page.clear()?;
+        Ok(())
+    }
 
-            free_frames.push(frame_id);
+    fn flush_all_pages(&self) -> Result<(), BufferPoolManagerError> {
+        let page_ids = self
+            .page_table
+            .read()
+            .unwrap()
+            .keys()
+            .copied()
+            .collect::<Vec<_>>();
+        for page_id in page_ids {
+            match self.flush_page(page_id) {
+                // Evicted since, which wrote it back if it needed it
+                Ok(()) | Err(BufferPoolManagerError::PageNotInPool) => {}
+                Err(e) => return Err(e),
+            }
         }
 
+        // Every write above has been waited on, so they're all covered
+        self.disk_scheduler.schedule_sync_pages().wait()?;
         Ok(())
     }
 
-    fn flush_all_pages(&self) -> Result<(), BufferPoolManagerError> {
-        let mut disk_manager = self.disk_manager.write().unwrap();
-        // Obtain the write latch on all pages
-        let mut pages = self
-            .pages
+    fn create_file(&self) -> Result<FileId, BufferPoolManagerError> {
+        Ok(self.disk_scheduler.schedule_create_file().wait()?)
+    }
+
+    fn drop_file(&self, file_id: FileId) -> Result<(), BufferPoolManagerError> {
+        // Checked up front, so the system file's pages aren't thrown away
+        // before the disk manager refuses to drop it
+        if file_id == SYSTEM_FILE_ID {
+            return Err(DiskManagerError::SystemFile.into());
+        }
+
+        let mut page_table = self.page_table.write().unwrap();
+        let mut replacer = self.replacer.write().unwrap();
+
+        let file_frames = page_table
             .iter()
-            .map(|page| page.write().unwrap())
+            .filter(|(&page_id, _)| page_file_id(page_id) == file_id)
+            .map(|(&page_id, &frame_id)| (page_id, frame_id))
             .collect::<Vec<_>>();
+        if file_frames
+            .iter()
+            .any(|&(_, frame_id)| self.frames[frame_id].pin_count.load(Ordering::SeqCst) > 0)
+        {
+            return Err(BufferPoolManagerError::PageInUse);
+        }
 
-        for page in pages.iter_mut() {
-            self.write_page(page, &mut disk_manager)?;
+        // The file's pages are going, so there's no need to write them out
+        for (page_id, frame_id) in file_frames {
+            let mut page = self.pages[frame_id].write().unwrap();
+            page_table.remove(&page_id);
+            // Frames on the free list mustn't also be victims in the replacer
+            replacer.pin(frame_id)?;
+            self.free_frame(frame_id, &mut page)?;
         }
+        // As with a deleted page, its file ID can be used again
+        replacer.remove_file(file_id)?;
+
+        let dropped = self.disk_scheduler.schedule_drop_file(file_id);
+        drop(replacer);
+        drop(page_table);
+        dropped.wait()?;
 
         Ok(())
     }
+
+    fn page_size(&self) -> usize {
+        self.page_size
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::dbms::buffer::pool_manager::testing::create_testing_pool_manager;
+    use crate::dbms::buffer::pool_manager::testing::{
+        create_faulty_pool_manager, create_testing_pool_manager,
+        create_testing_pool_manager_with_page_size,
+    };
+    use crate::dbms::buffer::replacer::arc_replacer::ArcReplacer;
+    use crate::dbms::buffer::replacer::clock_replacer::ClockReplacer;
+    use crate::dbms::buffer::replacer::lru_k_replacer::LruKReplacer;
+    use crate::dbms::buffer::replacer::two_queue_replacer::TwoQueueReplacer;
+    use crate::dbms::buffer::replacer::IBufferPoolReplacer;
+    use crate::dbms::storage::disk::testing::{DiskOperation, Fault, InMemoryDiskManager};
+    use crate::dbms::storage::disk::IDiskManager;
+    use crate::dbms::types::{PageData, DEFAULT_PAGE_SIZE, MAX_PAGE_SIZE, MIN_PAGE_SIZE};
     use rstest::*;
+    use std::sync::{Condvar, Mutex};
 
     #[rstest]
     #[case(1)]
@@ -541,6 +859,33 @@ mod tests {
         }
     }
 
+    #[rstest]
+    #[case(MIN_PAGE_SIZE)]
+    #[case(32768)]
+    #[case(MAX_PAGE_SIZE)]
+    fn test_pages_have_disk_page_size(#[case] page_size: usize) {
+        let buffer_pool_manager = create_testing_pool_manager_with_page_size(2, page_size);
+        assert_eq!(buffer_pool_manager.page_size(), page_size);
+
+        let page_ids = (0..3)
+            .map(|i| {
+                let mut page = buffer_pool_manager.new_page().unwrap();
+                let page_id = page.get_page_id().unwrap().unwrap();
+                assert_eq!(page.get_page_size().unwrap(), page_size);
+                assert_eq!(page.get_data().unwrap(), vec![0u8; page_size]);
+                page.write_data(page_size - 1, &[i + 1]).unwrap();
+                drop(page);
+                buffer_pool_manager.unpin_page(page_id, true).unwrap();
+                page_id
+            })
+            .collect::<Vec<_>>();
+
+        // The first page was evicted and read back in whole
+        let page = buffer_pool_manager.fetch_page(page_ids[0]).unwrap();
+        assert_eq!(page.get_page_size().unwrap(), page_size);
+        assert_eq!(page.read_data(page_size - 1, 1).unwrap(), vec![1]);
+    }
+
     #[rstest]
     fn test_flush_page() {
         let buffer_pool_manager = create_testing_pool_manager(10);
@@ -673,9 +1018,727 @@ mod tests {
         assert!(buffer_pool_manager.unpin_page(0, false).is_err());
     }
 
+    #[rstest]
+    fn test_unpin_page_twice() {
+        let buffer_pool_manager = create_testing_pool_manager(10);
+        let page_id = new_filled_page(&buffer_pool_manager, 1);
+
+        assert!(matches!(
+            buffer_pool_manager.unpin_page(page_id, true),
+            Err(BufferPoolManagerError::PageNotPinned)
+        ));
+
+        // Still fine to pin and unpin again afterwards
+        drop(buffer_pool_manager.fetch_page(page_id).unwrap());
+        buffer_pool_manager.unpin_page(page_id, false).unwrap();
+        assert!(matches!(
+            buffer_pool_manager.unpin_page(page_id, false),
+            Err(BufferPoolManagerError::PageNotPinned)
+        ));
+        let frame_id = buffer_pool_manager.page_table.read().unwrap()[&page_id];
+        assert_eq!(
+            buffer_pool_manager.frames[frame_id]
+                .pin_count
+                .load(Ordering::SeqCst),
+            0
+        );
+    }
+
     #[rstest]
     fn test_flush_page_not_in_pool() {
         let buffer_pool_manager = create_testing_pool_manager(10);
         assert!(buffer_pool_manager.flush_page(0).is_err());
     }
+
+    #[rstest]
+    fn test_fetch_page_miss_pins_page() {
+        let buffer_pool_manager = create_testing_pool_manager(1);
+
+        let page_id = buffer_pool_manager
+            .new_page()
+            .unwrap()
+            .get_page_id()
+            .unwrap()
+            .unwrap();
+        buffer_pool_manager.unpin_page(page_id, true).unwrap();
+
+        // Evict the page so the next fetch has to read it from disk
+        let other_page_id = buffer_pool_manager
+            .new_page()
+            .unwrap()
+            .get_page_id()
+            .unwrap()
+            .unwrap();
+        buffer_pool_manager
+            .unpin_page(other_page_id, false)
+            .unwrap();
+
+        // Pinned twice, so it stays in use after unpinning once
+        drop(buffer_pool_manager.fetch_page(page_id).unwrap());
+        drop(buffer_pool_manager.fetch_page(page_id).unwrap());
+        buffer_pool_manager.unpin_page(page_id, false).unwrap();
+
+        assert!(matches!(
+            buffer_pool_manager.new_page(),
+            Err(BufferPoolManagerError::NoFrameAvailable)
+        ));
+
+        buffer_pool_manager.unpin_page(page_id, false).unwrap();
+        assert!(buffer_pool_manager.new_page().is_ok());
+    }
+
+    #[rstest]
+    fn test_fetch_page_not_on_disk_frees_frame() {
+        let buffer_pool_manager = create_testing_pool_manager(1);
+
+        assert!(matches!(
+            buffer_pool_manager.fetch_page(5),
+            Err(BufferPoolManagerError::DiskManagerError(_))
+        ));
+
+        // The frame picked for the failed fetch is available again
+        assert!(buffer_pool_manager.new_page().is_ok());
+    }
+
+    #[rstest]
+    fn test_deleted_page_frame_not_reused_twice() {
+        let buffer_pool_manager = create_testing_pool_manager(2);
+
+        let page_id = buffer_pool_manager
+            .new_page()
+            .unwrap()
+            .get_page_id()
+            .unwrap()
+            .unwrap();
+        buffer_pool_manager.unpin_page(page_id, false).unwrap();
+        buffer_pool_manager.delete_page(page_id).unwrap();
+
+        // Both frames can be used at once, without the freed frame also being
+        // handed out by the replacer
+        let page_1 = buffer_pool_manager.new_page().unwrap();
+        let page_2 = buffer_pool_manager.new_page().unwrap();
+        assert_ne!(page_1.get_page_id().unwrap(), page_2.get_page_id().unwrap());
+    }
+
+    #[rstest]
+    fn test_pin_and_unpin_latched_page() {
+        let buffer_pool_manager = create_testing_pool_manager(10);
+
+        let page_id = buffer_pool_manager
+            .new_page()
+            .unwrap()
+            .get_page_id()
+            .unwrap()
+            .unwrap();
+
+        // Hold the page's latch while another thread pins and unpins it and
+        // uses other pages, none of which should have to wait for the latch
+        let _page = buffer_pool_manager.fetch_page_writable(page_id).unwrap();
+
+        let thread = {
+            let buffer_pool_manager = buffer_pool_manager.clone();
+            std::thread::spawn(move || {
+                buffer_pool_manager.fetch_page_frame(page_id).unwrap();
+                buffer_pool_manager.unpin_page(page_id, true).unwrap();
+
+                let other_page_id = buffer_pool_manager
+                    .new_page()
+                    .unwrap()
+                    .get_page_id()
+                    .unwrap()
+                    .unwrap();
+                buffer_pool_manager.unpin_page(other_page_id, true).unwrap();
+                drop(buffer_pool_manager.fetch_page(other_page_id).unwrap());
+            })
+        };
+
+        thread.join().unwrap();
+    }
+
+    #[test]
+    fn test_fetch_hits_recorded_by_replacer() {
+        let buffer_pool_manager = BufferPoolManager::new(
+            3,
+            Box::new(LruKReplacer::new(3, 2)),
+            Box::new(InMemoryDiskManager::new()),
+        );
+        let hot_page_id = new_filled_page(&buffer_pool_manager, 1);
+        assert_page_filled(&buffer_pool_manager, hot_page_id, 1);
+
+        // Pages only used once go before the one that's been fetched again
+        for byte in 2..10 {
+            new_filled_page(&buffer_pool_manager, byte);
+            assert!(buffer_pool_manager
+                .page_table
+                .read()
+                .unwrap()
+                .contains_key(&hot_page_id));
+        }
+        assert_page_filled(&buffer_pool_manager, hot_page_id, 1);
+    }
+
+    #[rstest]
+    #[case::clock(Box::new(ClockReplacer::new(8)), false)]
+    #[case::lru_k(Box::new(LruKReplacer::new(8, 2)), true)]
+    #[case::arc(Box::new(ArcReplacer::new(8)), true)]
+    #[case::two_queue(Box::new(TwoQueueReplacer::new(8)), true)]
+    fn test_hot_pages_resident_through_scan(
+        #[case] replacer: ReplacerGeneric,
+        #[case] hot_pages_kept: bool,
+    ) {
+        let mut disk_manager = InMemoryDiskManager::new();
+        let page_ids = (0..48)
+            .map(|_| disk_manager.allocate_page().unwrap())
+            .collect::<Vec<_>>();
+        let (hot_page_ids, cold_page_ids) = page_ids.split_at(4);
+        let (other_page_ids, scanned_page_ids) = cold_page_ids.split_at(8);
+        let buffer_pool_manager = BufferPoolManager::new(8, replacer, Box::new(disk_manager));
+        let touch = |page_id: &PageId| {
+            drop(buffer_pool_manager.fetch_page(*page_id).unwrap());
+            buffer_pool_manager.unpin_page(*page_id, false).unwrap();
+        };
+
+        // The hot pages are used over and over, with the odd other page
+        for other_page_ids in other_page_ids.chunks(2) {
+            hot_page_ids.iter().for_each(touch);
+            other_page_ids.iter().for_each(touch);
+        }
+        // Then something reads through lots of pages once
+        scanned_page_ids.iter().for_each(touch);
+
+        let page_table = buffer_pool_manager.page_table.read().unwrap();
+        let resident = hot_page_ids
+            .iter()
+            .filter(|page_id| page_table.contains_key(page_id))
+            .count();
+        assert_eq!(
+            resident,
+            if hot_pages_kept {
+                hot_page_ids.len()
+            } else {
+                0
+            }
+        );
+    }
+
+    /// The frames and pages a replacer's been asked to forget
+    type RemovedPages = Arc<Mutex<Vec<(Option<usize>, PageId)>>>;
+
+    /// Passes everything on to a [`ClockReplacer`], noting what the pool
+    /// asks it to forget
+    struct RemoveRecordingReplacer {
+        inner: ClockReplacer,
+        removed: RemovedPages,
+        removed_files: Arc<Mutex<Vec<FileId>>>,
+    }
+
+    impl IBufferPoolReplacer for RemoveRecordingReplacer {
+        fn victim(&mut self) -> Result<Option<usize>, BufferPoolReplacerError> {
+            self.inner.victim()
+        }
+
+        fn pin(&mut self, frame_id: usize) -> Result<(), BufferPoolReplacerError> {
+            self.inner.pin(frame_id)
+        }
+
+        fn unpin(&mut self, frame_id: usize) -> Result<(), BufferPoolReplacerError> {
+            self.inner.unpin(frame_id)
+        }
+
+        fn size(&self) -> Result<usize, BufferPoolReplacerError> {
+            self.inner.size()
+        }
+
+        fn record_access(
+            &mut self,
+            frame_id: usize,
+            page_id: PageId,
+        ) -> Result<(), BufferPoolReplacerError> {
+            self.inner.record_access(frame_id, page_id)
+        }
+
+        fn remove(
+            &mut self,
+            frame_id: Option<usize>,
+            page_id: PageId,
+        ) -> Result<(), BufferPoolReplacerError> {
+            self.removed.lock().unwrap().push((frame_id, page_id));
+            Ok(())
+        }
+
+        fn remove_file(&mut self, file_id: FileId) -> Result<(), BufferPoolReplacerError> {
+            self.removed_files.lock().unwrap().push(file_id);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_deleted_pages_removed_from_replacer() {
+        let removed = Arc::new(Mutex::new(Vec::new()));
+        let removed_files = Arc::new(Mutex::new(Vec::new()));
+        let buffer_pool_manager = BufferPoolManager::new(
+            1,
+            Box::new(RemoveRecordingReplacer {
+                inner: ClockReplacer::new(1),
+                removed: removed.clone(),
+                removed_files: removed_files.clone(),
+            }),
+            Box::new(InMemoryDiskManager::new()),
+        );
+        let evicted_page_id = new_filled_page(&buffer_pool_manager, 1);
+        let resident_page_id = new_filled_page(&buffer_pool_manager, 2);
+
+        // Whether or not the page's in a frame, the replacer forgets it, as
+        // its ID is about to be handed out again
+        buffer_pool_manager.delete_page(evicted_page_id).unwrap();
+        buffer_pool_manager.delete_page(resident_page_id).unwrap();
+        assert_eq!(
+            *removed.lock().unwrap(),
+            [(None, evicted_page_id), (Some(0), resident_page_id)]
+        );
+
+        // The in-memory disk manager only has the one file, so dropping
+        // another fails, but not before the replacer's told
+        assert!(buffer_pool_manager.drop_file(1).is_err());
+        assert_eq!(*removed_files.lock().unwrap(), [1]);
+    }
+
+    /// Create a page filled with the given byte, and unpin it dirty
+    fn new_filled_page(buffer_pool_manager: &BufferPoolManager, byte: u8) -> PageId {
+        let mut page = buffer_pool_manager.new_page().unwrap();
+        let page_id = page.get_page_id().unwrap().unwrap();
+        page.set_data(vec![byte; DEFAULT_PAGE_SIZE]).unwrap();
+        drop(page);
+        buffer_pool_manager.unpin_page(page_id, true).unwrap();
+        page_id
+    }
+
+    fn assert_page_filled(buffer_pool_manager: &BufferPoolManager, page_id: PageId, byte: u8) {
+        let page = buffer_pool_manager.fetch_page(page_id).unwrap();
+        assert_eq!(page.get_data().unwrap(), [byte; DEFAULT_PAGE_SIZE]);
+        drop(page);
+        buffer_pool_manager.unpin_page(page_id, false).unwrap();
+    }
+
+    #[rstest]
+    fn test_new_page_allocation_fails() {
+        let (buffer_pool_manager, injector) = create_faulty_pool_manager(1);
+        injector.inject(DiskOperation::AllocatePage, 1, Fault::Fail);
+
+        assert!(matches!(
+            buffer_pool_manager.new_page(),
+            Err(BufferPoolManagerError::DiskManagerError(
+                DiskManagerError::IoError(_)
+            ))
+        ));
+
+        // The frame picked for the failed page is available again
+        let page_id = new_filled_page(&buffer_pool_manager, 1);
+        assert_page_filled(&buffer_pool_manager, page_id, 1);
+    }
+
+    #[rstest]
+    fn test_fetch_page_read_fails() {
+        let (buffer_pool_manager, injector) = create_faulty_pool_manager(1);
+        let page_id = new_filled_page(&buffer_pool_manager, 1);
+        new_filled_page(&buffer_pool_manager, 2);
+        injector.fail_read(1);
+
+        assert!(matches!(
+            buffer_pool_manager.fetch_page(page_id),
+            Err(BufferPoolManagerError::DiskManagerError(
+                DiskManagerError::IoError(_)
+            ))
+        ));
+
+        // Nothing was left behind, so trying again reads the page
+        assert_page_filled(&buffer_pool_manager, page_id, 1);
+    }
+
+    #[rstest]
+    fn test_eviction_write_fails() {
+        let (buffer_pool_manager, injector) = create_faulty_pool_manager(1);
+        let page_id = new_filled_page(&buffer_pool_manager, 1);
+        injector.fail_write(1);
+
+        assert!(buffer_pool_manager.new_page().is_err());
+        assert_eq!(injector.count(DiskOperation::AllocatePage), 1);
+
+        // The dirty page stays in the pool rather than being lost
+        assert_page_filled(&buffer_pool_manager, page_id, 1);
+        assert_eq!(injector.count(DiskOperation::ReadPage), 0);
+
+        // And is written out when it's next evicted
+        let other_page_id = new_filled_page(&buffer_pool_manager, 2);
+        assert_page_filled(&buffer_pool_manager, page_id, 1);
+        assert_page_filled(&buffer_pool_manager, other_page_id, 2);
+    }
+
+    #[rstest]
+    fn test_flush_page_write_fails() {
+        let (buffer_pool_manager, injector) = create_faulty_pool_manager(1);
+        let page_id = new_filled_page(&buffer_pool_manager, 1);
+        injector.fail_write(1);
+
+        assert!(buffer_pool_manager.flush_page(page_id).is_err());
+
+        // Still dirty, so the flush can be retried
+        buffer_pool_manager.flush_page(page_id).unwrap();
+        assert_eq!(injector.count(DiskOperation::WritePage), 2);
+        // Clean now, so evicting it doesn't write it again
+        new_filled_page(&buffer_pool_manager, 2);
+        assert_eq!(injector.count(DiskOperation::WritePage), 2);
+    }
+
+    #[rstest]
+    fn test_flush_all_pages_write_fails() {
+        let (buffer_pool_manager, injector) = create_faulty_pool_manager(3);
+        let page_ids = (1..=3)
+            .map(|i| new_filled_page(&buffer_pool_manager, i))
+            .collect::<Vec<_>>();
+        injector.fail_write(2);
+
+        assert!(buffer_pool_manager.flush_all_pages().is_err());
+        buffer_pool_manager.flush_all_pages().unwrap();
+
+        // Every page made it to disk in the end, so they can all be evicted
+        // and read back
+        for i in 4..=6 {
+            new_filled_page(&buffer_pool_manager, i);
+        }
+        for (i, page_id) in page_ids.into_iter().enumerate() {
+            assert_page_filled(&buffer_pool_manager, page_id, i as u8 + 1);
+        }
+    }
+
+    #[rstest]
+    fn test_flush_all_pages_durable() {
+        let (buffer_pool_manager, injector) = create_faulty_pool_manager(3);
+        let page_ids = (1..=3)
+            .map(|i| new_filled_page(&buffer_pool_manager, i))
+            .collect::<Vec<_>>();
+        injector.inject(DiskOperation::SyncPages, 1, Fault::Fail);
+
+        // Written out, but not durable, so a crash loses them
+        assert!(buffer_pool_manager.flush_all_pages().is_err());
+        assert!(injector.crash().read_page(page_ids[0]).is_err());
+
+        // Already clean, so trying again only has to sync them
+        buffer_pool_manager.flush_all_pages().unwrap();
+        let recovered = injector.crash();
+        for (i, page_id) in page_ids.into_iter().enumerate() {
+            assert_eq!(
+                recovered.read_page(page_id).unwrap(),
+                [i as u8 + 1; DEFAULT_PAGE_SIZE]
+            );
+        }
+    }
+
+    #[rstest]
+    fn test_delete_page_deallocation_fails() {
+        let (buffer_pool_manager, injector) = create_faulty_pool_manager(2);
+        let page_id = new_filled_page(&buffer_pool_manager, 1);
+        injector.inject(DiskOperation::DeallocatePage, 1, Fault::Fail);
+
+        assert!(buffer_pool_manager.delete_page(page_id).is_err());
+
+        // Out of the pool already, but it can be deleted again to free it
+        buffer_pool_manager.delete_page(page_id).unwrap();
+        assert_eq!(injector.count(DiskOperation::DeallocatePage), 2);
+        assert!(buffer_pool_manager.fetch_page(page_id).is_err());
+    }
+
+    #[rstest]
+    fn test_short_read_not_detected() {
+        // Without checksums, a short read hands out a truncated page. This is
+        // what `FileDiskManager`'s checksums are for.
+        let (buffer_pool_manager, injector) = create_faulty_pool_manager(1);
+        let page_id = new_filled_page(&buffer_pool_manager, 1);
+        new_filled_page(&buffer_pool_manager, 2);
+        injector.short_read(1, 100);
+
+        let page = buffer_pool_manager.fetch_page(page_id).unwrap();
+        let data = page.get_data().unwrap();
+        assert_eq!(data[..100], [1u8; 100]);
+        assert_eq!(data[100..], [0u8; DEFAULT_PAGE_SIZE - 100]);
+    }
+
+    /// Holds reads or writes of one page until the test lets them through, so
+    /// a test can look at what the pool does while they're in progress
+    #[derive(Default)]
+    struct Gate {
+        state: Mutex<GateState>,
+        changed: Condvar,
+    }
+
+    #[derive(Default)]
+    struct GateState {
+        started: usize,
+        open: bool,
+        fail: bool,
+    }
+
+    impl Gate {
+        fn wait_for(&self, count: usize) {
+            let state = self.state.lock().unwrap();
+            drop(
+                self.changed
+                    .wait_while(state, |state| state.started < count)
+                    .unwrap(),
+            );
+        }
+
+        /// Let operations through, failing them if `fail` is set
+        fn open(&self, fail: bool) {
+            let mut state = self.state.lock().unwrap();
+            state.open = true;
+            state.fail = fail;
+            self.changed.notify_all();
+        }
+
+        /// Hold operations again
+        fn close(&self) {
+            self.state.lock().unwrap().open = false;
+        }
+
+        /// Wait until the gate's open, and fail if it's set to
+        fn pass(&self) -> Result<(), DiskManagerError> {
+            let mut state = self.state.lock().unwrap();
+            state.started += 1;
+            self.changed.notify_all();
+            let mut state = self.changed.wait_while(state, |state| !state.open).unwrap();
+            if std::mem::take(&mut state.fail) {
+                return Err(DiskManagerError::IoError(std::io::Error::other(
+                    "gated operation failed",
+                )));
+            }
+            Ok(())
+        }
+    }
+
+    struct GatedDiskManager {
+        inner: InMemoryDiskManager,
+        gated_page_id: PageId,
+        read_gate: Arc<Gate>,
+        write_gate: Arc<Gate>,
+    }
+
+    impl IDiskManager for GatedDiskManager {
+        fn page_size(&self) -> usize {
+            self.inner.page_size()
+        }
+
+        fn write_page(&mut self, page_id: PageId, page: &[u8]) -> Result<(), DiskManagerError> {
+            if page_id == self.gated_page_id {
+                self.write_gate.pass()?;
+            }
+            self.inner.write_page(page_id, page)
+        }
+
+        fn read_page(&self, page_id: PageId) -> Result<PageData, DiskManagerError> {
+            if page_id == self.gated_page_id {
+                self.read_gate.pass()?;
+            }
+            self.inner.read_page(page_id)
+        }
+
+        fn write_log(&mut self, log: &[u8]) -> Result<(), DiskManagerError> {
+            self.inner.write_log(log)
+        }
+
+        fn read_log(&self, size: usize, offset: usize) -> Result<PageData, DiskManagerError> {
+            self.inner.read_log(size, offset)
+        }
+
+        fn sync_log(&mut self) -> Result<(), DiskManagerError> {
+            self.inner.sync_log()
+        }
+
+        fn sync_pages(&mut self) -> Result<(), DiskManagerError> {
+            self.inner.sync_pages()
+        }
+
+        fn allocate_page(&mut self) -> Result<PageId, DiskManagerError> {
+            self.inner.allocate_page()
+        }
+
+        fn deallocate_page(&mut self, page_id: PageId) -> Result<(), DiskManagerError> {
+            self.inner.deallocate_page(page_id)
+        }
+    }
+
+    /// A pool of four frames holding two filled, clean pages, with two more
+    /// filled pages that are only on disk. Reads of the first of those are
+    /// held by the returned gate. Writes of it can be held by the pool's disk
+    /// manager too, see `create_write_gated_pool_manager`.
+    fn create_gated_pool_manager() -> (BufferPoolManager, Arc<Gate>, [PageId; 2], [PageId; 2]) {
+        let (buffer_pool_manager, read_gate, _, on_disk, resident) =
+            create_read_and_write_gated_pool_manager();
+        (buffer_pool_manager, read_gate, on_disk, resident)
+    }
+
+    /// As `create_gated_pool_manager`, with a second gate for writes of the
+    /// gated page, which starts off open
+    fn create_read_and_write_gated_pool_manager() -> (
+        BufferPoolManager,
+        Arc<Gate>,
+        Arc<Gate>,
+        [PageId; 2],
+        [PageId; 2],
+    ) {
+        let mut disk_manager = InMemoryDiskManager::new();
+        let on_disk = [9, 8].map(|byte| {
+            let page_id = disk_manager.allocate_page().unwrap();
+            disk_manager
+                .write_page(page_id, &[byte; DEFAULT_PAGE_SIZE])
+                .unwrap();
+            page_id
+        });
+        let read_gate = Arc::new(Gate::default());
+        let write_gate = Arc::new(Gate::default());
+        write_gate.open(false);
+        let disk_manager = GatedDiskManager {
+            inner: disk_manager,
+            gated_page_id: on_disk[0],
+            read_gate: read_gate.clone(),
+            write_gate: write_gate.clone(),
+        };
+        let buffer_pool_manager =
+            BufferPoolManager::new(4, Box::new(ClockReplacer::new(4)), Box::new(disk_manager));
+        let resident = [1, 2].map(|byte| new_filled_page(&buffer_pool_manager, byte));
+        buffer_pool_manager.flush_all_pages().unwrap();
+        (
+            buffer_pool_manager,
+            read_gate,
+            write_gate,
+            on_disk,
+            resident,
+        )
+    }
+
+    fn spawn_fetch(
+        buffer_pool_manager: &BufferPoolManager,
+        page_id: PageId,
+    ) -> std::thread::JoinHandle<Result<PageData, BufferPoolManagerError>> {
+        let buffer_pool_manager = buffer_pool_manager.clone();
+        std::thread::spawn(move || {
+            let data = buffer_pool_manager.fetch_page(page_id)?.get_data()?;
+            buffer_pool_manager.unpin_page(page_id, false)?;
+            Ok(data)
+        })
+    }
+
+    #[rstest]
+    fn test_pool_usable_during_read() {
+        let (buffer_pool_manager, gate, on_disk, resident) = create_gated_pool_manager();
+
+        let reader = spawn_fetch(&buffer_pool_manager, on_disk[0]);
+        gate.wait_for(1);
+
+        // While the read is held, pages already in the pool can be used, and
+        // other pages can be read in. The two pages on disk are next to each
+        // other, so their reads go to different disk workers.
+        assert_page_filled(&buffer_pool_manager, resident[0], 1);
+        assert_page_filled(&buffer_pool_manager, on_disk[1], 8);
+        assert_page_filled(&buffer_pool_manager, resident[1], 2);
+
+        gate.open(false);
+        assert_eq!(reader.join().unwrap().unwrap(), [9u8; DEFAULT_PAGE_SIZE]);
+    }
+
+    #[rstest]
+    fn test_pool_usable_during_flush() {
+        let (buffer_pool_manager, read_gate, write_gate, [gated_page_id, _], resident) =
+            create_read_and_write_gated_pool_manager();
+        read_gate.open(false);
+        let mut page = buffer_pool_manager
+            .fetch_page_writable(gated_page_id)
+            .unwrap();
+        page.set_data(vec![7u8; DEFAULT_PAGE_SIZE]).unwrap();
+        drop(page);
+        buffer_pool_manager.unpin_page(gated_page_id, true).unwrap();
+        // Every other frame is pinned, so the page being flushed is the only
+        // one that could be picked for eviction
+        let pinned = {
+            let page = buffer_pool_manager.new_page().unwrap();
+            page.get_page_id().unwrap().unwrap()
+        };
+        for page_id in resident {
+            drop(buffer_pool_manager.fetch_page(page_id).unwrap());
+        }
+
+        // A held write holds up the whole disk manager, so nothing else can
+        // touch the disk until it's let through
+        write_gate.close();
+        let flusher = {
+            let buffer_pool_manager = buffer_pool_manager.clone();
+            std::thread::spawn(move || buffer_pool_manager.flush_page(gated_page_id))
+        };
+        write_gate.wait_for(1);
+
+        // Nobody waits on the flushed page's latch with the page table held,
+        // so the pool carries on
+        assert!(matches!(
+            buffer_pool_manager.new_page(),
+            Err(BufferPoolManagerError::NoFrameAvailable)
+        ));
+        assert!(matches!(
+            buffer_pool_manager.delete_page(gated_page_id),
+            Err(BufferPoolManagerError::PageInUse)
+        ));
+        assert_page_filled(&buffer_pool_manager, resident[0], 1);
+
+        write_gate.open(false);
+        flusher.join().unwrap().unwrap();
+        // Unpinned again once it's flushed
+        for page_id in resident.into_iter().chain([pinned]) {
+            buffer_pool_manager.unpin_page(page_id, false).unwrap();
+        }
+        buffer_pool_manager.delete_page(gated_page_id).unwrap();
+    }
+
+    #[rstest]
+    fn test_concurrent_fetches_share_read() {
+        let (buffer_pool_manager, gate, [gated_page_id, _], _) = create_gated_pool_manager();
+
+        let first = spawn_fetch(&buffer_pool_manager, gated_page_id);
+        gate.wait_for(1);
+        // Waits for the first fetch's read rather than reading it again
+        let second = spawn_fetch(&buffer_pool_manager, gated_page_id);
+
+        gate.open(false);
+        assert_eq!(first.join().unwrap().unwrap(), [9u8; DEFAULT_PAGE_SIZE]);
+        assert_eq!(second.join().unwrap().unwrap(), [9u8; DEFAULT_PAGE_SIZE]);
+        assert_eq!(gate.state.lock().unwrap().started, 1);
+    }
+
+    #[rstest]
+    fn test_concurrent_fetch_retries_failed_read() {
+        let (buffer_pool_manager, gate, [gated_page_id, _], _) = create_gated_pool_manager();
+
+        let first = spawn_fetch(&buffer_pool_manager, gated_page_id);
+        gate.wait_for(1);
+        let second = spawn_fetch(&buffer_pool_manager, gated_page_id);
+        // Give the second fetch time to find the page being read in and wait
+        // for it. If it hasn't by the time the read fails, it reads the page
+        // itself, which is fine too.
+        std::thread::sleep(std::time::Duration::from_millis(50));
+
+        gate.open(true);
+        assert!(matches!(
+            first.join().unwrap(),
+            Err(BufferPoolManagerError::DiskManagerError(
+                DiskManagerError::IoError(_)
+            ))
+        ));
+        // The second fetch reads the page again rather than handing out the
+        // empty frame the failed read left behind
+        assert_eq!(second.join().unwrap().unwrap(), [9u8; DEFAULT_PAGE_SIZE]);
+        assert_eq!(gate.state.lock().unwrap().started, 2);
+
+        // Nothing was left pinned, so every frame can be used
+        for byte in 3..6 {
+            new_filled_page(&buffer_pool_manager, byte);
+        }
+    }
 }
@@ -1,6 +1,7 @@
 #[cfg(test)]
 use crate::dbms::{
-    buffer::replacer::clock_replacer::ClockReplacer, storage::disk::testing::InMemoryDiskManager,
+    buffer::replacer::clock_replacer::ClockReplacer,
+    storage::disk::testing::{FaultInjectingDiskManager, FaultInjector, InMemoryDiskManager},
 };
 
 #[cfg(test)]
@@ -12,3 +13,28 @@ pub fn create_testing_pool_manager(pool_size: usize) -> BufferPoolManager {
     let replacer = ClockReplacer::new(pool_size);
     BufferPoolManager::new(pool_size, Box::new(replacer), Box::new(disk_manager))
 }
+
+/// A pool manager over a database with the given page size
+#[cfg(test)]
+pub fn create_testing_pool_manager_with_page_size(
+    pool_size: usize,
+    page_size: usize,
+) -> BufferPoolManager {
+    let disk_manager = InMemoryDiskManager::with_page_size(page_size);
+    let replacer = ClockReplacer::new(pool_size);
+    BufferPoolManager::new(pool_size, Box::new(replacer), Box::new(disk_manager))
+}
+
+/// A pool manager whose disk can be scripted to misbehave with the returned
+/// fault injector
+#[cfg(test)]
+pub fn create_faulty_pool_manager(
+    pool_size: usize,
+) -> (BufferPoolManager, FaultInjector<InMemoryDiskManager>) {
+    let disk_manager = FaultInjectingDiskManager::new(InMemoryDiskManager::new());
+    let injector = disk_manager.injector();
+    let replacer = ClockReplacer::new(pool_size);
+    let pool_manager =
+        BufferPoolManager::new(pool_size, Box::new(replacer), Box::new(disk_manager));
+    (pool_manager, injector)
+}
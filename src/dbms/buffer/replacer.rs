@@ -1,4 +1,8 @@
+pub mod arc_replacer;
 mod buffer_pool_replacer;
 pub mod clock_replacer;
+mod linked_set;
+pub mod lru_k_replacer;
+pub mod two_queue_replacer;
 
 pub use buffer_pool_replacer::*;
@@ -0,0 +1,442 @@
+use crate::dbms::types::{page_file_id, FileId, PageId};
+
+use super::buffer_pool_replacer::{BufferPoolReplacerError, IBufferPoolReplacer};
+use super::linked_set::LinkedSet;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum ArcList {
+    /// T1, pages accessed once since they were loaded
+    Recent,
+    /// T2, pages accessed again while in the pool, or soon after eviction
+    Frequent,
+}
+
+#[derive(Debug, PartialEq, Clone, Default)]
+struct ArcFrame {
+    page_id: Option<PageId>,
+    list: Option<ArcList>,
+    evictable: bool,
+}
+
+/// Adaptive Replacement Cache. Frames are split between a list of pages
+/// seen once and a list of pages seen more than once, and the IDs of pages
+/// recently evicted from each are remembered. Loading one of those again
+/// shifts the target size of the recent list towards whichever would have
+/// kept it, so the balance between recency and frequency follows the
+/// workload. A scan only ever fills the recent list, leaving frequently
+/// used pages alone.
+pub struct ArcReplacer {
+    frames: Vec<ArcFrame>,
+    /// Frames in T1, least recently used first
+    recent: LinkedSet<usize>,
+    /// Frames in T2, least recently used first
+    frequent: LinkedSet<usize>,
+    /// B1, pages evicted from T1, least recently evicted first
+    recent_ghosts: LinkedSet<PageId>,
+    /// B2, pages evicted from T2, least recently evicted first
+    frequent_ghosts: LinkedSet<PageId>,
+    /// p, the number of frames T1 should ideally hold
+    target_recent_size: usize,
+}
+
+impl ArcReplacer {
+    /// Creates a new [`ArcReplacer`].
+    #[allow(dead_code)]
+    pub fn new(size: usize) -> Self {
+        ArcReplacer {
+            frames: vec![ArcFrame::default(); size],
+            recent: LinkedSet::new(),
+            frequent: LinkedSet::new(),
+            recent_ghosts: LinkedSet::new(),
+            frequent_ghosts: LinkedSet::new(),
+            target_recent_size: 0,
+        }
+    }
+}
+
+impl ArcReplacer {
+    fn max_size(&self) -> usize {
+        self.frames.len()
+    }
+
+    fn check_frame_id(&self, frame_id: usize) -> Result<(), BufferPoolReplacerError> {
+        if frame_id >= self.max_size() {
+            return Err(BufferPoolReplacerError::FrameOutOfRange(format!(
+                "frame_id {} is out of range",
+                frame_id
+            )));
+        }
+        Ok(())
+    }
+
+    fn list_mut(&mut self, list: ArcList) -> &mut LinkedSet<usize> {
+        match list {
+            ArcList::Recent => &mut self.recent,
+            ArcList::Frequent => &mut self.frequent,
+        }
+    }
+
+    fn push_frame(&mut self, frame_id: usize, list: ArcList) {
+        self.list_mut(list).push_back(frame_id);
+        self.frames[frame_id].list = Some(list);
+    }
+
+    fn remove_frame(&mut self, frame_id: usize) -> Option<ArcList> {
+        let list = self.frames[frame_id].list.take()?;
+        self.list_mut(list).remove(frame_id);
+        Some(list)
+    }
+
+    /// Take a frame's page out of the lists, remembering it in the ghost
+    /// list matching the one it was in.
+    fn evict_page(&mut self, frame_id: usize) {
+        let page_id = self.frames[frame_id].page_id.take();
+        match (self.remove_frame(frame_id), page_id) {
+            (Some(ArcList::Recent), Some(page_id)) => self.recent_ghosts.push_back(page_id),
+            (Some(ArcList::Frequent), Some(page_id)) => self.frequent_ghosts.push_back(page_id),
+            _ => {}
+        }
+    }
+
+    /// Keep T1 and B1 to at most the pool's size, and all four lists to at
+    /// most twice that.
+    fn trim_ghosts(&mut self) {
+        let max_size = self.max_size();
+        while self.recent.len() + self.recent_ghosts.len() > max_size
+            && self.recent_ghosts.pop_front().is_some()
+        {}
+        while self.recent.len()
+            + self.frequent.len()
+            + self.recent_ghosts.len()
+            + self.frequent_ghosts.len()
+            > 2 * max_size
+        {
+            if self.frequent_ghosts.pop_front().is_none() {
+                self.recent_ghosts.pop_front();
+            }
+        }
+    }
+
+    fn first_evictable(&self, list: &LinkedSet<usize>) -> Option<usize> {
+        list.iter()
+            .find(|&frame_id| self.frames[frame_id].evictable)
+    }
+}
+
+impl IBufferPoolReplacer for ArcReplacer {
+    fn victim(&mut self) -> Result<Option<usize>, BufferPoolReplacerError> {
+        // Frames that have never been accessed have nothing worth keeping
+        let unlisted = self
+            .frames
+            .iter()
+            .position(|frame| frame.evictable && frame.list.is_none());
+        let victim = unlisted.or_else(|| {
+            let (first, second) = if self.recent.len() > self.target_recent_size {
+                (&self.recent, &self.frequent)
+            } else {
+                (&self.frequent, &self.recent)
+            };
+            self.first_evictable(first)
+                .or_else(|| self.first_evictable(second))
+        });
+
+        // The frame stays in its list until another page is loaded into it,
+        // in case it's handed back unused
+        if let Some(frame_id) = victim {
+            self.frames[frame_id].evictable = false;
+        }
+
+        Ok(victim)
+    }
+
+    fn pin(&mut self, frame_id: usize) -> Result<(), BufferPoolReplacerError> {
+        self.check_frame_id(frame_id)?;
+
+        self.frames[frame_id].evictable = false;
+        Ok(())
+    }
+
+    fn unpin(&mut self, frame_id: usize) -> Result<(), BufferPoolReplacerError> {
+        self.check_frame_id(frame_id)?;
+
+        self.frames[frame_id].evictable = true;
+        Ok(())
+    }
+
+    fn size(&self) -> Result<usize, BufferPoolReplacerError> {
+        Ok(self.frames.iter().filter(|frame| frame.evictable).count())
+    }
+
+    fn record_access(
+        &mut self,
+        frame_id: usize,
+        page_id: PageId,
+    ) -> Result<(), BufferPoolReplacerError> {
+        self.check_frame_id(frame_id)?;
+
+        // Hit on a page in the pool
+        if self.frames[frame_id].page_id == Some(page_id) {
+            self.remove_frame(frame_id);
+            self.push_frame(frame_id, ArcList::Frequent);
+            return Ok(());
+        }
+
+        // A new page in the frame, so the old one's been evicted
+        self.evict_page(frame_id);
+        self.frames[frame_id].page_id = Some(page_id);
+
+        let recent_ghosts = self.recent_ghosts.len();
+        let frequent_ghosts = self.frequent_ghosts.len();
+        if self.recent_ghosts.remove(page_id) {
+            // T1 would have kept it if it were bigger
+            let delta = usize::max(frequent_ghosts / recent_ghosts, 1);
+            self.target_recent_size = usize::min(self.target_recent_size + delta, self.max_size());
+            self.push_frame(frame_id, ArcList::Frequent);
+        } else if self.frequent_ghosts.remove(page_id) {
+            // T2 would have kept it if it were bigger
+            let delta = usize::max(recent_ghosts / frequent_ghosts, 1);
+            self.target_recent_size = self.target_recent_size.saturating_sub(delta);
+            self.push_frame(frame_id, ArcList::Frequent);
+        } else {
+            self.push_frame(frame_id, ArcList::Recent);
+        }
+
+        self.trim_ghosts();
+        Ok(())
+    }
+
+    fn remove(
+        &mut self,
+        frame_id: Option<usize>,
+        page_id: PageId,
+    ) -> Result<(), BufferPoolReplacerError> {
+        if let Some(frame_id) = frame_id {
+            self.check_frame_id(frame_id)?;
+            if self.frames[frame_id].page_id == Some(page_id) {
+                self.remove_frame(frame_id);
+                self.frames[frame_id].page_id = None;
+            }
+        }
+        self.recent_ghosts.remove(page_id);
+        self.frequent_ghosts.remove(page_id);
+        Ok(())
+    }
+
+    fn remove_file(&mut self, file_id: FileId) -> Result<(), BufferPoolReplacerError> {
+        let in_frames = self
+            .frames
+            .iter()
+            .enumerate()
+            .filter_map(|(frame_id, frame)| Some((Some(frame_id), frame.page_id?)));
+        let ghosts = self
+            .recent_ghosts
+            .iter()
+            .chain(self.frequent_ghosts.iter())
+            .map(|page_id| (None, page_id));
+        let pages = in_frames
+            .chain(ghosts)
+            .filter(|&(_, page_id)| page_file_id(page_id) == file_id)
+            .collect::<Vec<_>>();
+
+        for (frame_id, page_id) in pages {
+            self.remove(frame_id, page_id)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dbms::types::make_page_id;
+    use rstest::rstest;
+
+    /// A list's entries, front to back
+    fn listed<T: Copy + Eq + std::hash::Hash>(list: &LinkedSet<T>) -> Vec<T> {
+        list.iter().collect()
+    }
+
+    /// Load a page into a frame the way the buffer pool does, accessing it
+    /// while pinned and then unpinning it
+    fn load(replacer: &mut ArcReplacer, frame_id: usize, page_id: PageId) {
+        replacer.record_access(frame_id, page_id).unwrap();
+        replacer.unpin(frame_id).unwrap();
+    }
+
+    #[rstest]
+    // Recent pages go first while T1's over its target, which starts at 0
+    #[case(vec![(0, 10), (1, 11), (2, 12)], vec![0, 1, 2])]
+    #[case(vec![(0, 10), (1, 11), (1, 11), (2, 12)], vec![0, 2, 1])]
+    #[case(vec![(0, 10), (0, 10), (1, 11), (1, 11), (0, 10)], vec![1, 0])]
+    #[case(vec![(0, 10), (0, 10), (1, 11), (1, 11), (1, 11)], vec![0, 1])]
+    // A new page in a frame starts in T1 again
+    #[case(vec![(0, 10), (0, 10), (1, 11), (0, 12)], vec![1, 0])]
+    fn test_victim_order(
+        #[case] accesses: Vec<(usize, PageId)>,
+        #[case] expected_victims: Vec<usize>,
+    ) {
+        let size = expected_victims.len();
+        let mut replacer = ArcReplacer::new(size);
+        for (frame_id, page_id) in accesses {
+            load(&mut replacer, frame_id, page_id);
+        }
+
+        let victims = (0..size)
+            .map(|_| replacer.victim().unwrap().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(victims, expected_victims);
+        assert_eq!(replacer.victim(), Ok(None));
+        assert_eq!(replacer.size(), Ok(0));
+    }
+
+    #[test]
+    fn test_unaccessed_frames_first() {
+        let mut replacer = ArcReplacer::new(3);
+        load(&mut replacer, 0, 10);
+        replacer.unpin(2).unwrap();
+
+        assert_eq!(replacer.victim(), Ok(Some(2)));
+        assert_eq!(replacer.victim(), Ok(Some(0)));
+        assert_eq!(replacer.victim(), Ok(None));
+    }
+
+    #[test]
+    fn test_ghost_hits_adapt_target() {
+        let mut replacer = ArcReplacer::new(3);
+        load(&mut replacer, 0, 10);
+        load(&mut replacer, 1, 11);
+        load(&mut replacer, 1, 11);
+        load(&mut replacer, 2, 12);
+
+        // Pages evicted from T1 are remembered in B1
+        assert_eq!(replacer.victim(), Ok(Some(0)));
+        load(&mut replacer, 0, 13);
+        assert_eq!(listed(&replacer.recent_ghosts), [10]);
+        assert_eq!(replacer.victim(), Ok(Some(2)));
+
+        // Loading one again means T1 was too small
+        load(&mut replacer, 2, 10);
+        assert_eq!(listed(&replacer.recent_ghosts), [12]);
+        assert_eq!(listed(&replacer.frequent), [1, 2]);
+        assert_eq!(replacer.target_recent_size, 1);
+
+        // So T2 gives up a frame before T1 does
+        assert_eq!(replacer.victim(), Ok(Some(1)));
+        load(&mut replacer, 1, 14);
+        assert_eq!(listed(&replacer.frequent_ghosts), [11]);
+        assert_eq!(replacer.victim(), Ok(Some(0)));
+
+        // Loading a page evicted from T2 again means T2 was too small
+        load(&mut replacer, 0, 11);
+        assert!(replacer.frequent_ghosts.is_empty());
+        assert_eq!(listed(&replacer.recent_ghosts), [12, 13]);
+        assert_eq!(listed(&replacer.frequent), [2, 0]);
+        assert_eq!(replacer.target_recent_size, 0);
+    }
+
+    #[test]
+    fn test_ghost_lists_bounded() {
+        let mut replacer = ArcReplacer::new(4);
+        load(&mut replacer, 0, 0);
+        load(&mut replacer, 0, 0);
+        for page_id in 1..20 {
+            load(&mut replacer, 1 + page_id as usize % 3, page_id);
+        }
+
+        // T1 and B1 together fit in the pool
+        assert_eq!(listed(&replacer.recent), [3, 1, 2]);
+        assert_eq!(listed(&replacer.recent_ghosts), [16]);
+        assert_eq!(listed(&replacer.frequent), [0]);
+    }
+
+    #[test]
+    fn test_victim_handed_back_keeps_place() {
+        let mut replacer = ArcReplacer::new(2);
+        load(&mut replacer, 0, 10);
+        load(&mut replacer, 1, 11);
+
+        assert_eq!(replacer.victim(), Ok(Some(0)));
+        replacer.unpin(0).unwrap();
+        assert_eq!(listed(&replacer.recent), [0, 1]);
+        assert!(replacer.recent_ghosts.is_empty());
+        assert_eq!(replacer.victim(), Ok(Some(0)));
+    }
+
+    #[test]
+    fn test_removed_pages_forgotten() {
+        let mut replacer = ArcReplacer::new(3);
+        load(&mut replacer, 0, 10);
+        load(&mut replacer, 1, 11);
+        load(&mut replacer, 1, 11);
+        load(&mut replacer, 2, 12);
+        assert_eq!(replacer.victim(), Ok(Some(0)));
+        load(&mut replacer, 0, 13);
+        assert_eq!(listed(&replacer.recent_ghosts), [10]);
+
+        // Deleted while only remembered
+        replacer.remove(None, 10).unwrap();
+        assert!(replacer.recent_ghosts.is_empty());
+        // Deleted from a frame, which isn't remembered as an eviction
+        replacer.pin(1).unwrap();
+        replacer.remove(Some(1), 11).unwrap();
+        assert!(replacer.frequent.is_empty());
+        assert!(replacer.frequent_ghosts.is_empty());
+
+        // So a page given its ID starts out on its first stay
+        load(&mut replacer, 1, 10);
+        assert_eq!(listed(&replacer.recent), [2, 0, 1]);
+        assert!(replacer.frequent.is_empty());
+        assert_eq!(replacer.target_recent_size, 0);
+    }
+
+    #[test]
+    fn test_removed_file_forgotten() {
+        let page_id = |file_id, page_number| make_page_id(file_id, page_number);
+        let mut replacer = ArcReplacer::new(3);
+        load(&mut replacer, 0, page_id(1, 1));
+        load(&mut replacer, 0, page_id(1, 1));
+        load(&mut replacer, 1, page_id(1, 2));
+        load(&mut replacer, 2, page_id(2, 1));
+        assert_eq!(replacer.victim(), Ok(Some(1)));
+        load(&mut replacer, 1, page_id(2, 2));
+        assert_eq!(listed(&replacer.recent_ghosts), [page_id(1, 2)]);
+
+        replacer.pin(0).unwrap();
+        replacer.remove_file(1).unwrap();
+
+        assert!(replacer.frequent.is_empty());
+        assert_eq!(listed(&replacer.recent), [2, 1]);
+        assert!(replacer.recent_ghosts.is_empty());
+        assert_eq!(replacer.frames[0].page_id, None);
+    }
+
+    #[test]
+    fn test_pinned_frames_not_victims() {
+        let mut replacer = ArcReplacer::new(3);
+        load(&mut replacer, 0, 10);
+        load(&mut replacer, 1, 11);
+        load(&mut replacer, 1, 11);
+        load(&mut replacer, 2, 12);
+
+        replacer.pin(0).unwrap();
+        replacer.pin(2).unwrap();
+        assert_eq!(replacer.size(), Ok(1));
+        // Falls back to T2 when nothing in T1 can go
+        assert_eq!(replacer.victim(), Ok(Some(1)));
+        assert_eq!(replacer.victim(), Ok(None));
+    }
+
+    #[rstest]
+    #[case(3, Err(BufferPoolReplacerError::FrameOutOfRange("frame_id 3 is out of range".to_string())))]
+    #[case(2, Ok(()))]
+    fn test_frame_out_of_range(
+        #[case] frame_id: usize,
+        #[case] expected_result: Result<(), BufferPoolReplacerError>,
+    ) {
+        let mut replacer = ArcReplacer::new(3);
+
+        assert_eq!(replacer.record_access(frame_id, 10), expected_result);
+        assert_eq!(replacer.unpin(frame_id), expected_result);
+        assert_eq!(replacer.pin(frame_id), expected_result);
+    }
+}
@@ -1,3 +1,5 @@
+use crate::dbms::types::{FileId, PageId};
+
 #[derive(Debug, PartialEq, Eq)]
 pub enum BufferPoolReplacerError {
     /// Frame is out of range
@@ -16,4 +18,29 @@ pub trait IBufferPoolReplacer {
     fn unpin(&mut self, frame_id: usize) -> Result<(), BufferPoolReplacerError>;
     /// Return the number of frames currently in the replacer.
     fn size(&self) -> Result<usize, BufferPoolReplacerError>;
+    /// Record that a page has been accessed in a frame, each time it's
+    /// fetched or created. Replacers that only care about pins and unpins
+    /// can ignore it.
+    fn record_access(
+        &mut self,
+        _frame_id: usize,
+        _page_id: PageId,
+    ) -> Result<(), BufferPoolReplacerError> {
+        Ok(())
+    }
+    /// Forget a page that's been deleted, and the frame it was in if it was
+    /// in the pool. Unlike an evicted page it isn't remembered, as its ID can
+    /// be handed out again for a different page.
+    fn remove(
+        &mut self,
+        _frame_id: Option<usize>,
+        _page_id: PageId,
+    ) -> Result<(), BufferPoolReplacerError> {
+        Ok(())
+    }
+    /// Forget every page in a file that's been dropped, as `remove` does for
+    /// a single page.
+    fn remove_file(&mut self, _file_id: FileId) -> Result<(), BufferPoolReplacerError> {
+        Ok(())
+    }
 }
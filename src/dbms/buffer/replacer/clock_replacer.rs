@@ -94,6 +94,7 @@ impl IBufferPoolReplacer for ClockReplacer {
 }
 
 #[cfg(test)]
+#[allow(clippy::useless_format)]
 mod tests {
     use super::*;
     use rstest::rstest;
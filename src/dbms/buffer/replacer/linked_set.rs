@@ -0,0 +1,150 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+
+#[derive(Debug, Clone, Copy)]
+struct Links<T> {
+    prev: Option<T>,
+    next: Option<T>,
+}
+
+/// An ordered set that can add to the back, and take out from anywhere, in
+/// constant time, for keeping replacers' lists in LRU or FIFO order without
+/// searching them. It's a doubly linked list threaded through a map from
+/// each entry to its neighbours.
+#[derive(Debug, Clone)]
+pub struct LinkedSet<T> {
+    links: HashMap<T, Links<T>>,
+    /// First entry, e.g. the least recently used
+    head: Option<T>,
+    /// Last entry, e.g. the most recently used
+    tail: Option<T>,
+}
+
+impl<T: Copy + Eq + Hash> LinkedSet<T> {
+    pub fn new() -> Self {
+        Self {
+            links: HashMap::new(),
+            head: None,
+            tail: None,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.links.len()
+    }
+
+    #[allow(dead_code)]
+    pub fn is_empty(&self) -> bool {
+        self.links.is_empty()
+    }
+
+    /// Add an entry to the back, moving it there if it's already in the set
+    pub fn push_back(&mut self, value: T) {
+        self.remove(value);
+        self.links.insert(
+            value,
+            Links {
+                prev: self.tail,
+                next: None,
+            },
+        );
+        match self.tail {
+            Some(tail) => self.links.get_mut(&tail).unwrap().next = Some(value),
+            None => self.head = Some(value),
+        }
+        self.tail = Some(value);
+    }
+
+    /// Take out an entry, returning whether it was there
+    pub fn remove(&mut self, value: T) -> bool {
+        let Some(Links { prev, next }) = self.links.remove(&value) else {
+            return false;
+        };
+        match prev {
+            Some(prev) => self.links.get_mut(&prev).unwrap().next = next,
+            None => self.head = next,
+        }
+        match next {
+            Some(next) => self.links.get_mut(&next).unwrap().prev = prev,
+            None => self.tail = prev,
+        }
+        true
+    }
+
+    /// Take out the entry at the front, if there is one
+    pub fn pop_front(&mut self) -> Option<T> {
+        let head = self.head?;
+        self.remove(head);
+        Some(head)
+    }
+
+    /// Every entry, front to back
+    pub fn iter(&self) -> impl Iterator<Item = T> + '_ {
+        std::iter::successors(self.head, |value| self.links[value].next)
+    }
+}
+
+impl<T: Copy + Eq + Hash> Default for LinkedSet<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Copy + Eq + Hash> FromIterator<T> for LinkedSet<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut set = Self::new();
+        for value in iter {
+            set.push_back(value);
+        }
+        set
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(vec![], vec![])]
+    #[case(vec![1, 2, 3], vec![1, 2, 3])]
+    // Pushing an entry that's already there moves it to the back
+    #[case(vec![1, 2, 3, 1], vec![2, 3, 1])]
+    #[case(vec![1, 2, 3, 3], vec![1, 2, 3])]
+    fn test_push_back(#[case] pushed: Vec<u64>, #[case] expected: Vec<u64>) {
+        let set = pushed.into_iter().collect::<LinkedSet<_>>();
+
+        assert_eq!(set.iter().collect::<Vec<_>>(), expected);
+        assert_eq!(set.len(), expected.len());
+    }
+
+    #[rstest]
+    #[case::front(1, vec![2, 3, 4])]
+    #[case::middle(3, vec![1, 2, 4])]
+    #[case::back(4, vec![1, 2, 3])]
+    fn test_remove(#[case] removed: u64, #[case] expected: Vec<u64>) {
+        let mut set = LinkedSet::from_iter([1, 2, 3, 4]);
+
+        assert!(set.remove(removed));
+        assert!(!set.remove(removed));
+        assert_eq!(set.iter().collect::<Vec<_>>(), expected);
+
+        // The ends are still joined up properly
+        set.push_back(5);
+        assert_eq!(set.pop_front(), Some(expected[0]));
+        assert_eq!(set.iter().last(), Some(5));
+    }
+
+    #[test]
+    fn test_pop_front() {
+        let mut set = LinkedSet::from_iter([1, 2]);
+
+        assert_eq!(set.pop_front(), Some(1));
+        assert_eq!(set.pop_front(), Some(2));
+        assert_eq!(set.pop_front(), None);
+        assert!(set.is_empty());
+
+        set.push_back(3);
+        assert_eq!(set.iter().collect::<Vec<_>>(), [3]);
+    }
+}
@@ -0,0 +1,302 @@
+use std::collections::VecDeque;
+
+use crate::dbms::types::{page_file_id, FileId, PageId};
+
+use super::buffer_pool_replacer::{BufferPoolReplacerError, IBufferPoolReplacer};
+
+#[derive(Debug, PartialEq, Clone, Default)]
+struct LruKFrame {
+    /// The page the access history belongs to
+    page_id: Option<PageId>,
+    /// Timestamps of the last K accesses, oldest first
+    history: VecDeque<u64>,
+    evictable: bool,
+}
+
+/// Evicts the frame whose Kth most recent access is furthest in the past,
+/// i.e. with the largest backward K-distance. Frames accessed fewer than K
+/// times have an infinite K-distance, and go first, oldest access first, so
+/// pages only touched once by a scan don't push out ones in regular use.
+pub struct LruKReplacer {
+    k: usize,
+    current_timestamp: u64,
+    frames: Vec<LruKFrame>,
+}
+
+impl LruKReplacer {
+    /// Creates a new [`LruKReplacer`] keeping the last `k` accesses to each
+    /// frame.
+    #[allow(dead_code)]
+    pub fn new(size: usize, k: usize) -> Self {
+        assert!(k > 0, "k must be at least 1");
+        LruKReplacer {
+            k,
+            current_timestamp: 0,
+            frames: vec![LruKFrame::default(); size],
+        }
+    }
+}
+
+impl LruKReplacer {
+    fn check_frame_id(&self, frame_id: usize) -> Result<(), BufferPoolReplacerError> {
+        if frame_id >= self.frames.len() {
+            return Err(BufferPoolReplacerError::FrameOutOfRange(format!(
+                "frame_id {} is out of range",
+                frame_id
+            )));
+        }
+        Ok(())
+    }
+
+    /// Sort key for picking a victim, the smallest being evicted first
+    fn eviction_order(&self, frame: &LruKFrame) -> (bool, u64) {
+        (
+            frame.history.len() >= self.k,
+            frame.history.front().copied().unwrap_or(0),
+        )
+    }
+}
+
+impl IBufferPoolReplacer for LruKReplacer {
+    fn victim(&mut self) -> Result<Option<usize>, BufferPoolReplacerError> {
+        let victim = self
+            .frames
+            .iter()
+            .enumerate()
+            .filter(|(_, frame)| frame.evictable)
+            .min_by_key(|(_, frame)| self.eviction_order(frame))
+            .map(|(frame_id, _)| frame_id);
+
+        if let Some(frame_id) = victim {
+            self.frames[frame_id].evictable = false;
+        }
+
+        Ok(victim)
+    }
+
+    fn pin(&mut self, frame_id: usize) -> Result<(), BufferPoolReplacerError> {
+        self.check_frame_id(frame_id)?;
+
+        self.frames[frame_id].evictable = false;
+        Ok(())
+    }
+
+    fn unpin(&mut self, frame_id: usize) -> Result<(), BufferPoolReplacerError> {
+        self.check_frame_id(frame_id)?;
+
+        self.frames[frame_id].evictable = true;
+        Ok(())
+    }
+
+    fn size(&self) -> Result<usize, BufferPoolReplacerError> {
+        Ok(self.frames.iter().filter(|frame| frame.evictable).count())
+    }
+
+    fn record_access(
+        &mut self,
+        frame_id: usize,
+        page_id: PageId,
+    ) -> Result<(), BufferPoolReplacerError> {
+        self.check_frame_id(frame_id)?;
+
+        let frame = &mut self.frames[frame_id];
+        // A different page's been loaded into the frame since, so the old
+        // history says nothing about this one
+        if frame.page_id != Some(page_id) {
+            frame.page_id = Some(page_id);
+            frame.history.clear();
+        }
+
+        frame.history.push_back(self.current_timestamp);
+        if frame.history.len() > self.k {
+            frame.history.pop_front();
+        }
+        self.current_timestamp += 1;
+
+        Ok(())
+    }
+
+    fn remove(
+        &mut self,
+        frame_id: Option<usize>,
+        page_id: PageId,
+    ) -> Result<(), BufferPoolReplacerError> {
+        // Only pages in frames have any history
+        if let Some(frame_id) = frame_id {
+            self.check_frame_id(frame_id)?;
+            let frame = &mut self.frames[frame_id];
+            if frame.page_id == Some(page_id) {
+                frame.page_id = None;
+                frame.history.clear();
+            }
+        }
+        Ok(())
+    }
+
+    fn remove_file(&mut self, file_id: FileId) -> Result<(), BufferPoolReplacerError> {
+        for frame in self.frames.iter_mut() {
+            if frame
+                .page_id
+                .is_some_and(|page_id| page_file_id(page_id) == file_id)
+            {
+                frame.page_id = None;
+                frame.history.clear();
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dbms::types::make_page_id;
+    use rstest::rstest;
+
+    /// Access each `(frame_id, page_id)` in order, then unpin every frame
+    fn replacer_with_accesses(size: usize, k: usize, accesses: &[(usize, PageId)]) -> LruKReplacer {
+        let mut replacer = LruKReplacer::new(size, k);
+        for &(frame_id, page_id) in accesses {
+            replacer.record_access(frame_id, page_id).unwrap();
+        }
+        for frame_id in 0..size {
+            replacer.unpin(frame_id).unwrap();
+        }
+        replacer
+    }
+
+    #[rstest]
+    #[case(3, 2, vec![], vec![0, 1, 2])]
+    // Frames with fewer than K accesses go first, oldest access first
+    #[case(3, 2, vec![(0, 10), (1, 11), (2, 12)], vec![0, 1, 2])]
+    #[case(3, 2, vec![(2, 12), (1, 11), (0, 10)], vec![2, 1, 0])]
+    #[case(3, 2, vec![(0, 10), (0, 10), (1, 11), (2, 12)], vec![1, 2, 0])]
+    // Then the largest backward K-distance, even if used more recently
+    #[case(2, 2, vec![(0, 10), (0, 10), (1, 11), (1, 11), (0, 10)], vec![0, 1])]
+    #[case(
+        3,
+        2,
+        vec![(0, 10), (1, 11), (0, 10), (2, 12), (1, 11), (2, 12)],
+        vec![0, 1, 2],
+    )]
+    // With K = 1 it's plain LRU
+    #[case(
+        3,
+        1,
+        vec![(0, 10), (1, 11), (2, 12), (0, 10)],
+        vec![1, 2, 0],
+    )]
+    // Only the last K accesses count
+    #[case(
+        2,
+        2,
+        vec![(0, 10), (0, 10), (0, 10), (1, 11), (1, 11)],
+        vec![0, 1],
+    )]
+    // A new page in a frame starts with no history
+    #[case(
+        2,
+        2,
+        vec![(0, 10), (0, 10), (1, 11), (1, 11), (0, 20)],
+        vec![0, 1],
+    )]
+    fn test_victim_order(
+        #[case] size: usize,
+        #[case] k: usize,
+        #[case] accesses: Vec<(usize, PageId)>,
+        #[case] expected_victims: Vec<usize>,
+    ) {
+        let mut replacer = replacer_with_accesses(size, k, &accesses);
+
+        let victims = (0..size)
+            .map(|_| replacer.victim().unwrap().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(victims, expected_victims);
+        assert_eq!(replacer.victim(), Ok(None));
+        assert_eq!(replacer.size(), Ok(0));
+    }
+
+    #[test]
+    fn test_pinned_frames_not_victims() {
+        let mut replacer = replacer_with_accesses(3, 2, &[(0, 10), (1, 11), (2, 12)]);
+
+        replacer.pin(0).unwrap();
+        assert_eq!(replacer.size(), Ok(2));
+        assert_eq!(replacer.victim(), Ok(Some(1)));
+
+        replacer.pin(2).unwrap();
+        assert_eq!(replacer.size(), Ok(0));
+        assert_eq!(replacer.victim(), Ok(None));
+
+        replacer.unpin(0).unwrap();
+        assert_eq!(replacer.size(), Ok(1));
+        assert_eq!(replacer.victim(), Ok(Some(0)));
+    }
+
+    #[test]
+    fn test_pin_and_unpin_idempotent() {
+        let mut replacer = LruKReplacer::new(2, 2);
+
+        replacer.unpin(1).unwrap();
+        replacer.unpin(1).unwrap();
+        assert_eq!(replacer.size(), Ok(1));
+
+        replacer.pin(1).unwrap();
+        replacer.pin(1).unwrap();
+        assert_eq!(replacer.size(), Ok(0));
+    }
+
+    #[test]
+    fn test_victim_history_kept_until_new_page() {
+        let mut replacer = replacer_with_accesses(2, 2, &[(0, 10), (0, 10), (1, 11)]);
+
+        // Given back after being picked, e.g. the pool didn't need it after
+        // all, it still has its page's history
+        assert_eq!(replacer.victim(), Ok(Some(1)));
+        replacer.unpin(1).unwrap();
+        replacer.record_access(1, 11).unwrap();
+        assert_eq!(replacer.victim(), Ok(Some(0)));
+    }
+
+    #[test]
+    fn test_removed_page_history_forgotten() {
+        let mut replacer = replacer_with_accesses(2, 2, &[(1, 11), (1, 11), (0, 10), (0, 10)]);
+
+        // The page in frame 0 was deleted, and its ID reused for a page
+        // that's only been accessed once
+        replacer.remove(Some(0), 10).unwrap();
+        replacer.record_access(0, 10).unwrap();
+        assert_eq!(replacer.victim(), Ok(Some(0)));
+        assert_eq!(replacer.victim(), Ok(Some(1)));
+    }
+
+    #[test]
+    fn test_removed_file_history_forgotten() {
+        let page_id = |file_id, page_number| make_page_id(file_id, page_number);
+        let mut replacer = replacer_with_accesses(
+            2,
+            2,
+            &[(0, page_id(1, 1)), (0, page_id(1, 1)), (1, page_id(2, 1))],
+        );
+
+        replacer.remove_file(1).unwrap();
+
+        assert_eq!(replacer.frames[0].page_id, None);
+        assert!(replacer.frames[0].history.is_empty());
+        assert_eq!(replacer.frames[1].page_id, Some(page_id(2, 1)));
+    }
+
+    #[rstest]
+    #[case(3, Err(BufferPoolReplacerError::FrameOutOfRange("frame_id 3 is out of range".to_string())))]
+    #[case(2, Ok(()))]
+    fn test_frame_out_of_range(
+        #[case] frame_id: usize,
+        #[case] expected_result: Result<(), BufferPoolReplacerError>,
+    ) {
+        let mut replacer = LruKReplacer::new(3, 2);
+
+        assert_eq!(replacer.record_access(frame_id, 10), expected_result);
+        assert_eq!(replacer.unpin(frame_id), expected_result);
+        assert_eq!(replacer.pin(frame_id), expected_result);
+    }
+}
@@ -0,0 +1,425 @@
+use crate::dbms::types::{page_file_id, FileId, PageId};
+
+use super::buffer_pool_replacer::{BufferPoolReplacerError, IBufferPoolReplacer};
+use super::linked_set::LinkedSet;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum TwoQueueList {
+    /// A1in, pages on their first stay in the pool
+    Recent,
+    /// Am, pages that have come back since they were evicted from A1in
+    Frequent,
+}
+
+#[derive(Debug, PartialEq, Clone, Default)]
+struct TwoQueueFrame {
+    page_id: Option<PageId>,
+    list: Option<TwoQueueList>,
+    evictable: bool,
+}
+
+/// 2Q. A page loaded for the first time goes in a FIFO queue, and further
+/// accesses while it's there don't count, as they tend to be part of the
+/// same operation. The IDs of pages evicted from it are remembered for a
+/// while, and a page loaded again in that time goes in an LRU list, which
+/// only gives up frames once the FIFO queue is down to its share of the
+/// pool. A scan passes through the FIFO queue without touching the LRU list.
+pub struct TwoQueueReplacer {
+    frames: Vec<TwoQueueFrame>,
+    /// Frames in A1in, oldest first
+    recent: LinkedSet<usize>,
+    /// Frames in Am, least recently used first
+    frequent: LinkedSet<usize>,
+    /// A1out, pages evicted from A1in, oldest first
+    recent_ghosts: LinkedSet<PageId>,
+    /// Kin, the number of frames A1in can hold before it gives them up first
+    max_recent_size: usize,
+    /// Kout, the number of pages A1out remembers
+    max_recent_ghosts: usize,
+}
+
+impl TwoQueueReplacer {
+    /// Creates a new [`TwoQueueReplacer`], giving a quarter of the pool to
+    /// the FIFO queue and remembering as many evicted pages as half the pool
+    /// holds.
+    #[allow(dead_code)]
+    pub fn new(size: usize) -> Self {
+        TwoQueueReplacer {
+            frames: vec![TwoQueueFrame::default(); size],
+            recent: LinkedSet::new(),
+            frequent: LinkedSet::new(),
+            recent_ghosts: LinkedSet::new(),
+            max_recent_size: usize::max(size / 4, 1),
+            max_recent_ghosts: usize::max(size / 2, 1),
+        }
+    }
+}
+
+impl TwoQueueReplacer {
+    fn check_frame_id(&self, frame_id: usize) -> Result<(), BufferPoolReplacerError> {
+        if frame_id >= self.frames.len() {
+            return Err(BufferPoolReplacerError::FrameOutOfRange(format!(
+                "frame_id {} is out of range",
+                frame_id
+            )));
+        }
+        Ok(())
+    }
+
+    fn list_mut(&mut self, list: TwoQueueList) -> &mut LinkedSet<usize> {
+        match list {
+            TwoQueueList::Recent => &mut self.recent,
+            TwoQueueList::Frequent => &mut self.frequent,
+        }
+    }
+
+    fn push_frame(&mut self, frame_id: usize, list: TwoQueueList) {
+        self.list_mut(list).push_back(frame_id);
+        self.frames[frame_id].list = Some(list);
+    }
+
+    fn remove_frame(&mut self, frame_id: usize) -> Option<TwoQueueList> {
+        let list = self.frames[frame_id].list.take()?;
+        self.list_mut(list).remove(frame_id);
+        Some(list)
+    }
+
+    /// Take a frame's page out of the lists, remembering it in A1out if it
+    /// was in A1in.
+    fn evict_page(&mut self, frame_id: usize) {
+        let page_id = self.frames[frame_id].page_id.take();
+        if let (Some(TwoQueueList::Recent), Some(page_id)) = (self.remove_frame(frame_id), page_id)
+        {
+            self.recent_ghosts.push_back(page_id);
+            if self.recent_ghosts.len() > self.max_recent_ghosts {
+                self.recent_ghosts.pop_front();
+            }
+        }
+    }
+
+    fn first_evictable(&self, list: &LinkedSet<usize>) -> Option<usize> {
+        list.iter()
+            .find(|&frame_id| self.frames[frame_id].evictable)
+    }
+}
+
+impl IBufferPoolReplacer for TwoQueueReplacer {
+    fn victim(&mut self) -> Result<Option<usize>, BufferPoolReplacerError> {
+        // Frames that have never been accessed have nothing worth keeping
+        let unlisted = self
+            .frames
+            .iter()
+            .position(|frame| frame.evictable && frame.list.is_none());
+        let victim = unlisted.or_else(|| {
+            let (first, second) = if self.recent.len() > self.max_recent_size {
+                (&self.recent, &self.frequent)
+            } else {
+                (&self.frequent, &self.recent)
+            };
+            self.first_evictable(first)
+                .or_else(|| self.first_evictable(second))
+        });
+
+        // The frame stays in its list until another page is loaded into it,
+        // in case it's handed back unused
+        if let Some(frame_id) = victim {
+            self.frames[frame_id].evictable = false;
+        }
+
+        Ok(victim)
+    }
+
+    fn pin(&mut self, frame_id: usize) -> Result<(), BufferPoolReplacerError> {
+        self.check_frame_id(frame_id)?;
+
+        self.frames[frame_id].evictable = false;
+        Ok(())
+    }
+
+    fn unpin(&mut self, frame_id: usize) -> Result<(), BufferPoolReplacerError> {
+        self.check_frame_id(frame_id)?;
+
+        self.frames[frame_id].evictable = true;
+        Ok(())
+    }
+
+    fn size(&self) -> Result<usize, BufferPoolReplacerError> {
+        Ok(self.frames.iter().filter(|frame| frame.evictable).count())
+    }
+
+    fn record_access(
+        &mut self,
+        frame_id: usize,
+        page_id: PageId,
+    ) -> Result<(), BufferPoolReplacerError> {
+        self.check_frame_id(frame_id)?;
+
+        let frame = &self.frames[frame_id];
+        if frame.page_id == Some(page_id) {
+            // Hits in A1in are left where they are
+            if frame.list == Some(TwoQueueList::Frequent) {
+                self.remove_frame(frame_id);
+                self.push_frame(frame_id, TwoQueueList::Frequent);
+            }
+            return Ok(());
+        }
+
+        // A new page in the frame, so the old one's been evicted
+        self.evict_page(frame_id);
+        self.frames[frame_id].page_id = Some(page_id);
+
+        if self.recent_ghosts.remove(page_id) {
+            self.push_frame(frame_id, TwoQueueList::Frequent);
+        } else {
+            self.push_frame(frame_id, TwoQueueList::Recent);
+        }
+
+        Ok(())
+    }
+
+    fn remove(
+        &mut self,
+        frame_id: Option<usize>,
+        page_id: PageId,
+    ) -> Result<(), BufferPoolReplacerError> {
+        if let Some(frame_id) = frame_id {
+            self.check_frame_id(frame_id)?;
+            if self.frames[frame_id].page_id == Some(page_id) {
+                self.remove_frame(frame_id);
+                self.frames[frame_id].page_id = None;
+            }
+        }
+        self.recent_ghosts.remove(page_id);
+        Ok(())
+    }
+
+    fn remove_file(&mut self, file_id: FileId) -> Result<(), BufferPoolReplacerError> {
+        let in_frames = self
+            .frames
+            .iter()
+            .enumerate()
+            .filter_map(|(frame_id, frame)| Some((Some(frame_id), frame.page_id?)));
+        let ghosts = self.recent_ghosts.iter().map(|page_id| (None, page_id));
+        let pages = in_frames
+            .chain(ghosts)
+            .filter(|&(_, page_id)| page_file_id(page_id) == file_id)
+            .collect::<Vec<_>>();
+
+        for (frame_id, page_id) in pages {
+            self.remove(frame_id, page_id)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dbms::types::make_page_id;
+    use rstest::rstest;
+
+    /// A list's entries, front to back
+    fn listed<T: Copy + Eq + std::hash::Hash>(list: &LinkedSet<T>) -> Vec<T> {
+        list.iter().collect()
+    }
+
+    /// Load a page into a frame the way the buffer pool does, accessing it
+    /// while pinned and then unpinning it
+    fn load(replacer: &mut TwoQueueReplacer, frame_id: usize, page_id: PageId) {
+        replacer.record_access(frame_id, page_id).unwrap();
+        replacer.unpin(frame_id).unwrap();
+    }
+
+    #[rstest]
+    #[case(1, 1, 1)]
+    #[case(4, 1, 2)]
+    #[case(10, 2, 5)]
+    #[case(64, 16, 32)]
+    fn test_queue_sizes(
+        #[case] size: usize,
+        #[case] expected_max_recent_size: usize,
+        #[case] expected_max_recent_ghosts: usize,
+    ) {
+        let replacer = TwoQueueReplacer::new(size);
+
+        assert_eq!(replacer.max_recent_size, expected_max_recent_size);
+        assert_eq!(replacer.max_recent_ghosts, expected_max_recent_ghosts);
+    }
+
+    #[rstest]
+    // A1in is first in, first out, however often its pages are accessed
+    #[case(vec![(0, 10), (1, 11), (2, 12)], vec![0, 1, 2])]
+    #[case(vec![(0, 10), (1, 11), (0, 10), (2, 12), (0, 10)], vec![0, 1, 2])]
+    #[case(vec![(2, 12), (1, 11), (0, 10)], vec![2, 1, 0])]
+    // A new page in a frame goes to the back of A1in
+    #[case(vec![(0, 10), (1, 11), (2, 12), (0, 13)], vec![1, 2, 0])]
+    fn test_victim_order(
+        #[case] accesses: Vec<(usize, PageId)>,
+        #[case] expected_victims: Vec<usize>,
+    ) {
+        let size = expected_victims.len();
+        let mut replacer = TwoQueueReplacer::new(size);
+        for (frame_id, page_id) in accesses {
+            load(&mut replacer, frame_id, page_id);
+        }
+
+        let victims = (0..size)
+            .map(|_| replacer.victim().unwrap().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(victims, expected_victims);
+        assert_eq!(replacer.victim(), Ok(None));
+        assert_eq!(replacer.size(), Ok(0));
+    }
+
+    #[test]
+    fn test_unaccessed_frames_first() {
+        let mut replacer = TwoQueueReplacer::new(3);
+        load(&mut replacer, 0, 10);
+        replacer.unpin(2).unwrap();
+
+        assert_eq!(replacer.victim(), Ok(Some(2)));
+        assert_eq!(replacer.victim(), Ok(Some(0)));
+        assert_eq!(replacer.victim(), Ok(None));
+    }
+
+    #[test]
+    fn test_page_loaded_again_promoted() {
+        let mut replacer = TwoQueueReplacer::new(4);
+        for frame_id in 0..4 {
+            load(&mut replacer, frame_id, 10 + frame_id as PageId);
+        }
+
+        // Pages evicted from A1in are remembered in A1out
+        assert_eq!(replacer.victim(), Ok(Some(0)));
+        load(&mut replacer, 0, 14);
+        assert_eq!(listed(&replacer.recent_ghosts), [10]);
+
+        // And go in Am if they're loaded again while remembered
+        assert_eq!(replacer.victim(), Ok(Some(1)));
+        load(&mut replacer, 1, 10);
+        assert_eq!(listed(&replacer.recent_ghosts), [11]);
+        assert_eq!(listed(&replacer.recent), [2, 3, 0]);
+        assert_eq!(listed(&replacer.frequent), [1]);
+
+        // Which keeps its frames while A1in is over its share of the pool
+        let victims = (0..4)
+            .map(|_| replacer.victim().unwrap().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(victims, [2, 3, 0, 1]);
+    }
+
+    #[test]
+    fn test_frequent_list_least_recently_used() {
+        let mut replacer = TwoQueueReplacer::new(4);
+        replacer.recent_ghosts = LinkedSet::from_iter([10, 11, 12]);
+        load(&mut replacer, 0, 10);
+        load(&mut replacer, 1, 11);
+        load(&mut replacer, 2, 12);
+        assert_eq!(listed(&replacer.frequent), [0, 1, 2]);
+
+        load(&mut replacer, 0, 10);
+        assert_eq!(listed(&replacer.frequent), [1, 2, 0]);
+        let victims = (0..3)
+            .map(|_| replacer.victim().unwrap().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(victims, [1, 2, 0]);
+    }
+
+    #[test]
+    fn test_recent_ghosts_bounded() {
+        let mut replacer = TwoQueueReplacer::new(4);
+        for page_id in 10..15 {
+            load(&mut replacer, 0, page_id);
+        }
+        assert_eq!(listed(&replacer.recent_ghosts), [12, 13]);
+
+        // Pages forgotten about are on their first stay again
+        load(&mut replacer, 1, 10);
+        assert_eq!(listed(&replacer.recent), [0, 1]);
+        assert!(replacer.frequent.is_empty());
+    }
+
+    #[test]
+    fn test_pages_evicted_from_frequent_list_forgotten() {
+        let mut replacer = TwoQueueReplacer::new(4);
+        replacer.recent_ghosts = LinkedSet::from_iter([10]);
+        load(&mut replacer, 0, 10);
+        load(&mut replacer, 0, 11);
+
+        assert!(replacer.recent_ghosts.is_empty());
+        assert_eq!(listed(&replacer.recent), [0]);
+    }
+
+    #[test]
+    fn test_removed_pages_forgotten() {
+        let mut replacer = TwoQueueReplacer::new(4);
+        replacer.recent_ghosts = LinkedSet::from_iter([10]);
+        load(&mut replacer, 0, 11);
+        load(&mut replacer, 1, 12);
+
+        // Deleted while only remembered
+        replacer.remove(None, 10).unwrap();
+        assert!(replacer.recent_ghosts.is_empty());
+        // Deleted from a frame, which isn't remembered as an eviction
+        replacer.pin(0).unwrap();
+        replacer.remove(Some(0), 11).unwrap();
+        assert_eq!(listed(&replacer.recent), [1]);
+        assert!(replacer.recent_ghosts.is_empty());
+
+        // So pages given their IDs start out on their first stay
+        load(&mut replacer, 0, 10);
+        load(&mut replacer, 2, 11);
+        assert_eq!(listed(&replacer.recent), [1, 0, 2]);
+        assert!(replacer.frequent.is_empty());
+    }
+
+    #[test]
+    fn test_removed_file_forgotten() {
+        let page_id = |file_id, page_number| make_page_id(file_id, page_number);
+        let mut replacer = TwoQueueReplacer::new(4);
+        replacer.recent_ghosts = LinkedSet::from_iter([page_id(1, 1), page_id(2, 1)]);
+        load(&mut replacer, 0, page_id(1, 1));
+        load(&mut replacer, 1, page_id(2, 2));
+        load(&mut replacer, 2, page_id(1, 2));
+
+        replacer.pin(0).unwrap();
+        replacer.pin(2).unwrap();
+        replacer.remove_file(1).unwrap();
+
+        assert!(replacer.frequent.is_empty());
+        assert_eq!(listed(&replacer.recent), [1]);
+        assert_eq!(listed(&replacer.recent_ghosts), [page_id(2, 1)]);
+    }
+
+    #[test]
+    fn test_pinned_frames_not_victims() {
+        let mut replacer = TwoQueueReplacer::new(4);
+        replacer.recent_ghosts = LinkedSet::from_iter([10]);
+        load(&mut replacer, 0, 10);
+        load(&mut replacer, 1, 11);
+        load(&mut replacer, 2, 12);
+        load(&mut replacer, 3, 13);
+
+        replacer.pin(1).unwrap();
+        replacer.pin(2).unwrap();
+        replacer.pin(3).unwrap();
+        assert_eq!(replacer.size(), Ok(1));
+        // Falls back to Am when nothing in A1in can go
+        assert_eq!(replacer.victim(), Ok(Some(0)));
+        assert_eq!(replacer.victim(), Ok(None));
+    }
+
+    #[rstest]
+    #[case(3, Err(BufferPoolReplacerError::FrameOutOfRange("frame_id 3 is out of range".to_string())))]
+    #[case(2, Ok(()))]
+    fn test_frame_out_of_range(
+        #[case] frame_id: usize,
+        #[case] expected_result: Result<(), BufferPoolReplacerError>,
+    ) {
+        let mut replacer = TwoQueueReplacer::new(3);
+
+        assert_eq!(replacer.record_access(frame_id, 10), expected_result);
+        assert_eq!(replacer.unpin(frame_id), expected_result);
+        assert_eq!(replacer.pin(frame_id), expected_result);
+    }
+}
@@ -0,0 +1,7 @@
+pub mod extendible_hash_table;
+mod hash_function;
+mod hash_table;
+pub mod linear_probe_hash_table;
+
+pub use hash_function::*;
+pub use hash_table::*;
@@ -0,0 +1,799 @@
+use std::marker::PhantomData;
+use std::sync::RwLock;
+
+use crate::dbms::{
+    buffer::pool_manager::{BufferPoolManager, IBufferPoolManager},
+    storage::{
+        page::hash_table::{
+            bucket::{
+                IHashTableBucketPageRead, IHashTableBucketPageWrite, ReadOnlyHashTableBucketPage,
+                WritableHashTableBucketPage,
+            },
+            directory::{
+                HashTableDirectoryError, IHashTableDirectoryPageRead, IHashTableDirectoryPageWrite,
+                ReadOnlyHashTableDirectoryPage, WritableHashTableDirectoryPage,
+            },
+            util::calculate_bucket_page_layout,
+        },
+        serialize::BytesSerialize,
+    },
+    types::PageId,
+};
+
+use super::{hash_key, HashTableError, IHashTable};
+
+/// A hash table using extendible hashing, stored across a directory page and
+/// a number of bucket pages in the buffer pool.
+///
+/// A key's bucket is found from the low `global_depth` bits of its hash. When
+/// a bucket fills up it's split in two on the next bit of the hash, doubling
+/// the directory first if the bucket already used every bit. When a bucket
+/// empties it's merged back into the bucket it was split from, and the
+/// directory is halved whenever it can be. Only the entries of the bucket
+/// being split move, so the table grows without rehashing everything.
+///
+/// All the values for a key live in the same bucket, so a key can have at most
+/// a bucket page's worth of values.
+///
+/// The table can be shared between threads. Inserts, removals and lookups take
+/// the table latch in shared mode and then latch one page at a time through
+/// the buffer pool. Splits and merges take the table latch exclusively.
+pub struct ExtendibleHashTable<KeyType: BytesSerialize, ValueType: BytesSerialize> {
+    buffer_pool_manager: BufferPoolManager,
+    directory_page_id: PageId,
+    /// Latch on the whole table, held exclusively while the directory changes
+    table_latch: RwLock<()>,
+
+    _phantom: PhantomData<KeyType>,
+    _phantom2: PhantomData<ValueType>,
+}
+
+impl<KeyType, ValueType> ExtendibleHashTable<KeyType, ValueType>
+where
+    KeyType: BytesSerialize + PartialEq + Clone,
+    ValueType: BytesSerialize + PartialEq + Clone,
+{
+    /// Create a new, empty hash table, allocating its directory page and a
+    /// first bucket page from the buffer pool.
+    #[allow(dead_code)]
+    pub fn new(buffer_pool_manager: BufferPoolManager) -> Result<Self, HashTableError> {
+        Self::check_layout(&buffer_pool_manager)?;
+
+        // A zeroed page is an empty bucket page, so there's nothing to write yet
+        let bucket_page_id = buffer_pool_manager.new_page()?.get_page_id()?.unwrap();
+        buffer_pool_manager.unpin_page(bucket_page_id, true)?;
+
+        let directory_page_id = {
+            let mut directory =
+                WritableHashTableDirectoryPage::new(buffer_pool_manager.new_page()?);
+            directory.initialize(bucket_page_id)?;
+            directory.get_page_id()?
+        };
+        buffer_pool_manager.unpin_page(directory_page_id, true)?;
+
+        Ok(Self {
+            buffer_pool_manager,
+            directory_page_id,
+            table_latch: RwLock::new(()),
+            _phantom: PhantomData,
+            _phantom2: PhantomData,
+        })
+    }
+
+    /// Open an existing hash table from its directory page.
+    #[allow(dead_code)]
+    pub fn open(
+        buffer_pool_manager: BufferPoolManager,
+        directory_page_id: PageId,
+    ) -> Result<Self, HashTableError> {
+        Self::check_layout(&buffer_pool_manager)?;
+
+        Ok(Self {
+            buffer_pool_manager,
+            directory_page_id,
+            table_latch: RwLock::new(()),
+            _phantom: PhantomData,
+            _phantom2: PhantomData,
+        })
+    }
+
+    /// The page ID of the table's directory page, used to reopen the table.
+    #[allow(dead_code)]
+    pub fn directory_page_id(&self) -> PageId {
+        self.directory_page_id
+    }
+
+    /// Number of hash bits currently used to index the directory.
+    #[allow(dead_code)]
+    pub fn global_depth(&self) -> Result<u32, HashTableError> {
+        let _latch = self.table_latch.read().unwrap();
+        let result = {
+            let directory = ReadOnlyHashTableDirectoryPage::new(
+                self.buffer_pool_manager
+                    .fetch_page(self.directory_page_id)?,
+            );
+            directory.get_global_depth()
+        };
+        self.buffer_pool_manager
+            .unpin_page(self.directory_page_id, false)?;
+        Ok(result?)
+    }
+
+    /// Check the key and value types fit in a bucket page.
+    fn check_layout(buffer_pool_manager: &BufferPoolManager) -> Result<(), HashTableError> {
+        calculate_bucket_page_layout(
+            KeyType::serialized_size() + ValueType::serialized_size(),
+            buffer_pool_manager.page_size(),
+        )?;
+        Ok(())
+    }
+
+    /// Look up the directory entry for a hash, as `(directory index, bucket
+    /// page ID)`.
+    fn bucket_for(&self, hash: u64) -> Result<(usize, PageId), HashTableError> {
+        let result = {
+            let directory = ReadOnlyHashTableDirectoryPage::new(
+                self.buffer_pool_manager
+                    .fetch_page(self.directory_page_id)?,
+            );
+            directory.get_global_depth().and_then(|global_depth| {
+                let index = (hash & ((1 << global_depth) - 1)) as usize;
+                Ok((index, directory.get_bucket_page_id(index)?))
+            })
+        };
+        self.buffer_pool_manager
+            .unpin_page(self.directory_page_id, false)?;
+        Ok(result?)
+    }
+
+    /// All the entries in a bucket.
+    fn bucket_entries(
+        &self,
+        bucket_page_id: PageId,
+    ) -> Result<Vec<(KeyType, ValueType)>, HashTableError> {
+        let result = {
+            let bucket = ReadOnlyHashTableBucketPage::<KeyType, ValueType>::new(
+                self.buffer_pool_manager.fetch_page(bucket_page_id)?,
+            );
+            Self::entries_in_bucket(&bucket)
+        };
+        self.buffer_pool_manager.unpin_page(bucket_page_id, false)?;
+        result
+    }
+
+    fn entries_in_bucket(
+        bucket: &impl IHashTableBucketPageRead<KeyType, ValueType>,
+    ) -> Result<Vec<(KeyType, ValueType)>, HashTableError> {
+        let mut entries = Vec::new();
+        for slot in 0..bucket.num_slots() {
+            if bucket.slot_occupied(slot)? {
+                entries.push((bucket.key_at(slot)?, bucket.value_at(slot)?));
+            }
+        }
+        Ok(entries)
+    }
+
+    /// Try to insert the pair into a bucket. Returns `Some(inserted)`, or
+    /// `None` if the bucket is full.
+    fn insert_in_bucket(
+        bucket: &mut impl IHashTableBucketPageWrite<KeyType, ValueType>,
+        key: &KeyType,
+        value: &ValueType,
+    ) -> Result<Option<bool>, HashTableError> {
+        let mut free_slot = None;
+        for slot in 0..bucket.num_slots() {
+            if !bucket.slot_occupied(slot)? {
+                free_slot = free_slot.or(Some(slot));
+            } else if bucket.key_at(slot)? == *key && bucket.value_at(slot)? == *value {
+                return Ok(Some(false));
+            }
+        }
+
+        match free_slot {
+            Some(slot) => {
+                bucket.put_slot(slot, key.clone(), value.clone())?;
+                Ok(Some(true))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Try to remove the pair from a bucket, returning whether it was found.
+    fn remove_in_bucket(
+        bucket: &mut impl IHashTableBucketPageWrite<KeyType, ValueType>,
+        key: &KeyType,
+        value: &ValueType,
+    ) -> Result<bool, HashTableError> {
+        for slot in 0..bucket.num_slots() {
+            if bucket.slot_occupied(slot)?
+                && bucket.key_at(slot)? == *key
+                && bucket.value_at(slot)? == *value
+            {
+                bucket.remove_slot(slot)?;
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Split the bucket a hash belongs to, if it's still full, moving the
+    /// entries with the next hash bit set into a new bucket. Needs the table
+    /// latch held exclusively.
+    fn split_bucket(&self, hash: u64) -> Result<(), HashTableError> {
+        let (index, bucket_page_id) = self.bucket_for(hash)?;
+        let result = {
+            let bucket = ReadOnlyHashTableBucketPage::<KeyType, ValueType>::new(
+                self.buffer_pool_manager.fetch_page(bucket_page_id)?,
+            );
+            Self::entries_in_bucket(&bucket).map(|entries| (entries, bucket.num_slots()))
+        };
+        self.buffer_pool_manager.unpin_page(bucket_page_id, false)?;
+        let (entries, capacity) = result?;
+        // Another thread may have already split it or removed from it while
+        // we waited for the latch
+        if entries.len() < capacity {
+            return Ok(());
+        }
+
+        let new_bucket_page_id = self.buffer_pool_manager.new_page()?.get_page_id()?.unwrap();
+        self.buffer_pool_manager
+            .unpin_page(new_bucket_page_id, true)?;
+
+        let result = {
+            let mut directory = WritableHashTableDirectoryPage::new(
+                self.buffer_pool_manager
+                    .fetch_page_writable(self.directory_page_id)?,
+            );
+            Self::split_directory_entries(&mut directory, index, new_bucket_page_id)
+        };
+        self.buffer_pool_manager
+            .unpin_page(self.directory_page_id, true)?;
+        let local_depth = match result {
+            Ok(local_depth) => local_depth,
+            Err(e) => {
+                self.buffer_pool_manager.delete_page(new_bucket_page_id)?;
+                return Err(match e {
+                    HashTableDirectoryError::MaxDepthReached => HashTableError::TableFull,
+                    e => e.into(),
+                });
+            }
+        };
+
+        let (moved, kept): (Vec<_>, Vec<_>) = entries
+            .into_iter()
+            .map(|(key, value)| Ok((hash_key(&key)?, key, value)))
+            .collect::<Result<Vec<_>, HashTableError>>()?
+            .into_iter()
+            .partition(|(hash, _, _)| hash & (1 << local_depth) != 0);
+
+        self.rewrite_bucket(bucket_page_id, kept)?;
+        self.rewrite_bucket(new_bucket_page_id, moved)
+    }
+
+    /// Point the upper half of the entries for the bucket at the directory
+    /// index to a new bucket, doubling the directory first if needed. Returns
+    /// the bucket's local depth from before the split, which is the hash bit
+    /// that now picks between the two buckets.
+    fn split_directory_entries(
+        directory: &mut impl IHashTableDirectoryPageWrite,
+        index: usize,
+        new_bucket_page_id: PageId,
+    ) -> Result<u32, HashTableDirectoryError> {
+        let local_depth = directory.get_local_depth(index)?;
+        if local_depth == directory.get_global_depth()? {
+            directory.incr_global_depth()?;
+        }
+
+        let bucket_page_id = directory.get_bucket_page_id(index)?;
+        for i in 0..directory.size()? {
+            if directory.get_bucket_page_id(i)? == bucket_page_id {
+                directory.set_local_depth(i, local_depth + 1)?;
+                if i & (1 << local_depth) != 0 {
+                    directory.set_bucket_page_id(i, new_bucket_page_id)?;
+                }
+            }
+        }
+        Ok(local_depth)
+    }
+
+    /// Replace the contents of a bucket with the given entries.
+    fn rewrite_bucket(
+        &self,
+        bucket_page_id: PageId,
+        entries: Vec<(u64, KeyType, ValueType)>,
+    ) -> Result<(), HashTableError> {
+        let result = {
+            let mut bucket = WritableHashTableBucketPage::<KeyType, ValueType>::new(
+                self.buffer_pool_manager
+                    .fetch_page_writable(bucket_page_id)?,
+            );
+            (0..bucket.num_slots())
+                .try_for_each(|slot| bucket.remove_slot(slot))
+                .and_then(|_| {
+                    entries
+                        .into_iter()
+                        .enumerate()
+                        .try_for_each(|(slot, (_, key, value))| bucket.put_slot(slot, key, value))
+                })
+        };
+        self.buffer_pool_manager.unpin_page(bucket_page_id, true)?;
+        Ok(result?)
+    }
+
+    /// Merge the bucket a hash belongs to with its split image for as long as
+    /// either of them is empty, then shrink the directory as far as it'll go.
+    /// Needs the table latch held exclusively.
+    fn merge_bucket(&self, hash: u64) -> Result<(), HashTableError> {
+        loop {
+            let (index, bucket_page_id) = self.bucket_for(hash)?;
+            let Some(image_page_id) = self.split_image(index)? else {
+                break;
+            };
+            let (removed_page_id, kept_page_id) = if self.bucket_entries(bucket_page_id)?.is_empty()
+            {
+                (bucket_page_id, image_page_id)
+            } else if self.bucket_entries(image_page_id)?.is_empty() {
+                (image_page_id, bucket_page_id)
+            } else {
+                break;
+            };
+
+            let result = {
+                let mut directory = WritableHashTableDirectoryPage::new(
+                    self.buffer_pool_manager
+                        .fetch_page_writable(self.directory_page_id)?,
+                );
+                Self::merge_directory_entries(&mut directory, removed_page_id, kept_page_id)
+            };
+            self.buffer_pool_manager
+                .unpin_page(self.directory_page_id, true)?;
+            result?;
+
+            self.buffer_pool_manager.delete_page(removed_page_id)?;
+        }
+
+        let result = {
+            let mut directory = WritableHashTableDirectoryPage::new(
+                self.buffer_pool_manager
+                    .fetch_page_writable(self.directory_page_id)?,
+            );
+            Self::shrink_directory(&mut directory)
+        };
+        let shrunk = matches!(result, Ok(true));
+        self.buffer_pool_manager
+            .unpin_page(self.directory_page_id, shrunk)?;
+        result?;
+        Ok(())
+    }
+
+    /// The bucket page that the bucket at the directory index was split from
+    /// or into, if the two are at the same local depth and so can be merged.
+    fn split_image(&self, index: usize) -> Result<Option<PageId>, HashTableError> {
+        let result = {
+            let directory = ReadOnlyHashTableDirectoryPage::new(
+                self.buffer_pool_manager
+                    .fetch_page(self.directory_page_id)?,
+            );
+            Self::split_image_in_directory(&directory, index)
+        };
+        self.buffer_pool_manager
+            .unpin_page(self.directory_page_id, false)?;
+        Ok(result?)
+    }
+
+    fn split_image_in_directory(
+        directory: &impl IHashTableDirectoryPageRead,
+        index: usize,
+    ) -> Result<Option<PageId>, HashTableDirectoryError> {
+        let local_depth = directory.get_local_depth(index)?;
+        if local_depth == 0 {
+            return Ok(None);
+        }
+        let image_index = index ^ (1 << (local_depth - 1));
+        if directory.get_local_depth(image_index)? != local_depth {
+            return Ok(None);
+        }
+        Ok(Some(directory.get_bucket_page_id(image_index)?))
+    }
+
+    /// Point every directory entry for a pair of split images at the one being
+    /// kept, one bit shallower.
+    fn merge_directory_entries(
+        directory: &mut impl IHashTableDirectoryPageWrite,
+        removed_page_id: PageId,
+        kept_page_id: PageId,
+    ) -> Result<(), HashTableDirectoryError> {
+        for i in 0..directory.size()? {
+            let page_id = directory.get_bucket_page_id(i)?;
+            if page_id == removed_page_id || page_id == kept_page_id {
+                let local_depth = directory.get_local_depth(i)?;
+                directory.set_bucket_page_id(i, kept_page_id)?;
+                directory.set_local_depth(i, local_depth - 1)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Halve the directory until some bucket needs every bit of the global
+    /// depth. Returns whether the directory changed.
+    fn shrink_directory(
+        directory: &mut impl IHashTableDirectoryPageWrite,
+    ) -> Result<bool, HashTableDirectoryError> {
+        let mut shrunk = false;
+        while directory.can_shrink()? {
+            directory.decr_global_depth()?;
+            shrunk = true;
+        }
+        Ok(shrunk)
+    }
+}
+
+impl<KeyType, ValueType> IHashTable<KeyType, ValueType> for ExtendibleHashTable<KeyType, ValueType>
+where
+    KeyType: BytesSerialize + PartialEq + Clone,
+    ValueType: BytesSerialize + PartialEq + Clone,
+{
+    fn insert(&self, key: &KeyType, value: &ValueType) -> Result<bool, HashTableError> {
+        let hash = hash_key(key)?;
+        loop {
+            {
+                let _latch = self.table_latch.read().unwrap();
+                let (_, bucket_page_id) = self.bucket_for(hash)?;
+                let result = {
+                    let mut bucket = WritableHashTableBucketPage::<KeyType, ValueType>::new(
+                        self.buffer_pool_manager
+                            .fetch_page_writable(bucket_page_id)?,
+                    );
+                    Self::insert_in_bucket(&mut bucket, key, value)
+                };
+                let inserted = matches!(result, Ok(Some(true)));
+                self.buffer_pool_manager
+                    .unpin_page(bucket_page_id, inserted)?;
+
+                if let Some(inserted) = result? {
+                    return Ok(inserted);
+                }
+            }
+
+            // The key's bucket is full, so split it and try again
+            let _latch = self.table_latch.write().unwrap();
+            self.split_bucket(hash)?;
+        }
+    }
+
+    fn remove(&self, key: &KeyType, value: &ValueType) -> Result<bool, HashTableError> {
+        let hash = hash_key(key)?;
+        let now_empty = {
+            let _latch = self.table_latch.read().unwrap();
+            let (_, bucket_page_id) = self.bucket_for(hash)?;
+            let result = {
+                let mut bucket = WritableHashTableBucketPage::<KeyType, ValueType>::new(
+                    self.buffer_pool_manager
+                        .fetch_page_writable(bucket_page_id)?,
+                );
+                Self::remove_in_bucket(&mut bucket, key, value)
+                    .and_then(|removed| Ok((removed, removed && bucket.num_occupied()? == 0)))
+            };
+            let removed = matches!(result, Ok((true, _)));
+            self.buffer_pool_manager
+                .unpin_page(bucket_page_id, removed)?;
+
+            match result? {
+                (false, _) => return Ok(false),
+                (true, now_empty) => now_empty,
+            }
+        };
+
+        if now_empty {
+            let _latch = self.table_latch.write().unwrap();
+            self.merge_bucket(hash)?;
+        }
+        Ok(true)
+    }
+
+    fn get_value(&self, key: &KeyType) -> Result<Vec<ValueType>, HashTableError> {
+        let hash = hash_key(key)?;
+        let _latch = self.table_latch.read().unwrap();
+        let (_, bucket_page_id) = self.bucket_for(hash)?;
+
+        Ok(self
+            .bucket_entries(bucket_page_id)?
+            .into_iter()
+            .filter(|(k, _)| k == key)
+            .map(|(_, value)| value)
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dbms::buffer::pool_manager::testing::create_testing_pool_manager;
+    use crate::dbms::storage::page::hash_table::directory::max_global_depth;
+    use crate::dbms::types::DEFAULT_PAGE_SIZE;
+    use crate::{tuple, tuple_type};
+    use rstest::*;
+    use std::sync::Arc;
+
+    type TestTable = ExtendibleHashTable<tuple_type![u32], tuple_type![u32, f64]>;
+
+    /// Number of entries a bucket page holds for the test table's types
+    fn bucket_capacity() -> usize {
+        calculate_bucket_page_layout(
+            <tuple_type![u32]>::serialized_size() + <tuple_type![u32, f64]>::serialized_size(),
+            DEFAULT_PAGE_SIZE,
+        )
+        .unwrap()
+        .max_values
+    }
+
+    #[rstest]
+    fn test_insert_and_get_value() {
+        let table = TestTable::new(create_testing_pool_manager(10)).unwrap();
+
+        assert!(table.insert(&1, &tuple![10, 1.5]).unwrap());
+        assert!(table.insert(&2, &tuple![20, 2.5]).unwrap());
+
+        assert_eq!(table.get_value(&1).unwrap(), vec![tuple![10, 1.5]]);
+        assert_eq!(table.get_value(&2).unwrap(), vec![tuple![20, 2.5]]);
+        assert_eq!(table.get_value(&3).unwrap(), vec![]);
+        assert_eq!(table.global_depth().unwrap(), 0);
+    }
+
+    #[rstest]
+    fn test_multiple_values_for_key() {
+        let table = TestTable::new(create_testing_pool_manager(10)).unwrap();
+
+        assert!(table.insert(&1, &tuple![10, 1.5]).unwrap());
+        assert!(table.insert(&1, &tuple![11, 1.5]).unwrap());
+        assert!(table.insert(&1, &tuple![12, 1.5]).unwrap());
+
+        assert_eq!(
+            table.get_value(&1).unwrap(),
+            vec![tuple![10, 1.5], tuple![11, 1.5], tuple![12, 1.5]]
+        );
+    }
+
+    #[rstest]
+    fn test_insert_duplicate_pair() {
+        let table = TestTable::new(create_testing_pool_manager(10)).unwrap();
+
+        assert!(table.insert(&1, &tuple![10, 1.5]).unwrap());
+        assert!(!table.insert(&1, &tuple![10, 1.5]).unwrap());
+
+        assert_eq!(table.get_value(&1).unwrap(), vec![tuple![10, 1.5]]);
+    }
+
+    #[rstest]
+    fn test_remove() {
+        let table = TestTable::new(create_testing_pool_manager(10)).unwrap();
+
+        table.insert(&1, &tuple![10, 1.5]).unwrap();
+        table.insert(&1, &tuple![11, 1.5]).unwrap();
+
+        assert!(table.remove(&1, &tuple![10, 1.5]).unwrap());
+        assert!(!table.remove(&1, &tuple![10, 1.5]).unwrap());
+        assert!(!table.remove(&2, &tuple![10, 1.5]).unwrap());
+
+        assert_eq!(table.get_value(&1).unwrap(), vec![tuple![11, 1.5]]);
+    }
+
+    #[rstest]
+    fn test_removed_slot_is_reused() {
+        let table = TestTable::new(create_testing_pool_manager(10)).unwrap();
+        let capacity = bucket_capacity() as u32;
+
+        // Keep the one bucket full, removing and inserting without splitting
+        for i in 0..capacity {
+            table.insert(&i, &tuple![i, 0.0]).unwrap();
+        }
+        for i in 0..capacity {
+            table.remove(&i, &tuple![i, 0.0]).unwrap();
+            table.insert(&(i + capacity), &tuple![i, 0.0]).unwrap();
+        }
+
+        assert_eq!(table.global_depth().unwrap(), 0);
+        for i in 0..capacity {
+            assert_eq!(table.get_value(&i).unwrap(), vec![]);
+            assert_eq!(
+                table.get_value(&(i + capacity)).unwrap(),
+                vec![tuple![i, 0.0]]
+            );
+        }
+    }
+
+    #[rstest]
+    #[case(2)]
+    #[case(50)]
+    fn test_split_buckets(#[case] pool_size: usize) {
+        let table = TestTable::new(create_testing_pool_manager(pool_size)).unwrap();
+        let num_keys = 5 * bucket_capacity() as u32;
+
+        for i in 0..num_keys {
+            assert!(table.insert(&i, &tuple![i, i as f64]).unwrap());
+        }
+
+        assert!(table.global_depth().unwrap() >= 3);
+        for i in 0..num_keys {
+            assert_eq!(table.get_value(&i).unwrap(), vec![tuple![i, i as f64]]);
+        }
+    }
+
+    #[rstest]
+    fn test_split_keeps_multiple_values() {
+        let table = TestTable::new(create_testing_pool_manager(10)).unwrap();
+        let num_keys = 2 * bucket_capacity() as u32;
+
+        for i in 0..num_keys {
+            table.insert(&(i % 10), &tuple![i, 0.0]).unwrap();
+        }
+
+        assert!(table.global_depth().unwrap() >= 1);
+        for key in 0..10 {
+            let values = table.get_value(&key).unwrap();
+            let expected = (0..num_keys)
+                .filter(|i| i % 10 == key)
+                .map(|i| tuple![i, 0.0])
+                .collect::<Vec<_>>();
+            assert_eq!(values.len(), expected.len());
+            assert!(expected.iter().all(|value| values.contains(value)));
+        }
+    }
+
+    #[rstest]
+    fn test_remove_merges_buckets() {
+        let pool_manager = create_testing_pool_manager(10);
+        let table = TestTable::new(pool_manager.clone()).unwrap();
+        let num_keys = 5 * bucket_capacity() as u32;
+
+        for i in 0..num_keys {
+            table.insert(&i, &tuple![i, 0.0]).unwrap();
+        }
+        assert!(table.global_depth().unwrap() > 0);
+
+        for i in 0..num_keys {
+            assert!(table.remove(&i, &tuple![i, 0.0]).unwrap());
+        }
+
+        // Everything has merged back down to a single bucket
+        assert_eq!(table.global_depth().unwrap(), 0);
+        for i in 0..num_keys {
+            assert_eq!(table.get_value(&i).unwrap(), vec![]);
+        }
+
+        // ...which still takes inserts and splits again as normal
+        for i in 0..num_keys {
+            assert!(table.insert(&i, &tuple![i, 1.0]).unwrap());
+        }
+        for i in 0..num_keys {
+            assert_eq!(table.get_value(&i).unwrap(), vec![tuple![i, 1.0]]);
+        }
+    }
+
+    #[rstest]
+    fn test_remove_all_but_one_key() {
+        let table = TestTable::new(create_testing_pool_manager(10)).unwrap();
+        let num_keys = 5 * bucket_capacity() as u32;
+
+        for i in 0..num_keys {
+            table.insert(&i, &tuple![i, 0.0]).unwrap();
+        }
+
+        // Buckets emptied before their split image can still be merged later
+        for i in 1..num_keys {
+            table.remove(&i, &tuple![i, 0.0]).unwrap();
+        }
+
+        assert_eq!(table.global_depth().unwrap(), 0);
+        assert_eq!(table.get_value(&0).unwrap(), vec![tuple![0, 0.0]]);
+    }
+
+    #[rstest]
+    fn test_key_with_too_many_values() {
+        let table = TestTable::new(create_testing_pool_manager(10)).unwrap();
+        let capacity = bucket_capacity() as u32;
+
+        // Every value for a key has to share a bucket, so splitting can't help
+        for i in 0..capacity {
+            assert!(table.insert(&1, &tuple![i, 0.0]).unwrap());
+        }
+        let result = table.insert(&1, &tuple![capacity, 0.0]);
+
+        assert!(matches!(result, Err(HashTableError::TableFull)));
+        assert_eq!(
+            table.global_depth().unwrap(),
+            max_global_depth(DEFAULT_PAGE_SIZE)
+        );
+        assert_eq!(table.get_value(&1).unwrap().len(), capacity as usize);
+
+        // Other keys still have room
+        assert!(table.insert(&2, &tuple![0, 0.0]).unwrap());
+        assert_eq!(table.get_value(&2).unwrap(), vec![tuple![0, 0.0]]);
+    }
+
+    #[rstest]
+    fn test_reopen_table() {
+        let pool_manager = create_testing_pool_manager(10);
+        let directory_page_id = {
+            let table = TestTable::new(pool_manager.clone()).unwrap();
+            for i in 0..1000 {
+                table.insert(&i, &tuple![i, 0.5]).unwrap();
+            }
+            table.directory_page_id()
+        };
+
+        let table = TestTable::open(pool_manager, directory_page_id).unwrap();
+        for i in 0..1000 {
+            assert_eq!(table.get_value(&i).unwrap(), vec![tuple![i, 0.5]]);
+        }
+    }
+
+    #[rstest]
+    fn test_threaded_inserts_no_lost_entries() {
+        let table = Arc::new(TestTable::new(create_testing_pool_manager(20)).unwrap());
+        let num_threads = 8;
+        let per_thread = 250;
+
+        let threads = (0..num_threads)
+            .map(|t| {
+                let table = table.clone();
+                std::thread::spawn(move || {
+                    for i in 0..per_thread {
+                        let key = t * per_thread + i;
+                        assert!(table.insert(&key, &tuple![key, t as f64]).unwrap());
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        assert!(table.global_depth().unwrap() > 0);
+        for t in 0..num_threads {
+            for i in 0..per_thread {
+                let key = t * per_thread + i;
+                assert_eq!(table.get_value(&key).unwrap(), vec![tuple![key, t as f64]]);
+            }
+        }
+    }
+
+    #[rstest]
+    fn test_threaded_readers_and_writers() {
+        let table = Arc::new(TestTable::new(create_testing_pool_manager(20)).unwrap());
+        for i in 0..1000 {
+            table.insert(&i, &tuple![i, 0.0]).unwrap();
+        }
+
+        let mut threads = Vec::new();
+        // Writers remove the first half, splitting and merging as they go
+        for t in 0..4u32 {
+            let table = table.clone();
+            threads.push(std::thread::spawn(move || {
+                for i in t * 125..(t + 1) * 125 {
+                    assert!(table.remove(&i, &tuple![i, 0.0]).unwrap());
+                    assert!(table.insert(&(i + 1000), &tuple![i, 0.0]).unwrap());
+                }
+            }));
+        }
+        // Readers only look at the second half, which nobody touches
+        for _ in 0..4 {
+            let table = table.clone();
+            threads.push(std::thread::spawn(move || {
+                for i in 500..1000 {
+                    assert_eq!(table.get_value(&i).unwrap(), vec![tuple![i, 0.0]]);
+                }
+            }));
+        }
+
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        for i in 0..500 {
+            assert_eq!(table.get_value(&i).unwrap(), vec![]);
+            assert_eq!(table.get_value(&(i + 1000)).unwrap(), vec![tuple![i, 0.0]]);
+        }
+    }
+}
@@ -0,0 +1,42 @@
+use crate::dbms::storage::serialize::{BytesSerialize, SerializeError};
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+const FNV_PRIME: u64 = 0x100000001b3;
+
+/// Hash a key from its serialized bytes using 64-bit FNV-1a.
+///
+/// The hash decides where an entry is stored on disk, so it has to be stable
+/// across runs and Rust versions, which rules out `DefaultHasher`.
+pub fn hash_key<K: BytesSerialize>(key: &K) -> Result<u64, SerializeError> {
+    let hash = key
+        .to_bytes()?
+        .iter()
+        .fold(FNV_OFFSET_BASIS, |hash, &byte| {
+            (hash ^ byte as u64).wrapping_mul(FNV_PRIME)
+        });
+    Ok(hash)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tuple;
+    use rstest::*;
+
+    #[rstest]
+    #[case(0u8, 0xaf63bd4c8601b7df)]
+    #[case(1u32, 0x4d25757f9dce1242)]
+    fn test_hash_key_known_values(#[case] key: impl BytesSerialize, #[case] expected: u64) {
+        assert_eq!(hash_key(&key).unwrap(), expected);
+    }
+
+    #[rstest]
+    fn test_hash_key_depends_on_all_bytes() {
+        let hash_1 = hash_key(&tuple![1u32, 2u64]).unwrap();
+        let hash_2 = hash_key(&tuple![1u32, 3u64]).unwrap();
+        let hash_3 = hash_key(&tuple![2u32, 2u64]).unwrap();
+        assert_ne!(hash_1, hash_2);
+        assert_ne!(hash_1, hash_3);
+        assert_ne!(hash_2, hash_3);
+    }
+}
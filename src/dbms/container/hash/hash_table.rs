@@ -0,0 +1,90 @@
+use crate::dbms::{
+    buffer::pool_manager::BufferPoolManagerError,
+    storage::{
+        page::{
+            hash_table::{
+                block::HashTableBlockError, bucket::HashTableBucketError,
+                directory::HashTableDirectoryError, header::HashTableHeaderError,
+                util::PageLayoutError,
+            },
+            PageError,
+        },
+        serialize::SerializeError,
+    },
+};
+
+#[derive(Debug)]
+pub enum HashTableError {
+    /// Requested table size is invalid, e.g. zero or needing more block pages
+    /// than fit in a header page
+    InvalidSize(String),
+    /// There's no room left in the table for the entry
+    TableFull,
+    BufferPoolManagerError(BufferPoolManagerError),
+    PageError(PageError),
+    HeaderError(HashTableHeaderError),
+    BlockError(HashTableBlockError),
+    DirectoryError(HashTableDirectoryError),
+    BucketError(HashTableBucketError),
+    LayoutError(PageLayoutError),
+    SerializeError(SerializeError),
+}
+
+impl From<BufferPoolManagerError> for HashTableError {
+    fn from(e: BufferPoolManagerError) -> Self {
+        Self::BufferPoolManagerError(e)
+    }
+}
+
+impl From<PageError> for HashTableError {
+    fn from(e: PageError) -> Self {
+        Self::PageError(e)
+    }
+}
+
+impl From<HashTableHeaderError> for HashTableError {
+    fn from(e: HashTableHeaderError) -> Self {
+        Self::HeaderError(e)
+    }
+}
+
+impl From<HashTableBlockError> for HashTableError {
+    fn from(e: HashTableBlockError) -> Self {
+        Self::BlockError(e)
+    }
+}
+
+impl From<HashTableDirectoryError> for HashTableError {
+    fn from(e: HashTableDirectoryError) -> Self {
+        Self::DirectoryError(e)
+    }
+}
+
+impl From<HashTableBucketError> for HashTableError {
+    fn from(e: HashTableBucketError) -> Self {
+        Self::BucketError(e)
+    }
+}
+
+impl From<PageLayoutError> for HashTableError {
+    fn from(e: PageLayoutError) -> Self {
+        Self::LayoutError(e)
+    }
+}
+
+impl From<SerializeError> for HashTableError {
+    fn from(e: SerializeError) -> Self {
+        Self::SerializeError(e)
+    }
+}
+
+/// A disk-backed hash table mapping keys to one or more values.
+pub trait IHashTable<KeyType, ValueType> {
+    /// Insert a key-value pair. Returns `false` if the exact pair is already
+    /// in the table.
+    fn insert(&self, key: &KeyType, value: &ValueType) -> Result<bool, HashTableError>;
+    /// Remove a key-value pair. Returns `false` if the pair wasn't found.
+    fn remove(&self, key: &KeyType, value: &ValueType) -> Result<bool, HashTableError>;
+    /// Get all the values stored against a key.
+    fn get_value(&self, key: &KeyType) -> Result<Vec<ValueType>, HashTableError>;
+}
@@ -0,0 +1,1142 @@
+use std::marker::PhantomData;
+use std::ops::Range;
+use std::sync::RwLock;
+
+use crate::dbms::{
+    buffer::pool_manager::{BufferPoolManager, IBufferPoolManager},
+    storage::{
+        page::hash_table::{
+            block::{
+                IHashTableBlockPageRead, IHashTableBlockPageWrite, ReadOnlyHashTableBlockPage,
+                WritableHashTableBlockPage,
+            },
+            header::{
+                max_block_page_ids, IHashTableHeaderPageRead, IHashTableHeaderPageWrite,
+                ReadOnlyHashTableHeaderPage, WritableHashTableHeaderPage,
+            },
+            util::calculate_block_page_layout,
+        },
+        serialize::BytesSerialize,
+    },
+    types::PageId,
+};
+
+use super::{hash_key, HashTableError, IHashTable};
+
+/// Snapshot of the header page, read at the start of each operation
+struct HeaderInfo {
+    size: usize,
+    block_page_ids: Vec<PageId>,
+}
+
+/// A hash table using linear probing, stored across a header page and a number
+/// of block pages in the buffer pool.
+///
+/// The header page records the number of slots in the table and the IDs of the
+/// block pages. Slot `n` of the table is slot `n % slots_per_block` of block
+/// page `n / slots_per_block`.
+///
+/// The table can be shared between threads. Inserts, removals and lookups take
+/// the table latch in shared mode and then latch one block page at a time
+/// through the buffer pool, so they only contend when probing the same block.
+/// Resizing takes the table latch exclusively.
+pub struct LinearProbeHashTable<KeyType: BytesSerialize, ValueType: BytesSerialize> {
+    buffer_pool_manager: BufferPoolManager,
+    /// Latch on the whole table, guarding the header page ID which changes
+    /// when the table is resized
+    table_latch: RwLock<PageId>,
+    slots_per_block: usize,
+
+    _phantom: PhantomData<KeyType>,
+    _phantom2: PhantomData<ValueType>,
+}
+
+impl<KeyType, ValueType> LinearProbeHashTable<KeyType, ValueType>
+where
+    KeyType: BytesSerialize + PartialEq + Clone,
+    ValueType: BytesSerialize + PartialEq + Clone,
+{
+    /// Create a new hash table with the given number of slots, allocating its
+    /// header and block pages from the buffer pool.
+    #[allow(dead_code)]
+    pub fn new(
+        buffer_pool_manager: BufferPoolManager,
+        num_slots: usize,
+    ) -> Result<Self, HashTableError> {
+        let slots_per_block = Self::slots_per_block(&buffer_pool_manager)?;
+        let (header_page_id, _) =
+            Self::allocate_table(&buffer_pool_manager, num_slots, slots_per_block)?;
+
+        Ok(Self {
+            buffer_pool_manager,
+            table_latch: RwLock::new(header_page_id),
+            slots_per_block,
+            _phantom: PhantomData,
+            _phantom2: PhantomData,
+        })
+    }
+
+    /// Open an existing hash table from its header page.
+    #[allow(dead_code)]
+    pub fn open(
+        buffer_pool_manager: BufferPoolManager,
+        header_page_id: PageId,
+    ) -> Result<Self, HashTableError> {
+        let slots_per_block = Self::slots_per_block(&buffer_pool_manager)?;
+        Ok(Self {
+            buffer_pool_manager,
+            table_latch: RwLock::new(header_page_id),
+            slots_per_block,
+            _phantom: PhantomData,
+            _phantom2: PhantomData,
+        })
+    }
+
+    /// The page ID of the table's header page, used to reopen the table. This
+    /// changes whenever the table is resized.
+    #[allow(dead_code)]
+    pub fn header_page_id(&self) -> PageId {
+        *self.table_latch.read().unwrap()
+    }
+
+    /// Number of slots in the table.
+    #[allow(dead_code)]
+    pub fn size(&self) -> Result<usize, HashTableError> {
+        let header_page_id = self.table_latch.read().unwrap();
+        Ok(self.read_header(*header_page_id)?.size)
+    }
+
+    /// Rebuild the table with the given number of slots.
+    ///
+    /// A new header page and block pages are allocated, every readable entry
+    /// is rehashed into them, and then the old pages are deleted. Removed
+    /// entries aren't carried over, so this also frees up their slots.
+    pub fn resize(&self, num_slots: usize) -> Result<(), HashTableError> {
+        let mut header_page_id = self.table_latch.write().unwrap();
+        self.resize_latched(&mut header_page_id, num_slots)
+    }
+
+    /// Drop the tombstones left behind by removed entries. Returns how many
+    /// bytes of entries were reclaimed.
+    ///
+    /// A tombstone can't simply be cleared, since probes for entries further
+    /// along the run need to carry on past it. Instead the table is rebuilt
+    /// at the same size, the same way as resizing, so the old pages are left
+    /// as they were until the new ones are complete. Like resizing, this
+    /// changes the header page ID.
+    #[allow(dead_code)]
+    pub fn vacuum(&self) -> Result<usize, HashTableError> {
+        // Held exclusively so nothing probes the table while it's rebuilt
+        let mut header_page_id = self.table_latch.write().unwrap();
+        let header = self.read_header(*header_page_id)?;
+
+        let mut tombstones = 0;
+        for &block_page_id in &header.block_page_ids {
+            let result = {
+                let block = ReadOnlyHashTableBlockPage::<KeyType, ValueType>::new(
+                    self.buffer_pool_manager.fetch_page(block_page_id)?,
+                );
+                Self::tombstones_in_block(&block)
+            };
+            self.buffer_pool_manager.unpin_page(block_page_id, false)?;
+            tombstones += result?;
+        }
+        if tombstones == 0 {
+            return Ok(0);
+        }
+
+        self.resize_latched(&mut header_page_id, header.size)?;
+        Ok(tombstones * (KeyType::serialized_size() + ValueType::serialized_size()))
+    }
+
+    /// Resize the table, with the table latch already held exclusively.
+    fn resize_latched(
+        &self,
+        header_page_id: &mut PageId,
+        num_slots: usize,
+    ) -> Result<(), HashTableError> {
+        let old_header = self.read_header(*header_page_id)?;
+        let (new_header_page_id, new_header) =
+            Self::allocate_table(&self.buffer_pool_manager, num_slots, self.slots_per_block)?;
+
+        if let Err(e) = self.rehash_entries(&old_header, &new_header) {
+            self.delete_table(new_header_page_id, &new_header)?;
+            return Err(e);
+        }
+
+        let old_header_page_id = *header_page_id;
+        *header_page_id = new_header_page_id;
+        self.delete_table(old_header_page_id, &old_header)
+    }
+
+    /// Allocate the header and block pages for a table with the given number
+    /// of slots, returning the header page ID along with what it holds. If
+    /// allocation fails part of the way, the pages allocated so far are
+    /// deleted again.
+    fn allocate_table(
+        buffer_pool_manager: &BufferPoolManager,
+        num_slots: usize,
+        slots_per_block: usize,
+    ) -> Result<(PageId, HeaderInfo), HashTableError> {
+        if num_slots == 0 {
+            return Err(HashTableError::InvalidSize(
+                "Hash table must have at least one slot".to_string(),
+            ));
+        }
+        let num_blocks = num_slots.div_ceil(slots_per_block);
+        let max_blocks = max_block_page_ids(buffer_pool_manager.page_size());
+        if num_blocks > max_blocks {
+            return Err(HashTableError::InvalidSize(format!(
+                "{} slots needs {} block pages, but a header page can only hold {}",
+                num_slots, num_blocks, max_blocks
+            )));
+        }
+
+        let mut block_page_ids = Vec::with_capacity(num_blocks);
+        match Self::allocate_table_pages(
+            buffer_pool_manager,
+            num_slots,
+            num_blocks,
+            &mut block_page_ids,
+        ) {
+            Ok(header_page_id) => Ok((
+                header_page_id,
+                HeaderInfo {
+                    size: num_slots,
+                    block_page_ids,
+                },
+            )),
+            Err(e) => {
+                for block_page_id in block_page_ids {
+                    buffer_pool_manager.delete_page(block_page_id)?;
+                }
+                Err(e)
+            }
+        }
+    }
+
+    /// Allocate a table's block pages, adding each one's page ID as it goes,
+    /// and then its header page, returning the header page ID.
+    fn allocate_table_pages(
+        buffer_pool_manager: &BufferPoolManager,
+        num_slots: usize,
+        num_blocks: usize,
+        block_page_ids: &mut Vec<PageId>,
+    ) -> Result<PageId, HashTableError> {
+        // A zeroed page is an empty block page, so there's nothing to write yet
+        for _ in 0..num_blocks {
+            let block_page_id = buffer_pool_manager.new_page()?.get_page_id()?.unwrap();
+            buffer_pool_manager.unpin_page(block_page_id, true)?;
+            block_page_ids.push(block_page_id);
+        }
+
+        // The header comes last, so it can be filled in without being fetched
+        // again once the block page IDs are known
+        let (header_page_id, result) = {
+            let mut header = WritableHashTableHeaderPage::new(buffer_pool_manager.new_page()?);
+            // A page from `new_page` always has its page ID set
+            header.initialize()?;
+            let result = header
+                .set_size(num_slots as u32)
+                .and_then(|_| {
+                    block_page_ids
+                        .iter()
+                        .enumerate()
+                        .try_for_each(|(i, &block_page_id)| {
+                            header.set_block_page_id(i, block_page_id)
+                        })
+                })
+                .and_then(|_| header.set_next_ind(num_blocks as u32));
+            (header.get_page_id()?, result)
+        };
+        buffer_pool_manager.unpin_page(header_page_id, true)?;
+        if let Err(e) = result {
+            buffer_pool_manager.delete_page(header_page_id)?;
+            return Err(e.into());
+        }
+
+        Ok(header_page_id)
+    }
+
+    /// Delete a table's header and block pages from the buffer pool.
+    fn delete_table(
+        &self,
+        header_page_id: PageId,
+        header: &HeaderInfo,
+    ) -> Result<(), HashTableError> {
+        for &block_page_id in &header.block_page_ids {
+            self.buffer_pool_manager.delete_page(block_page_id)?;
+        }
+        self.buffer_pool_manager.delete_page(header_page_id)?;
+        Ok(())
+    }
+
+    /// Insert every readable entry of one table into another.
+    fn rehash_entries(
+        &self,
+        from_header: &HeaderInfo,
+        to_header: &HeaderInfo,
+    ) -> Result<(), HashTableError> {
+        for &block_page_id in &from_header.block_page_ids {
+            let entries = {
+                let block = ReadOnlyHashTableBlockPage::<KeyType, ValueType>::new(
+                    self.buffer_pool_manager.fetch_page(block_page_id)?,
+                );
+                Self::readable_entries_in_block(&block)
+            };
+            self.buffer_pool_manager.unpin_page(block_page_id, false)?;
+
+            for (key, value) in entries? {
+                if self.insert_with_header(to_header, &key, &value)?.is_none() {
+                    return Err(HashTableError::TableFull);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The size to grow a full table to: double, up to as many slots as a
+    /// header page can address.
+    fn grown_size(&self, num_slots: usize) -> Result<usize, HashTableError> {
+        let max_slots =
+            max_block_page_ids(self.buffer_pool_manager.page_size()) * self.slots_per_block;
+        let new_size = usize::min(num_slots * 2, max_slots);
+        if new_size <= num_slots {
+            return Err(HashTableError::TableFull);
+        }
+        Ok(new_size)
+    }
+
+    fn slots_per_block(buffer_pool_manager: &BufferPoolManager) -> Result<usize, HashTableError> {
+        let layout = calculate_block_page_layout(
+            KeyType::serialized_size() + ValueType::serialized_size(),
+            buffer_pool_manager.page_size(),
+        )?;
+        Ok(layout.max_values)
+    }
+
+    fn read_header(&self, header_page_id: PageId) -> Result<HeaderInfo, HashTableError> {
+        let result = {
+            let header = ReadOnlyHashTableHeaderPage::new(
+                self.buffer_pool_manager.fetch_page(header_page_id)?,
+            );
+            Self::header_info(&header)
+        };
+        self.buffer_pool_manager.unpin_page(header_page_id, false)?;
+        result
+    }
+
+    fn header_info(header: &impl IHashTableHeaderPageRead) -> Result<HeaderInfo, HashTableError> {
+        let size = header.get_size()? as usize;
+        let num_blocks = header.get_next_ind()? as usize;
+        let block_page_ids = (0..num_blocks)
+            .map(|i| header.get_block_page_id(i))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(HeaderInfo {
+            size,
+            block_page_ids,
+        })
+    }
+
+    /// The order to visit slots in when looking for a key, starting at the
+    /// key's home slot and wrapping round the whole table once. Slots are
+    /// grouped by block so each block page only needs to be fetched once per
+    /// visit, given as `(block index, slots within the block)`.
+    fn probe_sequence(
+        &self,
+        header: &HeaderInfo,
+        key: &KeyType,
+    ) -> Result<Vec<(usize, Range<usize>)>, HashTableError> {
+        let start = (hash_key(key)? % header.size as u64) as usize;
+
+        let mut sequence = Vec::new();
+        for (from, to) in [(start, header.size), (0, start)] {
+            let mut slot = from;
+            while slot < to {
+                let block_index = slot / self.slots_per_block;
+                let block_end = usize::min((block_index + 1) * self.slots_per_block, to);
+                let block_start = block_index * self.slots_per_block;
+                sequence.push((block_index, slot - block_start..block_end - block_start));
+                slot = block_end;
+            }
+        }
+        Ok(sequence)
+    }
+
+    /// Collect values for the key from a run of slots in a block. Returns
+    /// `true` if an unoccupied slot was reached, meaning the probe is over.
+    fn collect_values_in_block(
+        block: &impl IHashTableBlockPageRead<KeyType, ValueType>,
+        slots: Range<usize>,
+        key: &KeyType,
+        values: &mut Vec<ValueType>,
+    ) -> Result<bool, HashTableError> {
+        for slot in slots {
+            if !block.slot_occupied(slot)? {
+                return Ok(true);
+            }
+            if block.slot_readable(slot)? && block.key_at(slot)? == *key {
+                values.push(block.value_at(slot)?);
+            }
+        }
+        Ok(false)
+    }
+
+    /// All the readable key-value pairs in a block.
+    fn readable_entries_in_block(
+        block: &impl IHashTableBlockPageRead<KeyType, ValueType>,
+    ) -> Result<Vec<(KeyType, ValueType)>, HashTableError> {
+        let mut entries = Vec::new();
+        for slot in 0..block.num_slots() {
+            if block.slot_readable(slot)? {
+                entries.push((block.key_at(slot)?, block.value_at(slot)?));
+            }
+        }
+        Ok(entries)
+    }
+
+    /// Number of removed entries in a block still taking up their slots.
+    fn tombstones_in_block(
+        block: &impl IHashTableBlockPageRead<KeyType, ValueType>,
+    ) -> Result<usize, HashTableError> {
+        let mut tombstones = 0;
+        for slot in 0..block.num_slots() {
+            if block.slot_occupied(slot)? && !block.slot_readable(slot)? {
+                tombstones += 1;
+            }
+        }
+        Ok(tombstones)
+    }
+
+    /// Insert a pair into the table described by the header. Returns
+    /// `Some(inserted)`, or `None` if the probe wrapped round the whole table
+    /// without finding a free slot.
+    fn insert_with_header(
+        &self,
+        header: &HeaderInfo,
+        key: &KeyType,
+        value: &ValueType,
+    ) -> Result<Option<bool>, HashTableError> {
+        for (block_index, slots) in self.probe_sequence(header, key)? {
+            let block_page_id = header.block_page_ids[block_index];
+            let result = {
+                let mut block = WritableHashTableBlockPage::<KeyType, ValueType>::new(
+                    self.buffer_pool_manager
+                        .fetch_page_writable(block_page_id)?,
+                );
+                Self::insert_in_block(&mut block, slots, key, value)
+            };
+            let inserted = matches!(result, Ok(Some(true)));
+            self.buffer_pool_manager
+                .unpin_page(block_page_id, inserted)?;
+
+            if let Some(inserted) = result? {
+                return Ok(Some(inserted));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Try to insert the pair into a run of slots in a block. Returns
+    /// `Some(inserted)` once the probe is over, or `None` to keep probing.
+    fn insert_in_block(
+        block: &mut impl IHashTableBlockPageWrite<KeyType, ValueType>,
+        slots: Range<usize>,
+        key: &KeyType,
+        value: &ValueType,
+    ) -> Result<Option<bool>, HashTableError> {
+        for slot in slots {
+            if !block.slot_occupied(slot)? {
+                block.put_slot(slot, key.clone(), value.clone())?;
+                return Ok(Some(true));
+            }
+            if block.slot_readable(slot)?
+                && block.key_at(slot)? == *key
+                && block.value_at(slot)? == *value
+            {
+                return Ok(Some(false));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Try to remove the pair from a run of slots in a block. Returns
+    /// `Some(removed)` once the probe is over, or `None` to keep probing.
+    fn remove_in_block(
+        block: &mut impl IHashTableBlockPageWrite<KeyType, ValueType>,
+        slots: Range<usize>,
+        key: &KeyType,
+        value: &ValueType,
+    ) -> Result<Option<bool>, HashTableError> {
+        for slot in slots {
+            if !block.slot_occupied(slot)? {
+                return Ok(Some(false));
+            }
+            if block.slot_readable(slot)?
+                && block.key_at(slot)? == *key
+                && block.value_at(slot)? == *value
+            {
+                block.remove_slot(slot)?;
+                return Ok(Some(true));
+            }
+        }
+        Ok(None)
+    }
+}
+
+impl<KeyType, ValueType> IHashTable<KeyType, ValueType> for LinearProbeHashTable<KeyType, ValueType>
+where
+    KeyType: BytesSerialize + PartialEq + Clone,
+    ValueType: BytesSerialize + PartialEq + Clone,
+{
+    fn insert(&self, key: &KeyType, value: &ValueType) -> Result<bool, HashTableError> {
+        loop {
+            let full_size = {
+                let header_page_id = self.table_latch.read().unwrap();
+                let header = self.read_header(*header_page_id)?;
+                match self.insert_with_header(&header, key, value)? {
+                    Some(inserted) => return Ok(inserted),
+                    None => header.size,
+                }
+            };
+
+            // No free slot anywhere in the table, so grow it and try again
+            let mut header_page_id = self.table_latch.write().unwrap();
+            // Another thread may have already grown it while we waited for the latch
+            if self.read_header(*header_page_id)?.size == full_size {
+                self.resize_latched(&mut header_page_id, self.grown_size(full_size)?)?;
+            }
+        }
+    }
+
+    fn remove(&self, key: &KeyType, value: &ValueType) -> Result<bool, HashTableError> {
+        let header_page_id = self.table_latch.read().unwrap();
+        let header = self.read_header(*header_page_id)?;
+
+        for (block_index, slots) in self.probe_sequence(&header, key)? {
+            let block_page_id = header.block_page_ids[block_index];
+            let result = {
+                let mut block = WritableHashTableBlockPage::<KeyType, ValueType>::new(
+                    self.buffer_pool_manager
+                        .fetch_page_writable(block_page_id)?,
+                );
+                Self::remove_in_block(&mut block, slots, key, value)
+            };
+            let removed = matches!(result, Ok(Some(true)));
+            self.buffer_pool_manager
+                .unpin_page(block_page_id, removed)?;
+
+            if let Some(removed) = result? {
+                return Ok(removed);
+            }
+        }
+
+        Ok(false)
+    }
+
+    fn get_value(&self, key: &KeyType) -> Result<Vec<ValueType>, HashTableError> {
+        let header_page_id = self.table_latch.read().unwrap();
+        let header = self.read_header(*header_page_id)?;
+        let mut values = Vec::new();
+
+        for (block_index, slots) in self.probe_sequence(&header, key)? {
+            let block_page_id = header.block_page_ids[block_index];
+            let result = {
+                let block = ReadOnlyHashTableBlockPage::<KeyType, ValueType>::new(
+                    self.buffer_pool_manager.fetch_page(block_page_id)?,
+                );
+                Self::collect_values_in_block(&block, slots, key, &mut values)
+            };
+            self.buffer_pool_manager.unpin_page(block_page_id, false)?;
+
+            if result? {
+                break;
+            }
+        }
+
+        Ok(values)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dbms::buffer::pool_manager::testing::{
+        create_faulty_pool_manager, create_testing_pool_manager,
+    };
+    use crate::dbms::buffer::pool_manager::BufferPoolManagerError;
+    use crate::dbms::buffer::replacer::clock_replacer::ClockReplacer;
+    use crate::dbms::storage::disk::testing::{DiskOperation, Fault};
+    use crate::{tuple, tuple_type};
+    use rstest::*;
+    use std::sync::Arc;
+
+    type TestTable = LinearProbeHashTable<tuple_type![u32], tuple_type![u32, f64]>;
+
+    #[rstest]
+    fn test_insert_and_get_value() {
+        let table = TestTable::new(create_testing_pool_manager(10), 100).unwrap();
+
+        assert!(table.insert(&1, &tuple![10, 1.5]).unwrap());
+        assert!(table.insert(&2, &tuple![20, 2.5]).unwrap());
+
+        assert_eq!(table.get_value(&1).unwrap(), vec![tuple![10, 1.5]]);
+        assert_eq!(table.get_value(&2).unwrap(), vec![tuple![20, 2.5]]);
+        assert_eq!(table.get_value(&3).unwrap(), vec![]);
+    }
+
+    #[rstest]
+    fn test_multiple_values_for_key() {
+        let table = TestTable::new(create_testing_pool_manager(10), 100).unwrap();
+
+        assert!(table.insert(&1, &tuple![10, 1.5]).unwrap());
+        assert!(table.insert(&1, &tuple![11, 1.5]).unwrap());
+        assert!(table.insert(&1, &tuple![12, 1.5]).unwrap());
+
+        assert_eq!(
+            table.get_value(&1).unwrap(),
+            vec![tuple![10, 1.5], tuple![11, 1.5], tuple![12, 1.5]]
+        );
+    }
+
+    #[rstest]
+    fn test_insert_duplicate_pair() {
+        let table = TestTable::new(create_testing_pool_manager(10), 100).unwrap();
+
+        assert!(table.insert(&1, &tuple![10, 1.5]).unwrap());
+        assert!(!table.insert(&1, &tuple![10, 1.5]).unwrap());
+
+        assert_eq!(table.get_value(&1).unwrap(), vec![tuple![10, 1.5]]);
+    }
+
+    #[rstest]
+    fn test_remove() {
+        let table = TestTable::new(create_testing_pool_manager(10), 100).unwrap();
+
+        table.insert(&1, &tuple![10, 1.5]).unwrap();
+        table.insert(&1, &tuple![11, 1.5]).unwrap();
+
+        assert!(table.remove(&1, &tuple![10, 1.5]).unwrap());
+        assert_eq!(table.get_value(&1).unwrap(), vec![tuple![11, 1.5]]);
+
+        // Already removed, or never inserted
+        assert!(!table.remove(&1, &tuple![10, 1.5]).unwrap());
+        assert!(!table.remove(&2, &tuple![10, 1.5]).unwrap());
+
+        // Can insert the pair again after removing it
+        assert!(table.insert(&1, &tuple![10, 1.5]).unwrap());
+        assert_eq!(
+            table.get_value(&1).unwrap(),
+            vec![tuple![11, 1.5], tuple![10, 1.5]]
+        );
+    }
+
+    #[rstest]
+    fn test_probe_past_removed_slots() {
+        // Tiny table, so everything collides
+        let table = TestTable::new(create_testing_pool_manager(10), 5).unwrap();
+
+        for i in 0..5 {
+            table.insert(&i, &tuple![i, 0.0]).unwrap();
+        }
+        for i in 0..4 {
+            table.remove(&i, &tuple![i, 0.0]).unwrap();
+        }
+
+        assert_eq!(table.get_value(&4).unwrap(), vec![tuple![4, 0.0]]);
+    }
+
+    #[rstest]
+    fn test_resize_when_full() {
+        let table = TestTable::new(create_testing_pool_manager(10), 5).unwrap();
+        let old_header_page_id = table.header_page_id();
+
+        for i in 0..5 {
+            assert!(table.insert(&i, &tuple![i, 0.0]).unwrap());
+        }
+        assert_eq!(table.size().unwrap(), 5);
+
+        // The probe wraps round the whole table, so it's doubled in size
+        assert!(table.insert(&5, &tuple![5, 0.0]).unwrap());
+        assert_eq!(table.size().unwrap(), 10);
+        assert_ne!(table.header_page_id(), old_header_page_id);
+
+        for i in 0..6 {
+            assert_eq!(table.get_value(&i).unwrap(), vec![tuple![i, 0.0]]);
+        }
+    }
+
+    #[rstest]
+    fn test_resize_drops_removed_entries() {
+        let table = TestTable::new(create_testing_pool_manager(10), 5).unwrap();
+
+        for i in 0..5 {
+            table.insert(&i, &tuple![i, 0.0]).unwrap();
+        }
+        for i in 0..4 {
+            table.remove(&i, &tuple![i, 0.0]).unwrap();
+        }
+
+        // Only one live entry, so it fits in a single slot again
+        table.resize(1).unwrap();
+        assert_eq!(table.size().unwrap(), 1);
+        assert_eq!(table.get_value(&4).unwrap(), vec![tuple![4, 0.0]]);
+        for i in 0..4 {
+            assert_eq!(table.get_value(&i).unwrap(), vec![]);
+        }
+    }
+
+    #[rstest]
+    fn test_vacuum_without_tombstones() {
+        let table = TestTable::new(create_testing_pool_manager(10), 100).unwrap();
+        for i in 0..50 {
+            table.insert(&i, &tuple![i, 0.0]).unwrap();
+        }
+
+        assert_eq!(table.vacuum().unwrap(), 0);
+
+        for i in 0..50 {
+            assert_eq!(table.get_value(&i).unwrap(), vec![tuple![i, 0.0]]);
+        }
+    }
+
+    #[rstest]
+    fn test_vacuum_drops_tombstones() {
+        // Tiny table, so everything collides
+        let table = TestTable::new(create_testing_pool_manager(10), 5).unwrap();
+        let header_page_id = table.header_page_id();
+        for i in 0..5 {
+            table.insert(&i, &tuple![i, 0.0]).unwrap();
+        }
+        for i in 0..4 {
+            table.remove(&i, &tuple![i, 0.0]).unwrap();
+        }
+
+        // A u32 key, and a u32 and f64 value
+        let entry_size = 4 + 4 + 8;
+        assert_eq!(table.vacuum().unwrap(), 4 * entry_size);
+
+        // Rebuilt in new pages at the same size, with the one live entry
+        // still found
+        assert_ne!(table.header_page_id(), header_page_id);
+        assert_eq!(table.size().unwrap(), 5);
+        assert_eq!(table.get_value(&4).unwrap(), vec![tuple![4, 0.0]]);
+        assert_eq!(table.vacuum().unwrap(), 0);
+
+        // The freed slots can be used without growing the table
+        for i in 10..14 {
+            assert!(table.insert(&i, &tuple![i, 0.0]).unwrap());
+        }
+        assert_eq!(table.size().unwrap(), 5);
+    }
+
+    #[rstest]
+    fn test_vacuum_keeps_probe_runs() {
+        let table = TestTable::new(create_testing_pool_manager(5), 2000).unwrap();
+        for i in 0..1500 {
+            table.insert(&i, &tuple![i, 0.0]).unwrap();
+        }
+        for i in (0..1500).step_by(3) {
+            table.remove(&i, &tuple![i, 0.0]).unwrap();
+        }
+
+        assert!(table.vacuum().unwrap() > 0);
+
+        for i in 0..1500 {
+            let expected = match i % 3 {
+                0 => vec![],
+                _ => vec![tuple![i, 0.0]],
+            };
+            assert_eq!(table.get_value(&i).unwrap(), expected);
+        }
+    }
+
+    #[rstest]
+    #[case::read(DiskOperation::ReadPage)]
+    #[case::write(DiskOperation::WritePage)]
+    fn test_disk_faults_during_vacuum(#[case] operation: DiskOperation) {
+        let (pool_manager, injector) = create_faulty_pool_manager(5);
+        let table = TestTable::new(pool_manager, 2000).unwrap();
+        for i in 0..1500 {
+            table.insert(&i, &tuple![i, 0.0]).unwrap();
+        }
+        for i in (0..1500).step_by(3) {
+            table.remove(&i, &tuple![i, 0.0]).unwrap();
+        }
+
+        // Fail further and further into the rebuild, until it gets far
+        // enough to finish
+        let mut failures = 0;
+        for n in 1.. {
+            injector.inject(operation, n, Fault::Fail);
+            let result = table.vacuum();
+            injector.clear();
+
+            // Whatever point it failed at, nothing was lost
+            for i in 0..1500 {
+                let expected = match i % 3 {
+                    0 => vec![],
+                    _ => vec![tuple![i, 0.0]],
+                };
+                assert_eq!(table.get_value(&i).unwrap(), expected);
+            }
+            match result {
+                Ok(_) => break,
+                Err(HashTableError::BufferPoolManagerError(
+                    BufferPoolManagerError::DiskManagerError(_),
+                )) => failures += 1,
+                Err(e) => panic!("unexpected error {e:?}"),
+            }
+        }
+
+        assert!(failures > 5);
+        assert_eq!(table.vacuum().unwrap(), 0);
+    }
+
+    #[rstest]
+    #[case::read(DiskOperation::ReadPage)]
+    #[case::write(DiskOperation::WritePage)]
+    #[case::allocate(DiskOperation::AllocatePage)]
+    fn test_disk_faults_during_resize(#[case] operation: DiskOperation) {
+        let (pool_manager, injector) = create_faulty_pool_manager(5);
+        let table = TestTable::new(pool_manager.clone(), 1000).unwrap();
+        for i in 0..900 {
+            table.insert(&i, &tuple![i, 0.0]).unwrap();
+        }
+        pool_manager.flush_all_pages().unwrap();
+        let pages_before = injector.crash().pages.len();
+
+        // Fail further and further into the rebuild, until it gets far
+        // enough to finish
+        let mut failures = 0;
+        for n in 1.. {
+            injector.inject(operation, n, Fault::Fail);
+            let result = table.resize(3000);
+            injector.clear();
+
+            // Whatever point it failed at, the table is whole at one size or
+            // the other
+            assert!([1000, 3000].contains(&table.size().unwrap()));
+            for i in 0..900 {
+                assert_eq!(table.get_value(&i).unwrap(), vec![tuple![i, 0.0]]);
+            }
+            match result {
+                Ok(_) => break,
+                Err(HashTableError::BufferPoolManagerError(
+                    BufferPoolManagerError::DiskManagerError(_),
+                )) => failures += 1,
+                Err(e) => panic!("unexpected error {e:?}"),
+            }
+        }
+        assert!(failures > 2);
+
+        // The failed attempts didn't leave any pages behind
+        pool_manager.flush_all_pages().unwrap();
+        let blocks = |num_slots: usize| num_slots.div_ceil(table.slots_per_block);
+        assert_eq!(
+            injector.crash().pages.len(),
+            pages_before - blocks(1000) + blocks(3000)
+        );
+    }
+
+    #[rstest]
+    #[case::read(DiskOperation::ReadPage)]
+    #[case::write(DiskOperation::WritePage)]
+    fn test_disk_faults_while_growing(#[case] operation: DiskOperation) {
+        let (pool_manager, injector) = create_faulty_pool_manager(5);
+        let table = TestTable::new(pool_manager, 1).unwrap();
+
+        // Inserts that grow the table fail at different points along the way
+        let mut failures = 0;
+        for i in 0..1000 {
+            injector.inject(operation, i as usize % 5 + 1, Fault::Fail);
+            match table.insert(&i, &tuple![i, 0.0]) {
+                Ok(inserted) => assert!(inserted),
+                Err(HashTableError::BufferPoolManagerError(
+                    BufferPoolManagerError::DiskManagerError(_),
+                )) => {
+                    failures += 1;
+                    injector.clear();
+                    assert_eq!(table.get_value(&i).unwrap(), vec![]);
+                    assert!(table.insert(&i, &tuple![i, 0.0]).unwrap());
+                }
+                Err(e) => panic!("unexpected error {e:?}"),
+            }
+            injector.clear();
+        }
+
+        assert!(failures > 5);
+        assert_eq!(table.size().unwrap(), 1024);
+        for i in 0..1000 {
+            assert_eq!(table.get_value(&i).unwrap(), vec![tuple![i, 0.0]]);
+        }
+    }
+
+    #[rstest]
+    fn test_crash_while_flushing_resize() {
+        let (pool_manager, injector) = create_faulty_pool_manager(5);
+        let table = TestTable::new(pool_manager.clone(), 1000).unwrap();
+        for i in 0..900 {
+            table.insert(&i, &tuple![i, 0.0]).unwrap();
+        }
+        pool_manager.flush_all_pages().unwrap();
+        let old_header_page_id = table.header_page_id();
+        table.resize(3000).unwrap();
+
+        // The machine goes down part way through writing the rebuilt table
+        injector.tear_write(1, 100);
+        injector.fail_write(2);
+        assert!(pool_manager.flush_all_pages().is_err());
+
+        // The old pages are only written over once they've been deleted, so
+        // the table as it was before the resize survives
+        let pool_manager = BufferPoolManager::new(
+            5,
+            Box::new(ClockReplacer::new(5)),
+            Box::new(injector.crash()),
+        );
+        let table = TestTable::open(pool_manager, old_header_page_id).unwrap();
+        assert_eq!(table.size().unwrap(), 1000);
+        for i in 0..900 {
+            assert_eq!(table.get_value(&i).unwrap(), vec![tuple![i, 0.0]]);
+        }
+    }
+
+    #[rstest]
+    fn test_resize_too_small() {
+        let table = TestTable::new(create_testing_pool_manager(10), 10).unwrap();
+        let header_page_id = table.header_page_id();
+
+        for i in 0..5 {
+            table.insert(&i, &tuple![i, 0.0]).unwrap();
+        }
+
+        let result = table.resize(4);
+        assert!(matches!(result, Err(HashTableError::TableFull)));
+
+        // The original table is left untouched
+        assert_eq!(table.header_page_id(), header_page_id);
+        assert_eq!(table.size().unwrap(), 10);
+        for i in 0..5 {
+            assert_eq!(table.get_value(&i).unwrap(), vec![tuple![i, 0.0]]);
+        }
+    }
+
+    #[rstest]
+    fn test_grow_from_one_slot() {
+        let table = TestTable::new(create_testing_pool_manager(5), 1).unwrap();
+
+        for i in 0..1000 {
+            assert!(table.insert(&i, &tuple![i, 0.0]).unwrap());
+        }
+        assert_eq!(table.size().unwrap(), 1024);
+
+        for i in 0..1000 {
+            assert_eq!(table.get_value(&i).unwrap(), vec![tuple![i, 0.0]]);
+        }
+    }
+
+    #[rstest]
+    #[case(1)]
+    #[case(5)]
+    fn test_many_blocks(#[case] pool_size: usize) {
+        // Enough slots to need several block pages, even with a tiny pool
+        let num_slots = 2000;
+        let table = TestTable::new(create_testing_pool_manager(pool_size), num_slots).unwrap();
+
+        for i in 0..num_slots as u32 {
+            assert!(table.insert(&i, &tuple![i * 2, i as f64]).unwrap());
+        }
+
+        for i in 0..num_slots as u32 {
+            assert_eq!(table.get_value(&i).unwrap(), vec![tuple![i * 2, i as f64]]);
+        }
+    }
+
+    #[rstest]
+    fn test_reopen_table() {
+        let buffer_pool_manager = create_testing_pool_manager(10);
+
+        let header_page_id = {
+            let table = TestTable::new(buffer_pool_manager.clone(), 100).unwrap();
+            table.insert(&1, &tuple![10, 1.5]).unwrap();
+            table.header_page_id()
+        };
+
+        buffer_pool_manager.flush_all_pages().unwrap();
+
+        let table = TestTable::open(buffer_pool_manager, header_page_id).unwrap();
+        assert_eq!(table.get_value(&1).unwrap(), vec![tuple![10, 1.5]]);
+    }
+
+    #[rstest]
+    fn test_new_table_zero_size() {
+        let result = TestTable::new(create_testing_pool_manager(10), 0);
+        assert!(matches!(result, Err(HashTableError::InvalidSize(_))));
+    }
+
+    #[rstest]
+    fn test_new_table_too_many_blocks() {
+        let result = TestTable::new(create_testing_pool_manager(10), 1_000_000);
+        assert!(matches!(result, Err(HashTableError::InvalidSize(_))));
+    }
+
+    #[rstest]
+    // Each thread may pin a header and a block page at once, and a resize
+    // needs a few more frames on top of that
+    #[case(20, 1)]
+    #[case(40, 10)]
+    #[case(100, 1000)]
+    fn test_threaded_inserts_no_lost_entries(
+        #[case] pool_size: usize,
+        #[case] initial_slots: usize,
+    ) {
+        // Start small so the table has to grow while threads are inserting
+        let table = Arc::new(
+            TestTable::new(create_testing_pool_manager(pool_size), initial_slots).unwrap(),
+        );
+        let num_threads = 8;
+        let per_thread = 150;
+
+        let threads = (0..num_threads)
+            .map(|t| {
+                let table = table.clone();
+                std::thread::spawn(move || {
+                    for i in 0..per_thread {
+                        let key = t * per_thread + i;
+                        assert!(table.insert(&key, &tuple![key, t as f64]).unwrap());
+                        // Every thread also adds a value to a shared hot key
+                        assert!(table.insert(&u32::MAX, &tuple![key, 0.0]).unwrap());
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        for t in 0..num_threads {
+            for i in 0..per_thread {
+                let key = t * per_thread + i;
+                assert_eq!(table.get_value(&key).unwrap(), vec![tuple![key, t as f64]]);
+            }
+        }
+
+        let mut hot_values = table
+            .get_value(&u32::MAX)
+            .unwrap()
+            .into_iter()
+            .map(|(value, _)| value)
+            .collect::<Vec<_>>();
+        hot_values.sort();
+        assert_eq!(
+            hot_values,
+            (0..num_threads * per_thread).collect::<Vec<_>>()
+        );
+    }
+
+    #[rstest]
+    fn test_threaded_duplicate_inserts() {
+        let table = Arc::new(TestTable::new(create_testing_pool_manager(20), 10).unwrap());
+        let num_pairs = 200;
+
+        // Every thread tries to insert the same pairs, so each should only be
+        // inserted by one of them
+        let threads = (0..8)
+            .map(|_| {
+                let table = table.clone();
+                std::thread::spawn(move || {
+                    (0..num_pairs)
+                        .filter(|&i| table.insert(&(i % 20), &tuple![i, 0.0]).unwrap())
+                        .count()
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let total_inserted: usize = threads.into_iter().map(|t| t.join().unwrap()).sum();
+        assert_eq!(total_inserted, num_pairs as usize);
+
+        for key in 0..20 {
+            assert_eq!(table.get_value(&key).unwrap().len(), 10);
+        }
+    }
+
+    #[rstest]
+    fn test_threaded_readers_and_writers() {
+        let table = Arc::new(TestTable::new(create_testing_pool_manager(20), 100).unwrap());
+        for i in 0..500 {
+            table.insert(&i, &tuple![i, 0.0]).unwrap();
+        }
+
+        let mut threads = Vec::new();
+        // Writers remove the first half and insert a new range
+        for t in 0..4u32 {
+            let table = table.clone();
+            threads.push(std::thread::spawn(move || {
+                for i in t * 50..(t + 1) * 50 {
+                    assert!(table.remove(&i, &tuple![i, 0.0]).unwrap());
+                    assert!(table.insert(&(i + 1000), &tuple![i, 0.0]).unwrap());
+                }
+            }));
+        }
+        // Readers only look at the second half, which nobody touches
+        for _ in 0..4 {
+            let table = table.clone();
+            threads.push(std::thread::spawn(move || {
+                for i in 200..500 {
+                    assert_eq!(table.get_value(&i).unwrap(), vec![tuple![i, 0.0]]);
+                }
+            }));
+        }
+
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        for i in 0..200 {
+            assert_eq!(table.get_value(&i).unwrap(), vec![]);
+            assert_eq!(table.get_value(&(i + 1000)).unwrap(), vec![tuple![i, 0.0]]);
+        }
+    }
+
+    #[rstest]
+    fn test_threaded_vacuum_during_writes() {
+        let table = Arc::new(TestTable::new(create_testing_pool_manager(20), 1000).unwrap());
+        for i in 0..500 {
+            table.insert(&i, &tuple![i, 0.0]).unwrap();
+        }
+
+        let mut threads = Vec::new();
+        for t in 0..4u32 {
+            let table = table.clone();
+            threads.push(std::thread::spawn(move || {
+                for i in t * 50..(t + 1) * 50 {
+                    assert!(table.remove(&i, &tuple![i, 0.0]).unwrap());
+                    assert!(table.insert(&(i + 1000), &tuple![i, 0.0]).unwrap());
+                }
+            }));
+        }
+        {
+            let table = table.clone();
+            threads.push(std::thread::spawn(move || {
+                for _ in 0..10 {
+                    table.vacuum().unwrap();
+                }
+            }));
+        }
+
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        for i in 0..200 {
+            assert_eq!(table.get_value(&i).unwrap(), vec![]);
+            assert_eq!(table.get_value(&(i + 1000)).unwrap(), vec![tuple![i, 0.0]]);
+        }
+        for i in 200..500 {
+            assert_eq!(table.get_value(&i).unwrap(), vec![tuple![i, 0.0]]);
+        }
+    }
+}
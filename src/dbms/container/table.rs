@@ -0,0 +1,7 @@
+pub mod free_space_map;
+mod rid;
+mod table_error;
+pub mod table_heap;
+
+pub use rid::*;
+pub use table_error::*;
@@ -0,0 +1,312 @@
+use crate::dbms::{
+    buffer::pool_manager::{BufferPoolManager, IBufferPoolManager},
+    storage::page::table::free_space_map::{
+        free_space_category, needed_space_category, FreeSpaceMapPageError, IFreeSpaceMapPageRead,
+        IFreeSpaceMapPageWrite, ReadOnlyFreeSpaceMapPage, WritableFreeSpaceMapPage,
+    },
+    types::PageId,
+};
+
+use super::TableHeapError;
+
+/// What recording a table page's free space did to a page of the map
+enum PageRecord {
+    Unchanged,
+    Changed,
+    /// The table page isn't tracked by this page, so the record carries on to
+    /// the next page
+    NotFound(PageId),
+}
+
+/// A table's free space map, recording roughly how much free space each of
+/// its pages has so an insert can go straight to a page with room for it.
+///
+/// The map is kept in its own chain of pages in the buffer pool, so it's
+/// persisted along with the table. Each map page tracks hundreds of table
+/// pages, so finding room only has to read a handful of pages however large
+/// the table gets.
+///
+/// Free space is only recorded approximately, rounded down, so a page the map
+/// picks always has at least as much room as was asked for as long as the
+/// map is up to date. It's up to the table to record a page's free space
+/// whenever it changes.
+pub struct FreeSpaceMap {
+    buffer_pool_manager: BufferPoolManager,
+    first_page_id: PageId,
+}
+
+impl FreeSpaceMap {
+    /// Create a new, empty map with one page, allocated from the buffer pool.
+    pub fn new(buffer_pool_manager: BufferPoolManager) -> Result<Self, TableHeapError> {
+        let page = buffer_pool_manager.new_page()?;
+        // A page from `new_page` always has its page ID set
+        let first_page_id = page.get_page_id()?.unwrap();
+        let result = WritableFreeSpaceMapPage::initialize(page);
+        buffer_pool_manager.unpin_page(first_page_id, true)?;
+        result?;
+
+        Ok(Self {
+            buffer_pool_manager,
+            first_page_id,
+        })
+    }
+
+    /// Open an existing map from its first page.
+    pub fn open(buffer_pool_manager: BufferPoolManager, first_page_id: PageId) -> Self {
+        Self {
+            buffer_pool_manager,
+            first_page_id,
+        }
+    }
+
+    /// The page ID of the map's first page, used to reopen the map.
+    pub fn first_page_id(&self) -> PageId {
+        self.first_page_id
+    }
+
+    /// Find the first table page with at least the given free space, if
+    /// there is one.
+    pub fn find_page(&self, needed: usize) -> Result<Option<PageId>, TableHeapError> {
+        let category = needed_space_category(needed, self.buffer_pool_manager.page_size());
+        self.find_in_pages(|page| page.find_page_with_category(category))
+    }
+
+    /// The table page that was tracked last, which is the last page of the
+    /// table.
+    pub fn last_page(&self) -> Result<Option<PageId>, TableHeapError> {
+        let mut last_page_id = None;
+        self.find_in_pages(|page| {
+            let count = page.get_entry_count()?;
+            if count > 0 {
+                last_page_id = Some(page.get_entry(count - 1)?.0);
+            }
+            Ok(None::<()>)
+        })?;
+        Ok(last_page_id)
+    }
+
+    /// Record how much free space a table page has, starting to track it if
+    /// it's new. Pages must be tracked in the order they're added to the
+    /// table.
+    pub fn record(&self, table_page_id: PageId, free_space: usize) -> Result<(), TableHeapError> {
+        let category = free_space_category(free_space, self.buffer_pool_manager.page_size());
+        let mut page_id = self.first_page_id;
+        loop {
+            let result = {
+                let mut page = WritableFreeSpaceMapPage::new(
+                    self.buffer_pool_manager.fetch_page_writable(page_id)?,
+                );
+                self.record_in_page(&mut page, table_page_id, category)
+            };
+            let dirty = matches!(result, Ok(PageRecord::Changed));
+            self.buffer_pool_manager.unpin_page(page_id, dirty)?;
+
+            match result? {
+                PageRecord::Unchanged | PageRecord::Changed => return Ok(()),
+                PageRecord::NotFound(next_page_id) => page_id = next_page_id,
+            }
+        }
+    }
+
+    fn record_in_page(
+        &self,
+        page: &mut WritableFreeSpaceMapPage,
+        table_page_id: PageId,
+        category: u8,
+    ) -> Result<PageRecord, TableHeapError> {
+        if let Some(index) = page.find_entry(table_page_id)? {
+            if page.get_entry(index)?.1 == category {
+                return Ok(PageRecord::Unchanged);
+            }
+            page.set_category(index, category)?;
+            return Ok(PageRecord::Changed);
+        }
+        if let Some(next_page_id) = page.get_next_page_id()? {
+            return Ok(PageRecord::NotFound(next_page_id));
+        }
+
+        // The last page stays latched, so no one else can add to the map too
+        if page.push_entry(table_page_id, category)? {
+            return Ok(PageRecord::Changed);
+        }
+        let new_page = self.buffer_pool_manager.new_page()?;
+        let new_page_id = new_page.get_page_id()?.unwrap();
+        let result = WritableFreeSpaceMapPage::initialize(new_page)
+            .and_then(|mut new_page| new_page.push_entry(table_page_id, category));
+        self.buffer_pool_manager.unpin_page(new_page_id, true)?;
+        result?;
+        page.set_next_page_id(Some(new_page_id))?;
+        Ok(PageRecord::Changed)
+    }
+
+    /// Read each page of the map in turn, until one of them has what's being
+    /// looked for.
+    fn find_in_pages<T>(
+        &self,
+        mut f: impl FnMut(&ReadOnlyFreeSpaceMapPage) -> Result<Option<T>, FreeSpaceMapPageError>,
+    ) -> Result<Option<T>, TableHeapError> {
+        let mut page_id = Some(self.first_page_id);
+        while let Some(current_page_id) = page_id {
+            let result = {
+                let page = ReadOnlyFreeSpaceMapPage::new(
+                    self.buffer_pool_manager.fetch_page(current_page_id)?,
+                );
+                f(&page).and_then(|found| Ok((found, page.get_next_page_id()?)))
+            };
+            self.buffer_pool_manager
+                .unpin_page(current_page_id, false)?;
+
+            let (found, next_page_id) = result?;
+            if found.is_some() {
+                return Ok(found);
+            }
+            page_id = next_page_id;
+        }
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dbms::buffer::pool_manager::testing::create_testing_pool_manager;
+    use crate::dbms::buffer::pool_manager::testing::create_testing_pool_manager_with_page_size;
+    use crate::dbms::storage::page::table::free_space_map::{
+        free_space_category_bytes, free_space_map_page_capacity,
+    };
+    use crate::dbms::types::DEFAULT_PAGE_SIZE;
+    use rstest::*;
+    use std::sync::Arc;
+
+    const FREE_SPACE_CATEGORY_BYTES: usize = free_space_category_bytes(DEFAULT_PAGE_SIZE);
+
+    /// Page IDs that don't clash with the map's own pages
+    fn table_page_id(i: usize) -> PageId {
+        (10_000 + i) as PageId
+    }
+
+    #[rstest]
+    fn test_empty_map() {
+        let map = FreeSpaceMap::new(create_testing_pool_manager(10)).unwrap();
+
+        assert_eq!(map.find_page(0).unwrap(), None);
+        assert_eq!(map.last_page().unwrap(), None);
+    }
+
+    #[rstest]
+    fn test_find_page() {
+        let map = FreeSpaceMap::new(create_testing_pool_manager(10)).unwrap();
+        map.record(table_page_id(0), 100).unwrap();
+        map.record(table_page_id(1), 2000).unwrap();
+        map.record(table_page_id(2), 3000).unwrap();
+
+        assert_eq!(map.find_page(50).unwrap(), Some(table_page_id(0)));
+        assert_eq!(map.find_page(1000).unwrap(), Some(table_page_id(1)));
+        assert_eq!(map.find_page(2500).unwrap(), Some(table_page_id(2)));
+        assert_eq!(map.find_page(3500).unwrap(), None);
+        assert_eq!(map.last_page().unwrap(), Some(table_page_id(2)));
+    }
+
+    #[rstest]
+    fn test_find_page_rounds_free_space_down() {
+        let map = FreeSpaceMap::new(create_testing_pool_manager(10)).unwrap();
+        map.record(table_page_id(0), 2 * FREE_SPACE_CATEGORY_BYTES - 1)
+            .unwrap();
+
+        assert_eq!(
+            map.find_page(FREE_SPACE_CATEGORY_BYTES).unwrap(),
+            Some(table_page_id(0))
+        );
+        assert_eq!(map.find_page(FREE_SPACE_CATEGORY_BYTES + 1).unwrap(), None);
+    }
+
+    #[rstest]
+    fn test_find_page_with_large_pages() {
+        let page_size = 65536;
+        let map =
+            FreeSpaceMap::new(create_testing_pool_manager_with_page_size(10, page_size)).unwrap();
+        map.record(
+            table_page_id(0),
+            2 * free_space_category_bytes(page_size) - 1,
+        )
+        .unwrap();
+        map.record(table_page_id(1), 40_000).unwrap();
+
+        assert_eq!(
+            map.find_page(free_space_category_bytes(page_size)).unwrap(),
+            Some(table_page_id(0))
+        );
+        assert_eq!(map.find_page(30_000).unwrap(), Some(table_page_id(1)));
+        assert_eq!(map.find_page(50_000).unwrap(), None);
+    }
+
+    #[rstest]
+    fn test_record_updates_page() {
+        let map = FreeSpaceMap::new(create_testing_pool_manager(10)).unwrap();
+        map.record(table_page_id(0), 3000).unwrap();
+        map.record(table_page_id(1), 100).unwrap();
+
+        map.record(table_page_id(0), 100).unwrap();
+        map.record(table_page_id(1), 3000).unwrap();
+
+        assert_eq!(map.find_page(1000).unwrap(), Some(table_page_id(1)));
+        assert_eq!(map.last_page().unwrap(), Some(table_page_id(1)));
+    }
+
+    #[rstest]
+    fn test_map_spans_many_pages() {
+        let map = FreeSpaceMap::new(create_testing_pool_manager(3)).unwrap();
+        let count = 3 * free_space_map_page_capacity(DEFAULT_PAGE_SIZE) + 10;
+        for i in 0..count {
+            map.record(table_page_id(i), 0).unwrap();
+        }
+
+        map.record(table_page_id(count - 5), 2000).unwrap();
+
+        assert_eq!(map.find_page(1000).unwrap(), Some(table_page_id(count - 5)));
+        assert_eq!(map.last_page().unwrap(), Some(table_page_id(count - 1)));
+    }
+
+    #[rstest]
+    fn test_open_existing_map() {
+        let pool_manager = create_testing_pool_manager(10);
+        let first_page_id = {
+            let map = FreeSpaceMap::new(pool_manager.clone()).unwrap();
+            map.record(table_page_id(0), 100).unwrap();
+            map.record(table_page_id(1), 2000).unwrap();
+            map.first_page_id()
+        };
+
+        let map = FreeSpaceMap::open(pool_manager, first_page_id);
+
+        assert_eq!(map.find_page(1000).unwrap(), Some(table_page_id(1)));
+        assert_eq!(map.last_page().unwrap(), Some(table_page_id(1)));
+    }
+
+    #[rstest]
+    fn test_threaded_records() {
+        let map = Arc::new(FreeSpaceMap::new(create_testing_pool_manager(10)).unwrap());
+
+        let threads = (0..4)
+            .map(|t| {
+                let map = map.clone();
+                std::thread::spawn(move || {
+                    for i in 0..free_space_map_page_capacity(DEFAULT_PAGE_SIZE) {
+                        map.record(table_page_id(t * 10_000 + i), 0).unwrap();
+                    }
+                    map.record(table_page_id(t * 10_000), 1000 * (t + 1))
+                        .unwrap();
+                })
+            })
+            .collect::<Vec<_>>();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        assert_eq!(map.find_page(3500).unwrap(), Some(table_page_id(30_000)));
+        for t in 0..4 {
+            let found = map.find_page(900 * (t + 1)).unwrap().unwrap();
+            assert!(found >= table_page_id(t * 10_000));
+        }
+    }
+}
@@ -0,0 +1,65 @@
+use crate::dbms::{
+    storage::serialize::{BytesSerialize, SerializeError},
+    types::PageId,
+};
+
+/// Record ID, locating a tuple in a table by its page and its slot in that
+/// page. It stays the same for as long as the tuple is in the table, so it
+/// can be stored in an index.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Rid {
+    pub page_id: PageId,
+    pub slot: u32,
+}
+
+impl Rid {
+    pub fn new(page_id: PageId, slot: u32) -> Self {
+        Self { page_id, slot }
+    }
+}
+
+impl BytesSerialize for Rid {
+    fn to_bytes(&self) -> Result<Vec<u8>, SerializeError> {
+        (self.page_id, self.slot).to_bytes()
+    }
+
+    fn from_bytes(bytes: Vec<u8>) -> Result<Self, SerializeError> {
+        let (page_id, slot) = <(PageId, u32)>::from_bytes(bytes)?;
+        Ok(Self { page_id, slot })
+    }
+
+    fn serialized_size() -> usize {
+        <(PageId, u32)>::serialized_size()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+
+    #[rstest]
+    fn test_rid_to_bytes() {
+        let rid = Rid::new(0x01020304, 5);
+
+        assert_eq!(rid.to_bytes(), Ok(vec![0, 0, 0, 0, 1, 2, 3, 4, 0, 0, 0, 5]));
+        assert_eq!(Rid::serialized_size(), 12);
+    }
+
+    #[rstest]
+    fn test_rid_from_bytes() {
+        let rid = Rid::new(123, 45);
+
+        assert_eq!(Rid::from_bytes(rid.to_bytes().unwrap()), Ok(rid));
+        assert_eq!(
+            Rid::from_bytes(vec![1, 2, 3]),
+            Err(SerializeError::InvalidSize)
+        );
+    }
+
+    #[rstest]
+    fn test_rid_ordering() {
+        assert!(Rid::new(1, 5) < Rid::new(2, 0));
+        assert!(Rid::new(1, 0) < Rid::new(1, 5));
+    }
+}
@@ -0,0 +1,51 @@
+use crate::dbms::{
+    buffer::pool_manager::BufferPoolManagerError,
+    storage::page::{
+        table::{
+            free_space_map::FreeSpaceMapPageError, overflow::OverflowPageError,
+            table_page::TablePageError,
+        },
+        PageError,
+    },
+};
+
+#[derive(Debug)]
+pub enum TableHeapError {
+    /// Tuple is larger than its size can be recorded as
+    TupleTooLarge(usize),
+    BufferPoolManagerError(BufferPoolManagerError),
+    PageError(PageError),
+    TablePageError(TablePageError),
+    OverflowPageError(OverflowPageError),
+    FreeSpaceMapPageError(FreeSpaceMapPageError),
+}
+
+impl From<BufferPoolManagerError> for TableHeapError {
+    fn from(e: BufferPoolManagerError) -> Self {
+        Self::BufferPoolManagerError(e)
+    }
+}
+
+impl From<PageError> for TableHeapError {
+    fn from(e: PageError) -> Self {
+        Self::PageError(e)
+    }
+}
+
+impl From<TablePageError> for TableHeapError {
+    fn from(e: TablePageError) -> Self {
+        Self::TablePageError(e)
+    }
+}
+
+impl From<OverflowPageError> for TableHeapError {
+    fn from(e: OverflowPageError) -> Self {
+        Self::OverflowPageError(e)
+    }
+}
+
+impl From<FreeSpaceMapPageError> for TableHeapError {
+    fn from(e: FreeSpaceMapPageError) -> Self {
+        Self::FreeSpaceMapPageError(e)
+    }
+}
@@ -0,0 +1,1144 @@
+use crate::dbms::{
+    buffer::pool_manager::{BufferPoolManager, IBufferPoolManager},
+    storage::page::table::{
+        overflow::{
+            overflow_page_capacity, IOverflowPageRead, IOverflowPageWrite, ReadOnlyOverflowPage,
+            WritableOverflowPage,
+        },
+        table_page::{
+            max_tuple_size, ITablePageRead, ITablePageWrite, ReadOnlyTablePage, StoredTuple,
+            TablePageError, WritableTablePage,
+        },
+    },
+    types::PageId,
+};
+
+use super::{free_space_map::FreeSpaceMap, Rid, TableHeapError};
+
+/// What an insert found on a page of the heap
+enum PageInsert {
+    Inserted(Rid),
+    /// The page is full, so the insert carries on to the next page
+    Full(PageId),
+}
+
+/// A table stored as an unordered heap of tuples, across a doubly linked list
+/// of slotted table pages in the buffer pool.
+///
+/// Tuples are stored as raw bytes and located by their record ID, which
+/// doesn't change for as long as they're in the table. Inserts go into the
+/// first page with room for them, and a new page is added to the end of the
+/// list when none has. The table keeps a free space map alongside its pages
+/// to find that page, rather than trying each page in turn.
+///
+/// Tuples too large to fit in a table page are split across a chain of
+/// overflow pages of their own, and their slot holds a pointer to the chain
+/// instead. The chain is read back whenever the tuple is, and deleted along
+/// with the tuple or when it's replaced.
+///
+/// The heap can be shared between threads. Each operation latches one table
+/// page at a time through the buffer pool, apart from adding a page, which
+/// keeps the last page latched while the new one is linked in so only one
+/// thread can add it. A tuple's overflow pages are only read while its table
+/// page is latched, so they can't be deleted out from under a reader. A page's
+/// free space is recorded in the map while the page is still latched, so the
+/// map is never behind for long.
+pub struct TableHeap {
+    buffer_pool_manager: BufferPoolManager,
+    first_page_id: PageId,
+    free_space_map: FreeSpaceMap,
+}
+
+impl TableHeap {
+    /// Create a new, empty table with one page, allocated from the buffer pool.
+    #[allow(dead_code)]
+    pub fn new(buffer_pool_manager: BufferPoolManager) -> Result<Self, TableHeapError> {
+        let (first_page_id, free_space) = {
+            let page = WritableTablePage::initialize(buffer_pool_manager.new_page()?, None)?;
+            (page.get_page_id()?, page.get_free_space()?)
+        };
+        buffer_pool_manager.unpin_page(first_page_id, true)?;
+
+        let free_space_map = FreeSpaceMap::new(buffer_pool_manager.clone())?;
+        free_space_map.record(first_page_id, free_space)?;
+
+        Ok(Self {
+            buffer_pool_manager,
+            first_page_id,
+            free_space_map,
+        })
+    }
+
+    /// Open an existing table from its first page and the first page of its
+    /// free space map.
+    #[allow(dead_code)]
+    pub fn open(
+        buffer_pool_manager: BufferPoolManager,
+        first_page_id: PageId,
+        free_space_map_page_id: PageId,
+    ) -> Self {
+        let free_space_map =
+            FreeSpaceMap::open(buffer_pool_manager.clone(), free_space_map_page_id);
+        Self {
+            buffer_pool_manager,
+            first_page_id,
+            free_space_map,
+        }
+    }
+
+    /// The page ID of the table's first page, used to reopen the table.
+    #[allow(dead_code)]
+    pub fn first_page_id(&self) -> PageId {
+        self.first_page_id
+    }
+
+    /// The page ID of the first page of the table's free space map, used to
+    /// reopen the table.
+    #[allow(dead_code)]
+    pub fn free_space_map_page_id(&self) -> PageId {
+        self.free_space_map.first_page_id()
+    }
+
+    /// Add a tuple to the table, returning its record ID.
+    #[allow(dead_code)]
+    pub fn insert_tuple(&self, tuple: &[u8]) -> Result<Rid, TableHeapError> {
+        let stored = self.store_tuple(tuple)?;
+        match self.insert_stored_tuple(&stored) {
+            Ok(rid) => Ok(rid),
+            Err(e) => {
+                self.free_tuple(&stored)?;
+                Err(e)
+            }
+        }
+    }
+
+    /// Get a tuple by its record ID. Returns `None` if it's been deleted.
+    #[allow(dead_code)]
+    pub fn get_tuple(&self, rid: Rid) -> Result<Option<Vec<u8>>, TableHeapError> {
+        let result = {
+            let page = ReadOnlyTablePage::new(self.buffer_pool_manager.fetch_page(rid.page_id)?);
+            match page.get_tuple(rid.slot as usize) {
+                Ok(Some(stored)) => self.load_tuple(stored).map(Some),
+                Ok(None) => Ok(None),
+                Err(e) => Err(e.into()),
+            }
+        };
+        self.buffer_pool_manager.unpin_page(rid.page_id, false)?;
+        result
+    }
+
+    /// Replace a tuple, keeping its record ID. Returns `false` if it's been
+    /// deleted. A tuple that's grown too large for what's left of its page is
+    /// moved out to overflow pages.
+    ///
+    /// On an error the old tuple is left as it was, unless it's freeing the
+    /// old tuple's overflow pages that failed, in which case the update has
+    /// been made and some of those pages may be leaked.
+    #[allow(dead_code)]
+    pub fn update_tuple(&self, rid: Rid, tuple: &[u8]) -> Result<bool, TableHeapError> {
+        let mut stored = self.store_tuple(tuple)?;
+        let result = self.with_page_writable(rid.page_id, |page| {
+            let slot = rid.slot as usize;
+            let Some(old) = page.get_tuple(slot)? else {
+                return Ok(None);
+            };
+            match page.update_tuple(slot, &stored) {
+                // A slot always has room for an overflow pointer
+                Err(TablePageError::NotEnoughSpace) if matches!(stored, StoredTuple::Inline(_)) => {
+                    stored = self.store_overflow_tuple(tuple)?;
+                    page.update_tuple(slot, &stored)?;
+                }
+                result => {
+                    result?;
+                }
+            }
+            if let Err(e) = self.record_free_space(page) {
+                // The old tuple goes back, so the new one's overflow pages
+                // can be freed
+                page.update_tuple(slot, &old)?;
+                return Err(e);
+            }
+            Ok(Some(old))
+        });
+
+        // Whichever tuple isn't in the table any more can have its overflow
+        // pages freed, now that no one can get to them
+        match result {
+            Ok(Some(old)) => {
+                self.free_tuple(&old)?;
+                Ok(true)
+            }
+            Ok(None) => {
+                self.free_tuple(&stored)?;
+                Ok(false)
+            }
+            Err(e) => {
+                self.free_tuple(&stored)?;
+                Err(e)
+            }
+        }
+    }
+
+    /// Mark a tuple as deleted, hiding it without removing it yet. Returns
+    /// `false` if it's already been deleted.
+    #[allow(dead_code)]
+    pub fn mark_delete(&self, rid: Rid) -> Result<bool, TableHeapError> {
+        self.with_page_writable(rid.page_id, |page| Ok(page.mark_delete(rid.slot as usize)?))
+    }
+
+    /// Remove a tuple for good, freeing its slot for a later insert along with
+    /// any overflow pages it has. Returns `false` if it's already been
+    /// removed.
+    #[allow(dead_code)]
+    pub fn apply_delete(&self, rid: Rid) -> Result<bool, TableHeapError> {
+        let (removed, recorded) = self.with_page_writable(rid.page_id, |page| {
+            let removed = page.apply_delete(rid.slot as usize)?;
+            Ok((removed, self.record_free_space(page)))
+        })?;
+        // The tuple's gone from its page even if its free space couldn't be
+        // recorded, so its overflow pages are freed either way
+        let removed = match removed {
+            Some(stored) => self.free_tuple(&stored).map(|_| true),
+            None => Ok(false),
+        };
+        recorded?;
+        removed
+    }
+
+    /// Compact every page of the table, so the space left behind by updates
+    /// and deletes can be used by later inserts. Returns how many bytes were
+    /// reclaimed.
+    ///
+    /// Only one page is latched at a time, so the table can still be used
+    /// while it's vacuumed. Tuples marked as deleted keep their space until
+    /// the delete is applied.
+    #[allow(dead_code)]
+    pub fn vacuum(&self) -> Result<usize, TableHeapError> {
+        let mut reclaimed = 0;
+        let mut page_id = Some(self.first_page_id);
+        while let Some(current_page_id) = page_id {
+            let (page_reclaimed, next_page_id) =
+                self.with_page_writable(current_page_id, |page| {
+                    let page_reclaimed = page.compact()?;
+                    self.record_free_space(page)?;
+                    Ok((page_reclaimed, page.get_next_page_id()?))
+                })?;
+            reclaimed += page_reclaimed;
+            page_id = next_page_id;
+        }
+        Ok(reclaimed)
+    }
+
+    fn insert_stored_tuple(&self, stored: &StoredTuple) -> Result<Rid, TableHeapError> {
+        // The map can be a little behind, so a page it picks might not have
+        // room after all. Its free space is recorded again if so, so the map
+        // won't pick it next time.
+        let needed = stored.space_needed();
+        while let Some(page_id) = self.free_space_map.find_page(needed)? {
+            let inserted =
+                self.with_page_writable(page_id, |page| self.insert_into_page(page, stored))?;
+            if let Some(rid) = inserted {
+                return Ok(rid);
+            }
+        }
+
+        // No page has room, so the tuple goes at the end of the table. Other
+        // threads may have added pages since the map's last page was read.
+        let mut page_id = self
+            .free_space_map
+            .last_page()?
+            .unwrap_or(self.first_page_id);
+        loop {
+            let result =
+                self.with_page_writable(page_id, |page| self.append_to_page(page, stored))?;
+            match result {
+                PageInsert::Inserted(rid) => return Ok(rid),
+                PageInsert::Full(next_page_id) => page_id = next_page_id,
+            }
+        }
+    }
+
+    /// Insert a tuple into a page if it has room, recording the page's free
+    /// space either way.
+    fn insert_into_page(
+        &self,
+        page: &mut WritableTablePage,
+        tuple: &StoredTuple,
+    ) -> Result<Option<Rid>, TableHeapError> {
+        let page_id = page.get_page_id()?;
+        let Some(slot) = page.insert_tuple(tuple)? else {
+            self.record_free_space(page)?;
+            return Ok(None);
+        };
+        if let Err(e) = self.record_free_space(page) {
+            // Taken back out, so the tuple's overflow pages can be freed
+            page.apply_delete(slot)?;
+            return Err(e);
+        }
+        Ok(Some(Rid::new(page_id, slot as u32)))
+    }
+
+    /// Insert a tuple into a page if it has room. If it doesn't and it's the
+    /// last page, a new page is added after it for the tuple.
+    fn append_to_page(
+        &self,
+        page: &mut WritableTablePage,
+        tuple: &StoredTuple,
+    ) -> Result<PageInsert, TableHeapError> {
+        if let Some(rid) = self.insert_into_page(page, tuple)? {
+            return Ok(PageInsert::Inserted(rid));
+        }
+        if let Some(next_page_id) = page.get_next_page_id()? {
+            return Ok(PageInsert::Full(next_page_id));
+        }
+
+        // The last page stays latched, so no one else can add a page too. The
+        // new page goes in the map before anyone can find it by following the
+        // last page, so the map keeps the table's order.
+        let page_id = page.get_page_id()?;
+        let (new_page_id, slot) = {
+            let mut new_page =
+                WritableTablePage::initialize(self.buffer_pool_manager.new_page()?, Some(page_id))?;
+            let new_page_id = new_page.get_page_id()?;
+            let slot = self
+                .free_space_map
+                .record(new_page_id, new_page.get_free_space()?)
+                .and_then(|_| self.insert_into_page(&mut new_page, tuple));
+            (new_page_id, slot)
+        };
+        self.buffer_pool_manager.unpin_page(new_page_id, true)?;
+        page.set_next_page_id(Some(new_page_id))?;
+
+        // A stored tuple always fits in an empty page
+        Ok(PageInsert::Inserted(slot?.unwrap()))
+    }
+
+    /// Record a latched page's free space in the free space map.
+    fn record_free_space(&self, page: &WritableTablePage) -> Result<(), TableHeapError> {
+        self.free_space_map
+            .record(page.get_page_id()?, page.get_free_space()?)
+    }
+
+    /// Run a change on a write latched page, unpinning it afterward. The page
+    /// is unpinned dirty even if the change failed, as it may have got part of
+    /// the way.
+    fn with_page_writable<T>(
+        &self,
+        page_id: PageId,
+        f: impl FnOnce(&mut WritableTablePage) -> Result<T, TableHeapError>,
+    ) -> Result<T, TableHeapError> {
+        let result = {
+            let mut page =
+                WritableTablePage::new(self.buffer_pool_manager.fetch_page_writable(page_id)?);
+            f(&mut page)
+        };
+        self.buffer_pool_manager.unpin_page(page_id, true)?;
+        result
+    }
+
+    /// Get a tuple ready to go in a slot, writing it out to overflow pages if
+    /// it's too large to fit in a table page.
+    fn store_tuple(&self, tuple: &[u8]) -> Result<StoredTuple, TableHeapError> {
+        let page_size = self.buffer_pool_manager.page_size();
+        if tuple.len() <= max_tuple_size(page_size) {
+            return Ok(StoredTuple::Inline(tuple.to_vec()));
+        }
+        self.store_overflow_tuple(tuple)
+    }
+
+    /// Write a tuple out to overflow pages, whatever its size.
+    fn store_overflow_tuple(&self, tuple: &[u8]) -> Result<StoredTuple, TableHeapError> {
+        let page_size = self.buffer_pool_manager.page_size();
+        if tuple.len() > u32::MAX as usize {
+            return Err(TableHeapError::TupleTooLarge(tuple.len()));
+        }
+
+        // Written from the back, so each page can link to the one after it
+        let mut next_page_id = None;
+        for chunk in tuple.chunks(overflow_page_capacity(page_size)).rev() {
+            match self.write_overflow_page(chunk, next_page_id) {
+                Ok(page_id) => next_page_id = Some(page_id),
+                Err(e) => {
+                    // Don't leave the part of the chain that was written behind
+                    if let Some(first_page_id) = next_page_id {
+                        self.free_tuple(&StoredTuple::Overflow {
+                            first_page_id,
+                            size: 0,
+                        })?;
+                    }
+                    return Err(e);
+                }
+            }
+        }
+
+        Ok(StoredTuple::Overflow {
+            first_page_id: next_page_id.unwrap(),
+            size: tuple.len(),
+        })
+    }
+
+    /// Write part of a tuple to a new overflow page, returning its page ID.
+    fn write_overflow_page(
+        &self,
+        data: &[u8],
+        next_page_id: Option<PageId>,
+    ) -> Result<PageId, TableHeapError> {
+        let page = self.buffer_pool_manager.new_page()?;
+        // A page from `new_page` always has its page ID set
+        let page_id = page.get_page_id()?.unwrap();
+        let result = WritableOverflowPage::initialize(page, data)
+            .and_then(|mut overflow| overflow.set_next_page_id(next_page_id));
+        self.buffer_pool_manager.unpin_page(page_id, true)?;
+        result?;
+        Ok(page_id)
+    }
+
+    /// Get a tuple's bytes back from how it's stored, reading its overflow
+    /// pages if it has any.
+    fn load_tuple(&self, stored: StoredTuple) -> Result<Vec<u8>, TableHeapError> {
+        let (first_page_id, size) = match stored {
+            StoredTuple::Inline(tuple) => return Ok(tuple),
+            StoredTuple::Overflow {
+                first_page_id,
+                size,
+            } => (first_page_id, size),
+        };
+
+        let mut tuple = Vec::with_capacity(size);
+        let mut page_id = Some(first_page_id);
+        while let Some(current_page_id) = page_id {
+            let result = {
+                let page = ReadOnlyOverflowPage::new(
+                    self.buffer_pool_manager.fetch_page(current_page_id)?,
+                );
+                page.get_data()
+                    .and_then(|data| Ok((data, page.get_next_page_id()?)))
+            };
+            self.buffer_pool_manager
+                .unpin_page(current_page_id, false)?;
+
+            let (data, next_page_id) = result?;
+            tuple.extend(data);
+            page_id = next_page_id;
+        }
+        Ok(tuple)
+    }
+
+    /// Delete a tuple's overflow pages, if it has any.
+    fn free_tuple(&self, stored: &StoredTuple) -> Result<(), TableHeapError> {
+        let StoredTuple::Overflow { first_page_id, .. } = stored else {
+            return Ok(());
+        };
+
+        let mut page_id = Some(*first_page_id);
+        while let Some(current_page_id) = page_id {
+            let result = {
+                let page = ReadOnlyOverflowPage::new(
+                    self.buffer_pool_manager.fetch_page(current_page_id)?,
+                );
+                page.get_next_page_id()
+            };
+            self.buffer_pool_manager
+                .unpin_page(current_page_id, false)?;
+
+            page_id = result?;
+            self.buffer_pool_manager.delete_page(current_page_id)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dbms::buffer::pool_manager::testing::{
+        create_faulty_pool_manager, create_testing_pool_manager,
+        create_testing_pool_manager_with_page_size,
+    };
+    use crate::dbms::buffer::pool_manager::BufferPoolManagerError;
+    use crate::dbms::storage::disk::testing::{DiskOperation, Fault};
+    use crate::dbms::storage::page::table::free_space_map::{
+        free_space_category, IFreeSpaceMapPageRead, ReadOnlyFreeSpaceMapPage,
+    };
+    use crate::dbms::types::DEFAULT_PAGE_SIZE;
+    use rstest::*;
+    use std::sync::Arc;
+
+    const MAX_TUPLE_SIZE: usize = max_tuple_size(DEFAULT_PAGE_SIZE);
+    const OVERFLOW_PAGE_CAPACITY: usize = overflow_page_capacity(DEFAULT_PAGE_SIZE);
+
+    fn tuple_for(i: usize) -> Vec<u8> {
+        format!("tuple number {}", i).into_bytes()
+    }
+
+    fn large_tuple(size: usize) -> Vec<u8> {
+        (0..size).map(|i| (i % 251) as u8).collect()
+    }
+
+    /// Check the free space map has an entry for each of the table's pages in
+    /// order, with the category for the free space the page has
+    fn assert_free_space_map_matches(heap: &TableHeap) {
+        let pool_manager = &heap.buffer_pool_manager;
+
+        let mut table_pages = vec![];
+        let mut page_id = Some(heap.first_page_id());
+        while let Some(current_page_id) = page_id {
+            let page = ReadOnlyTablePage::new(pool_manager.fetch_page(current_page_id).unwrap());
+            let free_space = page.get_free_space().unwrap();
+            table_pages.push((
+                current_page_id,
+                free_space_category(free_space, pool_manager.page_size()),
+            ));
+            page_id = page.get_next_page_id().unwrap();
+            drop(page);
+            pool_manager.unpin_page(current_page_id, false).unwrap();
+        }
+
+        let mut map_entries = vec![];
+        let mut page_id = Some(heap.free_space_map_page_id());
+        while let Some(current_page_id) = page_id {
+            let page =
+                ReadOnlyFreeSpaceMapPage::new(pool_manager.fetch_page(current_page_id).unwrap());
+            for index in 0..page.get_entry_count().unwrap() {
+                map_entries.push(page.get_entry(index).unwrap());
+            }
+            page_id = page.get_next_page_id().unwrap();
+            drop(page);
+            pool_manager.unpin_page(current_page_id, false).unwrap();
+        }
+
+        assert_eq!(map_entries, table_pages);
+    }
+
+    /// How a tuple is stored in its slot, without following overflow pages
+    fn stored_tuple(heap: &TableHeap, rid: Rid) -> Option<StoredTuple> {
+        let pool_manager = &heap.buffer_pool_manager;
+        let result = {
+            let page = ReadOnlyTablePage::new(pool_manager.fetch_page(rid.page_id).unwrap());
+            page.get_tuple(rid.slot as usize).unwrap()
+        };
+        pool_manager.unpin_page(rid.page_id, false).unwrap();
+        result
+    }
+
+    #[rstest]
+    fn test_insert_and_get_tuple() {
+        let heap = TableHeap::new(create_testing_pool_manager(10)).unwrap();
+
+        let first = heap.insert_tuple(b"first").unwrap();
+        let second = heap.insert_tuple(b"second").unwrap();
+
+        assert_eq!(first, Rid::new(heap.first_page_id(), 0));
+        assert_eq!(second, Rid::new(heap.first_page_id(), 1));
+        assert_eq!(heap.get_tuple(first).unwrap(), Some(b"first".to_vec()));
+        assert_eq!(heap.get_tuple(second).unwrap(), Some(b"second".to_vec()));
+    }
+
+    #[rstest]
+    fn test_insert_many_pages() {
+        let heap = TableHeap::new(create_testing_pool_manager(5)).unwrap();
+
+        let rids = (0..1000)
+            .map(|i| heap.insert_tuple(&tuple_for(i)).unwrap())
+            .collect::<Vec<_>>();
+
+        let pages = rids
+            .iter()
+            .map(|rid| rid.page_id)
+            .collect::<std::collections::BTreeSet<_>>();
+        assert!(pages.len() > 5);
+        for (i, rid) in rids.into_iter().enumerate() {
+            assert_eq!(heap.get_tuple(rid).unwrap(), Some(tuple_for(i)));
+        }
+    }
+
+    #[rstest]
+    fn test_pages_are_linked() {
+        let pool_manager = create_testing_pool_manager(10);
+        let heap = TableHeap::new(pool_manager.clone()).unwrap();
+        for _ in 0..10 {
+            heap.insert_tuple(&[0u8; 1300]).unwrap();
+        }
+
+        let mut page_ids = vec![heap.first_page_id()];
+        let mut prev_page_ids = vec![];
+        loop {
+            let page_id = *page_ids.last().unwrap();
+            let (prev, next) = {
+                let page = ReadOnlyTablePage::new(pool_manager.fetch_page(page_id).unwrap());
+                (
+                    page.get_prev_page_id().unwrap(),
+                    page.get_next_page_id().unwrap(),
+                )
+            };
+            pool_manager.unpin_page(page_id, false).unwrap();
+            prev_page_ids.push(prev);
+            match next {
+                Some(next) => page_ids.push(next),
+                None => break,
+            }
+        }
+
+        // Three 1300 byte tuples fit in a page
+        assert_eq!(page_ids.len(), 4);
+        for (i, prev) in prev_page_ids.into_iter().enumerate() {
+            assert_eq!(prev, i.checked_sub(1).map(|prev| page_ids[prev]));
+        }
+    }
+
+    #[rstest]
+    fn test_insert_fills_earlier_pages_first() {
+        let heap = TableHeap::new(create_testing_pool_manager(10)).unwrap();
+        let rids = (0..4)
+            .map(|_| heap.insert_tuple(&[0u8; 1300]).unwrap())
+            .collect::<Vec<_>>();
+        heap.apply_delete(rids[0]).unwrap();
+
+        // There's still no room in the first page for a large tuple, but the
+        // freed slot can take a small one
+        let large = heap.insert_tuple(&[1u8; 1300]).unwrap();
+        let small = heap.insert_tuple(b"small").unwrap();
+
+        assert_eq!(large.page_id, rids[3].page_id);
+        assert_eq!(small, rids[0]);
+    }
+
+    #[rstest]
+    fn test_insert_largest_inline_tuple() {
+        let heap = TableHeap::new(create_testing_pool_manager(10)).unwrap();
+        heap.insert_tuple(b"first").unwrap();
+
+        let rid = heap.insert_tuple(&vec![2u8; MAX_TUPLE_SIZE]).unwrap();
+
+        assert_ne!(rid.page_id, heap.first_page_id());
+        assert_eq!(
+            heap.get_tuple(rid).unwrap(),
+            Some(vec![2u8; MAX_TUPLE_SIZE])
+        );
+        assert!(matches!(
+            stored_tuple(&heap, rid),
+            Some(StoredTuple::Inline(_))
+        ));
+    }
+
+    #[rstest]
+    #[case(MAX_TUPLE_SIZE + 1, true)]
+    #[case(max_tuple_size(65536), true)]
+    #[case(max_tuple_size(65536) + 1, false)]
+    #[case(3 * overflow_page_capacity(65536), false)]
+    fn test_large_pages_hold_larger_tuples(#[case] size: usize, #[case] inline: bool) {
+        let heap = TableHeap::new(create_testing_pool_manager_with_page_size(5, 65536)).unwrap();
+
+        let rid = heap.insert_tuple(&large_tuple(size)).unwrap();
+
+        assert_eq!(heap.get_tuple(rid).unwrap(), Some(large_tuple(size)));
+        assert_eq!(
+            matches!(stored_tuple(&heap, rid), Some(StoredTuple::Inline(_))),
+            inline
+        );
+        assert_free_space_map_matches(&heap);
+    }
+
+    #[rstest]
+    #[case(MAX_TUPLE_SIZE + 1)]
+    #[case(OVERFLOW_PAGE_CAPACITY)]
+    #[case(OVERFLOW_PAGE_CAPACITY + 1)]
+    #[case(3 * OVERFLOW_PAGE_CAPACITY)]
+    #[case(10 * OVERFLOW_PAGE_CAPACITY + 123)]
+    fn test_insert_overflow_tuple(#[case] size: usize) {
+        let heap = TableHeap::new(create_testing_pool_manager(5)).unwrap();
+        heap.insert_tuple(b"first").unwrap();
+
+        let rid = heap.insert_tuple(&large_tuple(size)).unwrap();
+
+        // Only the pointer to the overflow pages goes in the table page
+        assert_eq!(rid, Rid::new(heap.first_page_id(), 1));
+        assert!(matches!(
+            stored_tuple(&heap, rid),
+            Some(StoredTuple::Overflow { size: s, .. }) if s == size
+        ));
+        assert_eq!(heap.get_tuple(rid).unwrap(), Some(large_tuple(size)));
+    }
+
+    #[rstest]
+    fn test_many_overflow_tuples() {
+        let heap = TableHeap::new(create_testing_pool_manager(5)).unwrap();
+
+        let rids = (0..20)
+            .map(|i| heap.insert_tuple(&large_tuple(5000 + i)).unwrap())
+            .collect::<Vec<_>>();
+
+        for (i, rid) in rids.into_iter().enumerate() {
+            assert_eq!(heap.get_tuple(rid).unwrap(), Some(large_tuple(5000 + i)));
+        }
+    }
+    #[rstest]
+    fn test_update_tuple() {
+        let heap = TableHeap::new(create_testing_pool_manager(10)).unwrap();
+        let rid = heap.insert_tuple(b"first").unwrap();
+
+        assert!(heap.update_tuple(rid, b"a longer first tuple").unwrap());
+        assert_eq!(
+            heap.get_tuple(rid).unwrap(),
+            Some(b"a longer first tuple".to_vec())
+        );
+        assert!(heap.update_tuple(rid, b"short").unwrap());
+        assert_eq!(heap.get_tuple(rid).unwrap(), Some(b"short".to_vec()));
+    }
+
+    #[rstest]
+    fn test_update_tuple_moves_to_overflow_when_page_full() {
+        let heap = TableHeap::new(create_testing_pool_manager(10)).unwrap();
+        let rid = heap.insert_tuple(b"first").unwrap();
+        let other = heap.insert_tuple(&vec![0u8; MAX_TUPLE_SIZE - 100]).unwrap();
+
+        assert!(heap.update_tuple(rid, &[1u8; 100]).unwrap());
+
+        assert_eq!(heap.get_tuple(rid).unwrap(), Some(vec![1u8; 100]));
+        assert!(matches!(
+            stored_tuple(&heap, rid),
+            Some(StoredTuple::Overflow { size: 100, .. })
+        ));
+        assert_eq!(
+            heap.get_tuple(other).unwrap(),
+            Some(vec![0u8; MAX_TUPLE_SIZE - 100])
+        );
+    }
+
+    #[rstest]
+    #[case(b"small".to_vec(), large_tuple(5000))]
+    #[case(large_tuple(5000), b"small".to_vec())]
+    #[case(large_tuple(5000), large_tuple(20000))]
+    #[case(large_tuple(20000), large_tuple(5000))]
+    fn test_update_overflow_tuple(#[case] before: Vec<u8>, #[case] after: Vec<u8>) {
+        let pool_manager = create_testing_pool_manager(10);
+        let heap = TableHeap::new(pool_manager.clone()).unwrap();
+        let rid = heap.insert_tuple(&before).unwrap();
+        let old = stored_tuple(&heap, rid).unwrap();
+
+        assert!(heap.update_tuple(rid, &after).unwrap());
+
+        assert_eq!(heap.get_tuple(rid).unwrap(), Some(after));
+        if let StoredTuple::Overflow { first_page_id, .. } = old {
+            assert!(pool_manager.fetch_page(first_page_id).is_err());
+        }
+    }
+
+    #[rstest]
+    fn test_update_deleted_tuple_frees_new_overflow_pages() {
+        let pool_manager = create_testing_pool_manager(10);
+        let heap = TableHeap::new(pool_manager.clone()).unwrap();
+        let rid = heap.insert_tuple(b"first").unwrap();
+        heap.mark_delete(rid).unwrap();
+        let next_page_id = {
+            let page = pool_manager.new_page().unwrap();
+            let page_id = page.get_page_id().unwrap().unwrap();
+            drop(page);
+            pool_manager.unpin_page(page_id, false).unwrap();
+            pool_manager.delete_page(page_id).unwrap();
+            page_id
+        };
+
+        assert!(!heap.update_tuple(rid, &large_tuple(5000)).unwrap());
+
+        // The update's two overflow pages were freed, so this insert's
+        // overflow pages reuse them
+        let next = heap.insert_tuple(&large_tuple(5000)).unwrap();
+        let Some(StoredTuple::Overflow { first_page_id, .. }) = stored_tuple(&heap, next) else {
+            panic!("expected an overflow tuple");
+        };
+        assert!((next_page_id..next_page_id + 2).contains(&first_page_id));
+        let page = pool_manager.new_page().unwrap();
+        assert_eq!(page.get_page_id().unwrap(), Some(next_page_id + 2));
+    }
+
+    #[rstest]
+    fn test_apply_delete_frees_overflow_pages() {
+        let pool_manager = create_testing_pool_manager(10);
+        let heap = TableHeap::new(pool_manager.clone()).unwrap();
+        let rid = heap
+            .insert_tuple(&large_tuple(3 * OVERFLOW_PAGE_CAPACITY))
+            .unwrap();
+        let Some(StoredTuple::Overflow { first_page_id, .. }) = stored_tuple(&heap, rid) else {
+            panic!("expected an overflow tuple");
+        };
+
+        // Marking a tuple deleted keeps its pages, in case it's rolled back
+        assert!(heap.mark_delete(rid).unwrap());
+        assert_eq!(heap.get_tuple(rid).unwrap(), None);
+        assert!(pool_manager.fetch_page(first_page_id).is_ok());
+        pool_manager.unpin_page(first_page_id, false).unwrap();
+
+        assert!(heap.apply_delete(rid).unwrap());
+        for page_id in first_page_id - 2..=first_page_id {
+            assert!(pool_manager.fetch_page(page_id).is_err());
+        }
+    }
+
+    #[rstest]
+    fn test_mark_and_apply_delete() {
+        let heap = TableHeap::new(create_testing_pool_manager(10)).unwrap();
+        let first = heap.insert_tuple(b"first").unwrap();
+        let second = heap.insert_tuple(b"second").unwrap();
+
+        assert!(heap.mark_delete(first).unwrap());
+        assert!(!heap.mark_delete(first).unwrap());
+        assert_eq!(heap.get_tuple(first).unwrap(), None);
+        assert!(!heap.update_tuple(first, b"again").unwrap());
+
+        assert!(heap.apply_delete(first).unwrap());
+        assert!(!heap.apply_delete(first).unwrap());
+        assert_eq!(heap.get_tuple(first).unwrap(), None);
+        assert_eq!(heap.get_tuple(second).unwrap(), Some(b"second".to_vec()));
+    }
+
+    #[rstest]
+    fn test_get_tuple_bad_slot() {
+        let heap = TableHeap::new(create_testing_pool_manager(10)).unwrap();
+        heap.insert_tuple(b"first").unwrap();
+
+        let result = heap.get_tuple(Rid::new(heap.first_page_id(), 3));
+
+        assert!(matches!(
+            result,
+            Err(TableHeapError::TablePageError(
+                TablePageError::SlotOutOfRange(3)
+            ))
+        ));
+    }
+
+    #[rstest]
+    fn test_read_faults_during_get_tuple() {
+        let (pool_manager, injector) = create_faulty_pool_manager(5);
+        let heap = TableHeap::new(pool_manager).unwrap();
+        let tuples = (0..60)
+            .map(|i| {
+                if i % 4 == 0 {
+                    large_tuple(3 * OVERFLOW_PAGE_CAPACITY)
+                } else {
+                    tuple_for(i)
+                }
+            })
+            .collect::<Vec<_>>();
+        let rids = tuples
+            .iter()
+            .map(|tuple| heap.insert_tuple(tuple).unwrap())
+            .collect::<Vec<_>>();
+
+        // Fail reads at different points, including part way along overflow
+        // chains
+        let mut failures = 0;
+        for (i, (rid, tuple)) in rids.iter().zip(&tuples).enumerate() {
+            injector.fail_read(i % 3 + 1);
+            match heap.get_tuple(*rid) {
+                Ok(found) => assert_eq!(found.as_ref(), Some(tuple)),
+                Err(TableHeapError::BufferPoolManagerError(
+                    BufferPoolManagerError::DiskManagerError(_),
+                )) => failures += 1,
+                Err(e) => panic!("unexpected error {e:?}"),
+            }
+            injector.clear();
+
+            // Nothing was left pinned, so trying again works
+            assert_eq!(heap.get_tuple(*rid).unwrap().as_ref(), Some(tuple));
+        }
+
+        assert!(failures > 10);
+        let rid = heap.insert_tuple(b"after").unwrap();
+        assert_eq!(heap.get_tuple(rid).unwrap(), Some(b"after".to_vec()));
+    }
+
+    #[rstest]
+    #[case::failed_read(DiskOperation::ReadPage)]
+    #[case::failed_write(DiskOperation::WritePage)]
+    fn test_disk_faults_during_updates(#[case] operation: DiskOperation) {
+        let (pool_manager, injector) = create_faulty_pool_manager(5);
+        let heap = TableHeap::new(pool_manager).unwrap();
+        let mut tuples = (0..60)
+            .map(|i| {
+                if i % 4 == 0 {
+                    large_tuple(2 * OVERFLOW_PAGE_CAPACITY)
+                } else {
+                    tuple_for(i)
+                }
+            })
+            .collect::<Vec<_>>();
+        let rids = tuples
+            .iter()
+            .map(|tuple| heap.insert_tuple(tuple).unwrap())
+            .collect::<Vec<_>>();
+
+        // Updates that grow, shrink, and move tuples to and from overflow
+        // pages, failing at different points along the way
+        let mut failures = 0;
+        for (i, rid) in rids.iter().enumerate() {
+            let new_tuple = match i % 3 {
+                0 => large_tuple(3 * OVERFLOW_PAGE_CAPACITY + i),
+                1 => vec![i as u8; 500 + i],
+                _ => tuple_for(1000 + i),
+            };
+            injector.inject(operation, i % 3 + 1, Fault::Fail);
+            match heap.update_tuple(*rid, &new_tuple) {
+                Ok(updated) => assert!(updated),
+                Err(TableHeapError::BufferPoolManagerError(
+                    BufferPoolManagerError::DiskManagerError(_),
+                )) => {
+                    failures += 1;
+                    // Either the update wasn't made, or it was and freeing
+                    // the old tuple's overflow pages failed, but the tuple is
+                    // never a mix of the two
+                    injector.clear();
+                    let tuple = heap.get_tuple(*rid).unwrap().unwrap();
+                    assert!(tuple == tuples[i] || tuple == new_tuple);
+                }
+                Err(e) => panic!("unexpected error {e:?}"),
+            }
+            injector.clear();
+
+            // Nothing was left pinned or half done, so trying again works
+            assert!(heap.update_tuple(*rid, &new_tuple).unwrap());
+            tuples[i] = new_tuple;
+        }
+
+        assert!(failures > 10, "{failures}");
+        for (rid, tuple) in rids.iter().zip(&tuples) {
+            assert_eq!(heap.get_tuple(*rid).unwrap().as_ref(), Some(tuple));
+        }
+        assert_free_space_map_matches(&heap);
+    }
+
+    #[rstest]
+    fn test_open_existing_heap() {
+        let pool_manager = create_testing_pool_manager(10);
+        let (first_page_id, free_space_map_page_id, rids) = {
+            let heap = TableHeap::new(pool_manager.clone()).unwrap();
+            let rids = (0..100)
+                .map(|i| heap.insert_tuple(&tuple_for(i)).unwrap())
+                .collect::<Vec<_>>();
+            (heap.first_page_id(), heap.free_space_map_page_id(), rids)
+        };
+
+        let heap = TableHeap::open(pool_manager, first_page_id, free_space_map_page_id);
+
+        for (i, rid) in rids.into_iter().enumerate() {
+            assert_eq!(heap.get_tuple(rid).unwrap(), Some(tuple_for(i)));
+        }
+        assert_eq!(heap.insert_tuple(b"more").unwrap().slot, 100);
+        assert_free_space_map_matches(&heap);
+    }
+
+    #[rstest]
+    fn test_free_space_map_tracks_pages() {
+        let heap = TableHeap::new(create_testing_pool_manager(10)).unwrap();
+        let rids = (0..30)
+            .map(|i| heap.insert_tuple(&vec![0u8; 100 * (i % 15) + 1]).unwrap())
+            .collect::<Vec<_>>();
+        assert_free_space_map_matches(&heap);
+
+        for rid in rids.iter().step_by(3) {
+            heap.update_tuple(*rid, &[1u8; 10]).unwrap();
+        }
+        for rid in rids.iter().skip(1).step_by(3) {
+            heap.apply_delete(*rid).unwrap();
+        }
+        for i in 0..10 {
+            heap.insert_tuple(&tuple_for(i)).unwrap();
+        }
+        assert_free_space_map_matches(&heap);
+    }
+
+    #[rstest]
+    fn test_insert_finds_room_in_earlier_pages() {
+        let heap = TableHeap::new(create_testing_pool_manager(10)).unwrap();
+        // Leaves a little room at the end of each page
+        let large = (0..9)
+            .map(|_| heap.insert_tuple(&[0u8; 1300]).unwrap())
+            .collect::<Vec<_>>();
+
+        let small = (0..9)
+            .map(|i| heap.insert_tuple(&tuple_for(i)).unwrap())
+            .collect::<Vec<_>>();
+
+        assert_eq!(small[0].page_id, large[0].page_id);
+        let pages = small
+            .iter()
+            .map(|rid| rid.page_id)
+            .collect::<std::collections::BTreeSet<_>>();
+        let large_pages = large
+            .iter()
+            .map(|rid| rid.page_id)
+            .collect::<std::collections::BTreeSet<_>>();
+        assert!(pages.is_subset(&large_pages));
+    }
+
+    #[rstest]
+    fn test_insert_after_reopen_adds_to_last_page() {
+        let pool_manager = create_testing_pool_manager(10);
+        let (first_page_id, free_space_map_page_id, last) = {
+            let heap = TableHeap::new(pool_manager.clone()).unwrap();
+            let rids = (0..10)
+                .map(|_| heap.insert_tuple(&[0u8; 1300]).unwrap())
+                .collect::<Vec<_>>();
+            (
+                heap.first_page_id(),
+                heap.free_space_map_page_id(),
+                *rids.last().unwrap(),
+            )
+        };
+
+        let heap = TableHeap::open(pool_manager, first_page_id, free_space_map_page_id);
+
+        let rid = heap.insert_tuple(&[1u8; 1300]).unwrap();
+        assert_eq!(rid, Rid::new(last.page_id, last.slot + 1));
+    }
+
+    #[rstest]
+    fn test_vacuum_empty_table() {
+        let heap = TableHeap::new(create_testing_pool_manager(10)).unwrap();
+
+        assert_eq!(heap.vacuum().unwrap(), 0);
+    }
+
+    #[rstest]
+    fn test_vacuum_reclaims_deleted_and_updated_tuples() {
+        let heap = TableHeap::new(create_testing_pool_manager(10)).unwrap();
+        let rids = (0..9)
+            .map(|_| heap.insert_tuple(&[0u8; 1300]).unwrap())
+            .collect::<Vec<_>>();
+        heap.apply_delete(rids[0]).unwrap();
+        heap.apply_delete(rids[4]).unwrap();
+        heap.update_tuple(rids[8], &[1u8; 300]).unwrap();
+        // Marked tuples keep their space
+        heap.mark_delete(rids[5]).unwrap();
+
+        assert_eq!(heap.vacuum().unwrap(), 1300 + 1300 + 1000);
+
+        assert_eq!(heap.get_tuple(rids[8]).unwrap(), Some(vec![1u8; 300]));
+        for i in [1, 2, 3, 6, 7] {
+            assert_eq!(heap.get_tuple(rids[i]).unwrap(), Some(vec![0u8; 1300]));
+        }
+        assert_eq!(heap.vacuum().unwrap(), 0);
+        assert_free_space_map_matches(&heap);
+    }
+
+    #[rstest]
+    fn test_vacuum_makes_room_for_inserts() {
+        let heap = TableHeap::new(create_testing_pool_manager(10)).unwrap();
+        let rids = (0..9)
+            .map(|_| heap.insert_tuple(&[0u8; 1300]).unwrap())
+            .collect::<Vec<_>>();
+        heap.apply_delete(rids[1]).unwrap();
+
+        // Deleting leaves a hole the new tuple doesn't fit in until it's
+        // reclaimed
+        let before = heap.insert_tuple(&[1u8; 1300]).unwrap();
+        heap.apply_delete(before).unwrap();
+        assert_ne!(before.page_id, rids[1].page_id);
+        heap.vacuum().unwrap();
+
+        let after = heap.insert_tuple(&[1u8; 1300]).unwrap();
+        assert_eq!(after, rids[1]);
+    }
+
+    #[rstest]
+    fn test_threaded_vacuum_during_updates() {
+        let heap = Arc::new(TableHeap::new(create_testing_pool_manager(20)).unwrap());
+        let rids = (0..400)
+            .map(|_| heap.insert_tuple(&[0u8; 20]).unwrap())
+            .collect::<Vec<_>>();
+
+        // Tuples shrink and grow in turn, so pages fill up with holes
+        let writers = (0..4)
+            .map(|t| {
+                let heap = heap.clone();
+                let rids = rids.clone();
+                std::thread::spawn(move || {
+                    for round in 1..=10 {
+                        for rid in rids.iter().skip(t).step_by(4) {
+                            let tuple = vec![round as u8; 10 + (round % 2) * 10];
+                            heap.update_tuple(*rid, &tuple).unwrap();
+                        }
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        let vacuum = {
+            let heap = heap.clone();
+            std::thread::spawn(move || {
+                for _ in 0..10 {
+                    heap.vacuum().unwrap();
+                }
+            })
+        };
+        for writer in writers {
+            writer.join().unwrap();
+        }
+        vacuum.join().unwrap();
+        heap.vacuum().unwrap();
+
+        for rid in rids {
+            assert_eq!(heap.get_tuple(rid).unwrap(), Some(vec![10u8; 10]));
+        }
+        assert_free_space_map_matches(&heap);
+    }
+
+    #[rstest]
+    fn test_threaded_overflow_inserts() {
+        let heap = Arc::new(TableHeap::new(create_testing_pool_manager(20)).unwrap());
+
+        let threads = (0..4)
+            .map(|t| {
+                let heap = heap.clone();
+                std::thread::spawn(move || {
+                    (0..20)
+                        .map(|i| {
+                            let tuple = large_tuple(5000 + t * 100 + i);
+                            (heap.insert_tuple(&tuple).unwrap(), tuple)
+                        })
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect::<Vec<_>>();
+        let inserted = threads
+            .into_iter()
+            .flat_map(|thread| thread.join().unwrap())
+            .collect::<Vec<_>>();
+
+        for (rid, tuple) in inserted {
+            assert_eq!(heap.get_tuple(rid).unwrap(), Some(tuple));
+        }
+    }
+
+    #[rstest]
+    fn test_threaded_inserts() {
+        let heap = Arc::new(TableHeap::new(create_testing_pool_manager(20)).unwrap());
+
+        let threads = (0..8)
+            .map(|t| {
+                let heap = heap.clone();
+                std::thread::spawn(move || {
+                    (0..200)
+                        .map(|i| {
+                            let tuple = tuple_for(t * 1000 + i);
+                            (heap.insert_tuple(&tuple).unwrap(), tuple)
+                        })
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect::<Vec<_>>();
+        let inserted = threads
+            .into_iter()
+            .flat_map(|thread| thread.join().unwrap())
+            .collect::<Vec<_>>();
+
+        let rids = inserted
+            .iter()
+            .map(|(rid, _)| *rid)
+            .collect::<std::collections::HashSet<_>>();
+        assert_eq!(rids.len(), inserted.len());
+        assert_free_space_map_matches(&heap);
+        for (rid, tuple) in inserted {
+            assert_eq!(heap.get_tuple(rid).unwrap(), Some(tuple));
+        }
+    }
+}
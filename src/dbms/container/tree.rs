@@ -0,0 +1,6 @@
+pub mod b_plus_tree;
+mod key_comparator;
+mod tree_error;
+
+pub use key_comparator::*;
+pub use tree_error::*;
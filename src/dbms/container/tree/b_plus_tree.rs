@@ -0,0 +1,1526 @@
+use std::cmp::Ordering;
+use std::marker::PhantomData;
+use std::ops::RangeBounds;
+
+use crate::dbms::{
+    buffer::{
+        pool_manager::{BufferPoolManager, BufferPoolManagerError, IBufferPoolManager},
+        types::WritablePage,
+    },
+    storage::{
+        page::b_plus_tree::{
+            header::{
+                IBPlusTreeHeaderPageRead, IBPlusTreeHeaderPageWrite, ReadOnlyBPlusTreeHeaderPage,
+                WritableBPlusTreeHeaderPage,
+            },
+            internal::{
+                internal_page_capacity, IBPlusTreeInternalPageRead, IBPlusTreeInternalPageWrite,
+                WritableBPlusTreeInternalPage, MIN_INTERNAL_MAX_SIZE,
+            },
+            leaf::{
+                leaf_page_capacity, IBPlusTreeLeafPageRead, IBPlusTreeLeafPageWrite,
+                ReadOnlyBPlusTreeLeafPage, WritableBPlusTreeLeafPage, MIN_LEAF_MAX_SIZE,
+            },
+            node::BPlusTreePageError,
+        },
+        serialize::BytesSerialize,
+    },
+    types::PageId,
+};
+
+use super::{BPlusTreeError, IKeyComparator};
+
+mod iterator;
+mod pinned;
+
+pub use iterator::*;
+use pinned::{PinnedPage, ReadOnlyNode, WritableNode};
+
+/// A pinned leaf, read latched
+type PinnedLeaf<'a, KeyType, ValueType> =
+    PinnedPage<'a, ReadOnlyBPlusTreeLeafPage<'a, KeyType, ValueType>>;
+/// A pinned leaf, write latched
+type PinnedWritableLeaf<'a, KeyType, ValueType> =
+    PinnedPage<'a, WritableBPlusTreeLeafPage<'a, KeyType, ValueType>>;
+
+/// Which leaf a search is looking for
+enum Target<'k, KeyType> {
+    /// The leaf that would hold the key
+    Key(&'k KeyType),
+    /// The first leaf in key order
+    First,
+    /// The last leaf in key order
+    Last,
+}
+
+// Derived impls would need the key type to be `Copy` too
+impl<KeyType> Clone for Target<'_, KeyType> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<KeyType> Copy for Target<'_, KeyType> {}
+
+/// The kinds of change a writer can make to a leaf
+#[derive(Clone, Copy)]
+enum WriteOperation {
+    Insert,
+    Remove,
+}
+
+/// An internal node on a writer's path
+struct Ancestor<'a, KeyType: BytesSerialize, ValueType: BytesSerialize> {
+    node: PinnedPage<'a, WritableBPlusTreeInternalPage<'a, KeyType>>,
+    /// Index of the child the path goes through
+    child_index: usize,
+    /// For a removal, the sibling that child would borrow from or be merged
+    /// with
+    child_sibling: Option<PinnedPage<'a, WritableNode<'a, KeyType, ValueType>>>,
+}
+
+/// Latches held above a leaf by a writer that might have to split or merge
+/// nodes
+struct WritePath<'a, KeyType: BytesSerialize, ValueType: BytesSerialize> {
+    /// The header page, held while the root might change
+    header: Option<PinnedPage<'a, WritableBPlusTreeHeaderPage<'a>>>,
+    /// The internal nodes that might change, from the top down
+    ancestors: Vec<Ancestor<'a, KeyType, ValueType>>,
+    /// Nodes taken out of the tree, to be discarded once the change is done
+    removed: Vec<PinnedPage<'a, WritableNode<'a, KeyType, ValueType>>>,
+}
+
+/// A writer's path, and the leaf at the bottom of it, write latched
+type WritePathAndLeaf<'a, KeyType, ValueType> = (
+    WritePath<'a, KeyType, ValueType>,
+    PinnedWritableLeaf<'a, KeyType, ValueType>,
+);
+
+/// The entries a node is to be left with, before they're written to its page
+enum NodeEntries<KeyType, ValueType> {
+    Leaf(Vec<(KeyType, ValueType)>),
+    /// The first child, then each separating key with the child after it
+    Internal(PageId, Vec<(KeyType, PageId)>),
+}
+
+impl<KeyType: BytesSerialize, ValueType: BytesSerialize> NodeEntries<KeyType, ValueType> {
+    /// The entries a node has now
+    fn read(node: &WritableNode<'_, KeyType, ValueType>) -> Result<Self, BPlusTreePageError> {
+        match node {
+            WritableNode::Leaf(leaf) => Ok(Self::Leaf(leaf.entries()?)),
+            WritableNode::Internal(internal) => {
+                let (first_child, entries) = internal.children()?;
+                Ok(Self::Internal(first_child, entries))
+            }
+        }
+    }
+}
+
+/// A B+ tree index with unique keys, stored across a header page and a number
+/// of leaf and internal node pages in the buffer pool.
+///
+/// Keys are ordered by the tree's comparator rather than by their serialized
+/// bytes. Internal nodes hold `n` children separated by `n - 1` keys, where
+/// every key in a child is at least the key before it and less than the key
+/// after it. Leaves hold the entries, and are linked to their neighbours in
+/// both directions.
+///
+/// Nodes split in half when they overflow, and when a node falls below half
+/// full it borrows entries from a sibling, or is merged into it if they fit in
+/// one node. The header page records the root page ID and node sizes, so the
+/// tree can be reopened from it.
+///
+/// The tree can be shared between threads, with latch crabbing on the pages.
+/// Every operation walks down from the header page, latching each node before
+/// releasing the one above it. Lookups use read latches all the way down.
+/// Inserts and removals first try the same, write latching only the leaf, and
+/// if it turns out the leaf would split or merge they start again holding
+/// write latches on every node that might change. Siblings are always latched
+/// left to right, so writers can't deadlock with each other.
+///
+/// A split or merge latches every node and allocates every page it needs
+/// before it writes anything, so running out of frames or a disk fault part
+/// way through leaves the tree as it was. Removals latch the siblings they
+/// might need on the way down for that reason. Pages of nodes merged away are
+/// only discarded once the tree is whole again.
+pub struct BPlusTree<KeyType, ValueType, Comparator> {
+    buffer_pool_manager: BufferPoolManager,
+    header_page_id: PageId,
+    comparator: Comparator,
+
+    _phantom: PhantomData<KeyType>,
+    _phantom2: PhantomData<ValueType>,
+}
+
+impl<KeyType, ValueType, Comparator> BPlusTree<KeyType, ValueType, Comparator>
+where
+    KeyType: BytesSerialize + Clone,
+    ValueType: BytesSerialize + Clone,
+    Comparator: IKeyComparator<KeyType>,
+{
+    /// Create a new, empty tree with nodes as large as fit in a page,
+    /// allocating its header page from the buffer pool.
+    #[allow(dead_code)]
+    pub fn new(
+        buffer_pool_manager: BufferPoolManager,
+        comparator: Comparator,
+    ) -> Result<Self, BPlusTreeError> {
+        let page_size = buffer_pool_manager.page_size();
+        Self::with_max_sizes(
+            buffer_pool_manager,
+            comparator,
+            leaf_page_capacity::<KeyType, ValueType>(page_size),
+            internal_page_capacity::<KeyType>(page_size),
+        )
+    }
+
+    /// Create a new, empty tree with the given max number of entries in a
+    /// leaf and max number of children of an internal node.
+    #[allow(dead_code)]
+    pub fn with_max_sizes(
+        buffer_pool_manager: BufferPoolManager,
+        comparator: Comparator,
+        leaf_max_size: usize,
+        internal_max_size: usize,
+    ) -> Result<Self, BPlusTreeError> {
+        let page_size = buffer_pool_manager.page_size();
+        let leaf_capacity = leaf_page_capacity::<KeyType, ValueType>(page_size);
+        if !(MIN_LEAF_MAX_SIZE..=leaf_capacity).contains(&leaf_max_size) {
+            return Err(BPlusTreeError::InvalidMaxSize(format!(
+                "Leaf max size {} is outside {}..={}",
+                leaf_max_size, MIN_LEAF_MAX_SIZE, leaf_capacity
+            )));
+        }
+        let internal_capacity = internal_page_capacity::<KeyType>(page_size);
+        if !(MIN_INTERNAL_MAX_SIZE..=internal_capacity).contains(&internal_max_size) {
+            return Err(BPlusTreeError::InvalidMaxSize(format!(
+                "Internal max size {} is outside {}..={}",
+                internal_max_size, MIN_INTERNAL_MAX_SIZE, internal_capacity
+            )));
+        }
+
+        let header_page_id = {
+            let mut header = WritableBPlusTreeHeaderPage::new(buffer_pool_manager.new_page()?);
+            header.initialize(leaf_max_size, internal_max_size)?;
+            header.get_page_id()?
+        };
+        buffer_pool_manager.unpin_page(header_page_id, true)?;
+
+        Ok(Self::open(buffer_pool_manager, comparator, header_page_id))
+    }
+
+    /// Open an existing tree from its header page.
+    #[allow(dead_code)]
+    pub fn open(
+        buffer_pool_manager: BufferPoolManager,
+        comparator: Comparator,
+        header_page_id: PageId,
+    ) -> Self {
+        Self {
+            buffer_pool_manager,
+            header_page_id,
+            comparator,
+            _phantom: PhantomData,
+            _phantom2: PhantomData,
+        }
+    }
+
+    /// The page ID of the tree's header page, used to reopen the tree.
+    #[allow(dead_code)]
+    pub fn header_page_id(&self) -> PageId {
+        self.header_page_id
+    }
+
+    /// Whether the tree has no entries.
+    #[allow(dead_code)]
+    pub fn is_empty(&self) -> Result<bool, BPlusTreeError> {
+        Ok(self.fetch_header()?.get_root_page_id()?.is_none())
+    }
+
+    /// Look up the value for a key.
+    #[allow(dead_code)]
+    pub fn get_value(&self, key: &KeyType) -> Result<Option<ValueType>, BPlusTreeError> {
+        let Some(leaf) = self.find_leaf(Target::Key(key))? else {
+            return Ok(None);
+        };
+        match self.search_leaf(&*leaf, key)? {
+            Ok(index) => Ok(Some(leaf.value_at(index)?)),
+            Err(_) => Ok(None),
+        }
+    }
+
+    /// Iterate over every entry in key order.
+    #[allow(dead_code)]
+    pub fn iter(
+        &self,
+    ) -> Result<BPlusTreeIterator<'_, KeyType, ValueType, Comparator>, BPlusTreeError> {
+        self.range(..)
+    }
+
+    /// Iterate over the entries with keys in the range, in key order.
+    #[allow(dead_code)]
+    pub fn range(
+        &self,
+        range: impl RangeBounds<KeyType>,
+    ) -> Result<BPlusTreeIterator<'_, KeyType, ValueType, Comparator>, BPlusTreeError> {
+        BPlusTreeIterator::new(
+            self,
+            range.start_bound().cloned(),
+            range.end_bound().cloned(),
+            false,
+        )
+    }
+
+    /// Iterate over the entries with keys in the range, in reverse key order.
+    #[allow(dead_code)]
+    pub fn range_rev(
+        &self,
+        range: impl RangeBounds<KeyType>,
+    ) -> Result<BPlusTreeIterator<'_, KeyType, ValueType, Comparator>, BPlusTreeError> {
+        BPlusTreeIterator::new(
+            self,
+            range.start_bound().cloned(),
+            range.end_bound().cloned(),
+            true,
+        )
+    }
+
+    /// Insert a key and its value. Returns `false` without changing anything
+    /// if the key is already in the tree.
+    #[allow(dead_code)]
+    pub fn insert(&self, key: &KeyType, value: &ValueType) -> Result<bool, BPlusTreeError> {
+        // Hope the leaf has room, so nothing above it needs a write latch
+        if let Some(mut leaf) = self.find_leaf_writable(key)? {
+            match self.search_leaf(&*leaf, key)? {
+                Ok(_) => return Ok(false),
+                Err(index) if leaf.get_size()? < leaf.get_max_size()? => {
+                    leaf.insert_at(index, key.clone(), value.clone())?;
+                    return Ok(true);
+                }
+                Err(_) => {}
+            }
+        }
+
+        let mut header = self.fetch_header_writable()?;
+        let Some(root_page_id) = header.get_root_page_id()? else {
+            let page = self.new_pages(1)?.pop().unwrap();
+            let mut leaf = self.new_leaf(page, header.get_leaf_max_size()?)?;
+            leaf.insert_at(0, key.clone(), value.clone())?;
+            header.set_root_page_id(Some(leaf.page_id()))?;
+            return Ok(true);
+        };
+        let (mut path, mut leaf) =
+            self.find_write_path(header, root_page_id, key, WriteOperation::Insert)?;
+
+        // The leaf may have changed since it was last looked at
+        let index = match self.search_leaf(&*leaf, key)? {
+            Ok(_) => return Ok(false),
+            Err(index) => index,
+        };
+        if leaf.get_size()? < leaf.get_max_size()? {
+            leaf.insert_at(index, key.clone(), value.clone())?;
+            return Ok(true);
+        }
+
+        // Every full node above the leaf splits too, and the root's only still
+        // latched if it's one of them, in which case the tree grows a level
+        let mut new_internals = usize::from(path.header.is_some());
+        for ancestor in &path.ancestors {
+            if ancestor.node.get_size()? >= ancestor.node.get_max_size()? {
+                new_internals += 1;
+            }
+        }
+        let next_page_id = leaf.get_next_page_id()?;
+        let mut next_leaf = next_page_id
+            .map(|next_page_id| self.fetch_leaf_writable(next_page_id))
+            .transpose()?;
+        let mut new_pages = self.new_pages(new_internals + 1)?;
+
+        let mut entries = leaf.entries()?;
+        entries.insert(index, (key.clone(), value.clone()));
+        let right_entries = entries.split_off(entries.len().div_ceil(2));
+
+        let mut right = self.new_leaf(new_pages.pop().unwrap(), leaf.get_max_size()?)?;
+        right.set_entries(&right_entries)?;
+        right.set_prev_page_id(Some(leaf.page_id()))?;
+        right.set_next_page_id(next_page_id)?;
+        leaf.set_entries(&entries)?;
+        leaf.set_next_page_id(Some(right.page_id()))?;
+        if let Some(next_leaf) = &mut next_leaf {
+            next_leaf.set_prev_page_id(Some(right.page_id()))?;
+        }
+
+        let separator = right_entries[0].0.clone();
+        self.insert_into_parent(
+            &mut path,
+            &mut new_pages,
+            leaf.page_id(),
+            separator,
+            right.page_id(),
+        )?;
+        Ok(true)
+    }
+
+    /// Remove a key and its value, returning whether the key was found.
+    #[allow(dead_code)]
+    pub fn remove(&self, key: &KeyType) -> Result<bool, BPlusTreeError> {
+        // Hope the leaf won't fall below half full, so nothing above it needs
+        // a write latch
+        match self.find_leaf_writable(key)? {
+            None => return Ok(false),
+            Some(mut leaf) => {
+                let Ok(index) = self.search_leaf(&*leaf, key)? else {
+                    return Ok(false);
+                };
+                let (size, max_size) = (leaf.get_size()?, leaf.get_max_size()?);
+                // The only leaf with no neighbours is the root
+                let is_root =
+                    leaf.get_prev_page_id()?.is_none() && leaf.get_next_page_id()?.is_none();
+                if is_safe(WriteOperation::Remove, size, max_size, true, is_root) {
+                    leaf.remove_at(index)?;
+                    return Ok(true);
+                }
+            }
+        }
+
+        let header = self.fetch_header_writable()?;
+        let Some(root_page_id) = header.get_root_page_id()? else {
+            return Ok(false);
+        };
+        let (mut path, mut leaf) =
+            self.find_write_path(header, root_page_id, key, WriteOperation::Remove)?;
+
+        let Ok(index) = self.search_leaf(&*leaf, key)? else {
+            return Ok(false);
+        };
+
+        // What the leaf's left with once the key's gone
+        let size = leaf.get_size()? - 1;
+        if leaf.page_id() == root_page_id {
+            leaf.remove_at(index)?;
+            // The root can be as small as it likes until the tree is empty
+            if size == 0 {
+                // The root wasn't safe, so the header is still latched
+                let header = path.header.as_mut().unwrap();
+                header.set_root_page_id(None)?;
+                path.removed.push(leaf.map(WritableNode::Leaf));
+            }
+        } else if size < min_leaf_size(leaf.get_max_size()?) {
+            let mut entries = leaf.entries()?;
+            entries.remove(index);
+            self.rebalance(
+                &mut path,
+                leaf.map(WritableNode::Leaf),
+                NodeEntries::Leaf(entries),
+            )?;
+        } else {
+            leaf.remove_at(index)?;
+        }
+
+        // Every node taken out is discarded, even if one of them fails, as
+        // the key's gone either way
+        let discarded = std::mem::take(&mut path.removed)
+            .into_iter()
+            .map(|node| self.discard(node))
+            .collect::<Vec<_>>();
+        discarded.into_iter().collect::<Result<(), _>>()?;
+        Ok(true)
+    }
+
+    fn fetch_header(
+        &self,
+    ) -> Result<PinnedPage<'_, ReadOnlyBPlusTreeHeaderPage<'_>>, BPlusTreeError> {
+        let page = self.buffer_pool_manager.fetch_page(self.header_page_id)?;
+        Ok(PinnedPage::new(
+            &self.buffer_pool_manager,
+            self.header_page_id,
+            ReadOnlyBPlusTreeHeaderPage::new(page),
+        ))
+    }
+
+    fn fetch_header_writable(
+        &self,
+    ) -> Result<PinnedPage<'_, WritableBPlusTreeHeaderPage<'_>>, BPlusTreeError> {
+        let page = self
+            .buffer_pool_manager
+            .fetch_page_writable(self.header_page_id)?;
+        Ok(PinnedPage::new(
+            &self.buffer_pool_manager,
+            self.header_page_id,
+            WritableBPlusTreeHeaderPage::new(page),
+        ))
+    }
+
+    fn fetch_node(
+        &self,
+        page_id: PageId,
+    ) -> Result<PinnedPage<'_, ReadOnlyNode<'_, KeyType, ValueType>>, BPlusTreeError> {
+        let page = self.buffer_pool_manager.fetch_page(page_id)?;
+        Ok(PinnedPage::try_new(
+            &self.buffer_pool_manager,
+            page_id,
+            ReadOnlyNode::new(page),
+        )?)
+    }
+
+    fn fetch_node_writable(
+        &self,
+        page_id: PageId,
+    ) -> Result<PinnedPage<'_, WritableNode<'_, KeyType, ValueType>>, BPlusTreeError> {
+        let page = self.buffer_pool_manager.fetch_page_writable(page_id)?;
+        Ok(PinnedPage::try_new(
+            &self.buffer_pool_manager,
+            page_id,
+            WritableNode::new(page),
+        )?)
+    }
+
+    fn fetch_leaf(
+        &self,
+        page_id: PageId,
+    ) -> Result<PinnedLeaf<'_, KeyType, ValueType>, BPlusTreeError> {
+        let page = self.buffer_pool_manager.fetch_page(page_id)?;
+        Ok(PinnedPage::try_new(
+            &self.buffer_pool_manager,
+            page_id,
+            ReadOnlyBPlusTreeLeafPage::new(page),
+        )?)
+    }
+
+    fn fetch_leaf_writable(
+        &self,
+        page_id: PageId,
+    ) -> Result<PinnedWritableLeaf<'_, KeyType, ValueType>, BPlusTreeError> {
+        let page = self.buffer_pool_manager.fetch_page_writable(page_id)?;
+        Ok(PinnedPage::try_new(
+            &self.buffer_pool_manager,
+            page_id,
+            WritableBPlusTreeLeafPage::new(page),
+        )?)
+    }
+
+    /// Allocate pages for new nodes. A change allocates all the pages it
+    /// needs before it writes anything, and if they can't all be allocated,
+    /// the ones that were are deleted again.
+    fn new_pages(
+        &self,
+        count: usize,
+    ) -> Result<Vec<PinnedPage<'_, WritablePage<'_>>>, BPlusTreeError> {
+        let mut pages = Vec::with_capacity(count);
+        for _ in 0..count {
+            match self.buffer_pool_manager.new_page() {
+                Ok(page) => {
+                    let page_id = page.get_page_id()?.unwrap();
+                    pages.push(PinnedPage::new(&self.buffer_pool_manager, page_id, page));
+                }
+                Err(e) => {
+                    for page in pages {
+                        let page_id = page.page_id();
+                        drop(page);
+                        self.buffer_pool_manager.delete_page(page_id)?;
+                    }
+                    return Err(e.into());
+                }
+            }
+        }
+        Ok(pages)
+    }
+
+    fn new_leaf<'t>(
+        &self,
+        page: PinnedPage<'t, WritablePage<'t>>,
+        max_size: usize,
+    ) -> Result<PinnedWritableLeaf<'t, KeyType, ValueType>, BPlusTreeError> {
+        let mut leaf =
+            page.try_map(|page| WritableBPlusTreeLeafPage::initialize(page, max_size))?;
+        leaf.mark_dirty();
+        Ok(leaf)
+    }
+
+    fn new_internal<'t>(
+        &self,
+        page: PinnedPage<'t, WritablePage<'t>>,
+        max_size: usize,
+    ) -> Result<PinnedPage<'t, WritableBPlusTreeInternalPage<'t, KeyType>>, BPlusTreeError> {
+        let mut internal =
+            page.try_map(|page| WritableBPlusTreeInternalPage::initialize(page, max_size))?;
+        internal.mark_dirty();
+        Ok(internal)
+    }
+
+    /// Remove a node's page for good. It's marked as removed and written out
+    /// before it's deleted, so anyone following a stale link to it finds out
+    /// it's gone rather than reading what it used to hold.
+    fn discard(
+        &self,
+        mut node: PinnedPage<'_, WritableNode<'_, KeyType, ValueType>>,
+    ) -> Result<(), BPlusTreeError> {
+        node.mark_removed()?;
+        let page_id = node.page_id();
+        node.unlatch();
+        self.buffer_pool_manager.flush_page(page_id)?;
+        drop(node);
+
+        match self.buffer_pool_manager.delete_page(page_id) {
+            // Whoever followed a stale link to it still has it pinned. It's
+            // left to be evicted like any other page rather than waiting
+            Err(BufferPoolManagerError::PageInUse) => Ok(()),
+            result => Ok(result?),
+        }
+    }
+
+    /// Walk down from the root to the leaf for a target with read latches,
+    /// latching each node before releasing the one above it. The leaf is
+    /// passed to `at_leaf` while the node above it, or the header if it's the
+    /// root, is still latched.
+    fn descend<'t, T>(
+        &'t self,
+        target: Target<'_, KeyType>,
+        at_leaf: impl FnOnce(
+            PinnedPage<'t, ReadOnlyNode<'t, KeyType, ValueType>>,
+        ) -> Result<T, BPlusTreeError>,
+    ) -> Result<Option<T>, BPlusTreeError> {
+        let mut header = Some(self.fetch_header()?);
+        let Some(root_page_id) = header.as_ref().unwrap().get_root_page_id()? else {
+            return Ok(None);
+        };
+
+        let mut _parent = None;
+        let mut node = self.fetch_node(root_page_id)?;
+        loop {
+            let child_page_id = match &*node {
+                ReadOnlyNode::Leaf(_) => return Ok(Some(at_leaf(node)?)),
+                ReadOnlyNode::Internal(internal) => {
+                    internal.child_at(self.search_internal(internal, target)?)?
+                }
+            };
+            let child = self.fetch_node(child_page_id)?;
+            drop(header.take());
+            _parent = Some(std::mem::replace(&mut node, child));
+        }
+    }
+
+    /// Find the leaf for a target, read latched.
+    fn find_leaf(
+        &self,
+        target: Target<'_, KeyType>,
+    ) -> Result<Option<PinnedLeaf<'_, KeyType, ValueType>>, BPlusTreeError> {
+        self.descend(target, |node| Ok(node.try_map(ReadOnlyNode::into_leaf)?))
+    }
+
+    /// Find the leaf that would hold a key, write latched.
+    fn find_leaf_writable(
+        &self,
+        key: &KeyType,
+    ) -> Result<Option<PinnedWritableLeaf<'_, KeyType, ValueType>>, BPlusTreeError> {
+        self.descend(Target::Key(key), |node| {
+            // Swap the read latch for a write latch. The node above is still
+            // latched, so the leaf can't be split or merged away in between.
+            let page_id = node.page_id();
+            drop(node);
+            self.fetch_leaf_writable(page_id)
+        })
+    }
+
+    /// Walk down from the root to the leaf that would hold a key with write
+    /// latches, for an operation that might split or merge nodes. Nodes are
+    /// kept latched until the operation is known not to reach them, which is
+    /// once a node below them is safe from splitting or merging. For a
+    /// removal, the sibling of each node that isn't safe is latched too.
+    fn find_write_path<'t>(
+        &'t self,
+        header: PinnedPage<'t, WritableBPlusTreeHeaderPage<'t>>,
+        root_page_id: PageId,
+        key: &KeyType,
+        operation: WriteOperation,
+    ) -> Result<WritePathAndLeaf<'t, KeyType, ValueType>, BPlusTreeError> {
+        let mut path = WritePath {
+            header: Some(header),
+            ancestors: Vec::new(),
+            removed: Vec::new(),
+        };
+        let mut page_id = root_page_id;
+        loop {
+            let mut node = self.fetch_node_writable(page_id)?;
+            let (size, max_size, is_leaf) = match &*node {
+                WritableNode::Leaf(leaf) => (leaf.get_size()?, leaf.get_max_size()?, true),
+                WritableNode::Internal(internal) => {
+                    (internal.get_size()?, internal.get_max_size()?, false)
+                }
+            };
+            if is_safe(operation, size, max_size, is_leaf, page_id == root_page_id) {
+                path.header = None;
+                path.ancestors.clear();
+            } else if let (WriteOperation::Remove, Some(parent)) =
+                (operation, path.ancestors.last_mut())
+            {
+                // Pair the node with its left sibling if it has one, otherwise
+                // its right sibling. Siblings are latched left to right, so the
+                // node is released and latched again after its left sibling.
+                // Its parent is latched, so no other writer can get to it in
+                // between.
+                let (first_child, entries) = parent.node.children()?;
+                let sibling = match parent.child_index {
+                    0 => self.fetch_node_writable(entries[0].1)?,
+                    child_index => {
+                        let left_page_id = match child_index - 1 {
+                            0 => first_child,
+                            left_index => entries[left_index - 1].1,
+                        };
+                        drop(node);
+                        let sibling = self.fetch_node_writable(left_page_id)?;
+                        node = self.fetch_node_writable(page_id)?;
+                        sibling
+                    }
+                };
+                parent.child_sibling = Some(sibling);
+            }
+
+            let child = match &*node {
+                WritableNode::Leaf(_) => None,
+                WritableNode::Internal(internal) => {
+                    let index = self.search_internal(internal, Target::Key(key))?;
+                    Some((index, internal.child_at(index)?))
+                }
+            };
+            match child {
+                None => return Ok((path, node.try_map(WritableNode::into_leaf)?)),
+                Some((child_index, child_page_id)) => {
+                    path.ancestors.push(Ancestor {
+                        node: node.try_map(WritableNode::into_internal)?,
+                        child_index,
+                        child_sibling: None,
+                    });
+                    page_id = child_page_id;
+                }
+            }
+        }
+    }
+
+    /// Index of the child of an internal node whose subtree holds the target.
+    /// For a key, that's the last child whose separating key is at most the
+    /// key.
+    fn search_internal(
+        &self,
+        node: &impl IBPlusTreeInternalPageRead<KeyType>,
+        target: Target<'_, KeyType>,
+    ) -> Result<usize, BPlusTreePageError> {
+        let size = node.get_size()?;
+        let key = match target {
+            Target::Key(key) => key,
+            Target::First => return Ok(0),
+            Target::Last => return Ok(size - 1),
+        };
+
+        // The answer is in [low, high)
+        let mut low = 0;
+        let mut high = size;
+        while high - low > 1 {
+            let mid = (low + high) / 2;
+            match self.comparator.compare(&node.key_at(mid)?, key) {
+                Ordering::Greater => high = mid,
+                _ => low = mid,
+            }
+        }
+        Ok(low)
+    }
+
+    /// Search a leaf for a key. Returns `Ok` with its index if it's there,
+    /// otherwise `Err` with the index it would be inserted at.
+    fn search_leaf(
+        &self,
+        leaf: &impl IBPlusTreeLeafPageRead<KeyType, ValueType>,
+        key: &KeyType,
+    ) -> Result<Result<usize, usize>, BPlusTreePageError> {
+        let mut low = 0;
+        let mut high = leaf.get_size()?;
+        while low < high {
+            let mid = (low + high) / 2;
+            match self.comparator.compare(&leaf.key_at(mid)?, key) {
+                Ordering::Less => low = mid + 1,
+                Ordering::Greater => high = mid,
+                Ordering::Equal => return Ok(Ok(mid)),
+            }
+        }
+        Ok(Err(low))
+    }
+
+    /// Add a new node to the right of a node that was just split, with the
+    /// given separating key, splitting parents in turn if they overflow. New
+    /// nodes' pages come from those allocated for the split.
+    fn insert_into_parent<'t>(
+        &self,
+        path: &mut WritePath<'t, KeyType, ValueType>,
+        new_pages: &mut Vec<PinnedPage<'t, WritablePage<'t>>>,
+        left_page_id: PageId,
+        key: KeyType,
+        right_page_id: PageId,
+    ) -> Result<(), BPlusTreeError> {
+        let Some(Ancestor {
+            node: mut parent,
+            child_index,
+            ..
+        }) = path.ancestors.pop()
+        else {
+            // The root was split, so the tree grows a level. The root wasn't
+            // safe, so the header is still latched.
+            let header = path.header.as_mut().unwrap();
+            let mut root =
+                self.new_internal(new_pages.pop().unwrap(), header.get_internal_max_size()?)?;
+            root.set_children(left_page_id, &[(key, right_page_id)])?;
+            header.set_root_page_id(Some(root.page_id()))?;
+            return Ok(());
+        };
+
+        let (first_child, mut entries) = parent.children()?;
+        entries.insert(child_index, (key, right_page_id));
+        let max_size = parent.get_max_size()?;
+        if entries.len() < max_size {
+            parent.set_children(first_child, &entries)?;
+            return Ok(());
+        }
+
+        // The middle key moves up to the grandparent rather than being copied
+        let left_children = (entries.len() + 2) / 2;
+        let mut right_entries = entries.split_off(left_children - 1);
+        let (separator, right_first_child) = right_entries.remove(0);
+        let mut right = self.new_internal(new_pages.pop().unwrap(), max_size)?;
+        right.set_children(right_first_child, &right_entries)?;
+        parent.set_children(first_child, &entries)?;
+
+        self.insert_into_parent(
+            path,
+            new_pages,
+            parent.page_id(),
+            separator,
+            right.page_id(),
+        )
+    }
+
+    /// Fix up a node that's to fall below half full, given the entries it's
+    /// to be left with, by borrowing from or merging with the sibling latched
+    /// on the way down. Merging can leave the parent below half full in turn.
+    /// Nodes merged away are added to the path's removed nodes.
+    fn rebalance<'t>(
+        &'t self,
+        path: &mut WritePath<'t, KeyType, ValueType>,
+        node: PinnedPage<'t, WritableNode<'t, KeyType, ValueType>>,
+        entries: NodeEntries<KeyType, ValueType>,
+    ) -> Result<(), BPlusTreeError> {
+        // The node isn't the root and wasn't safe, so its parent is latched,
+        // along with its sibling
+        let Ancestor {
+            node: mut parent,
+            child_index,
+            child_sibling,
+        } = path.ancestors.pop().unwrap();
+        let sibling = child_sibling.unwrap();
+        let sibling_entries = NodeEntries::read(&sibling)?;
+        let (parent_first_child, mut parent_entries) = parent.children()?;
+
+        let left_index = child_index.saturating_sub(1);
+        let ((mut left, left_entries), (mut right, right_entries)) = match child_index {
+            0 => ((node, entries), (sibling, sibling_entries)),
+            _ => ((sibling, sibling_entries), (node, entries)),
+        };
+        let (left_page_id, separator) = (left.page_id(), parent_entries[left_index].0.clone());
+
+        let merged = match (&mut *left, &mut *right, left_entries, right_entries) {
+            (
+                WritableNode::Leaf(left_leaf),
+                WritableNode::Leaf(right_leaf),
+                NodeEntries::Leaf(mut entries),
+                NodeEntries::Leaf(right_entries),
+            ) => {
+                entries.extend(right_entries);
+                if entries.len() > left_leaf.get_max_size()? {
+                    let right_entries = entries.split_off(entries.len() / 2);
+                    parent_entries[left_index].0 = right_entries[0].0.clone();
+                    left_leaf.set_entries(&entries)?;
+                    right_leaf.set_entries(&right_entries)?;
+                    false
+                } else {
+                    // Latched before anything's written, as it's the only
+                    // page a removal can't latch on the way down
+                    let next_page_id = right_leaf.get_next_page_id()?;
+                    let next_leaf = next_page_id
+                        .map(|next_page_id| self.fetch_leaf_writable(next_page_id))
+                        .transpose()?;
+                    left_leaf.set_entries(&entries)?;
+                    left_leaf.set_next_page_id(next_page_id)?;
+                    if let Some(mut next_leaf) = next_leaf {
+                        next_leaf.set_prev_page_id(Some(left_page_id))?;
+                    }
+                    true
+                }
+            }
+            (
+                WritableNode::Internal(left_internal),
+                WritableNode::Internal(right_internal),
+                NodeEntries::Internal(left_first_child, mut entries),
+                NodeEntries::Internal(right_first_child, right_entries),
+            ) => {
+                // The separator comes down from the parent between the two
+                // nodes' children
+                entries.push((separator, right_first_child));
+                entries.extend(right_entries);
+                if entries.len() + 1 > left_internal.get_max_size()? {
+                    let left_children = (entries.len() + 2) / 2;
+                    let mut right_entries = entries.split_off(left_children - 1);
+                    let (separator, right_first_child) = right_entries.remove(0);
+                    parent_entries[left_index].0 = separator;
+                    left_internal.set_children(left_first_child, &entries)?;
+                    right_internal.set_children(right_first_child, &right_entries)?;
+                    false
+                } else {
+                    left_internal.set_children(left_first_child, &entries)?;
+                    true
+                }
+            }
+            _ => return Err(BPlusTreePageError::WrongPageType.into()),
+        };
+        if !merged {
+            parent.set_children(parent_first_child, &parent_entries)?;
+            return Ok(());
+        }
+
+        // Merged into the left node, so the right one goes
+        parent_entries.remove(left_index);
+        drop(left);
+        path.removed.push(right);
+
+        if parent_entries.is_empty() && path.ancestors.is_empty() {
+            // The root is left with one child, so the tree shrinks a level.
+            // Any other node that wasn't safe has its parent latched, so this
+            // is the root, and it wasn't safe either so the header is latched.
+            let header = path.header.as_mut().unwrap();
+            header.set_root_page_id(Some(left_page_id))?;
+            path.removed.push(parent.map(WritableNode::Internal));
+            return Ok(());
+        }
+
+        if !path.ancestors.is_empty()
+            && parent_entries.len() + 1 < min_internal_size(parent.get_max_size()?)
+        {
+            return self.rebalance(
+                path,
+                parent.map(WritableNode::Internal),
+                NodeEntries::Internal(parent_first_child, parent_entries),
+            );
+        }
+        parent.set_children(parent_first_child, &parent_entries)?;
+        Ok(())
+    }
+}
+
+/// Whether an operation on a node can't make it split or merge, so nothing
+/// above it can change
+fn is_safe(
+    operation: WriteOperation,
+    size: usize,
+    max_size: usize,
+    is_leaf: bool,
+    is_root: bool,
+) -> bool {
+    match operation {
+        WriteOperation::Insert => size < max_size,
+        WriteOperation::Remove => {
+            // The root only has to keep one entry, or two children
+            let min_size = match (is_leaf, is_root) {
+                (true, true) => 1,
+                (false, true) => 2,
+                (true, false) => min_leaf_size(max_size),
+                (false, false) => min_internal_size(max_size),
+            };
+            size > min_size
+        }
+    }
+}
+
+/// Fewest entries a leaf other than the root can have
+fn min_leaf_size(max_size: usize) -> usize {
+    max_size / 2
+}
+
+/// Fewest children an internal node other than the root can have
+fn min_internal_size(max_size: usize) -> usize {
+    max_size.div_ceil(2)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dbms::buffer::pool_manager::testing::{
+        create_faulty_pool_manager, create_testing_pool_manager,
+        create_testing_pool_manager_with_page_size,
+    };
+    use crate::dbms::buffer::replacer::clock_replacer::ClockReplacer;
+    use crate::dbms::container::tree::OrdComparator;
+    use crate::dbms::storage::disk::testing::{DiskOperation, Fault};
+    use crate::dbms::types::{DEFAULT_PAGE_SIZE, MAX_PAGE_SIZE, MIN_PAGE_SIZE};
+    use crate::{tuple, tuple_type};
+    use rstest::*;
+    use std::sync::Arc;
+
+    type TestTree = BPlusTree<tuple_type![u32], tuple_type![u32, f64], OrdComparator>;
+
+    fn value_for(key: u32) -> tuple_type![u32, f64] {
+        tuple![key * 10, key as f64 / 2.0]
+    }
+
+    /// Walk the whole tree checking that keys are ordered and bounded by the
+    /// separators above them, that every node other than the root is at least
+    /// half full, that all leaves are at the same depth and that the leaf
+    /// chain visits every leaf in order. Returns the keys in order.
+    fn check_tree<V: BytesSerialize + Clone, C: IKeyComparator<u32>>(
+        tree: &BPlusTree<u32, V, C>,
+    ) -> Vec<u32> {
+        let header = tree.fetch_header().unwrap();
+        let Some(root_page_id) = header.get_root_page_id().unwrap() else {
+            return vec![];
+        };
+        let leaf_max_size = header.get_leaf_max_size().unwrap();
+        let internal_max_size = header.get_internal_max_size().unwrap();
+        drop(header);
+
+        let mut leaves = Vec::new();
+        let mut keys = Vec::new();
+        let mut leaf_depth = None;
+        // (page ID, depth, lower bound, upper bound)
+        let mut stack = vec![(root_page_id, 0, None, None)];
+        while let Some((page_id, depth, lower, upper)) = stack.pop() {
+            let in_bounds = |key: &u32| {
+                lower.is_none_or(|lower| tree.comparator.compare(&lower, key).is_le())
+                    && upper.is_none_or(|upper| tree.comparator.compare(key, &upper).is_lt())
+            };
+            match &*tree.fetch_node(page_id).unwrap() {
+                ReadOnlyNode::Leaf(leaf) => {
+                    let entries = leaf.entries().unwrap();
+                    assert_eq!(*leaf_depth.get_or_insert(depth), depth);
+                    if page_id != root_page_id {
+                        assert!(entries.len() >= min_leaf_size(leaf_max_size));
+                    }
+                    assert!(entries.len() <= leaf_max_size);
+                    assert!(entries.iter().all(|(key, _)| in_bounds(key)));
+                    leaves.push(page_id);
+                    keys.extend(entries.into_iter().map(|(key, _)| key));
+                }
+                ReadOnlyNode::Internal(internal) => {
+                    let (first_child, entries) = internal.children().unwrap();
+                    if page_id != root_page_id {
+                        assert!(entries.len() + 1 >= min_internal_size(internal_max_size));
+                    }
+                    assert!(!entries.is_empty());
+                    assert!(entries.len() < internal_max_size);
+                    assert!(entries.iter().all(|(key, _)| in_bounds(key)));
+
+                    let mut children = vec![(first_child, lower)];
+                    children.extend(entries.iter().map(|(key, child)| (*child, Some(*key))));
+                    // Pushed in reverse so they're visited left to right
+                    for (i, (child, child_lower)) in children.iter().enumerate().rev() {
+                        let child_upper = children.get(i + 1).map_or(upper, |(_, key)| *key);
+                        stack.push((*child, depth + 1, *child_lower, child_upper));
+                    }
+                }
+            }
+        }
+
+        for window in keys.windows(2) {
+            assert!(tree.comparator.compare(&window[0], &window[1]).is_lt());
+        }
+
+        // The next-leaf links visit every leaf in order, and the
+        // previous-leaf links are the same chain backwards
+        let mut chain = vec![leaves[0]];
+        let mut prev_page_ids = vec![];
+        loop {
+            let leaf = tree.fetch_leaf(*chain.last().unwrap()).unwrap();
+            prev_page_ids.push(leaf.get_prev_page_id().unwrap());
+            match leaf.get_next_page_id().unwrap() {
+                Some(next_page_id) => chain.push(next_page_id),
+                None => break,
+            }
+        }
+        assert_eq!(chain, leaves);
+        for (i, prev_page_id) in prev_page_ids.into_iter().enumerate() {
+            assert_eq!(prev_page_id, i.checked_sub(1).map(|prev| leaves[prev]));
+        }
+
+        keys
+    }
+
+    /// Deterministic shuffle of the keys, so tests don't need a random source
+    fn shuffled(num_keys: u32) -> Vec<u32> {
+        let mut keys: Vec<u32> = (0..num_keys).collect();
+        let mut state: u64 = 12345;
+        for i in (1..keys.len()).rev() {
+            state = state.wrapping_mul(6364136223846793005).wrapping_add(1);
+            keys.swap(i, (state >> 33) as usize % (i + 1));
+        }
+        keys
+    }
+
+    #[rstest]
+    fn test_empty_tree() {
+        let tree = TestTree::new(create_testing_pool_manager(10), OrdComparator).unwrap();
+
+        assert!(tree.is_empty().unwrap());
+        assert_eq!(tree.get_value(&1).unwrap(), None);
+        assert!(!tree.remove(&1).unwrap());
+    }
+
+    #[rstest]
+    fn test_insert_and_get_value() {
+        let tree = TestTree::new(create_testing_pool_manager(10), OrdComparator).unwrap();
+
+        assert!(tree.insert(&2, &value_for(2)).unwrap());
+        assert!(tree.insert(&1, &value_for(1)).unwrap());
+
+        assert!(!tree.is_empty().unwrap());
+        assert_eq!(tree.get_value(&1).unwrap(), Some(value_for(1)));
+        assert_eq!(tree.get_value(&2).unwrap(), Some(value_for(2)));
+        assert_eq!(tree.get_value(&3).unwrap(), None);
+        assert_eq!(check_tree(&tree), vec![1, 2]);
+    }
+
+    #[rstest]
+    fn test_insert_duplicate_key() {
+        let tree = TestTree::new(create_testing_pool_manager(10), OrdComparator).unwrap();
+
+        assert!(tree.insert(&1, &value_for(1)).unwrap());
+        assert!(!tree.insert(&1, &value_for(2)).unwrap());
+
+        assert_eq!(tree.get_value(&1).unwrap(), Some(value_for(1)));
+    }
+
+    #[rstest]
+    #[case(2, 3)]
+    #[case(3, 3)]
+    #[case(4, 5)]
+    #[case(5, 4)]
+    #[case(leaf_page_capacity::<u32, tuple_type![u32, f64]>(DEFAULT_PAGE_SIZE), internal_page_capacity::<u32>(DEFAULT_PAGE_SIZE))]
+    fn test_insert_many(#[case] leaf_max_size: usize, #[case] internal_max_size: usize) {
+        let tree = TestTree::with_max_sizes(
+            create_testing_pool_manager(50),
+            OrdComparator,
+            leaf_max_size,
+            internal_max_size,
+        )
+        .unwrap();
+
+        for key in shuffled(1000) {
+            assert!(tree.insert(&key, &value_for(key)).unwrap());
+        }
+
+        assert_eq!(check_tree(&tree), (0..1000).collect::<Vec<_>>());
+        for key in 0..1000 {
+            assert_eq!(tree.get_value(&key).unwrap(), Some(value_for(key)));
+        }
+        assert_eq!(tree.get_value(&1000).unwrap(), None);
+    }
+
+    #[rstest]
+    #[case(MIN_PAGE_SIZE)]
+    #[case(16384)]
+    #[case(MAX_PAGE_SIZE)]
+    fn test_nodes_fill_configured_page_size(#[case] page_size: usize) {
+        let tree = TestTree::new(
+            create_testing_pool_manager_with_page_size(10, page_size),
+            OrdComparator,
+        )
+        .unwrap();
+
+        for key in shuffled(20_000) {
+            assert!(tree.insert(&key, &value_for(key)).unwrap());
+        }
+
+        assert_eq!(check_tree(&tree), (0..20_000).collect::<Vec<_>>());
+        assert_eq!(tree.get_value(&12_345).unwrap(), Some(value_for(12_345)));
+    }
+
+    #[rstest]
+    #[case::ascending((0..500).collect())]
+    #[case::descending((0..500).rev().collect())]
+    fn test_insert_in_order(#[case] keys: Vec<u32>) {
+        let tree =
+            TestTree::with_max_sizes(create_testing_pool_manager(50), OrdComparator, 4, 4).unwrap();
+
+        for key in keys {
+            assert!(tree.insert(&key, &value_for(key)).unwrap());
+            check_tree(&tree);
+        }
+
+        assert_eq!(check_tree(&tree), (0..500).collect::<Vec<_>>());
+    }
+
+    #[rstest]
+    #[case(2, 3)]
+    #[case(3, 3)]
+    #[case(4, 5)]
+    #[case(5, 4)]
+    fn test_remove_some(#[case] leaf_max_size: usize, #[case] internal_max_size: usize) {
+        let tree = TestTree::with_max_sizes(
+            create_testing_pool_manager(50),
+            OrdComparator,
+            leaf_max_size,
+            internal_max_size,
+        )
+        .unwrap();
+        for key in shuffled(500) {
+            tree.insert(&key, &value_for(key)).unwrap();
+        }
+
+        for key in shuffled(500).into_iter().filter(|key| key % 3 != 0) {
+            assert!(tree.remove(&key).unwrap());
+            assert!(!tree.remove(&key).unwrap());
+            check_tree(&tree);
+        }
+
+        let expected: Vec<u32> = (0..500).filter(|key| key % 3 == 0).collect();
+        assert_eq!(check_tree(&tree), expected);
+        for key in 0..500 {
+            let expected = (key % 3 == 0).then(|| value_for(key));
+            assert_eq!(tree.get_value(&key).unwrap(), expected);
+        }
+    }
+
+    #[rstest]
+    #[case::ascending((0..300).collect())]
+    #[case::descending((0..300).rev().collect())]
+    #[case::shuffled(shuffled(300))]
+    fn test_remove_all(#[case] keys: Vec<u32>) {
+        let pool_manager = create_testing_pool_manager(50);
+        let tree = TestTree::with_max_sizes(pool_manager.clone(), OrdComparator, 3, 3).unwrap();
+        for key in shuffled(300) {
+            tree.insert(&key, &value_for(key)).unwrap();
+        }
+
+        for key in keys {
+            assert!(tree.remove(&key).unwrap());
+            check_tree(&tree);
+        }
+
+        assert!(tree.is_empty().unwrap());
+        assert_eq!(check_tree(&tree), vec![]);
+        // The emptied tree can be filled again
+        assert!(tree.insert(&1, &value_for(1)).unwrap());
+        assert_eq!(tree.get_value(&1).unwrap(), Some(value_for(1)));
+    }
+
+    #[rstest]
+    fn test_interleaved_inserts_and_removes() {
+        let tree =
+            TestTree::with_max_sizes(create_testing_pool_manager(50), OrdComparator, 3, 4).unwrap();
+        let mut expected = std::collections::BTreeSet::new();
+
+        for (i, key) in shuffled(2000).into_iter().enumerate() {
+            let key = key % 400;
+            if i % 3 == 2 {
+                assert_eq!(tree.remove(&key).unwrap(), expected.remove(&key));
+            } else {
+                assert_eq!(
+                    tree.insert(&key, &value_for(key)).unwrap(),
+                    expected.insert(key)
+                );
+            }
+        }
+
+        assert_eq!(check_tree(&tree), expected.into_iter().collect::<Vec<_>>());
+    }
+
+    #[rstest]
+    fn test_custom_comparator() {
+        let reverse = |lhs: &u32, rhs: &u32| rhs.cmp(lhs);
+        let tree = BPlusTree::<u32, u32, _>::with_max_sizes(
+            create_testing_pool_manager(50),
+            reverse,
+            3,
+            3,
+        )
+        .unwrap();
+
+        for key in shuffled(100) {
+            tree.insert(&key, &(key + 1)).unwrap();
+        }
+        for key in (0..100).filter(|key| key % 2 == 0) {
+            tree.remove(&key).unwrap();
+        }
+
+        let expected: Vec<u32> = (0..100).rev().filter(|key| key % 2 == 1).collect();
+        assert_eq!(check_tree(&tree), expected);
+        assert_eq!(tree.get_value(&51).unwrap(), Some(52));
+        assert_eq!(tree.get_value(&50).unwrap(), None);
+    }
+
+    #[rstest]
+    fn test_open_existing_tree() {
+        let pool_manager = create_testing_pool_manager(50);
+        let header_page_id = {
+            let tree = TestTree::with_max_sizes(pool_manager.clone(), OrdComparator, 4, 4).unwrap();
+            for key in 0..200 {
+                tree.insert(&key, &value_for(key)).unwrap();
+            }
+            tree.header_page_id()
+        };
+        pool_manager.flush_all_pages().unwrap();
+
+        let tree = TestTree::open(pool_manager, OrdComparator, header_page_id);
+
+        assert_eq!(check_tree(&tree), (0..200).collect::<Vec<_>>());
+        assert_eq!(tree.get_value(&123).unwrap(), Some(value_for(123)));
+    }
+
+    #[rstest]
+    fn test_disk_faults_during_lookups() {
+        let (pool_manager, injector) = create_faulty_pool_manager(20);
+        let tree = TestTree::with_max_sizes(pool_manager, OrdComparator, 4, 4).unwrap();
+        for key in shuffled(300) {
+            tree.insert(&key, &value_for(key)).unwrap();
+        }
+
+        // Every other lookup has its next read or write fail, which only
+        // happens if it has to go to disk
+        let mut failures = 0;
+        for key in shuffled(300) {
+            if key % 2 == 0 {
+                injector.fail_read(1);
+            } else {
+                injector.fail_write(1);
+            }
+            match tree.get_value(&key) {
+                Ok(value) => assert_eq!(value, Some(value_for(key))),
+                Err(BPlusTreeError::BufferPoolManagerError(
+                    BufferPoolManagerError::DiskManagerError(_),
+                )) => failures += 1,
+                Err(e) => panic!("unexpected error {e:?}"),
+            }
+            injector.clear();
+
+            // Nothing was left pinned or half done, so trying again works
+            assert_eq!(tree.get_value(&key).unwrap(), Some(value_for(key)));
+        }
+
+        assert!(failures > 100);
+        assert_eq!(check_tree(&tree), (0..300).collect::<Vec<_>>());
+        // And the tree can still be changed
+        assert!(tree.insert(&300, &value_for(300)).unwrap());
+        assert!(tree.remove(&0).unwrap());
+        assert_eq!(check_tree(&tree), (1..301).collect::<Vec<_>>());
+    }
+
+    #[rstest]
+    #[case::failed_read(DiskOperation::ReadPage, Fault::Fail)]
+    #[case::failed_write(DiskOperation::WritePage, Fault::Fail)]
+    fn test_disk_faults_during_splits_and_merges(
+        #[case] operation: DiskOperation,
+        #[case] fault: Fault,
+    ) {
+        let (pool_manager, injector) = create_faulty_pool_manager(20);
+        let tree = TestTree::with_max_sizes(pool_manager, OrdComparator, 4, 4).unwrap();
+
+        let mut failures = 0;
+        for (i, key) in shuffled(300).into_iter().enumerate() {
+            // The change fails part way through, at whichever read or write
+            injector.inject(operation, i % 2 + 1, fault);
+            match tree.insert(&key, &value_for(key)) {
+                Ok(inserted) => assert!(inserted),
+                Err(BPlusTreeError::BufferPoolManagerError(
+                    BufferPoolManagerError::DiskManagerError(_),
+                )) => failures += 1,
+                Err(e) => panic!("unexpected error {e:?}"),
+            }
+            injector.clear();
+            // But it didn't leave the tree half changed or anything pinned
+            tree.insert(&key, &value_for(key)).unwrap();
+        }
+        assert!(failures > 100);
+        assert_eq!(check_tree(&tree), (0..300).collect::<Vec<_>>());
+
+        let mut failures = 0;
+        for (i, key) in shuffled(300).into_iter().enumerate() {
+            injector.inject(operation, i % 2 + 1, fault);
+            match tree.remove(&key) {
+                Ok(removed) => assert!(removed),
+                Err(BPlusTreeError::BufferPoolManagerError(
+                    BufferPoolManagerError::DiskManagerError(_),
+                )) => failures += 1,
+                Err(e) => panic!("unexpected error {e:?}"),
+            }
+            injector.clear();
+            tree.remove(&key).unwrap();
+        }
+        assert!(failures > 100);
+        assert!(tree.is_empty().unwrap());
+        assert_eq!(check_tree(&tree), vec![]);
+    }
+
+    #[rstest]
+    fn test_crash_while_flushing() {
+        let (pool_manager, injector) = create_faulty_pool_manager(20);
+        let tree = TestTree::with_max_sizes(pool_manager.clone(), OrdComparator, 4, 4).unwrap();
+        for key in shuffled(300) {
+            tree.insert(&key, &value_for(key)).unwrap();
+        }
+        pool_manager.flush_all_pages().unwrap();
+        for key in (0..300).filter(|key| key % 2 == 0) {
+            tree.remove(&key).unwrap();
+        }
+        for key in 300..400 {
+            tree.insert(&key, &value_for(key)).unwrap();
+        }
+
+        // The machine goes down part way through writing a page, before it
+        // gets to the next one
+        injector.tear_write(3, 100);
+        injector.fail_write(4);
+        assert!(pool_manager.flush_all_pages().is_err());
+
+        // Nothing since the last flush survives, but that's still a whole tree
+        let pool_manager = BufferPoolManager::new(
+            20,
+            Box::new(ClockReplacer::new(20)),
+            Box::new(injector.crash()),
+        );
+        let tree = TestTree::open(pool_manager, OrdComparator, tree.header_page_id());
+        assert_eq!(check_tree(&tree), (0..300).collect::<Vec<_>>());
+        assert_eq!(tree.get_value(&150).unwrap(), Some(value_for(150)));
+        assert_eq!(tree.get_value(&350).unwrap(), None);
+    }
+
+    #[rstest]
+    #[case(1, 3)]
+    #[case(leaf_page_capacity::<u32, tuple_type![u32, f64]>(DEFAULT_PAGE_SIZE) + 1, 3)]
+    #[case(2, 2)]
+    #[case(2, internal_page_capacity::<u32>(DEFAULT_PAGE_SIZE) + 1)]
+    fn test_invalid_max_sizes(#[case] leaf_max_size: usize, #[case] internal_max_size: usize) {
+        let result = TestTree::with_max_sizes(
+            create_testing_pool_manager(10),
+            OrdComparator,
+            leaf_max_size,
+            internal_max_size,
+        );
+
+        assert!(matches!(result, Err(BPlusTreeError::InvalidMaxSize(_))));
+    }
+
+    #[rstest]
+    fn test_threaded_inserts_and_removes() {
+        let tree = Arc::new(
+            TestTree::with_max_sizes(create_testing_pool_manager(50), OrdComparator, 4, 4).unwrap(),
+        );
+        let num_threads = 8;
+        let per_thread = 200;
+
+        let threads = (0..num_threads)
+            .map(|t| {
+                let tree = tree.clone();
+                std::thread::spawn(move || {
+                    for i in 0..per_thread {
+                        let key = i * num_threads + t;
+                        assert!(tree.insert(&key, &value_for(key)).unwrap());
+                    }
+                    for i in (0..per_thread).filter(|i| i % 2 == 0) {
+                        let key = i * num_threads + t;
+                        assert!(tree.remove(&key).unwrap());
+                        assert_eq!(tree.get_value(&key).unwrap(), None);
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        let expected: Vec<u32> = (0..num_threads * per_thread)
+            .filter(|key| (key / num_threads) % 2 == 1)
+            .collect();
+        assert_eq!(check_tree(&tree), expected);
+    }
+
+    #[rstest]
+    fn test_threaded_races_on_same_keys() {
+        let tree = Arc::new(
+            TestTree::with_max_sizes(create_testing_pool_manager(100), OrdComparator, 3, 3)
+                .unwrap(),
+        );
+        let num_keys = 300;
+
+        // Every thread tries every key, so each insert and remove should
+        // succeed for exactly one of them
+        let race = |insert: bool| {
+            let threads = (0..8)
+                .map(|t| {
+                    let tree = tree.clone();
+                    std::thread::spawn(move || {
+                        let mut keys = shuffled(num_keys);
+                        keys.rotate_left(t * 37);
+                        keys.into_iter()
+                            .filter(|key| match insert {
+                                true => tree.insert(key, &value_for(*key)).unwrap(),
+                                false => tree.remove(key).unwrap(),
+                            })
+                            .count()
+                    })
+                })
+                .collect::<Vec<_>>();
+            threads
+                .into_iter()
+                .map(|thread| thread.join().unwrap())
+                .sum::<usize>()
+        };
+
+        assert_eq!(race(true), num_keys as usize);
+        assert_eq!(check_tree(&tree), (0..num_keys).collect::<Vec<_>>());
+        assert_eq!(race(false), num_keys as usize);
+        assert!(tree.is_empty().unwrap());
+    }
+
+    #[rstest]
+    fn test_threaded_reads_during_writes() {
+        let tree = Arc::new(
+            TestTree::with_max_sizes(create_testing_pool_manager(100), OrdComparator, 3, 3)
+                .unwrap(),
+        );
+        for key in (0..400).step_by(2) {
+            tree.insert(&key, &value_for(key)).unwrap();
+        }
+
+        // The odd keys come and go while the even keys are always there
+        let writers = (0..4)
+            .map(|t| {
+                let tree = tree.clone();
+                std::thread::spawn(move || {
+                    let keys: Vec<u32> = (1..400)
+                        .step_by(2)
+                        .filter(|key| key % 8 == t * 2 + 1)
+                        .collect();
+                    for _ in 0..10 {
+                        for key in &keys {
+                            assert!(tree.insert(key, &value_for(*key)).unwrap());
+                        }
+                        for key in &keys {
+                            assert!(tree.remove(key).unwrap());
+                        }
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        let readers = (0..4)
+            .map(|_| {
+                let tree = tree.clone();
+                std::thread::spawn(move || {
+                    for _ in 0..10 {
+                        for key in (0..400).step_by(2) {
+                            assert_eq!(tree.get_value(&key).unwrap(), Some(value_for(key)));
+                        }
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        for thread in writers.into_iter().chain(readers) {
+            thread.join().unwrap();
+        }
+
+        assert_eq!(check_tree(&tree), (0..400).step_by(2).collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,509 @@
+use std::ops::Bound;
+
+use crate::dbms::buffer::pool_manager::BufferPoolManagerError;
+use crate::dbms::storage::{
+    disk::DiskManagerError,
+    page::b_plus_tree::{
+        leaf::{IBPlusTreeLeafPageRead, ReadOnlyBPlusTreeLeafPage},
+        node::BPlusTreePageError,
+    },
+    serialize::BytesSerialize,
+};
+use crate::dbms::types::PageId;
+
+use super::{BPlusTree, BPlusTreeError, IKeyComparator, PinnedPage, Target};
+
+/// Iterates over a range of a B+ tree's entries in key order, or in reverse
+/// key order, by walking along the chain of leaves.
+///
+/// Only the leaf currently being read is pinned in the buffer pool, and it
+/// stays read latched between calls to `next`, so the tree can be modified
+/// elsewhere while the iterator is alive. Writers to that leaf wait until the
+/// iterator moves on or is dropped, so don't write to the tree from the same
+/// thread while holding one.
+///
+/// If the next leaf has been split, merged or removed by the time the
+/// iterator gets to it, it finds its place again by searching from the root
+/// for the last key it returned.
+pub struct BPlusTreeIterator<'a, KeyType, ValueType, Comparator>
+where
+    KeyType: BytesSerialize,
+    ValueType: BytesSerialize,
+{
+    tree: &'a BPlusTree<KeyType, ValueType, Comparator>,
+    /// The leaf being read, if the scan isn't finished
+    leaf: Option<PinnedPage<'a, ReadOnlyBPlusTreeLeafPage<'a, KeyType, ValueType>>>,
+    /// Index of the next entry in the leaf, or one past it for reverse scans
+    index: usize,
+    /// Bound on the keys where the scan carries on from, moved past each key
+    /// as it's returned
+    start: Bound<KeyType>,
+    /// Bound on the keys where the scan stops
+    end: Bound<KeyType>,
+    reverse: bool,
+}
+
+impl<'a, KeyType, ValueType, Comparator> BPlusTreeIterator<'a, KeyType, ValueType, Comparator>
+where
+    KeyType: BytesSerialize + Clone,
+    ValueType: BytesSerialize + Clone,
+    Comparator: IKeyComparator<KeyType>,
+{
+    /// Start a scan over the keys between the bounds, from the lower bound
+    /// up or, if `reverse` is set, from the upper bound down.
+    pub(super) fn new(
+        tree: &'a BPlusTree<KeyType, ValueType, Comparator>,
+        lower: Bound<KeyType>,
+        upper: Bound<KeyType>,
+        reverse: bool,
+    ) -> Result<Self, BPlusTreeError> {
+        let (start, end) = match reverse {
+            false => (lower, upper),
+            true => (upper, lower),
+        };
+        let mut iterator = Self {
+            tree,
+            leaf: None,
+            index: 0,
+            start,
+            end,
+            reverse,
+        };
+        iterator.seek()?;
+        Ok(iterator)
+    }
+
+    /// Search from the root for the leaf where the scan carries on from.
+    fn seek(&mut self) -> Result<(), BPlusTreeError> {
+        self.leaf = None;
+        let target = match (&self.start, self.reverse) {
+            (Bound::Included(key) | Bound::Excluded(key), _) => Target::Key(key),
+            (Bound::Unbounded, false) => Target::First,
+            (Bound::Unbounded, true) => Target::Last,
+        };
+        let Some(leaf) = self.tree.find_leaf(target)? else {
+            return Ok(());
+        };
+
+        self.index = match (&self.start, self.reverse) {
+            (Bound::Unbounded, false) => 0,
+            (Bound::Unbounded, true) => leaf.get_size()?,
+            (Bound::Included(key), false) | (Bound::Excluded(key), true) => self
+                .tree
+                .search_leaf(&*leaf, key)?
+                .unwrap_or_else(|index| index),
+            (Bound::Excluded(key), false) | (Bound::Included(key), true) => {
+                match self.tree.search_leaf(&*leaf, key)? {
+                    Ok(index) => index + 1,
+                    Err(index) => index,
+                }
+            }
+        };
+        self.leaf = Some(leaf);
+        Ok(())
+    }
+
+    /// Latch the next leaf along from the one just left, and start reading it
+    /// from the end the scan comes in from. If it no longer follows on from
+    /// that leaf, search for the right one instead.
+    fn move_to(&mut self, page_id: PageId, from_page_id: PageId) -> Result<(), BPlusTreeError> {
+        let leaf = match self.tree.fetch_leaf(page_id) {
+            Ok(leaf) => leaf,
+            // It's been merged away since the link to it was read, and its
+            // page either reused for something else or deleted
+            Err(
+                BPlusTreeError::TreePageError(BPlusTreePageError::WrongPageType)
+                | BPlusTreeError::BufferPoolManagerError(BufferPoolManagerError::DiskManagerError(
+                    DiskManagerError::PageNotFound,
+                )),
+            ) => {
+                return self.seek();
+            }
+            Err(e) => return Err(e),
+        };
+
+        let back_page_id = match self.reverse {
+            false => leaf.get_prev_page_id()?,
+            true => leaf.get_next_page_id()?,
+        };
+        if back_page_id != Some(from_page_id) {
+            // Something was split or merged in between
+            drop(leaf);
+            return self.seek();
+        }
+
+        self.index = match self.reverse {
+            false => 0,
+            true => leaf.get_size()?,
+        };
+        self.leaf = Some(leaf);
+        Ok(())
+    }
+
+    /// Whether a key is on the near side of the bound where the scan stops.
+    fn before_end(&self, key: &KeyType) -> bool {
+        let (Bound::Included(end) | Bound::Excluded(end)) = &self.end else {
+            return true;
+        };
+        let ordering = match self.reverse {
+            false => self.tree.comparator.compare(key, end),
+            true => self.tree.comparator.compare(end, key),
+        };
+        match self.end {
+            Bound::Included(_) => ordering.is_le(),
+            _ => ordering.is_lt(),
+        }
+    }
+
+    fn advance(&mut self) -> Result<Option<(KeyType, ValueType)>, BPlusTreeError> {
+        loop {
+            let Some(leaf) = &self.leaf else {
+                return Ok(None);
+            };
+
+            let size = leaf.get_size()?;
+            let index = match self.reverse {
+                false => Some(self.index).filter(|index| *index < size),
+                true => self.index.checked_sub(1),
+            };
+            let Some(index) = index else {
+                let from_page_id = leaf.page_id();
+                let sibling = match self.reverse {
+                    false => leaf.get_next_page_id()?,
+                    true => leaf.get_prev_page_id()?,
+                };
+                // Only one leaf is latched at a time, so scans in opposite
+                // directions can't deadlock
+                self.leaf = None;
+                match sibling {
+                    Some(page_id) => self.move_to(page_id, from_page_id)?,
+                    None => return Ok(None),
+                }
+                continue;
+            };
+
+            let key = leaf.key_at(index)?;
+            if !self.before_end(&key) {
+                self.leaf = None;
+                return Ok(None);
+            }
+            let value = leaf.value_at(index)?;
+            self.index = match self.reverse {
+                false => index + 1,
+                true => index,
+            };
+            self.start = Bound::Excluded(key.clone());
+            return Ok(Some((key, value)));
+        }
+    }
+}
+
+impl<KeyType, ValueType, Comparator> Iterator
+    for BPlusTreeIterator<'_, KeyType, ValueType, Comparator>
+where
+    KeyType: BytesSerialize + Clone,
+    ValueType: BytesSerialize + Clone,
+    Comparator: IKeyComparator<KeyType>,
+{
+    type Item = Result<(KeyType, ValueType), BPlusTreeError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.advance() {
+            Ok(entry) => entry.map(Ok),
+            Err(e) => {
+                // Nothing more can be read after an error
+                self.leaf = None;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dbms::buffer::pool_manager::{
+        testing::create_testing_pool_manager, IBufferPoolManager,
+    };
+    use crate::dbms::container::tree::OrdComparator;
+    use rstest::*;
+    use std::ops::RangeBounds;
+
+    type TestTree = BPlusTree<u32, u64, OrdComparator>;
+
+    /// A tree holding the even keys below 200, over many small leaves
+    fn even_keys_tree(pool_size: usize) -> TestTree {
+        let tree =
+            TestTree::with_max_sizes(create_testing_pool_manager(pool_size), OrdComparator, 3, 3)
+                .unwrap();
+        for key in (0..200).filter(|key| key % 2 == 0) {
+            tree.insert(&key, &(key as u64 * 100)).unwrap();
+        }
+        tree
+    }
+
+    fn keys(iterator: BPlusTreeIterator<'_, u32, u64, OrdComparator>) -> Vec<u32> {
+        iterator.map(|entry| entry.unwrap().0).collect()
+    }
+
+    #[rstest]
+    fn test_iter_empty_tree() {
+        let tree = TestTree::new(create_testing_pool_manager(10), OrdComparator).unwrap();
+
+        assert_eq!(keys(tree.iter().unwrap()), vec![]);
+        assert_eq!(keys(tree.range_rev(..).unwrap()), vec![]);
+    }
+
+    #[rstest]
+    fn test_iter_entries() {
+        let tree = even_keys_tree(50);
+
+        let entries: Vec<(u32, u64)> = tree.iter().unwrap().map(Result::unwrap).collect();
+
+        let expected: Vec<(u32, u64)> = (0..200)
+            .filter(|key| key % 2 == 0)
+            .map(|key| (key, key as u64 * 100))
+            .collect();
+        assert_eq!(entries, expected);
+    }
+
+    #[rstest]
+    #[case::all(..)]
+    #[case::from_present(10..)]
+    #[case::from_absent(11..)]
+    #[case::below_first(..0)]
+    #[case::to_present(..=50)]
+    #[case::to_absent(..51)]
+    #[case::between_present(10..50)]
+    #[case::between_present_inclusive(10..=50)]
+    #[case::between_absent(11..=51)]
+    #[case::past_last(150..1000)]
+    #[case::after_last(250..)]
+    #[case::empty(50..50)]
+    #[case::single(50..=50)]
+    fn test_range(#[case] range: impl RangeBounds<u32> + Clone) {
+        let tree = even_keys_tree(50);
+        let expected: Vec<u32> = (0..200)
+            .filter(|key| key % 2 == 0 && range.contains(key))
+            .collect();
+
+        assert_eq!(keys(tree.range(range.clone()).unwrap()), expected);
+        assert_eq!(
+            keys(tree.range_rev(range).unwrap()),
+            expected.into_iter().rev().collect::<Vec<_>>()
+        );
+    }
+
+    #[rstest]
+    #[case((Bound::Excluded(10), Bound::Excluded(20)), vec![12, 14, 16, 18])]
+    #[case((Bound::Excluded(11), Bound::Included(20)), vec![12, 14, 16, 18, 20])]
+    #[case((Bound::Excluded(10), Bound::Unbounded), (12..200).step_by(2).collect())]
+    #[case((Bound::Excluded(198), Bound::Unbounded), vec![])]
+    fn test_range_excluded_start(
+        #[case] range: (Bound<u32>, Bound<u32>),
+        #[case] expected: Vec<u32>,
+    ) {
+        let tree = even_keys_tree(50);
+
+        assert_eq!(keys(tree.range(range).unwrap()), expected);
+        assert_eq!(
+            keys(tree.range_rev(range).unwrap()),
+            expected.into_iter().rev().collect::<Vec<_>>()
+        );
+    }
+
+    #[rstest]
+    fn test_range_custom_comparator() {
+        let reverse = |lhs: &u32, rhs: &u32| rhs.cmp(lhs);
+        let tree = BPlusTree::<u32, u64, _>::with_max_sizes(
+            create_testing_pool_manager(50),
+            reverse,
+            3,
+            3,
+        )
+        .unwrap();
+        for key in 0..100 {
+            tree.insert(&key, &(key as u64)).unwrap();
+        }
+
+        // Bounds are in the comparator's order, so they run from high to low
+        let range = (Bound::Included(60), Bound::Included(50));
+        let forward: Vec<u32> = tree.range(range).unwrap().map(|e| e.unwrap().0).collect();
+        let backward: Vec<u32> = tree
+            .range_rev(range)
+            .unwrap()
+            .map(|e| e.unwrap().0)
+            .collect();
+
+        assert_eq!(forward, (50..=60).rev().collect::<Vec<_>>());
+        assert_eq!(backward, (50..=60).collect::<Vec<_>>());
+    }
+
+    /// Check nothing in the pool is left pinned, by taking every frame at once
+    fn assert_all_unpinned(tree: &TestTree, pool_size: usize) {
+        let pages = (0..pool_size)
+            .map(|_| tree.buffer_pool_manager.new_page().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(pages.len(), pool_size);
+    }
+
+    #[rstest]
+    fn test_scan_unpins_leaves() {
+        let tree = even_keys_tree(50);
+
+        assert_eq!(keys(tree.iter().unwrap()).len(), 100);
+        assert_eq!(keys(tree.range_rev(..).unwrap()).len(), 100);
+
+        assert_all_unpinned(&tree, 50);
+    }
+
+    #[rstest]
+    fn test_drop_partway_unpins() {
+        let tree = even_keys_tree(50);
+
+        let mut iterator = tree.range(10..).unwrap();
+        assert_eq!(iterator.next().unwrap().unwrap(), (10, 1000));
+        drop(iterator);
+
+        // The leaf the iterator was on can be written again
+        assert!(tree.insert(&11, &1100).unwrap());
+        assert_eq!(tree.get_value(&11).unwrap(), Some(1100));
+        assert_all_unpinned(&tree, 50);
+    }
+
+    #[rstest]
+    fn test_next_leaf_deleted() {
+        let tree = even_keys_tree(50);
+        let mut iterator = tree.iter().unwrap();
+        let first_leaf_size = iterator.leaf.as_ref().unwrap().get_size().unwrap();
+        let mut scanned = (0..first_leaf_size)
+            .map(|_| iterator.next().unwrap().unwrap().0)
+            .collect::<Vec<_>>();
+
+        // Let go of the first leaf as if about to move on from it, having read
+        // the link to the next one
+        let leaf = iterator.leaf.take().unwrap();
+        let (from_page_id, next_page_id) =
+            (leaf.page_id(), leaf.get_next_page_id().unwrap().unwrap());
+        drop(leaf);
+
+        // Empty out the next leaf until it's merged into the first and its
+        // page deleted
+        let next_keys = {
+            let next_leaf = tree.fetch_leaf(next_page_id).unwrap();
+            (0..next_leaf.get_size().unwrap())
+                .map(|index| next_leaf.key_at(index).unwrap())
+                .collect::<Vec<_>>()
+        };
+        let mut removed = vec![];
+        for key in next_keys {
+            tree.remove(&key).unwrap();
+            removed.push(key);
+            if tree.fetch_leaf(next_page_id).is_err() {
+                break;
+            }
+        }
+        assert!(matches!(
+            tree.fetch_leaf(next_page_id),
+            Err(BPlusTreeError::BufferPoolManagerError(
+                BufferPoolManagerError::DiskManagerError(DiskManagerError::PageNotFound)
+            ))
+        ));
+
+        // The scan finds its place again rather than failing
+        iterator.move_to(next_page_id, from_page_id).unwrap();
+        scanned.extend(keys(iterator));
+        let expected = (0..200)
+            .filter(|key| key % 2 == 0 && !removed.contains(key))
+            .collect::<Vec<_>>();
+        assert_eq!(scanned, expected);
+    }
+
+    #[rstest]
+    fn test_threaded_scans_while_modified() {
+        let tree = std::sync::Arc::new(even_keys_tree(100));
+        let evens: Vec<u32> = (0..200).step_by(2).collect();
+
+        // Writers keep splitting and merging leaves with the odd keys, while
+        // scans check they still see every even key exactly once, in order
+        let writers = (0..4)
+            .map(|t| {
+                let tree = tree.clone();
+                std::thread::spawn(move || {
+                    for round in 0..20 {
+                        let odds = (1..200).step_by(2).filter(|key| key % 8 == t * 2 + 1);
+                        for key in odds {
+                            match round % 2 {
+                                0 => assert!(tree.insert(&key, &0).unwrap()),
+                                _ => assert!(tree.remove(&key).unwrap()),
+                            }
+                        }
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        let scanners = (0..4)
+            .map(|t| {
+                let tree = tree.clone();
+                let evens = evens.clone();
+                std::thread::spawn(move || {
+                    for _ in 0..20 {
+                        let scanned = match t % 2 {
+                            0 => keys(tree.iter().unwrap()),
+                            _ => {
+                                let mut scanned = keys(tree.range_rev(..).unwrap());
+                                scanned.reverse();
+                                scanned
+                            }
+                        };
+                        assert!(scanned.windows(2).all(|pair| pair[0] < pair[1]));
+                        let scanned_evens: Vec<u32> =
+                            scanned.into_iter().filter(|key| key % 2 == 0).collect();
+                        assert_eq!(scanned_evens, evens);
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        for thread in writers.into_iter().chain(scanners) {
+            thread.join().unwrap();
+        }
+
+        assert_eq!(keys(tree.iter().unwrap()), evens);
+    }
+
+    #[rstest]
+    fn test_scan_after_removes() {
+        let tree = even_keys_tree(50);
+        for key in (0..200).filter(|key| key % 4 == 0) {
+            tree.remove(&key).unwrap();
+        }
+
+        let expected: Vec<u32> = (0..200).filter(|key| key % 4 == 2).collect();
+        assert_eq!(keys(tree.iter().unwrap()), expected);
+        assert_eq!(
+            keys(tree.range_rev(..).unwrap()),
+            expected.into_iter().rev().collect::<Vec<_>>()
+        );
+    }
+
+    #[rstest]
+    fn test_threaded_scans() {
+        let tree = std::sync::Arc::new(even_keys_tree(50));
+
+        let threads = (0..8)
+            .map(|t| {
+                let tree = tree.clone();
+                std::thread::spawn(move || {
+                    let start = t * 20;
+                    let expected: Vec<u32> = (start..start + 40).step_by(2).collect();
+                    for _ in 0..20 {
+                        assert_eq!(keys(tree.range(start..start + 40).unwrap()), expected);
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+    }
+}
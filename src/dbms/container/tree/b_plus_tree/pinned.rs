@@ -0,0 +1,211 @@
+use std::convert::Infallible;
+use std::ops::{Deref, DerefMut};
+
+use crate::dbms::{
+    buffer::{
+        pool_manager::{BufferPoolManager, BufferPoolManagerError, IBufferPoolManager},
+        types::{ReadOnlyPage, WritablePage},
+    },
+    storage::{
+        page::b_plus_tree::{
+            internal::{ReadOnlyBPlusTreeInternalPage, WritableBPlusTreeInternalPage},
+            leaf::{ReadOnlyBPlusTreeLeafPage, WritableBPlusTreeLeafPage},
+            node::{read_page_type, BPlusTreePageError, BPlusTreePageType},
+        },
+        serialize::BytesSerialize,
+    },
+    types::PageId,
+};
+
+/// A page that's pinned in the buffer pool and latched, through one of the
+/// page wrappers. Dropping it releases the latch and then unpins the page,
+/// marking it dirty if it was ever borrowed mutably.
+///
+/// Latch crabbing holds several pages at once and releases them in whatever
+/// order the tree's shape allows, so it's easier to have each page release
+/// itself than to unpin them all by hand.
+pub(super) struct PinnedPage<'a, Wrapper> {
+    buffer_pool_manager: &'a BufferPoolManager,
+    page_id: PageId,
+    wrapper: Option<Wrapper>,
+    dirty: bool,
+}
+
+impl<'a, Wrapper> PinnedPage<'a, Wrapper> {
+    /// Take ownership of a pinned page's wrapper.
+    pub fn new(
+        buffer_pool_manager: &'a BufferPoolManager,
+        page_id: PageId,
+        wrapper: Wrapper,
+    ) -> Self {
+        Self {
+            buffer_pool_manager,
+            page_id,
+            wrapper: Some(wrapper),
+            dirty: false,
+        }
+    }
+
+    /// Take ownership of a pinned page's wrapper. If the page couldn't be
+    /// wrapped, it's unpinned straight away.
+    pub fn try_new<E>(
+        buffer_pool_manager: &'a BufferPoolManager,
+        page_id: PageId,
+        wrapper: Result<Wrapper, E>,
+    ) -> Result<Self, E> {
+        let mut pinned = Self {
+            buffer_pool_manager,
+            page_id,
+            wrapper: None,
+            dirty: false,
+        };
+        pinned.wrapper = Some(wrapper?);
+        Ok(pinned)
+    }
+
+    pub fn page_id(&self) -> PageId {
+        self.page_id
+    }
+
+    /// Make sure the page is written out, for changes made before it was
+    /// wrapped.
+    pub fn mark_dirty(&mut self) {
+        self.dirty = true;
+    }
+
+    /// Swap the wrapper for another one around the same page, keeping it
+    /// pinned.
+    pub fn map<Other>(self, f: impl FnOnce(Wrapper) -> Other) -> PinnedPage<'a, Other> {
+        let Ok(mapped) = self.try_map(|wrapper| Ok::<_, Infallible>(f(wrapper)));
+        mapped
+    }
+
+    /// Swap the wrapper for another one around the same page, keeping it
+    /// pinned. If that fails the page is unpinned.
+    pub fn try_map<Other, E>(
+        mut self,
+        f: impl FnOnce(Wrapper) -> Result<Other, E>,
+    ) -> Result<PinnedPage<'a, Other>, E> {
+        let wrapper = f(self.wrapper.take().unwrap())?;
+        let mapped = PinnedPage {
+            buffer_pool_manager: self.buffer_pool_manager,
+            page_id: self.page_id,
+            wrapper: Some(wrapper),
+            dirty: self.dirty,
+        };
+        // The pin moves over to the new one
+        std::mem::forget(self);
+        Ok(mapped)
+    }
+
+    /// Release the latch but keep the page pinned until this is dropped, for
+    /// work on the page that takes the latch itself.
+    pub fn unlatch(&mut self) {
+        self.wrapper = None;
+    }
+}
+
+impl<Wrapper> Deref for PinnedPage<'_, Wrapper> {
+    type Target = Wrapper;
+
+    fn deref(&self) -> &Self::Target {
+        self.wrapper.as_ref().unwrap()
+    }
+}
+
+impl<Wrapper> DerefMut for PinnedPage<'_, Wrapper> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.dirty = true;
+        self.wrapper.as_mut().unwrap()
+    }
+}
+
+impl<Wrapper> Drop for PinnedPage<'_, Wrapper> {
+    fn drop(&mut self) {
+        // Release the latch before the pin, as is done everywhere else
+        self.wrapper = None;
+        let unpinned = self
+            .buffer_pool_manager
+            .unpin_page(self.page_id, self.dirty);
+        // Errors can't be passed on from here, but a page that was already
+        // unpinned means a pin's been dropped twice somewhere
+        debug_assert!(!matches!(
+            unpinned,
+            Err(BufferPoolManagerError::PageNotPinned)
+        ));
+    }
+}
+
+/// A tree node read out of a page, when it's not known in advance whether
+/// it's a leaf or an internal node.
+pub(super) enum ReadOnlyNode<'a, KeyType: BytesSerialize, ValueType: BytesSerialize> {
+    Leaf(ReadOnlyBPlusTreeLeafPage<'a, KeyType, ValueType>),
+    Internal(ReadOnlyBPlusTreeInternalPage<'a, KeyType>),
+}
+
+/// A tree node that can be written, when it's not known in advance whether
+/// it's a leaf or an internal node.
+pub(super) enum WritableNode<'a, KeyType: BytesSerialize, ValueType: BytesSerialize> {
+    Leaf(WritableBPlusTreeLeafPage<'a, KeyType, ValueType>),
+    Internal(WritableBPlusTreeInternalPage<'a, KeyType>),
+}
+
+impl<'a, KeyType: BytesSerialize, ValueType: BytesSerialize> ReadOnlyNode<'a, KeyType, ValueType> {
+    /// Wrap a page holding either kind of node.
+    pub fn new(page: ReadOnlyPage<'a>) -> Result<Self, BPlusTreePageError> {
+        match read_page_type(&page)? {
+            BPlusTreePageType::Leaf => Ok(Self::Leaf(ReadOnlyBPlusTreeLeafPage::new(page)?)),
+            BPlusTreePageType::Internal => {
+                Ok(Self::Internal(ReadOnlyBPlusTreeInternalPage::new(page)?))
+            }
+        }
+    }
+
+    pub fn into_leaf(
+        self,
+    ) -> Result<ReadOnlyBPlusTreeLeafPage<'a, KeyType, ValueType>, BPlusTreePageError> {
+        match self {
+            Self::Leaf(leaf) => Ok(leaf),
+            Self::Internal(_) => Err(BPlusTreePageError::WrongPageType),
+        }
+    }
+}
+
+impl<'a, KeyType: BytesSerialize, ValueType: BytesSerialize> WritableNode<'a, KeyType, ValueType> {
+    /// Wrap a page holding either kind of node.
+    pub fn new(page: WritablePage<'a>) -> Result<Self, BPlusTreePageError> {
+        match read_page_type(&page)? {
+            BPlusTreePageType::Leaf => Ok(Self::Leaf(WritableBPlusTreeLeafPage::new(page)?)),
+            BPlusTreePageType::Internal => {
+                Ok(Self::Internal(WritableBPlusTreeInternalPage::new(page)?))
+            }
+        }
+    }
+
+    pub fn into_leaf(
+        self,
+    ) -> Result<WritableBPlusTreeLeafPage<'a, KeyType, ValueType>, BPlusTreePageError> {
+        match self {
+            Self::Leaf(leaf) => Ok(leaf),
+            Self::Internal(_) => Err(BPlusTreePageError::WrongPageType),
+        }
+    }
+
+    pub fn into_internal(
+        self,
+    ) -> Result<WritableBPlusTreeInternalPage<'a, KeyType>, BPlusTreePageError> {
+        match self {
+            Self::Internal(internal) => Ok(internal),
+            Self::Leaf(_) => Err(BPlusTreePageError::WrongPageType),
+        }
+    }
+
+    /// Mark the page as no longer holding a node, once it's been removed from
+    /// the tree.
+    pub fn mark_removed(&mut self) -> Result<(), BPlusTreePageError> {
+        match self {
+            Self::Leaf(leaf) => leaf.mark_removed(),
+            Self::Internal(internal) => internal.mark_removed(),
+        }
+    }
+}
@@ -0,0 +1,56 @@
+use std::cmp::Ordering;
+
+/// Orders the keys of a tree index.
+pub trait IKeyComparator<KeyType> {
+    fn compare(&self, lhs: &KeyType, rhs: &KeyType) -> Ordering;
+}
+
+impl<KeyType, F> IKeyComparator<KeyType> for F
+where
+    F: Fn(&KeyType, &KeyType) -> Ordering,
+{
+    fn compare(&self, lhs: &KeyType, rhs: &KeyType) -> Ordering {
+        self(lhs, rhs)
+    }
+}
+
+/// Compares keys with their `Ord` implementation
+#[derive(Debug, Default, Clone, Copy)]
+pub struct OrdComparator;
+
+impl<KeyType: Ord> IKeyComparator<KeyType> for OrdComparator {
+    fn compare(&self, lhs: &KeyType, rhs: &KeyType) -> Ordering {
+        lhs.cmp(rhs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{tuple, tuple_type};
+    use rstest::*;
+
+    #[rstest]
+    #[case(1, 2, Ordering::Less)]
+    #[case(2, 2, Ordering::Equal)]
+    #[case(3, 2, Ordering::Greater)]
+    fn test_ord_comparator(#[case] lhs: u32, #[case] rhs: u32, #[case] expected: Ordering) {
+        assert_eq!(OrdComparator.compare(&lhs, &rhs), expected);
+    }
+
+    #[rstest]
+    fn test_closure_comparator() {
+        // Order by the second field, descending
+        let comparator =
+            |lhs: &tuple_type![u32, f64], rhs: &tuple_type![u32, f64]| rhs.1.total_cmp(&lhs.1);
+
+        assert_eq!(
+            comparator.compare(&tuple![1, 2.0], &tuple![2, 1.0]),
+            Ordering::Less
+        );
+        assert_eq!(
+            comparator.compare(&tuple![1, 1.0], &tuple![2, 1.0]),
+            Ordering::Equal
+        );
+    }
+}
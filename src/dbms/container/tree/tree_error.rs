@@ -0,0 +1,31 @@
+use crate::dbms::{
+    buffer::pool_manager::BufferPoolManagerError,
+    storage::page::{b_plus_tree::node::BPlusTreePageError, PageError},
+};
+
+#[derive(Debug)]
+pub enum BPlusTreeError {
+    /// Requested node size is too small to split, or doesn't fit in a page
+    InvalidMaxSize(String),
+    BufferPoolManagerError(BufferPoolManagerError),
+    PageError(PageError),
+    TreePageError(BPlusTreePageError),
+}
+
+impl From<BufferPoolManagerError> for BPlusTreeError {
+    fn from(e: BufferPoolManagerError) -> Self {
+        Self::BufferPoolManagerError(e)
+    }
+}
+
+impl From<PageError> for BPlusTreeError {
+    fn from(e: PageError) -> Self {
+        Self::PageError(e)
+    }
+}
+
+impl From<BPlusTreePageError> for BPlusTreeError {
+    fn from(e: BPlusTreePageError) -> Self {
+        Self::TreePageError(e)
+    }
+}
@@ -1,4 +1,18 @@
+mod compressed_disk_manager;
 mod disk_manager;
+mod disk_scheduler;
+mod encrypted_disk_manager;
+mod file_disk_manager;
+mod mmap_disk_manager;
+mod superblock;
+mod tablespace_disk_manager;
 pub mod testing;
 
+pub use compressed_disk_manager::*;
 pub use disk_manager::*;
+pub use disk_scheduler::*;
+pub use encrypted_disk_manager::*;
+pub use file_disk_manager::*;
+pub use mmap_disk_manager::*;
+pub use superblock::*;
+pub use tablespace_disk_manager::*;
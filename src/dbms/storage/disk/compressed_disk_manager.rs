@@ -0,0 +1,1019 @@
+use std::collections::HashMap;
+
+use crate::dbms::types::{PageData, PageId, INVALID_PAGE_ID};
+
+use super::{DiskManagerError, IDiskManager};
+
+const PAGE_ID_SIZE_BYTES: usize = std::mem::size_of::<PageId>();
+
+/// Map pages are chained together, each holding the entries for a run of
+/// page IDs
+const MAP_NEXT_PAGE_ID_OFFSET_BYTES: usize = 0;
+const MAP_ENTRIES_OFFSET_BYTES: usize = MAP_NEXT_PAGE_ID_OFFSET_BYTES + PAGE_ID_SIZE_BYTES;
+
+/// An entry is the page's state, then the inner page holding it, then where
+/// in that page it is and how long it is
+const ENTRY_STATE_OFFSET_BYTES: usize = 0;
+const ENTRY_PAGE_ID_OFFSET_BYTES: usize = ENTRY_STATE_OFFSET_BYTES + 1;
+const ENTRY_OFFSET_OFFSET_BYTES: usize = ENTRY_PAGE_ID_OFFSET_BYTES + PAGE_ID_SIZE_BYTES;
+const ENTRY_LEN_OFFSET_BYTES: usize = ENTRY_OFFSET_OFFSET_BYTES + 4;
+const MAP_ENTRY_SIZE_BYTES: usize = ENTRY_LEN_OFFSET_BYTES + 4;
+
+/// How many pages' entries fit in one page of the map, for the given page
+/// size
+pub const fn compression_map_page_capacity(page_size: usize) -> usize {
+    (page_size - MAP_ENTRIES_OFFSET_BYTES) / MAP_ENTRY_SIZE_BYTES
+}
+
+const STATE_UNALLOCATED: u8 = 0;
+const STATE_ZEROED: u8 = 1;
+const STATE_COMPRESSED: u8 = 2;
+const STATE_UNCOMPRESSED: u8 = 3;
+
+/// Where a page's contents are kept in the inner disk manager
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PageLocation {
+    Unallocated,
+    /// Allocated, but all zeroes, so nothing needs storing
+    Zeroed,
+    /// Compressed, packed into a bin page alongside other compressed pages
+    Compressed {
+        bin_page_id: PageId,
+        offset: u32,
+        len: u32,
+    },
+    /// Didn't compress, so stored as is in a page of its own
+    Uncompressed {
+        inner_page_id: PageId,
+    },
+}
+
+impl PageLocation {
+    fn to_bytes(self, page_size: usize) -> [u8; MAP_ENTRY_SIZE_BYTES] {
+        let (state, page_id, offset, len) = match self {
+            Self::Unallocated => (STATE_UNALLOCATED, INVALID_PAGE_ID, 0, 0),
+            Self::Zeroed => (STATE_ZEROED, INVALID_PAGE_ID, 0, 0),
+            Self::Compressed {
+                bin_page_id,
+                offset,
+                len,
+            } => (STATE_COMPRESSED, bin_page_id, offset, len),
+            Self::Uncompressed { inner_page_id } => {
+                (STATE_UNCOMPRESSED, inner_page_id, 0, page_size as u32)
+            }
+        };
+        let mut bytes = [0u8; MAP_ENTRY_SIZE_BYTES];
+        bytes[ENTRY_STATE_OFFSET_BYTES] = state;
+        bytes[ENTRY_PAGE_ID_OFFSET_BYTES..ENTRY_OFFSET_OFFSET_BYTES]
+            .copy_from_slice(&page_id.to_be_bytes());
+        bytes[ENTRY_OFFSET_OFFSET_BYTES..ENTRY_LEN_OFFSET_BYTES]
+            .copy_from_slice(&offset.to_be_bytes());
+        bytes[ENTRY_LEN_OFFSET_BYTES..MAP_ENTRY_SIZE_BYTES].copy_from_slice(&len.to_be_bytes());
+        bytes
+    }
+
+    fn from_bytes(
+        page_id: PageId,
+        bytes: &[u8],
+        page_size: usize,
+    ) -> Result<Self, DiskManagerError> {
+        let inner_page_id = PageId::from_be_bytes(
+            bytes[ENTRY_PAGE_ID_OFFSET_BYTES..ENTRY_OFFSET_OFFSET_BYTES]
+                .try_into()
+                .unwrap(),
+        );
+        let offset = u32::from_be_bytes(
+            bytes[ENTRY_OFFSET_OFFSET_BYTES..ENTRY_LEN_OFFSET_BYTES]
+                .try_into()
+                .unwrap(),
+        );
+        let len = u32::from_be_bytes(
+            bytes[ENTRY_LEN_OFFSET_BYTES..MAP_ENTRY_SIZE_BYTES]
+                .try_into()
+                .unwrap(),
+        );
+        match bytes[ENTRY_STATE_OFFSET_BYTES] {
+            STATE_UNALLOCATED => Ok(Self::Unallocated),
+            STATE_ZEROED => Ok(Self::Zeroed),
+            STATE_COMPRESSED if offset as usize + len as usize <= page_size => {
+                Ok(Self::Compressed {
+                    bin_page_id: inner_page_id,
+                    offset,
+                    len,
+                })
+            }
+            STATE_UNCOMPRESSED => Ok(Self::Uncompressed { inner_page_id }),
+            _ => Err(DiskManagerError::InvalidCompressedPage(page_id)),
+        }
+    }
+}
+
+/// A page of the inner disk manager that compressed pages are packed into
+#[derive(Debug, Default)]
+struct Bin {
+    /// How many pages are stored in the bin
+    live: usize,
+    /// How many bytes of the bin those pages take up
+    live_bytes: usize,
+    /// How much of the bin has been written to
+    used: usize,
+}
+
+impl Bin {
+    /// Whether most of what's been written to the bin is from pages that have
+    /// since moved out, so it's worth moving the rest out too
+    fn is_sparse(&self) -> bool {
+        self.live_bytes * 2 < self.used
+    }
+}
+
+/// A disk manager that compresses pages before handing them on to another
+/// disk manager, and decompresses them again when they're read, so pages stay
+/// the inner disk manager's page size in memory but take up less space on
+/// disk.
+///
+/// Compressed pages are a variable size, so they're packed together into the
+/// inner disk manager's pages, called bins here. A map from each page ID to
+/// where its compressed contents are lives in a chain of the inner disk
+/// manager's own pages, so the compressed pages can be found again after a
+/// reopen. Pages that don't compress are stored whole in a page of their own,
+/// and pages that are all zeroes aren't stored at all.
+///
+/// Bins are only written to the end, so space in a bin from a page that's been
+/// overwritten or deallocated isn't reused in place. Instead, once less than
+/// half of a full bin is still in use, the pages left in it are moved to the
+/// bin being filled, and the emptied bin is handed back to the inner disk
+/// manager. That keeps every bin but the one being filled at least half full,
+/// so however often pages are rewritten, they take up no more than about
+/// twice the space they compress to.
+///
+/// A page's new contents are always written before its map entry is changed to
+/// point at them, so a page is never left pointing at a partly written payload.
+///
+/// The log isn't compressed, and is passed straight through.
+pub struct CompressedDiskManager<D: IDiskManager> {
+    inner: D,
+    /// The inner disk manager's page size, which is the size of pages
+    /// before and after compression
+    page_size: usize,
+    /// The inner pages holding the map, in order
+    map_page_ids: Vec<PageId>,
+    /// Where each page is, indexed by page ID
+    locations: Vec<PageLocation>,
+    /// Deallocated page IDs, handed out again before new ones
+    free_page_ids: Vec<PageId>,
+    bins: HashMap<PageId, Bin>,
+    /// The bin new compressed pages are being written to
+    open_bin_page_id: Option<PageId>,
+    /// Full bins that have become sparse, waiting for their pages to be moved
+    /// out
+    sparse_bin_page_ids: Vec<PageId>,
+}
+
+impl<D: IDiskManager> CompressedDiskManager<D> {
+    /// Start compressing pages into the given disk manager, with a new, empty
+    /// map allocated from it.
+    #[allow(dead_code)]
+    pub fn new(mut inner: D) -> Result<Self, DiskManagerError> {
+        let map_page_id = Self::new_map_page(&mut inner)?;
+        Ok(Self {
+            page_size: inner.page_size(),
+            inner,
+            map_page_ids: vec![map_page_id],
+            locations: Vec::new(),
+            free_page_ids: Vec::new(),
+            bins: HashMap::new(),
+            open_bin_page_id: None,
+            sparse_bin_page_ids: Vec::new(),
+        })
+    }
+
+    /// Open pages previously compressed into the given disk manager, from the
+    /// first page of their map.
+    #[allow(dead_code)]
+    pub fn open(inner: D, map_page_id: PageId) -> Result<Self, DiskManagerError> {
+        let page_size = inner.page_size();
+        let map_capacity = compression_map_page_capacity(page_size);
+        let mut map_page_ids = Vec::new();
+        let mut locations = Vec::new();
+        let mut next_map_page_id = Some(map_page_id);
+        while let Some(map_page_id) = next_map_page_id {
+            let map_page = inner.read_page(map_page_id)?;
+            for i in 0..map_capacity {
+                let page_id = (map_page_ids.len() * map_capacity + i) as PageId;
+                let offset = MAP_ENTRIES_OFFSET_BYTES + i * MAP_ENTRY_SIZE_BYTES;
+                locations.push(PageLocation::from_bytes(
+                    page_id,
+                    &map_page[offset..offset + MAP_ENTRY_SIZE_BYTES],
+                    page_size,
+                )?);
+            }
+            map_page_ids.push(map_page_id);
+            next_map_page_id = read_page_id(&map_page, MAP_NEXT_PAGE_ID_OFFSET_BYTES);
+        }
+
+        // Page IDs carry on from the last allocated page, and any unallocated
+        // ones before it are free
+        while locations.last() == Some(&PageLocation::Unallocated) {
+            locations.pop();
+        }
+        let free_page_ids = (0..locations.len())
+            .rev()
+            .filter(|&page_id| locations[page_id] == PageLocation::Unallocated)
+            .map(|page_id| page_id as PageId)
+            .collect();
+
+        let mut bins = HashMap::<PageId, Bin>::new();
+        for location in &locations {
+            if let PageLocation::Compressed {
+                bin_page_id,
+                offset,
+                len,
+            } = *location
+            {
+                let bin = bins.entry(bin_page_id).or_default();
+                bin.live += 1;
+                bin.live_bytes += len as usize;
+                bin.used = usize::max(bin.used, offset as usize + len as usize);
+            }
+        }
+        // None of the bins is being filled any more, so any sparse ones are
+        // emptied out as pages are next written
+        let sparse_bin_page_ids = bins
+            .iter()
+            .filter(|(_, bin)| bin.is_sparse())
+            .map(|(&bin_page_id, _)| bin_page_id)
+            .collect();
+
+        Ok(Self {
+            inner,
+            page_size,
+            map_page_ids,
+            locations,
+            free_page_ids,
+            bins,
+            open_bin_page_id: None,
+            sparse_bin_page_ids,
+        })
+    }
+
+    /// The inner page ID of the map's first page, used to reopen the pages
+    #[allow(dead_code)]
+    pub fn map_page_id(&self) -> PageId {
+        self.map_page_ids[0]
+    }
+
+    /// The disk manager compressed pages are written to
+    #[allow(dead_code)]
+    pub fn inner(&self) -> &D {
+        &self.inner
+    }
+
+    /// Stop compressing pages, and get back the disk manager they were
+    /// written to
+    #[allow(dead_code)]
+    pub fn into_inner(self) -> D {
+        self.inner
+    }
+
+    fn new_map_page(inner: &mut D) -> Result<PageId, DiskManagerError> {
+        let page_size = inner.page_size();
+        let map_page_id = inner.allocate_page()?;
+        let mut map_page = vec![0u8; page_size];
+        write_page_id(&mut map_page, MAP_NEXT_PAGE_ID_OFFSET_BYTES, None);
+        for i in 0..compression_map_page_capacity(page_size) {
+            let offset = MAP_ENTRIES_OFFSET_BYTES + i * MAP_ENTRY_SIZE_BYTES;
+            map_page[offset..offset + MAP_ENTRY_SIZE_BYTES]
+                .copy_from_slice(&PageLocation::Unallocated.to_bytes(page_size));
+        }
+        inner.write_page(map_page_id, &map_page)?;
+        Ok(map_page_id)
+    }
+
+    fn location(&self, page_id: PageId) -> PageLocation {
+        self.locations
+            .get(page_id as usize)
+            .copied()
+            .unwrap_or(PageLocation::Unallocated)
+    }
+
+    /// Point a page's map entry at a new location
+    fn set_location(
+        &mut self,
+        page_id: PageId,
+        location: PageLocation,
+    ) -> Result<(), DiskManagerError> {
+        let map_capacity = compression_map_page_capacity(self.page_size);
+        let map_page_id = self.map_page_ids[page_id as usize / map_capacity];
+        let offset =
+            MAP_ENTRIES_OFFSET_BYTES + (page_id as usize % map_capacity) * MAP_ENTRY_SIZE_BYTES;
+        let mut map_page = self.inner.read_page(map_page_id)?;
+        map_page[offset..offset + MAP_ENTRY_SIZE_BYTES]
+            .copy_from_slice(&location.to_bytes(self.page_size));
+        self.inner.write_page(map_page_id, &map_page)?;
+
+        self.locations[page_id as usize] = location;
+        Ok(())
+    }
+
+    /// Write a page's contents to the inner disk manager, compressed if that
+    /// saves any space, and return where they went
+    fn store(&mut self, page: &[u8]) -> Result<PageLocation, DiskManagerError> {
+        if page.iter().all(|&b| b == 0) {
+            return Ok(PageLocation::Zeroed);
+        }
+
+        let compressed = lz4_flex::block::compress(page);
+        if compressed.len() >= self.page_size {
+            let inner_page_id = self.inner.allocate_page()?;
+            self.inner.write_page(inner_page_id, page)?;
+            return Ok(PageLocation::Uncompressed { inner_page_id });
+        }
+        self.store_compressed(&compressed)
+    }
+
+    /// Append a compressed page to the bin being filled, starting a new one
+    /// if it doesn't fit, and return where it went
+    fn store_compressed(&mut self, compressed: &[u8]) -> Result<PageLocation, DiskManagerError> {
+        let bin_page_id = match self.open_bin_page_id {
+            Some(bin_page_id)
+                if self.bins[&bin_page_id].used + compressed.len() <= self.page_size =>
+            {
+                bin_page_id
+            }
+            old_bin_page_id => {
+                let bin_page_id = self.inner.allocate_page()?;
+                self.bins.insert(bin_page_id, Bin::default());
+                self.open_bin_page_id = Some(bin_page_id);
+                // The old bin is full now, and may already be mostly unused
+                if let Some(old_bin_page_id) = old_bin_page_id {
+                    if self.bins[&old_bin_page_id].is_sparse() {
+                        self.sparse_bin_page_ids.push(old_bin_page_id);
+                    }
+                }
+                bin_page_id
+            }
+        };
+
+        let offset = self.bins[&bin_page_id].used;
+        let mut bin_page = self.inner.read_page(bin_page_id)?;
+        bin_page[offset..offset + compressed.len()].copy_from_slice(compressed);
+        self.inner.write_page(bin_page_id, &bin_page)?;
+
+        let bin = self.bins.get_mut(&bin_page_id).unwrap();
+        bin.live += 1;
+        bin.live_bytes += compressed.len();
+        bin.used += compressed.len();
+        Ok(PageLocation::Compressed {
+            bin_page_id,
+            offset: offset as u32,
+            len: compressed.len() as u32,
+        })
+    }
+
+    /// Free up the space used by a page's old contents
+    fn release(&mut self, location: PageLocation) -> Result<(), DiskManagerError> {
+        match location {
+            PageLocation::Unallocated | PageLocation::Zeroed => Ok(()),
+            PageLocation::Uncompressed { inner_page_id } => {
+                self.inner.deallocate_page(inner_page_id)
+            }
+            PageLocation::Compressed {
+                bin_page_id, len, ..
+            } => {
+                let is_open = self.open_bin_page_id == Some(bin_page_id);
+                let Some(bin) = self.bins.get_mut(&bin_page_id) else {
+                    return Ok(());
+                };
+                let was_sparse = bin.is_sparse();
+                bin.live -= 1;
+                bin.live_bytes -= len as usize;
+                if bin.live > 0 {
+                    if !is_open && !was_sparse && bin.is_sparse() {
+                        self.sparse_bin_page_ids.push(bin_page_id);
+                    }
+                    return Ok(());
+                }
+                if is_open {
+                    // Nothing left in the open bin, so start filling it again
+                    bin.used = 0;
+                    return Ok(());
+                }
+                self.bins.remove(&bin_page_id);
+                self.inner.deallocate_page(bin_page_id)
+            }
+        }
+    }
+
+    /// Move the pages out of every bin that's become sparse, so the bins can
+    /// be handed back to the inner disk manager
+    fn compact_sparse_bins(&mut self) -> Result<(), DiskManagerError> {
+        while let Some(bin_page_id) = self.sparse_bin_page_ids.pop() {
+            // It may have emptied out on its own since
+            if !self.bins.contains_key(&bin_page_id) {
+                continue;
+            }
+            if let Err(e) = self.move_out_of_bin(bin_page_id) {
+                // Tried again after the next write
+                self.sparse_bin_page_ids.push(bin_page_id);
+                return Err(e);
+            }
+        }
+        Ok(())
+    }
+
+    /// Move every page in a bin to the bin being filled. Each page's map
+    /// entry is only pointed at its new copy once that's been written, and
+    /// the bin is deallocated as its last page moves out.
+    fn move_out_of_bin(&mut self, bin_page_id: PageId) -> Result<(), DiskManagerError> {
+        let bin_page = self.inner.read_page(bin_page_id)?;
+        for page_id in 0..self.locations.len() {
+            let location = self.locations[page_id];
+            let PageLocation::Compressed {
+                bin_page_id: page_bin_page_id,
+                offset,
+                len,
+            } = location
+            else {
+                continue;
+            };
+            if page_bin_page_id != bin_page_id {
+                continue;
+            }
+
+            let compressed = &bin_page[offset as usize..offset as usize + len as usize];
+            let new_location = self.store_compressed(compressed)?;
+            self.set_location(page_id as PageId, new_location)?;
+            self.release(location)?;
+        }
+        Ok(())
+    }
+}
+
+impl<D: IDiskManager> IDiskManager for CompressedDiskManager<D> {
+    fn page_size(&self) -> usize {
+        self.page_size
+    }
+
+    fn write_page(&mut self, page_id: PageId, page: &[u8]) -> Result<(), DiskManagerError> {
+        // Must allocate page before writing to it
+        let old_location = self.location(page_id);
+        if old_location == PageLocation::Unallocated {
+            return Err(DiskManagerError::PageNotFound);
+        }
+        if page.len() != self.page_size {
+            return Err(DiskManagerError::InvalidPageSize(page.len()));
+        }
+
+        let location = self.store(page)?;
+        self.set_location(page_id, location)?;
+        self.release(old_location)?;
+        self.compact_sparse_bins()
+    }
+
+    fn read_page(&self, page_id: PageId) -> Result<PageData, DiskManagerError> {
+        match self.location(page_id) {
+            PageLocation::Unallocated => Err(DiskManagerError::PageNotFound),
+            PageLocation::Zeroed => Ok(vec![0u8; self.page_size]),
+            PageLocation::Uncompressed { inner_page_id } => self.inner.read_page(inner_page_id),
+            PageLocation::Compressed {
+                bin_page_id,
+                offset,
+                len,
+            } => {
+                let bin_page = self.inner.read_page(bin_page_id)?;
+                let compressed = &bin_page[offset as usize..offset as usize + len as usize];
+                lz4_flex::block::decompress(compressed, self.page_size)
+                    .ok()
+                    .filter(|page| page.len() == self.page_size)
+                    .ok_or(DiskManagerError::InvalidCompressedPage(page_id))
+            }
+        }
+    }
+
+    fn write_log(&mut self, log: &[u8]) -> Result<(), DiskManagerError> {
+        self.inner.write_log(log)
+    }
+
+    fn read_log(&self, size: usize, offset: usize) -> Result<PageData, DiskManagerError> {
+        self.inner.read_log(size, offset)
+    }
+
+    fn sync_log(&mut self) -> Result<(), DiskManagerError> {
+        self.inner.sync_log()
+    }
+
+    fn sync_pages(&mut self) -> Result<(), DiskManagerError> {
+        self.inner.sync_pages()
+    }
+
+    fn allocate_page(&mut self) -> Result<PageId, DiskManagerError> {
+        let page_id = match self.free_page_ids.pop() {
+            Some(page_id) => page_id,
+            None => {
+                let page_id = self.locations.len() as PageId;
+                let map_capacity = compression_map_page_capacity(self.page_size);
+                if page_id as usize / map_capacity >= self.map_page_ids.len() {
+                    // Out of room in the map, so chain on another page
+                    let new_map_page_id = Self::new_map_page(&mut self.inner)?;
+                    let last_map_page_id = *self.map_page_ids.last().unwrap();
+                    let mut last_map_page = self.inner.read_page(last_map_page_id)?;
+                    write_page_id(
+                        &mut last_map_page,
+                        MAP_NEXT_PAGE_ID_OFFSET_BYTES,
+                        Some(new_map_page_id),
+                    );
+                    self.inner.write_page(last_map_page_id, &last_map_page)?;
+                    self.map_page_ids.push(new_map_page_id);
+                }
+                self.locations.push(PageLocation::Unallocated);
+                page_id
+            }
+        };
+
+        self.set_location(page_id, PageLocation::Zeroed)?;
+        Ok(page_id)
+    }
+
+    fn deallocate_page(&mut self, page_id: PageId) -> Result<(), DiskManagerError> {
+        let old_location = self.location(page_id);
+        if old_location == PageLocation::Unallocated {
+            return Ok(());
+        }
+
+        self.set_location(page_id, PageLocation::Unallocated)?;
+        self.free_page_ids.push(page_id);
+        self.release(old_location)?;
+        self.compact_sparse_bins()
+    }
+}
+
+fn read_page_id(page: &[u8], offset: usize) -> Option<PageId> {
+    match PageId::from_be_bytes(
+        page[offset..offset + PAGE_ID_SIZE_BYTES]
+            .try_into()
+            .unwrap(),
+    ) {
+        INVALID_PAGE_ID => None,
+        page_id => Some(page_id),
+    }
+}
+
+fn write_page_id(page: &mut [u8], offset: usize, page_id: Option<PageId>) {
+    page[offset..offset + PAGE_ID_SIZE_BYTES]
+        .copy_from_slice(&page_id.unwrap_or(INVALID_PAGE_ID).to_be_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dbms::buffer::pool_manager::{BufferPoolManager, IBufferPoolManager};
+    use crate::dbms::buffer::replacer::clock_replacer::ClockReplacer;
+    use crate::dbms::storage::disk::{testing::InMemoryDiskManager, FileDiskManager};
+    use crate::dbms::types::{DEFAULT_PAGE_SIZE, MAX_PAGE_SIZE};
+    use rstest::*;
+    use tempfile::tempdir;
+
+    const COMPRESSION_MAP_PAGE_CAPACITY: usize = compression_map_page_capacity(DEFAULT_PAGE_SIZE);
+
+    /// A page that compresses well, different for each seed
+    fn compressible_page(seed: u8) -> PageData {
+        compressible_page_of_size(seed, DEFAULT_PAGE_SIZE)
+    }
+
+    fn compressible_page_of_size(seed: u8, page_size: usize) -> PageData {
+        let mut page = vec![0u8; page_size];
+        for (i, byte) in page.iter_mut().enumerate() {
+            *byte = seed.wrapping_add((i / 64) as u8);
+        }
+        page
+    }
+
+    /// A page of pseudo-random bytes, which won't compress
+    fn incompressible_page(seed: u32) -> PageData {
+        incompressible_page_of_size(seed, DEFAULT_PAGE_SIZE)
+    }
+
+    fn incompressible_page_of_size(seed: u32, page_size: usize) -> PageData {
+        let mut state = seed.wrapping_mul(2_654_435_761).wrapping_add(1);
+        let mut page = vec![0u8; page_size];
+        for byte in page.iter_mut() {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            *byte = state as u8;
+        }
+        page
+    }
+
+    fn create_disk_manager() -> CompressedDiskManager<InMemoryDiskManager> {
+        CompressedDiskManager::new(InMemoryDiskManager::new()).unwrap()
+    }
+
+    #[rstest]
+    #[case::compressible(compressible_page(1))]
+    #[case::incompressible(incompressible_page(1))]
+    #[case::zeroed(vec![0u8; DEFAULT_PAGE_SIZE])]
+    fn test_write_and_read_page(#[case] page: PageData) {
+        let mut disk_manager = create_disk_manager();
+        let page_id = disk_manager.allocate_page().unwrap();
+
+        disk_manager.write_page(page_id, &page).unwrap();
+
+        assert_eq!(disk_manager.read_page(page_id).unwrap(), page);
+    }
+
+    #[rstest]
+    fn test_allocated_page_zeroed() {
+        let mut disk_manager = create_disk_manager();
+        let page_id = disk_manager.allocate_page().unwrap();
+
+        assert_eq!(
+            disk_manager.read_page(page_id).unwrap(),
+            [0u8; DEFAULT_PAGE_SIZE]
+        );
+        // Only the map's page is stored
+        assert_eq!(disk_manager.inner().pages.len(), 1);
+    }
+
+    #[rstest]
+    fn test_compressed_pages_share_inner_pages() {
+        let mut disk_manager = create_disk_manager();
+        for i in 0..20 {
+            let page_id = disk_manager.allocate_page().unwrap();
+            disk_manager
+                .write_page(page_id, &compressible_page(i))
+                .unwrap();
+        }
+
+        // The map's page and a handful of bins
+        assert!(disk_manager.inner().pages.len() <= 4);
+        for i in 0..20 {
+            assert_eq!(
+                disk_manager.read_page(i as PageId).unwrap(),
+                compressible_page(i)
+            );
+        }
+    }
+
+    #[rstest]
+    fn test_incompressible_pages_stored_whole() {
+        let mut disk_manager = create_disk_manager();
+        for i in 0..3 {
+            let page_id = disk_manager.allocate_page().unwrap();
+            disk_manager
+                .write_page(page_id, &incompressible_page(i))
+                .unwrap();
+        }
+
+        assert_eq!(disk_manager.inner().pages.len(), 4);
+        assert!(disk_manager
+            .inner()
+            .pages
+            .values()
+            .any(|page| *page == incompressible_page(1)));
+    }
+
+    #[rstest]
+    fn test_overwritten_pages_free_inner_pages() {
+        let mut disk_manager = create_disk_manager();
+        let compressed = disk_manager.allocate_page().unwrap();
+        let uncompressed = disk_manager.allocate_page().unwrap();
+
+        for i in 0..200 {
+            disk_manager
+                .write_page(compressed, &compressible_page(i as u8))
+                .unwrap();
+            disk_manager
+                .write_page(uncompressed, &incompressible_page(i))
+                .unwrap();
+        }
+
+        // Full bins are handed back once nothing's left in them, and each
+        // uncompressed page only takes one page at a time
+        assert!(disk_manager.inner().pages.len() <= 4);
+        assert_eq!(
+            disk_manager.read_page(compressed).unwrap(),
+            compressible_page(199)
+        );
+        assert_eq!(
+            disk_manager.read_page(uncompressed).unwrap(),
+            incompressible_page(199)
+        );
+    }
+
+    /// Every round rewrites the pages still in use, and one more page is
+    /// written for the last time, leaving it behind in that round's bin
+    fn rewrite_rounds(
+        disk_manager: &mut CompressedDiskManager<InMemoryDiskManager>,
+        page_ids: &[PageId],
+        pages: &mut [PageData],
+        rounds: std::ops::Range<usize>,
+    ) {
+        for round in rounds {
+            for i in round % page_ids.len()..page_ids.len() {
+                pages[i] = compressible_page((round * 7 + i) as u8);
+                disk_manager.write_page(page_ids[i], &pages[i]).unwrap();
+            }
+        }
+    }
+
+    #[rstest]
+    fn test_rewrites_keep_inner_pages_bounded() {
+        let mut disk_manager = create_disk_manager();
+        let page_ids = (0..100)
+            .map(|_| disk_manager.allocate_page().unwrap())
+            .collect::<Vec<_>>();
+        let mut pages = vec![vec![]; page_ids.len()];
+
+        rewrite_rounds(&mut disk_manager, &page_ids, &mut pages, 0..100);
+
+        // Bins other than the one being filled stay at least half full, so
+        // there's the map's page, twice as many bins as the pages need, and
+        // the one being filled
+        let live_bytes: usize = pages
+            .iter()
+            .map(|page| lz4_flex::block::compress(page).len())
+            .sum();
+        let max_inner_pages = 1 + 2 * live_bytes.div_ceil(DEFAULT_PAGE_SIZE) + 1;
+        assert!(disk_manager.inner().pages.len() <= max_inner_pages);
+        for (&page_id, page) in page_ids.iter().zip(&pages) {
+            assert_eq!(&disk_manager.read_page(page_id).unwrap(), page);
+        }
+
+        // Bins are found sparse again after a reopen
+        let map_page_id = disk_manager.map_page_id();
+        let mut disk_manager =
+            CompressedDiskManager::open(disk_manager.into_inner(), map_page_id).unwrap();
+        rewrite_rounds(&mut disk_manager, &page_ids, &mut pages, 100..200);
+        assert!(disk_manager.inner().pages.len() <= max_inner_pages);
+        for (&page_id, page) in page_ids.iter().zip(&pages) {
+            assert_eq!(&disk_manager.read_page(page_id).unwrap(), page);
+        }
+    }
+
+    #[rstest]
+    fn test_page_changes_between_compressed_and_not() {
+        let mut disk_manager = create_disk_manager();
+        let page_id = disk_manager.allocate_page().unwrap();
+
+        for page in [
+            compressible_page(1),
+            incompressible_page(1),
+            vec![0u8; DEFAULT_PAGE_SIZE],
+            compressible_page(2),
+        ] {
+            disk_manager.write_page(page_id, &page).unwrap();
+            assert_eq!(disk_manager.read_page(page_id).unwrap(), page);
+        }
+        // The map's page and the bin
+        assert_eq!(disk_manager.inner().pages.len(), 2);
+    }
+
+    #[rstest]
+    fn test_deallocate_page() {
+        let mut disk_manager = create_disk_manager();
+        for i in 0..3 {
+            let page_id = disk_manager.allocate_page().unwrap();
+            disk_manager
+                .write_page(page_id, &incompressible_page(i))
+                .unwrap();
+        }
+
+        disk_manager.deallocate_page(1).unwrap();
+        // Deallocating twice, or a page that was never allocated, does nothing
+        disk_manager.deallocate_page(1).unwrap();
+        disk_manager.deallocate_page(10).unwrap();
+
+        assert!(matches!(
+            disk_manager.read_page(1),
+            Err(DiskManagerError::PageNotFound)
+        ));
+        assert!(matches!(
+            disk_manager.write_page(1, &compressible_page(0)),
+            Err(DiskManagerError::PageNotFound)
+        ));
+        assert_eq!(disk_manager.inner().pages.len(), 3);
+
+        // The page ID is reused, zeroed
+        assert_eq!(disk_manager.allocate_page().unwrap(), 1);
+        assert_eq!(disk_manager.read_page(1).unwrap(), [0u8; DEFAULT_PAGE_SIZE]);
+        assert_eq!(disk_manager.allocate_page().unwrap(), 3);
+    }
+
+    #[rstest]
+    fn test_write_page_wrong_size() {
+        let mut disk_manager = create_disk_manager();
+        let page_id = disk_manager.allocate_page().unwrap();
+
+        let result = disk_manager.write_page(page_id, &[1u8; 10]);
+        assert!(matches!(result, Err(DiskManagerError::InvalidPageSize(10))));
+    }
+
+    #[rstest]
+    fn test_reopen() {
+        let mut disk_manager = create_disk_manager();
+        let page_count = COMPRESSION_MAP_PAGE_CAPACITY + 10;
+        for i in 0..page_count {
+            let page_id = disk_manager.allocate_page().unwrap();
+            let page = if i % 5 == 0 {
+                incompressible_page(i as u32)
+            } else {
+                compressible_page(i as u8)
+            };
+            disk_manager.write_page(page_id, &page).unwrap();
+        }
+        disk_manager.deallocate_page(3).unwrap();
+        disk_manager.deallocate_page(7).unwrap();
+        let map_page_id = disk_manager.map_page_id();
+
+        let mut disk_manager =
+            CompressedDiskManager::open(disk_manager.into_inner(), map_page_id).unwrap();
+
+        for i in 0..page_count {
+            let result = disk_manager.read_page(i as PageId);
+            match i {
+                3 | 7 => assert!(matches!(result, Err(DiskManagerError::PageNotFound))),
+                i if i % 5 == 0 => assert_eq!(result.unwrap(), incompressible_page(i as u32)),
+                i => assert_eq!(result.unwrap(), compressible_page(i as u8)),
+            }
+        }
+
+        // Freed page IDs are reused, then new ones carry on from the last
+        assert_eq!(disk_manager.allocate_page().unwrap(), 3);
+        assert_eq!(disk_manager.allocate_page().unwrap(), 7);
+        assert_eq!(disk_manager.allocate_page().unwrap(), page_count as PageId);
+    }
+
+    #[rstest]
+    fn test_large_pages() {
+        let mut disk_manager =
+            CompressedDiskManager::new(InMemoryDiskManager::with_page_size(MAX_PAGE_SIZE)).unwrap();
+        assert_eq!(disk_manager.page_size(), MAX_PAGE_SIZE);
+        let page_count = compression_map_page_capacity(MAX_PAGE_SIZE) + 3;
+        for i in 0..page_count {
+            let page_id = disk_manager.allocate_page().unwrap();
+            if i % 500 == 0 {
+                let page = incompressible_page_of_size(i as u32, MAX_PAGE_SIZE);
+                disk_manager.write_page(page_id, &page).unwrap();
+            } else if i % 100 == 0 {
+                let page = compressible_page_of_size(i as u8, MAX_PAGE_SIZE);
+                disk_manager.write_page(page_id, &page).unwrap();
+            }
+        }
+        let map_page_id = disk_manager.map_page_id();
+
+        let disk_manager =
+            CompressedDiskManager::open(disk_manager.into_inner(), map_page_id).unwrap();
+
+        for i in 0..page_count {
+            let expected = if i % 500 == 0 {
+                incompressible_page_of_size(i as u32, MAX_PAGE_SIZE)
+            } else if i % 100 == 0 {
+                compressible_page_of_size(i as u8, MAX_PAGE_SIZE)
+            } else {
+                vec![0u8; MAX_PAGE_SIZE]
+            };
+            assert_eq!(disk_manager.read_page(i as PageId).unwrap(), expected);
+        }
+    }
+
+    #[rstest]
+    fn test_reopen_frees_emptied_bins() {
+        let mut disk_manager = create_disk_manager();
+        let first = disk_manager.allocate_page().unwrap();
+        let second = disk_manager.allocate_page().unwrap();
+        disk_manager
+            .write_page(first, &compressible_page(1))
+            .unwrap();
+        let map_page_id = disk_manager.map_page_id();
+        let mut disk_manager =
+            CompressedDiskManager::open(disk_manager.into_inner(), map_page_id).unwrap();
+
+        // Written to a new bin after a reopen, so the old one empties out
+        disk_manager
+            .write_page(second, &compressible_page(2))
+            .unwrap();
+        disk_manager
+            .write_page(first, &compressible_page(3))
+            .unwrap();
+
+        assert_eq!(disk_manager.inner().pages.len(), 2);
+        assert_eq!(disk_manager.read_page(first).unwrap(), compressible_page(3));
+        assert_eq!(
+            disk_manager.read_page(second).unwrap(),
+            compressible_page(2)
+        );
+    }
+
+    #[rstest]
+    fn test_read_corrupted_page() {
+        let mut disk_manager = create_disk_manager();
+        let page_id = disk_manager.allocate_page().unwrap();
+        disk_manager
+            .write_page(page_id, &compressible_page(1))
+            .unwrap();
+
+        let map_page_id = disk_manager.map_page_id();
+        let mut inner = disk_manager.into_inner();
+        let bin_page_id = *inner.pages.keys().find(|&&id| id != map_page_id).unwrap();
+        inner
+            .write_page(bin_page_id, &[0xff; DEFAULT_PAGE_SIZE])
+            .unwrap();
+        let disk_manager = CompressedDiskManager::open(inner, map_page_id).unwrap();
+
+        assert!(matches!(
+            disk_manager.read_page(page_id),
+            Err(DiskManagerError::InvalidCompressedPage(id)) if id == page_id
+        ));
+    }
+
+    #[rstest]
+    fn test_open_invalid_map() {
+        let mut inner = InMemoryDiskManager::new();
+        let map_page_id = inner.allocate_page().unwrap();
+        let mut map_page = vec![0u8; DEFAULT_PAGE_SIZE];
+        write_page_id(&mut map_page, MAP_NEXT_PAGE_ID_OFFSET_BYTES, None);
+        // An entry for page 2 with an unknown state
+        map_page[MAP_ENTRIES_OFFSET_BYTES + 2 * MAP_ENTRY_SIZE_BYTES] = 9;
+        inner.write_page(map_page_id, &map_page).unwrap();
+
+        let result = CompressedDiskManager::open(inner, map_page_id);
+        assert!(matches!(
+            result,
+            Err(DiskManagerError::InvalidCompressedPage(2))
+        ));
+    }
+
+    #[rstest]
+    fn test_log_passed_through() {
+        let mut disk_manager = create_disk_manager();
+
+        disk_manager.write_log(&[1, 2, 3]).unwrap();
+        disk_manager.sync_log().unwrap();
+
+        assert_eq!(disk_manager.read_log(2, 1).unwrap()[..2], [2, 3]);
+        assert_eq!(disk_manager.inner().log, vec![1, 2, 3]);
+    }
+
+    #[rstest]
+    fn test_compressed_file_smaller() {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("test.db");
+        let page_count = 50;
+
+        let map_page_id = {
+            let mut disk_manager =
+                CompressedDiskManager::new(FileDiskManager::new(&db_path).unwrap()).unwrap();
+            // The file's own checksums leave less room for the pages
+            let page_size = disk_manager.page_size();
+            for i in 0..page_count {
+                let page_id = disk_manager.allocate_page().unwrap();
+                disk_manager
+                    .write_page(page_id, &compressible_page_of_size(i as u8, page_size))
+                    .unwrap();
+            }
+            disk_manager.map_page_id()
+        };
+
+        let file_size = std::fs::metadata(&db_path).unwrap().len();
+        assert!(file_size < (page_count * DEFAULT_PAGE_SIZE / 4) as u64);
+
+        let disk_manager =
+            CompressedDiskManager::open(FileDiskManager::new(&db_path).unwrap(), map_page_id)
+                .unwrap();
+        for i in 0..page_count {
+            assert_eq!(
+                disk_manager.read_page(i as PageId).unwrap(),
+                compressible_page_of_size(i as u8, disk_manager.page_size())
+            );
+        }
+    }
+
+    #[rstest]
+    fn test_buffer_pool_with_compression() {
+        let buffer_pool_manager = BufferPoolManager::new(
+            2,
+            Box::new(ClockReplacer::new(2)),
+            Box::new(create_disk_manager()),
+        );
+
+        let page_ids = (0..10u8)
+            .map(|i| {
+                let mut page = buffer_pool_manager.new_page().unwrap();
+                let page_id = page.get_page_id().unwrap().unwrap();
+                page.set_data(compressible_page(i)).unwrap();
+                drop(page);
+                buffer_pool_manager.unpin_page(page_id, true).unwrap();
+                page_id
+            })
+            .collect::<Vec<_>>();
+
+        for (i, page_id) in page_ids.into_iter().enumerate() {
+            let page = buffer_pool_manager.fetch_page(page_id).unwrap();
+            assert_eq!(page.get_data().unwrap(), compressible_page(i as u8));
+            drop(page);
+            buffer_pool_manager.unpin_page(page_id, false).unwrap();
+        }
+    }
+}
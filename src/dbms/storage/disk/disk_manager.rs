@@ -1,15 +1,102 @@
-use crate::dbms::types::{PageData, PageId};
+use crate::dbms::types::{FileId, PageData, PageId, SYSTEM_FILE_ID};
 
 #[derive(Debug)]
 pub enum DiskManagerError {
     PageNotFound,
+    /// Page data passed in doesn't match the page size
+    InvalidPageSize(usize),
+    /// The database file's size isn't a whole number of pages
+    InvalidFileSize(u64),
+    /// The database file doesn't start with the superblock's magic number,
+    /// so isn't a database file
+    InvalidMagic,
+    /// The database file was written with a format version this build can't
+    /// read
+    UnsupportedVersion(u32),
+    /// The database was created with the given page size, which isn't the
+    /// page size it was opened with
+    PageSizeMismatch(usize),
+    /// The given page size isn't one a database can be created with, see
+    /// `is_valid_page_size`
+    UnsupportedPageSize(usize),
+    /// The database file's free list is broken at the given page, which is
+    /// either outside the file or already in the list
+    InvalidFreeList(PageId),
+    /// The page's contents don't match the checksum stored with it, e.g.
+    /// after a torn write or corruption on disk
+    ChecksumMismatch {
+        page_id: PageId,
+    },
+    /// The given page's compressed contents, or its entry in the map of
+    /// where compressed pages are, can't be read
+    InvalidCompressedPage(PageId),
+    /// There's no file with the given ID
+    FileNotFound(FileId),
+    /// No more files can be added, because the disk manager only keeps one
+    /// or because every file ID is in use
+    TooManyFiles,
+    /// The given file has as many pages as a page ID can address
+    FileFull(FileId),
+    /// The system file was asked to do something it can't, e.g. be dropped
+    SystemFile,
+    /// The disk manager was opened without a log, so can't write one
+    NoLog,
+    /// Requested log offset is at or beyond the end of the log
+    LogOffsetOutOfRange(usize),
+    /// Requested log read is larger than a page
+    InvalidLogReadSize(usize),
+    /// Error from the underlying file
+    IoError(std::io::Error),
+}
+
+impl From<std::io::Error> for DiskManagerError {
+    fn from(e: std::io::Error) -> Self {
+        Self::IoError(e)
+    }
 }
 
 pub trait IDiskManager {
+    /// Size of every page, chosen when the database was created
+    fn page_size(&self) -> usize;
     fn write_page(&mut self, page_id: PageId, page: &[u8]) -> Result<(), DiskManagerError>;
     fn read_page(&self, page_id: PageId) -> Result<PageData, DiskManagerError>;
+    /// Append the given bytes to the end of the log. The write isn't
+    /// guaranteed to be durable until `sync_log` is called.
     fn write_log(&mut self, log: &[u8]) -> Result<(), DiskManagerError>;
+    /// Read `size` bytes of the log starting from `offset`, into a page-sized
+    /// buffer. If the log ends before `offset + size` the remainder of the
+    /// page is zeroed. Reading from an offset at or past the end of the log is
+    /// an error.
     fn read_log(&self, size: usize, offset: usize) -> Result<PageData, DiskManagerError>;
+    /// Make all log writes so far durable.
+    fn sync_log(&mut self) -> Result<(), DiskManagerError>;
+    /// Make all page writes, allocations and deallocations so far durable.
+    /// Until then, a crash can lose any of them.
+    fn sync_pages(&mut self) -> Result<(), DiskManagerError>;
+    /// Allocate a zeroed page, reusing a deallocated page's ID if there is
+    /// one.
     fn allocate_page(&mut self) -> Result<PageId, DiskManagerError>;
+    /// Free up a page so its ID can be handed out again. Does nothing if the
+    /// page isn't allocated.
     fn deallocate_page(&mut self, page_id: PageId) -> Result<(), DiskManagerError>;
+    /// Allocate a zeroed page in the given file. Disk managers that only keep
+    /// one file only have the system file, which `allocate_page` allocates
+    /// in.
+    fn allocate_page_in(&mut self, file_id: FileId) -> Result<PageId, DiskManagerError> {
+        match file_id {
+            SYSTEM_FILE_ID => self.allocate_page(),
+            _ => Err(DiskManagerError::FileNotFound(file_id)),
+        }
+    }
+    /// Add a new, empty file, returning its ID
+    fn create_file(&mut self) -> Result<FileId, DiskManagerError> {
+        Err(DiskManagerError::TooManyFiles)
+    }
+    /// Remove a file and every page in it. The system file can't be dropped.
+    fn drop_file(&mut self, file_id: FileId) -> Result<(), DiskManagerError> {
+        match file_id {
+            SYSTEM_FILE_ID => Err(DiskManagerError::SystemFile),
+            _ => Err(DiskManagerError::FileNotFound(file_id)),
+        }
+    }
 }
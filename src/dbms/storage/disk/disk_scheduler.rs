@@ -0,0 +1,430 @@
+use std::sync::mpsc::{self, Receiver, Sender, SyncSender};
+use std::sync::{Arc, RwLock};
+use std::thread::JoinHandle;
+
+use crate::dbms::types::{FileId, PageData, PageId};
+
+use super::{DiskManagerError, IDiskManager};
+
+/// Number of worker threads a buffer pool's disk scheduler runs by default
+pub const DEFAULT_DISK_WORKER_COUNT: usize = 4;
+
+type SchedulerDiskManager = Box<dyn IDiskManager + Send + Sync>;
+
+/// Completes a `DiskFuture` once the request it was handed out for is done.
+pub struct DiskPromise<T> {
+    sender: SyncSender<Result<T, DiskManagerError>>,
+}
+
+impl<T> DiskPromise<T> {
+    /// Hand the result over to whoever's waiting on the future. They may have
+    /// stopped waiting, in which case the result is dropped.
+    fn fulfil(self, result: Result<T, DiskManagerError>) {
+        let _ = self.sender.send(result);
+    }
+}
+
+/// The result of a scheduled disk request, which may not have finished yet.
+#[must_use = "the request's result is only known by waiting on it"]
+pub struct DiskFuture<T> {
+    receiver: Receiver<Result<T, DiskManagerError>>,
+}
+
+impl<T> DiskFuture<T> {
+    /// Block until the request has finished, returning its result
+    pub fn wait(self) -> Result<T, DiskManagerError> {
+        // The promise is only dropped unfulfilled if the worker panicked
+        self.receiver
+            .recv()
+            .expect("disk worker stopped without finishing a request")
+    }
+}
+
+/// Create a promise and the future it completes
+fn promise<T>() -> (DiskPromise<T>, DiskFuture<T>) {
+    let (sender, receiver) = mpsc::sync_channel(1);
+    (DiskPromise { sender }, DiskFuture { receiver })
+}
+
+/// A request for a disk worker to carry out
+pub enum DiskRequest {
+    Read {
+        page_id: PageId,
+        promise: DiskPromise<PageData>,
+    },
+    Write {
+        page_id: PageId,
+        data: PageData,
+        promise: DiskPromise<()>,
+    },
+    Allocate {
+        file_id: FileId,
+        promise: DiskPromise<PageId>,
+    },
+    Deallocate {
+        page_id: PageId,
+        promise: DiskPromise<()>,
+    },
+    SyncPages {
+        promise: DiskPromise<()>,
+    },
+    CreateFile {
+        promise: DiskPromise<FileId>,
+    },
+    DropFile {
+        file_id: FileId,
+        promise: DiskPromise<()>,
+    },
+}
+
+impl DiskRequest {
+    fn execute(self, disk_manager: &RwLock<SchedulerDiskManager>) {
+        match self {
+            // Reads don't change the disk manager, so can run alongside each
+            // other on different workers
+            Self::Read { page_id, promise } => {
+                promise.fulfil(disk_manager.read().unwrap().read_page(page_id))
+            }
+            Self::Write {
+                page_id,
+                data,
+                promise,
+            } => promise.fulfil(disk_manager.write().unwrap().write_page(page_id, &data)),
+            Self::Allocate { file_id, promise } => {
+                promise.fulfil(disk_manager.write().unwrap().allocate_page_in(file_id))
+            }
+            Self::Deallocate { page_id, promise } => {
+                promise.fulfil(disk_manager.write().unwrap().deallocate_page(page_id))
+            }
+            Self::SyncPages { promise } => {
+                promise.fulfil(disk_manager.write().unwrap().sync_pages())
+            }
+            Self::CreateFile { promise } => {
+                promise.fulfil(disk_manager.write().unwrap().create_file())
+            }
+            Self::DropFile { file_id, promise } => {
+                promise.fulfil(disk_manager.write().unwrap().drop_file(file_id))
+            }
+        }
+    }
+}
+
+struct DiskWorker {
+    /// Dropped to tell the worker to stop, once it's finished its queue
+    sender: Option<Sender<DiskRequest>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+/// Queues page requests onto background worker threads that carry them out
+/// against a disk manager, handing back a future for each request so the
+/// caller can get on with other things, or at least let go of its locks,
+/// while the I/O happens.
+///
+/// Requests for the same page always go to the same worker, which handles
+/// its queue in order, so a page is never read before a write to it that was
+/// scheduled first has landed. Requests for different pages may finish in
+/// any order.
+///
+/// Reads of different pages run alongside each other. Anything that changes
+/// the disk manager needs it to itself, so waits for reads in progress to
+/// finish, and holds up any reads scheduled behind it.
+pub struct DiskScheduler {
+    workers: Vec<DiskWorker>,
+}
+
+impl DiskScheduler {
+    /// Start `worker_count` workers sharing the given disk manager
+    pub fn new(disk_manager: SchedulerDiskManager, worker_count: usize) -> Self {
+        assert!(worker_count > 0, "disk scheduler needs at least one worker");
+        let disk_manager = Arc::new(RwLock::new(disk_manager));
+
+        let workers = (0..worker_count)
+            .map(|_| {
+                let (sender, receiver) = mpsc::channel::<DiskRequest>();
+                let disk_manager = disk_manager.clone();
+                let thread = std::thread::spawn(move || {
+                    for request in receiver {
+                        request.execute(&disk_manager);
+                    }
+                });
+                DiskWorker {
+                    sender: Some(sender),
+                    thread: Some(thread),
+                }
+            })
+            .collect();
+
+        Self { workers }
+    }
+
+    /// Queue a request on the worker responsible for the given page
+    fn schedule(&self, page_id: PageId, request: DiskRequest) {
+        let worker = &self.workers[page_id as usize % self.workers.len()];
+        worker
+            .sender
+            .as_ref()
+            .unwrap()
+            .send(request)
+            .expect("disk worker stopped while the scheduler is running");
+    }
+
+    /// Read a page in the background
+    pub fn schedule_read(&self, page_id: PageId) -> DiskFuture<PageData> {
+        let (promise, future) = promise();
+        self.schedule(page_id, DiskRequest::Read { page_id, promise });
+        future
+    }
+
+    /// Write a page in the background. The data is copied, so the page can be
+    /// changed again as soon as the write has been scheduled.
+    pub fn schedule_write(&self, page_id: PageId, data: &[u8]) -> DiskFuture<()> {
+        let (promise, future) = promise();
+        self.schedule(
+            page_id,
+            DiskRequest::Write {
+                page_id,
+                data: data.to_vec(),
+                promise,
+            },
+        );
+        future
+    }
+
+    /// Allocate a page in the given file in the background
+    pub fn schedule_allocate(&self, file_id: FileId) -> DiskFuture<PageId> {
+        let (promise, future) = promise();
+        // There's no page to keep this in order with yet, so any worker will do
+        self.schedule(0, DiskRequest::Allocate { file_id, promise });
+        future
+    }
+
+    /// Deallocate a page in the background, after anything already scheduled
+    /// for it
+    pub fn schedule_deallocate(&self, page_id: PageId) -> DiskFuture<()> {
+        let (promise, future) = promise();
+        self.schedule(page_id, DiskRequest::Deallocate { page_id, promise });
+        future
+    }
+
+    /// Make every page write that's finished so far durable, in the
+    /// background. Writes still queued on other workers may not be included,
+    /// so they should be waited on first.
+    pub fn schedule_sync_pages(&self) -> DiskFuture<()> {
+        let (promise, future) = promise();
+        self.schedule(0, DiskRequest::SyncPages { promise });
+        future
+    }
+
+    /// Add a file in the background
+    pub fn schedule_create_file(&self) -> DiskFuture<FileId> {
+        let (promise, future) = promise();
+        self.schedule(0, DiskRequest::CreateFile { promise });
+        future
+    }
+
+    /// Drop a file in the background. Requests already scheduled for its
+    /// pages may be on other workers, so they should be waited on first.
+    pub fn schedule_drop_file(&self, file_id: FileId) -> DiskFuture<()> {
+        let (promise, future) = promise();
+        self.schedule(0, DiskRequest::DropFile { file_id, promise });
+        future
+    }
+}
+
+impl Drop for DiskScheduler {
+    /// Let the workers finish what's been queued, then wait for them to stop,
+    /// so the disk manager has been dropped by the time the scheduler has
+    fn drop(&mut self) {
+        for worker in self.workers.iter_mut() {
+            worker.sender.take();
+        }
+        for worker in self.workers.iter_mut() {
+            if let Some(thread) = worker.thread.take() {
+                // A worker that panicked has already reported it
+                let _ = thread.join();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Barrier, Mutex};
+
+    use super::*;
+    use crate::dbms::storage::disk::testing::{
+        DiskOperation, Fault, FaultInjectingDiskManager, InMemoryDiskManager,
+    };
+    use crate::dbms::types::{DEFAULT_PAGE_SIZE, SYSTEM_FILE_ID};
+    use rstest::*;
+
+    fn create_scheduler(worker_count: usize) -> DiskScheduler {
+        DiskScheduler::new(Box::new(InMemoryDiskManager::new()), worker_count)
+    }
+
+    #[rstest]
+    #[case(1)]
+    #[case(4)]
+    fn test_write_then_read(#[case] worker_count: usize) {
+        let scheduler = create_scheduler(worker_count);
+
+        let page_ids = (0..10)
+            .map(|_| scheduler.schedule_allocate(SYSTEM_FILE_ID).wait().unwrap())
+            .collect::<Vec<_>>();
+        let writes = page_ids
+            .iter()
+            .map(|&page_id| scheduler.schedule_write(page_id, &[page_id as u8; DEFAULT_PAGE_SIZE]))
+            .collect::<Vec<_>>();
+        let reads = page_ids
+            .iter()
+            .map(|&page_id| (page_id, scheduler.schedule_read(page_id)))
+            .collect::<Vec<_>>();
+
+        for write in writes {
+            write.wait().unwrap();
+        }
+        for (page_id, read) in reads {
+            assert_eq!(read.wait().unwrap(), [page_id as u8; DEFAULT_PAGE_SIZE]);
+        }
+    }
+
+    #[rstest]
+    fn test_requests_for_a_page_stay_in_order() {
+        let scheduler = create_scheduler(4);
+        let page_id = scheduler.schedule_allocate(SYSTEM_FILE_ID).wait().unwrap();
+
+        // Each read sees the write scheduled just before it, without waiting
+        // for the write first
+        let reads = (1..=50u8)
+            .map(|i| {
+                let _ = scheduler.schedule_write(page_id, &[i; DEFAULT_PAGE_SIZE]);
+                (i, scheduler.schedule_read(page_id))
+            })
+            .collect::<Vec<_>>();
+
+        for (i, read) in reads {
+            assert_eq!(read.wait().unwrap(), [i; DEFAULT_PAGE_SIZE]);
+        }
+    }
+
+    #[rstest]
+    fn test_errors_returned_through_future() {
+        let disk_manager = FaultInjectingDiskManager::new(InMemoryDiskManager::new());
+        let injector = disk_manager.injector();
+        let scheduler = DiskScheduler::new(Box::new(disk_manager), 2);
+        let page_id = scheduler.schedule_allocate(SYSTEM_FILE_ID).wait().unwrap();
+
+        assert!(matches!(
+            scheduler.schedule_read(page_id + 1).wait(),
+            Err(DiskManagerError::PageNotFound)
+        ));
+
+        injector.inject(DiskOperation::WritePage, 1, Fault::Fail);
+        assert!(matches!(
+            scheduler
+                .schedule_write(page_id, &[1u8; DEFAULT_PAGE_SIZE])
+                .wait(),
+            Err(DiskManagerError::IoError(_))
+        ));
+        assert_eq!(
+            scheduler.schedule_read(page_id).wait().unwrap(),
+            [0u8; DEFAULT_PAGE_SIZE]
+        );
+    }
+
+    #[rstest]
+    fn test_deallocate_after_scheduled_write() {
+        let scheduler = create_scheduler(4);
+        let page_id = scheduler.schedule_allocate(SYSTEM_FILE_ID).wait().unwrap();
+
+        let write = scheduler.schedule_write(page_id, &[1u8; DEFAULT_PAGE_SIZE]);
+        let deallocate = scheduler.schedule_deallocate(page_id);
+
+        write.wait().unwrap();
+        deallocate.wait().unwrap();
+        assert!(matches!(
+            scheduler.schedule_read(page_id).wait(),
+            Err(DiskManagerError::PageNotFound)
+        ));
+        // The page's ID is handed out again
+        assert_eq!(
+            scheduler.schedule_allocate(SYSTEM_FILE_ID).wait().unwrap(),
+            page_id
+        );
+    }
+
+    #[rstest]
+    fn test_single_file_disk_manager_files() {
+        let scheduler = create_scheduler(2);
+
+        assert!(matches!(
+            scheduler.schedule_create_file().wait(),
+            Err(DiskManagerError::TooManyFiles)
+        ));
+        assert!(matches!(
+            scheduler.schedule_allocate(1).wait(),
+            Err(DiskManagerError::FileNotFound(1))
+        ));
+        assert!(matches!(
+            scheduler.schedule_drop_file(SYSTEM_FILE_ID).wait(),
+            Err(DiskManagerError::SystemFile)
+        ));
+    }
+
+    #[rstest]
+    fn test_drop_finishes_queued_requests() {
+        let disk_manager = FaultInjectingDiskManager::new(InMemoryDiskManager::new());
+        let injector = disk_manager.injector();
+        let scheduler = DiskScheduler::new(Box::new(disk_manager), 2);
+        let page_id = scheduler.schedule_allocate(SYSTEM_FILE_ID).wait().unwrap();
+
+        // Writes nobody waits for still happen before the scheduler's gone
+        for i in 0..20 {
+            let _ = scheduler.schedule_write(page_id, &[i; DEFAULT_PAGE_SIZE]);
+        }
+        drop(scheduler);
+
+        assert_eq!(injector.count(DiskOperation::WritePage), 20);
+    }
+
+    #[rstest]
+    fn test_schedule_from_many_threads() {
+        let scheduler = Arc::new(create_scheduler(4));
+        let thread_count = 8;
+        let barrier = Arc::new(Barrier::new(thread_count));
+        let page_ids = Arc::new(Mutex::new(Vec::new()));
+
+        let threads = (0..thread_count)
+            .map(|_| {
+                let scheduler = scheduler.clone();
+                let barrier = barrier.clone();
+                let page_ids = page_ids.clone();
+                std::thread::spawn(move || {
+                    barrier.wait();
+                    for _ in 0..10 {
+                        let page_id = scheduler.schedule_allocate(SYSTEM_FILE_ID).wait().unwrap();
+                        scheduler
+                            .schedule_write(page_id, &[page_id as u8; DEFAULT_PAGE_SIZE])
+                            .wait()
+                            .unwrap();
+                        page_ids.lock().unwrap().push(page_id);
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        let mut page_ids = page_ids.lock().unwrap().clone();
+        page_ids.sort();
+        page_ids.dedup();
+        assert_eq!(page_ids.len(), thread_count * 10);
+        for page_id in page_ids {
+            assert_eq!(
+                scheduler.schedule_read(page_id).wait().unwrap(),
+                [page_id as u8; DEFAULT_PAGE_SIZE]
+            );
+        }
+    }
+}
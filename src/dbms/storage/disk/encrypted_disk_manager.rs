@@ -0,0 +1,661 @@
+use chacha20::{
+    cipher::{KeyIvInit, StreamCipher, StreamCipherSeek},
+    XChaCha20,
+};
+
+use crate::dbms::types::{PageData, PageId};
+
+use super::{DiskManagerError, IDiskManager};
+
+/// Size of the key used to encrypt pages and the log
+pub const ENCRYPTION_KEY_SIZE_BYTES: usize = 32;
+
+pub type EncryptionKey = [u8; ENCRYPTION_KEY_SIZE_BYTES];
+
+/// The first byte of a nonce says what it's encrypting, so a page's nonce can
+/// never match the log's
+const NONCE_DOMAIN_OFFSET_BYTES: usize = 0;
+const NONCE_SIZE_BYTES: usize = 24;
+
+const PAGE_NONCE_DOMAIN: u8 = 0;
+const LOG_NONCE_DOMAIN: u8 = 1;
+
+/// Each write of a page is given its own ID, made from a salt picked at random
+/// when the disk manager is opened and a count of the writes since. It's kept
+/// in a trailer at the end of the inner page, after the encrypted page.
+const WRITE_ID_SALT_OFFSET_BYTES: usize = 0;
+const WRITE_ID_COUNTER_OFFSET_BYTES: usize = 8;
+const WRITE_ID_SIZE_BYTES: usize = 16;
+
+/// A page's nonce is the write ID at the end of the extended nonce
+const PAGE_NONCE_WRITE_ID_OFFSET_BYTES: usize = 8;
+
+/// The log starts with a header holding a salt picked at random when the log
+/// is started, so a log that's recreated never reuses an old one's keystream.
+/// The log's nonce is the salt at the end of the extended nonce.
+const LOG_SALT_SIZE_BYTES: usize = 16;
+const LOG_HEADER_SIZE_BYTES: usize = LOG_SALT_SIZE_BYTES;
+const LOG_NONCE_SALT_OFFSET_BYTES: usize = 8;
+
+/// A disk manager that encrypts pages and the log before handing them on to
+/// another disk manager, and decrypts them again when they're read, so nothing
+/// reaches the inner disk manager as plaintext.
+///
+/// Pages and the log are encrypted with ChaCha20 using a key supplied when the
+/// disk manager is opened. Reusing a stream cipher's nonce gives away the XOR
+/// of the plaintexts, so every write of a page gets a nonce of its own: a
+/// random salt for this disk manager and a count of its writes, stored in the
+/// last 16 bytes of the inner page, which leaves pages that much smaller than
+/// the inner disk manager's. The log is only ever appended to, so it's
+/// encrypted as one long stream, each byte according to its offset, with a
+/// nonce made from a random salt kept in a 16 byte header at the start of the
+/// inner log. Both use XChaCha20 for its longer nonce.
+///
+/// A page that's never been written has no write ID, and reads as zeroes
+/// without anything being stored for it. Nothing is authenticated, so
+/// tampering with the encrypted data isn't detected here.
+pub struct EncryptedDiskManager<D: IDiskManager> {
+    inner: D,
+    key: EncryptionKey,
+    /// Picked at random when the disk manager is opened, so write IDs from
+    /// earlier opens aren't repeated
+    salt: u64,
+    /// Writes so far since the disk manager was opened. Zero is kept for
+    /// pages that have never been written.
+    write_count: u64,
+    /// Picked at random when the log is started, and kept in its header
+    log_salt: [u8; LOG_SALT_SIZE_BYTES],
+    /// Size of the inner log, header and all, needed to tell the padding at
+    /// the end of a log read apart from the log itself
+    inner_log_size: usize,
+}
+
+impl<D: IDiskManager> EncryptedDiskManager<D> {
+    /// Start encrypting pages and the log written to the given disk manager,
+    /// and decrypting those already there, with the given key.
+    #[allow(dead_code)]
+    pub fn new(inner: D, key: EncryptionKey) -> Result<Self, DiskManagerError> {
+        let inner_log_size = Self::find_log_size(&inner)?;
+        let mut salt = [0u8; 8];
+        getrandom::getrandom(&mut salt).map_err(std::io::Error::from)?;
+
+        // A new log's header is written along with its first entry. If that
+        // write was cut short, nothing was encrypted with the salt yet, so
+        // what made it into the header is kept and the rest picked afresh.
+        let mut log_salt = [0u8; LOG_SALT_SIZE_BYTES];
+        let header_written = usize::min(inner_log_size, LOG_HEADER_SIZE_BYTES);
+        if header_written > 0 {
+            log_salt[..header_written]
+                .copy_from_slice(&inner.read_log(header_written, 0)?[..header_written]);
+        }
+        getrandom::getrandom(&mut log_salt[header_written..]).map_err(std::io::Error::from)?;
+
+        Ok(Self {
+            inner,
+            key,
+            salt: u64::from_be_bytes(salt),
+            write_count: 0,
+            log_salt,
+            inner_log_size,
+        })
+    }
+
+    /// The disk manager encrypted pages are written to
+    #[allow(dead_code)]
+    pub fn inner(&self) -> &D {
+        &self.inner
+    }
+
+    /// Stop encrypting pages, and get back the disk manager they were written
+    /// to
+    #[allow(dead_code)]
+    pub fn into_inner(self) -> D {
+        self.inner
+    }
+
+    /// Find how long the inner disk manager's log is, as the first offset it
+    /// won't read from
+    fn find_log_size(inner: &D) -> Result<usize, DiskManagerError> {
+        let has_offset = |offset: usize| match inner.read_log(1, offset) {
+            Ok(_) => Ok(true),
+            Err(DiskManagerError::LogOffsetOutOfRange(_)) => Ok(false),
+            Err(e) => Err(e),
+        };
+
+        // Double up to an offset past the end, then narrow down to the end
+        let mut low = 0;
+        let mut high = 1;
+        while has_offset(high - 1)? {
+            low = high;
+            high *= 2;
+        }
+        while low < high {
+            let middle = low + (high - low) / 2;
+            if has_offset(middle)? {
+                low = middle + 1;
+            } else {
+                high = middle;
+            }
+        }
+        Ok(low)
+    }
+
+    /// A new write ID, never used for any other write
+    fn next_write_id(&mut self) -> [u8; WRITE_ID_SIZE_BYTES] {
+        self.write_count += 1;
+        let mut write_id = [0u8; WRITE_ID_SIZE_BYTES];
+        write_id[WRITE_ID_SALT_OFFSET_BYTES..WRITE_ID_COUNTER_OFFSET_BYTES]
+            .copy_from_slice(&self.salt.to_be_bytes());
+        write_id[WRITE_ID_COUNTER_OFFSET_BYTES..].copy_from_slice(&self.write_count.to_be_bytes());
+        write_id
+    }
+
+    /// Encrypt or decrypt a page in place, which are the same thing, with the
+    /// keystream for the write it was given
+    fn apply_page_keystream(&self, write_id: &[u8], page: &mut [u8]) {
+        let mut nonce = [0u8; NONCE_SIZE_BYTES];
+        nonce[NONCE_DOMAIN_OFFSET_BYTES] = PAGE_NONCE_DOMAIN;
+        nonce[PAGE_NONCE_WRITE_ID_OFFSET_BYTES..].copy_from_slice(write_id);
+        XChaCha20::new(&self.key.into(), &nonce.into()).apply_keystream(page);
+    }
+
+    /// Size of the log, not counting its header
+    fn log_size(&self) -> usize {
+        self.inner_log_size.saturating_sub(LOG_HEADER_SIZE_BYTES)
+    }
+
+    /// Encrypt or decrypt part of the log in place, starting from the given
+    /// offset in it
+    fn apply_log_keystream(&self, offset: usize, log: &mut [u8]) {
+        let mut nonce = [0u8; NONCE_SIZE_BYTES];
+        nonce[NONCE_DOMAIN_OFFSET_BYTES] = LOG_NONCE_DOMAIN;
+        nonce[LOG_NONCE_SALT_OFFSET_BYTES..].copy_from_slice(&self.log_salt);
+        let mut cipher = XChaCha20::new(&self.key.into(), &nonce.into());
+        cipher.seek(offset as u64);
+        cipher.apply_keystream(log);
+    }
+}
+
+impl<D: IDiskManager> IDiskManager for EncryptedDiskManager<D> {
+    fn page_size(&self) -> usize {
+        self.inner.page_size() - WRITE_ID_SIZE_BYTES
+    }
+
+    fn write_page(&mut self, page_id: PageId, page: &[u8]) -> Result<(), DiskManagerError> {
+        if page.len() != self.page_size() {
+            return Err(DiskManagerError::InvalidPageSize(page.len()));
+        }
+
+        let write_id = self.next_write_id();
+        let mut encrypted = page.to_vec();
+        self.apply_page_keystream(&write_id, &mut encrypted);
+        encrypted.extend_from_slice(&write_id);
+        self.inner.write_page(page_id, &encrypted)
+    }
+
+    fn read_page(&self, page_id: PageId) -> Result<PageData, DiskManagerError> {
+        let mut page = self.inner.read_page(page_id)?;
+        let write_id = page.split_off(self.page_size());
+        if write_id.iter().all(|&b| b == 0) {
+            // Never written since it was allocated, so it's still zeroed
+            return Ok(vec![0u8; self.page_size()]);
+        }
+        self.apply_page_keystream(&write_id, &mut page);
+        Ok(page)
+    }
+
+    fn write_log(&mut self, log: &[u8]) -> Result<(), DiskManagerError> {
+        // The header goes in front of the first entry, along with whatever's
+        // missing of it after a write that was cut short
+        let header_written = usize::min(self.inner_log_size, LOG_HEADER_SIZE_BYTES);
+        let mut encrypted = self.log_salt[header_written..].to_vec();
+        let mut entry = log.to_vec();
+        self.apply_log_keystream(self.log_size(), &mut entry);
+        encrypted.extend_from_slice(&entry);
+
+        if let Err(e) = self.inner.write_log(&encrypted) {
+            // Some of it may have been written anyway, so the next entry's
+            // keystream has to carry on from wherever the log ends now
+            self.inner_log_size = Self::find_log_size(&self.inner)?;
+            return Err(e);
+        }
+        self.inner_log_size += encrypted.len();
+        Ok(())
+    }
+
+    fn read_log(&self, size: usize, offset: usize) -> Result<PageData, DiskManagerError> {
+        if offset >= self.log_size() {
+            return Err(DiskManagerError::LogOffsetOutOfRange(offset));
+        }
+        let mut log_data = self.inner.read_log(size, LOG_HEADER_SIZE_BYTES + offset)?;
+
+        // Past the end of the log is padding, which was never encrypted
+        let read_size = usize::min(size, self.log_size() - offset);
+        self.apply_log_keystream(offset, &mut log_data[..read_size]);
+        Ok(log_data)
+    }
+
+    fn sync_log(&mut self) -> Result<(), DiskManagerError> {
+        self.inner.sync_log()
+    }
+
+    fn sync_pages(&mut self) -> Result<(), DiskManagerError> {
+        self.inner.sync_pages()
+    }
+
+    fn allocate_page(&mut self) -> Result<PageId, DiskManagerError> {
+        // The inner disk manager hands out zeroed pages, whose write ID of
+        // zero marks them as never written
+        self.inner.allocate_page()
+    }
+
+    fn deallocate_page(&mut self, page_id: PageId) -> Result<(), DiskManagerError> {
+        self.inner.deallocate_page(page_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dbms::buffer::pool_manager::{BufferPoolManager, IBufferPoolManager};
+    use crate::dbms::buffer::replacer::clock_replacer::ClockReplacer;
+    use crate::dbms::storage::disk::{
+        testing::{DiskOperation, Fault, FaultInjectingDiskManager, InMemoryDiskManager},
+        CompressedDiskManager, FileDiskManager,
+    };
+    use crate::dbms::types::{DEFAULT_PAGE_SIZE, MAX_PAGE_SIZE};
+    use rstest::*;
+    use tempfile::tempdir;
+
+    const KEY: EncryptionKey = [7u8; ENCRYPTION_KEY_SIZE_BYTES];
+    /// Size of pages over an inner disk manager with the default page size
+    const PAGE_SIZE: usize = DEFAULT_PAGE_SIZE - WRITE_ID_SIZE_BYTES;
+    const PLAINTEXT: &[u8] = b"top secret customer record";
+
+    fn create_disk_manager() -> EncryptedDiskManager<InMemoryDiskManager> {
+        EncryptedDiskManager::new(InMemoryDiskManager::new(), KEY).unwrap()
+    }
+
+    /// A page with the plaintext repeated through it
+    fn plaintext_page() -> PageData {
+        plaintext_page_of_size(PAGE_SIZE)
+    }
+
+    fn plaintext_page_of_size(page_size: usize) -> PageData {
+        let mut page = vec![0u8; page_size];
+        for (i, byte) in page.iter_mut().enumerate() {
+            *byte = PLAINTEXT[i % PLAINTEXT.len()];
+        }
+        page
+    }
+
+    fn contains_plaintext(bytes: &[u8]) -> bool {
+        bytes
+            .windows(PLAINTEXT.len())
+            .any(|window| window == PLAINTEXT)
+    }
+
+    #[rstest]
+    fn test_write_and_read_page() {
+        let mut disk_manager = create_disk_manager();
+        let page_id = disk_manager.allocate_page().unwrap();
+
+        disk_manager.write_page(page_id, &plaintext_page()).unwrap();
+
+        assert_eq!(disk_manager.read_page(page_id).unwrap(), plaintext_page());
+    }
+
+    #[rstest]
+    fn test_allocated_page_zeroed() {
+        let mut disk_manager = create_disk_manager();
+        let page_id = disk_manager.allocate_page().unwrap();
+
+        assert_eq!(disk_manager.read_page(page_id).unwrap(), [0u8; PAGE_SIZE]);
+        // Nothing's written until the page is, so no keystream is given away
+        assert_eq!(
+            disk_manager.inner().pages[&page_id],
+            vec![0u8; DEFAULT_PAGE_SIZE]
+        );
+
+        // Written zeroes are still encrypted
+        disk_manager.write_page(page_id, &[0u8; PAGE_SIZE]).unwrap();
+        assert_eq!(disk_manager.read_page(page_id).unwrap(), [0u8; PAGE_SIZE]);
+        assert_ne!(
+            disk_manager.inner().pages[&page_id][..PAGE_SIZE],
+            [0u8; PAGE_SIZE]
+        );
+    }
+
+    /// XOR of two stored versions of a page, leaving out their write IDs
+    fn xor_versions(first: &[u8], second: &[u8]) -> Vec<u8> {
+        first[..PAGE_SIZE]
+            .iter()
+            .zip(&second[..PAGE_SIZE])
+            .map(|(a, b)| a ^ b)
+            .collect()
+    }
+
+    #[rstest]
+    fn test_versions_xor_to_nothing() {
+        let mut disk_manager = create_disk_manager();
+        let page_id = disk_manager.allocate_page().unwrap();
+        let allocated = disk_manager.inner().pages[&page_id].clone();
+
+        // Had both versions used the same keystream, their XOR would be the
+        // plaintext, as the second version is all zeroes
+        disk_manager.write_page(page_id, &plaintext_page()).unwrap();
+        let first = disk_manager.inner().pages[&page_id].clone();
+        disk_manager.write_page(page_id, &[0u8; PAGE_SIZE]).unwrap();
+        let second = disk_manager.inner().pages[&page_id].clone();
+
+        assert!(!contains_plaintext(&xor_versions(&first, &second)));
+        assert!(!contains_plaintext(&xor_versions(&allocated, &first)));
+        assert!(!contains_plaintext(&xor_versions(&allocated, &second)));
+
+        // Nor is the same page written again encrypted the same way, even
+        // after a reopen
+        let mut disk_manager = EncryptedDiskManager::new(disk_manager.into_inner(), KEY).unwrap();
+        disk_manager.write_page(page_id, &plaintext_page()).unwrap();
+        let third = disk_manager.inner().pages[&page_id].clone();
+        assert_ne!(first[..PAGE_SIZE], third[..PAGE_SIZE]);
+        assert!(!contains_plaintext(&xor_versions(&second, &third)));
+        assert_eq!(disk_manager.read_page(page_id).unwrap(), plaintext_page());
+    }
+
+    #[rstest]
+    fn test_large_pages() {
+        let mut disk_manager =
+            EncryptedDiskManager::new(InMemoryDiskManager::with_page_size(MAX_PAGE_SIZE), KEY)
+                .unwrap();
+        let page_size = MAX_PAGE_SIZE - WRITE_ID_SIZE_BYTES;
+        assert_eq!(disk_manager.page_size(), page_size);
+        let page_id = disk_manager.allocate_page().unwrap();
+        assert_eq!(
+            disk_manager.read_page(page_id).unwrap(),
+            vec![0u8; page_size]
+        );
+
+        disk_manager
+            .write_page(page_id, &plaintext_page_of_size(page_size))
+            .unwrap();
+
+        assert_eq!(
+            disk_manager.read_page(page_id).unwrap(),
+            plaintext_page_of_size(page_size)
+        );
+        assert!(!contains_plaintext(&disk_manager.inner().pages[&page_id]));
+        assert!(matches!(
+            disk_manager.write_page(page_id, &plaintext_page()),
+            Err(DiskManagerError::InvalidPageSize(PAGE_SIZE))
+        ));
+    }
+
+    #[rstest]
+    fn test_reused_page_zeroed() {
+        let mut disk_manager = create_disk_manager();
+        let page_id = disk_manager.allocate_page().unwrap();
+        disk_manager.write_page(page_id, &plaintext_page()).unwrap();
+        disk_manager.deallocate_page(page_id).unwrap();
+
+        assert_eq!(disk_manager.allocate_page().unwrap(), page_id);
+        assert_eq!(disk_manager.read_page(page_id).unwrap(), [0u8; PAGE_SIZE]);
+    }
+
+    #[rstest]
+    fn test_inner_store_never_has_plaintext() {
+        let mut disk_manager = create_disk_manager();
+        for _ in 0..5 {
+            let page_id = disk_manager.allocate_page().unwrap();
+            disk_manager.write_page(page_id, &plaintext_page()).unwrap();
+        }
+        disk_manager.write_log(PLAINTEXT).unwrap();
+        disk_manager.write_log(PLAINTEXT).unwrap();
+
+        let inner = disk_manager.inner();
+        assert!(!inner.pages.values().any(|page| contains_plaintext(page)));
+        assert!(!contains_plaintext(&inner.log));
+        assert_eq!(inner.log.len(), LOG_HEADER_SIZE_BYTES + 2 * PLAINTEXT.len());
+    }
+
+    #[rstest]
+    fn test_pages_encrypted_differently() {
+        let mut disk_manager = create_disk_manager();
+        let first = disk_manager.allocate_page().unwrap();
+        let second = disk_manager.allocate_page().unwrap();
+        disk_manager.write_page(first, &plaintext_page()).unwrap();
+        disk_manager.write_page(second, &plaintext_page()).unwrap();
+
+        let inner = disk_manager.inner();
+        assert_ne!(inner.pages[&first], inner.pages[&second]);
+    }
+
+    #[rstest]
+    fn test_wrong_key() {
+        let mut disk_manager = create_disk_manager();
+        let page_id = disk_manager.allocate_page().unwrap();
+        disk_manager.write_page(page_id, &plaintext_page()).unwrap();
+        disk_manager.write_log(PLAINTEXT).unwrap();
+
+        let disk_manager = EncryptedDiskManager::new(disk_manager.into_inner(), [8u8; 32]).unwrap();
+
+        assert!(!contains_plaintext(
+            &disk_manager.read_page(page_id).unwrap()
+        ));
+        assert!(!contains_plaintext(
+            &disk_manager.read_log(PLAINTEXT.len(), 0).unwrap()
+        ));
+    }
+
+    #[rstest]
+    fn test_write_and_read_log() {
+        let mut disk_manager = create_disk_manager();
+
+        disk_manager.write_log(&[1, 2, 3]).unwrap();
+        disk_manager.write_log(&[4, 5]).unwrap();
+        disk_manager.sync_log().unwrap();
+
+        let log_data = disk_manager.read_log(3, 1).unwrap();
+        assert_eq!(log_data[..3], [2, 3, 4]);
+        assert_eq!(log_data[3..], [0u8; DEFAULT_PAGE_SIZE - 3]);
+
+        // Reads running off the end of the log are zero-padded
+        let log_data = disk_manager.read_log(10, 3).unwrap();
+        assert_eq!(log_data[..2], [4, 5]);
+        assert_eq!(log_data[2..], [0u8; DEFAULT_PAGE_SIZE - 2]);
+
+        assert!(matches!(
+            disk_manager.read_log(1, 5),
+            Err(DiskManagerError::LogOffsetOutOfRange(5))
+        ));
+    }
+
+    /// The same entry encrypted into two different logs under the same key
+    fn encrypt_in_two_logs(recreate: bool) -> (Vec<u8>, Vec<u8>) {
+        let mut disk_manager = create_disk_manager();
+        disk_manager.write_log(PLAINTEXT).unwrap();
+        let first = disk_manager.inner().log.clone();
+
+        let mut disk_manager = if recreate {
+            let mut inner = disk_manager.into_inner();
+            inner.log.clear();
+            EncryptedDiskManager::new(inner, KEY).unwrap()
+        } else {
+            create_disk_manager()
+        };
+        disk_manager.write_log(PLAINTEXT).unwrap();
+        let second = disk_manager.inner().log.clone();
+        (first, second)
+    }
+
+    #[rstest]
+    #[case(false)]
+    #[case(true)]
+    fn test_logs_encrypted_differently(#[case] recreate: bool) {
+        let (first, second) = encrypt_in_two_logs(recreate);
+
+        let first = &first[LOG_HEADER_SIZE_BYTES..];
+        let second = &second[LOG_HEADER_SIZE_BYTES..];
+        assert_ne!(first, second);
+        // With the same keystream, XORing the two would cancel it out
+        let xor = first.iter().zip(second).map(|(a, b)| a ^ b);
+        assert!(xor.into_iter().any(|byte| byte != 0));
+    }
+
+    #[rstest]
+    // The header's cut short
+    #[case(1, 3, &[5, 6, 7, 8, 9, 10])]
+    // The entry's cut short after the header
+    #[case(1, LOG_HEADER_SIZE_BYTES + 2, &[1, 2, 5, 6, 7, 8, 9, 10])]
+    #[case(2, 2, &[1, 2, 3, 4, 5, 6, 9, 10])]
+    fn test_failed_log_write(#[case] n: usize, #[case] len: usize, #[case] expected: &[u8]) {
+        let inner = FaultInjectingDiskManager::new(InMemoryDiskManager::new());
+        let injector = inner.injector();
+        let mut disk_manager = EncryptedDiskManager::new(inner, KEY).unwrap();
+        injector.inject(DiskOperation::WriteLog, n, Fault::PartialFail(len));
+
+        for (i, entry) in [&[1, 2, 3, 4][..], &[5, 6, 7, 8], &[9, 10]]
+            .into_iter()
+            .enumerate()
+        {
+            assert_eq!(disk_manager.write_log(entry).is_err(), i + 1 == n);
+        }
+
+        assert_log_is(&disk_manager, expected);
+        let disk_manager = EncryptedDiskManager::new(disk_manager.into_inner(), KEY).unwrap();
+        assert_log_is(&disk_manager, expected);
+    }
+
+    fn assert_log_is(disk_manager: &impl IDiskManager, expected: &[u8]) {
+        let log_data = disk_manager.read_log(expected.len(), 0).unwrap();
+        assert_eq!(log_data[..expected.len()], *expected);
+        assert!(matches!(
+            disk_manager.read_log(1, expected.len()),
+            Err(DiskManagerError::LogOffsetOutOfRange(_))
+        ));
+    }
+
+    #[rstest]
+    #[case(0)]
+    #[case(1)]
+    #[case(2)]
+    #[case(1000)]
+    #[case(DEFAULT_PAGE_SIZE + 1)]
+    fn test_log_size_found_on_reopen(#[case] log_size: usize) {
+        let mut disk_manager = create_disk_manager();
+        let log = (0..log_size).map(|i| i as u8).collect::<Vec<_>>();
+        disk_manager.write_log(&log).unwrap();
+
+        let mut disk_manager = EncryptedDiskManager::new(disk_manager.into_inner(), KEY).unwrap();
+        disk_manager.write_log(&[42]).unwrap();
+
+        let log_data = disk_manager
+            .read_log(2, log_size.saturating_sub(1))
+            .unwrap();
+        if log_size == 0 {
+            assert_eq!(log_data[..2], [42, 0]);
+        } else {
+            assert_eq!(log_data[..2], [(log_size - 1) as u8, 42]);
+        }
+        assert_eq!(log_data[2..], [0u8; DEFAULT_PAGE_SIZE - 2]);
+    }
+
+    #[rstest]
+    fn test_write_page_wrong_size() {
+        let mut disk_manager = create_disk_manager();
+        let page_id = disk_manager.allocate_page().unwrap();
+
+        let result = disk_manager.write_page(page_id, &[1u8; 10]);
+        assert!(matches!(result, Err(DiskManagerError::InvalidPageSize(10))));
+    }
+
+    #[rstest]
+    fn test_read_page_nonexistent() {
+        let disk_manager = create_disk_manager();
+
+        assert!(matches!(
+            disk_manager.read_page(0),
+            Err(DiskManagerError::PageNotFound)
+        ));
+    }
+
+    #[rstest]
+    fn test_files_never_have_plaintext() {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("test.db");
+
+        let page_ids = {
+            let mut disk_manager =
+                EncryptedDiskManager::new(FileDiskManager::new(&db_path).unwrap(), KEY).unwrap();
+            // The file's own checksums leave less room for the pages
+            let page_size = disk_manager.page_size();
+            let page_ids = (0..3)
+                .map(|_| {
+                    let page_id = disk_manager.allocate_page().unwrap();
+                    disk_manager
+                        .write_page(page_id, &plaintext_page_of_size(page_size))
+                        .unwrap();
+                    page_id
+                })
+                .collect::<Vec<_>>();
+            disk_manager.write_log(PLAINTEXT).unwrap();
+            disk_manager.sync_log().unwrap();
+            page_ids
+        };
+
+        assert!(!contains_plaintext(&std::fs::read(&db_path).unwrap()));
+        assert!(!contains_plaintext(
+            &std::fs::read(dir.path().join("test.db.log")).unwrap()
+        ));
+
+        let disk_manager =
+            EncryptedDiskManager::new(FileDiskManager::new(&db_path).unwrap(), KEY).unwrap();
+        for page_id in page_ids {
+            assert_eq!(
+                disk_manager.read_page(page_id).unwrap(),
+                plaintext_page_of_size(disk_manager.page_size())
+            );
+        }
+        assert_eq!(
+            disk_manager.read_log(PLAINTEXT.len(), 0).unwrap()[..PLAINTEXT.len()],
+            *PLAINTEXT
+        );
+    }
+
+    #[rstest]
+    fn test_compressed_then_encrypted() {
+        let mut disk_manager = CompressedDiskManager::new(create_disk_manager()).unwrap();
+        let page_id = disk_manager.allocate_page().unwrap();
+        disk_manager.write_page(page_id, &plaintext_page()).unwrap();
+
+        assert_eq!(disk_manager.read_page(page_id).unwrap(), plaintext_page());
+        let inner = disk_manager.inner().inner();
+        assert!(!inner.pages.values().any(|page| contains_plaintext(page)));
+    }
+
+    #[rstest]
+    fn test_buffer_pool_with_encryption() {
+        let buffer_pool_manager = BufferPoolManager::new(
+            1,
+            Box::new(ClockReplacer::new(1)),
+            Box::new(create_disk_manager()),
+        );
+
+        let page_ids = (0..3)
+            .map(|_| {
+                let mut page = buffer_pool_manager.new_page().unwrap();
+                let page_id = page.get_page_id().unwrap().unwrap();
+                page.set_data(plaintext_page()).unwrap();
+                drop(page);
+                buffer_pool_manager.unpin_page(page_id, true).unwrap();
+                page_id
+            })
+            .collect::<Vec<_>>();
+
+        for page_id in page_ids {
+            let page = buffer_pool_manager.fetch_page(page_id).unwrap();
+            assert_eq!(page.get_data().unwrap(), plaintext_page());
+            drop(page);
+            buffer_pool_manager.unpin_page(page_id, false).unwrap();
+        }
+    }
+}
@@ -0,0 +1,1398 @@
+use std::collections::HashSet;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::os::unix::fs::FileExt;
+use std::path::{Path, PathBuf};
+
+use crate::dbms::types::{
+    is_valid_page_size, PageData, PageId, DEFAULT_PAGE_SIZE, INVALID_PAGE_ID,
+};
+
+use super::{
+    DiskManagerError, IDiskManager, Superblock, SUPERBLOCK_PAGE_ID, SUPERBLOCK_SIZE_BYTES,
+};
+
+/// Each page's slot ends with a trailer holding a checksum of its contents,
+/// rather than the page starting with one; see `FileDiskManager`
+const CHECKSUM_SIZE_BYTES: usize = std::mem::size_of::<u32>();
+pub(super) const PAGE_TRAILER_SIZE_BYTES: usize = CHECKSUM_SIZE_BYTES;
+
+/// Size of the pages stored in a file with the given page size, which is
+/// what's left of each slot after its trailer
+pub(super) const fn usable_page_size(page_size: usize) -> usize {
+    page_size - PAGE_TRAILER_SIZE_BYTES
+}
+
+/// Where a page's slot starts in the database file
+fn page_offset(page_id: PageId, page_size: usize) -> u64 {
+    page_id * page_size as u64
+}
+
+/// The log's path for the database file at the given path, which is the
+/// database file's name with `.log` added on the end. It's added rather than
+/// swapped for any extension the name already has, so no database path can
+/// end up sharing a file with its log.
+pub(super) fn log_path(db_path: &Path) -> PathBuf {
+    let mut log_path = db_path.as_os_str().to_owned();
+    log_path.push(".log");
+    PathBuf::from(log_path)
+}
+
+/// A free page holds the ID of the next free page at its start
+const NEXT_FREE_PAGE_OFFSET_BYTES: usize = 0;
+const PAGE_ID_SIZE_BYTES: usize = std::mem::size_of::<PageId>();
+
+/// The database file under a `FileDiskManager`, read and written at byte
+/// offsets
+pub trait IDbFile: Sized {
+    /// Open the file at the given path, creating it empty if it doesn't exist
+    fn open(path: &Path) -> Result<Self, DiskManagerError>;
+    /// Size of the file in bytes
+    fn size(&self) -> Result<u64, DiskManagerError>;
+    /// Fill `buf` from the file, starting at `offset`. Reading past the end of
+    /// the file is an error.
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> Result<(), DiskManagerError>;
+    /// Write `data` to the file, starting at `offset`. The file grows if the
+    /// write runs past its end.
+    fn write_at(&mut self, offset: u64, data: &[u8]) -> Result<(), DiskManagerError>;
+    /// Cut the file down to the given size
+    fn truncate(&mut self, size: u64) -> Result<(), DiskManagerError>;
+    /// Make every write so far durable, including any change to the file's
+    /// size. Writes after it can't land on disk before the ones before it.
+    fn sync(&mut self) -> Result<(), DiskManagerError>;
+}
+
+/// A database file read and written with plain file I/O
+pub struct PlainDbFile {
+    file: File,
+}
+
+impl IDbFile for PlainDbFile {
+    fn open(path: &Path) -> Result<Self, DiskManagerError> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)?;
+        Ok(Self { file })
+    }
+
+    fn size(&self) -> Result<u64, DiskManagerError> {
+        Ok(self.file.metadata()?.len())
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> Result<(), DiskManagerError> {
+        // Positioned reads don't move the file's cursor, so reads on different
+        // threads don't get in each other's way
+        self.file.read_exact_at(buf, offset)?;
+        Ok(())
+    }
+
+    fn write_at(&mut self, offset: u64, data: &[u8]) -> Result<(), DiskManagerError> {
+        self.file.write_all_at(data, offset)?;
+        Ok(())
+    }
+
+    fn truncate(&mut self, size: u64) -> Result<(), DiskManagerError> {
+        self.file.set_len(size)?;
+        Ok(())
+    }
+
+    fn sync(&mut self) -> Result<(), DiskManagerError> {
+        self.file.sync_data()?;
+        Ok(())
+    }
+}
+
+/// A disk manager that stores pages in a single database file, with the log
+/// kept in a separate file alongside it.
+///
+/// Page `n` lives in the slot at byte offset `n * page_size` in the file, so
+/// slots line up with the filesystem's blocks and never straddle two. The
+/// last 4 bytes of each slot hold a CRC32 checksum of the page ID and the
+/// page's contents, leaving `page_size - 4` bytes for the page itself; that's
+/// the size `page_size()` reports. The checksum is written with every page
+/// and checked whenever one is read, so a torn write, a corrupted page or a
+/// page written to the wrong place is reported as an error rather than
+/// handed out.
+///
+/// The checksum is deliberately kept in a trailer on the slot rather than in
+/// a header on the page. That keeps it out of the bytes handed to callers, so
+/// none of the page formats above the disk layer has to leave room for it,
+/// and it keeps each page starting on its slot's boundary. The cost is that a
+/// page is smaller than its slot, so everything above the disk layer sizes
+/// pages by `page_size()` rather than by the size the file was created with.
+///
+/// The first page is the file's superblock, which records the file's format
+/// and page size (the size of its slots), the next page ID, the head of the
+/// free list and the catalog's root page. The page size is chosen when the file is created, and
+/// is read back from the superblock when it's opened again. The superblock is
+/// checked before anything else in the file is trusted, so a file written in
+/// a different format is rejected rather than misread.
+///
+/// Deallocated pages are kept in a free list and handed out again before the
+/// file is grown. The list is linked through the free pages themselves, each
+/// holding the ID of the next, with the head kept in the superblock, so it
+/// survives a reopen.
+///
+/// Page writes aren't durable until `sync_pages` is called. Allocating and
+/// deallocating pages sync the file between their writes, though, so
+/// whichever of them a crash cuts short, the superblock and free list are
+/// left describing the file as it was before or after.
+///
+/// How the database file is accessed is up to `F`. By default it's read and
+/// written with plain file I/O; see `MmapDiskManager` for a memory-mapped
+/// alternative using the same file format.
+pub struct FileDiskManager<F: IDbFile = PlainDbFile> {
+    db_file: F,
+    /// `None` for a file opened without a log
+    log_file: Option<File>,
+    superblock: Superblock,
+    log_size: usize,
+    /// Every page in the free list, to check pages are in use without
+    /// walking it
+    free_pages: HashSet<PageId>,
+}
+
+impl FileDiskManager {
+    /// Open the database file at the given path, creating it with the default
+    /// page size if it doesn't exist yet. The log lives next to it, with
+    /// `.log` added to its name.
+    #[allow(dead_code)]
+    pub fn new(db_path: impl AsRef<Path>) -> Result<Self, DiskManagerError> {
+        Self::open(db_path)
+    }
+
+    /// Open the database file at the given path, creating it with the given
+    /// page size if it doesn't exist yet. An existing file must have been
+    /// created with the same page size.
+    #[allow(dead_code)]
+    pub fn with_page_size(
+        db_path: impl AsRef<Path>,
+        page_size: usize,
+    ) -> Result<Self, DiskManagerError> {
+        Self::open_with_page_size(db_path, page_size)
+    }
+
+    /// Open the database file at the given path like `with_page_size`, but
+    /// without a log, for a file whose log is kept somewhere else. No log
+    /// file is created for it, and writing to its log is an error.
+    #[allow(dead_code)]
+    pub fn without_log(
+        db_path: impl AsRef<Path>,
+        page_size: usize,
+    ) -> Result<Self, DiskManagerError> {
+        Self::open_file(db_path.as_ref(), Some(page_size), false)
+    }
+}
+
+impl<F: IDbFile> FileDiskManager<F> {
+    /// Open the database file at the given path through `F`, creating it with
+    /// the default page size if it doesn't exist yet. The log lives next to it,
+    /// with `.log` added to its name.
+    pub fn open(db_path: impl AsRef<Path>) -> Result<Self, DiskManagerError> {
+        Self::open_file(db_path.as_ref(), None, true)
+    }
+
+    /// Open the database file at the given path through `F`, creating it with
+    /// the given page size if it doesn't exist yet. An existing file must have
+    /// been created with the same page size.
+    pub fn open_with_page_size(
+        db_path: impl AsRef<Path>,
+        page_size: usize,
+    ) -> Result<Self, DiskManagerError> {
+        Self::open_file(db_path.as_ref(), Some(page_size), true)
+    }
+
+    fn open_file(
+        db_path: &Path,
+        page_size: Option<usize>,
+        with_log: bool,
+    ) -> Result<Self, DiskManagerError> {
+        // Checked before anything is created, so asking for a bad page size
+        // doesn't leave an empty file behind
+        if let Some(page_size) = page_size.filter(|&size| !is_valid_page_size(size)) {
+            return Err(DiskManagerError::UnsupportedPageSize(page_size));
+        }
+        let db_file = F::open(db_path)?;
+        let log_file = with_log
+            .then(|| {
+                OpenOptions::new()
+                    .read(true)
+                    .append(true)
+                    .create(true)
+                    .open(log_path(db_path))
+            })
+            .transpose()?;
+
+        let log_size = match &log_file {
+            Some(log_file) => log_file.metadata()?.len() as usize,
+            None => 0,
+        };
+
+        let mut disk_manager = Self {
+            db_file,
+            log_file,
+            superblock: Superblock::new(page_size.unwrap_or(DEFAULT_PAGE_SIZE)),
+            log_size,
+            free_pages: HashSet::new(),
+        };
+        if disk_manager.db_file.size()? == 0 {
+            // A new file starts with just the superblock's page
+            disk_manager.write_superblock()?;
+        } else {
+            disk_manager.load_superblock()?;
+            match page_size {
+                Some(page_size) if page_size != disk_manager.superblock.page_size => {
+                    return Err(DiskManagerError::PageSizeMismatch(
+                        disk_manager.superblock.page_size,
+                    ));
+                }
+                _ => {}
+            }
+            disk_manager.load_free_list()?;
+            disk_manager.trim()?;
+        }
+        Ok(disk_manager)
+    }
+
+    /// Close the database file, reporting anything that goes wrong doing so
+    /// rather than leaving it to be dropped
+    #[allow(dead_code)]
+    pub fn close(mut self) -> Result<(), DiskManagerError> {
+        self.trim()
+    }
+
+    /// Cut off anything in the file past the superblock's next page ID,
+    /// including any space `F` has grown the file by ahead of time
+    fn trim(&mut self) -> Result<(), DiskManagerError> {
+        let end = self.page_offset(self.superblock.next_page_id);
+        self.db_file.truncate(end)
+    }
+
+    /// The size of the file's page slots, trailers included, which is what
+    /// it's created with and what the superblock records
+    #[allow(dead_code)]
+    pub fn file_page_size(&self) -> usize {
+        self.superblock.page_size
+    }
+
+    /// The root page of the database's catalog, if one has been set
+    #[allow(dead_code)]
+    pub fn catalog_root_page_id(&self) -> Option<PageId> {
+        self.superblock.catalog_root_page_id
+    }
+
+    /// Record the root page of the database's catalog in the superblock, so
+    /// it can be found again when the database is reopened
+    #[allow(dead_code)]
+    pub fn set_catalog_root_page_id(
+        &mut self,
+        page_id: Option<PageId>,
+    ) -> Result<(), DiskManagerError> {
+        if let Some(page_id) = page_id {
+            self.check_allocated(page_id)?;
+        }
+        self.superblock.catalog_root_page_id = page_id;
+        self.write_superblock()
+    }
+
+    /// The checksum stored with a page. The page ID is included so a page
+    /// that ends up in the wrong place doesn't pass as the page that should
+    /// be there.
+    fn checksum(page_id: PageId, page: &[u8]) -> u32 {
+        let mut hasher = crc32fast::Hasher::new();
+        hasher.update(&page_id.to_be_bytes());
+        hasher.update(page);
+        hasher.finalize()
+    }
+
+    /// Where a page's slot starts in the database file
+    fn page_offset(&self, page_id: PageId) -> u64 {
+        page_offset(page_id, self.superblock.page_size)
+    }
+
+    fn check_allocated(&self, page_id: PageId) -> Result<(), DiskManagerError> {
+        if page_id == SUPERBLOCK_PAGE_ID
+            || page_id >= self.superblock.next_page_id
+            || self.free_pages.contains(&page_id)
+        {
+            return Err(DiskManagerError::PageNotFound);
+        }
+        Ok(())
+    }
+
+    /// Read and check the superblock, before anything else in the file is
+    /// trusted
+    fn load_superblock(&mut self) -> Result<(), DiskManagerError> {
+        let file_size = self.db_file.size()?;
+        let mut superblock_bytes = [0u8; SUPERBLOCK_SIZE_BYTES];
+        if file_size < superblock_bytes.len() as u64 {
+            return Err(DiskManagerError::InvalidMagic);
+        }
+
+        // The superblock's contents are checked before its checksum, so a
+        // file that isn't a database, or is from a different version, says so.
+        // The page size has to come from the superblock before its whole page
+        // can be read to check the checksum. Its page is the first in the
+        // file, and it starts at the start of its page, whatever the page size.
+        self.db_file.read_at(0, &mut superblock_bytes)?;
+        self.superblock = Superblock::from_bytes(&superblock_bytes)?;
+        let file_page_size = self.superblock.page_size as u64;
+        if file_size < file_page_size {
+            return Err(DiskManagerError::InvalidFileSize(file_size));
+        }
+        self.read_slot(SUPERBLOCK_PAGE_ID)?;
+
+        // Pages past the superblock's next page ID are left over from an
+        // allocation that didn't finish, and are cut off once the file's
+        // opened. Pages it says are there must be, though.
+        if file_size % file_page_size != 0
+            || file_size < self.page_offset(self.superblock.next_page_id)
+        {
+            return Err(DiskManagerError::InvalidFileSize(file_size));
+        }
+        Ok(())
+    }
+
+    fn write_superblock(&mut self) -> Result<(), DiskManagerError> {
+        let mut page = vec![0u8; self.page_size()];
+        page[..SUPERBLOCK_SIZE_BYTES].copy_from_slice(&self.superblock.to_bytes());
+        self.write_slot(SUPERBLOCK_PAGE_ID, &page)
+    }
+
+    /// Follow the free list from its head in the superblock, checking it
+    /// only holds pages in the file and doesn't loop.
+    fn load_free_list(&mut self) -> Result<(), DiskManagerError> {
+        let mut page_id = self.superblock.free_list_head;
+        while let Some(free_page_id) = page_id {
+            if free_page_id == SUPERBLOCK_PAGE_ID
+                || free_page_id >= self.superblock.next_page_id
+                || !self.free_pages.insert(free_page_id)
+            {
+                return Err(DiskManagerError::InvalidFreeList(free_page_id));
+            }
+            page_id = self.read_next_free_page_id(free_page_id)?;
+        }
+        Ok(())
+    }
+
+    fn read_next_free_page_id(&self, page_id: PageId) -> Result<Option<PageId>, DiskManagerError> {
+        let page = self.read_slot(page_id)?;
+        let offset = NEXT_FREE_PAGE_OFFSET_BYTES;
+        match PageId::from_be_bytes(
+            page[offset..offset + PAGE_ID_SIZE_BYTES]
+                .try_into()
+                .unwrap(),
+        ) {
+            INVALID_PAGE_ID => Ok(None),
+            page_id => Ok(Some(page_id)),
+        }
+    }
+
+    /// Overwrite a page with a free page pointing at the next free page
+    fn write_free_page(
+        &mut self,
+        page_id: PageId,
+        next_free_page_id: Option<PageId>,
+    ) -> Result<(), DiskManagerError> {
+        let mut page = vec![0u8; self.page_size()];
+        let offset = NEXT_FREE_PAGE_OFFSET_BYTES;
+        page[offset..offset + PAGE_ID_SIZE_BYTES]
+            .copy_from_slice(&next_free_page_id.unwrap_or(INVALID_PAGE_ID).to_be_bytes());
+        self.write_slot(page_id, &page)
+    }
+
+    /// Read a page's contents, checking them against its checksum
+    fn read_slot(&self, page_id: PageId) -> Result<PageData, DiskManagerError> {
+        let mut slot = vec![0u8; self.superblock.page_size];
+        self.db_file.read_at(self.page_offset(page_id), &mut slot)?;
+
+        let trailer = slot.split_off(self.page_size());
+        let stored = u32::from_be_bytes(trailer[..CHECKSUM_SIZE_BYTES].try_into().unwrap());
+        if stored != Self::checksum(page_id, &slot) {
+            return Err(DiskManagerError::ChecksumMismatch { page_id });
+        }
+        Ok(slot)
+    }
+
+    /// Write a page's contents along with a trailer holding their checksum.
+    /// Both go in a single write, so there's no window where the page is on
+    /// disk with a stale checksum, short of a torn write.
+    fn write_slot(&mut self, page_id: PageId, page: &[u8]) -> Result<(), DiskManagerError> {
+        let mut slot = Vec::with_capacity(self.superblock.page_size);
+        slot.extend_from_slice(page);
+        slot.extend_from_slice(&Self::checksum(page_id, page).to_be_bytes());
+        self.db_file.write_at(self.page_offset(page_id), &slot)
+    }
+}
+
+impl<F: IDbFile> IDiskManager for FileDiskManager<F> {
+    fn page_size(&self) -> usize {
+        usable_page_size(self.superblock.page_size)
+    }
+
+    fn write_page(&mut self, page_id: PageId, page: &[u8]) -> Result<(), DiskManagerError> {
+        // Must allocate page before writing to it
+        self.check_allocated(page_id)?;
+        if page.len() != self.page_size() {
+            return Err(DiskManagerError::InvalidPageSize(page.len()));
+        }
+
+        self.write_slot(page_id, page)
+    }
+
+    fn read_page(&self, page_id: PageId) -> Result<PageData, DiskManagerError> {
+        self.check_allocated(page_id)?;
+        self.read_slot(page_id)
+    }
+
+    fn write_log(&mut self, log: &[u8]) -> Result<(), DiskManagerError> {
+        let log_file = self.log_file.as_mut().ok_or(DiskManagerError::NoLog)?;
+        // The log file is opened in append mode, so this always lands at the end
+        log_file.write_all(log)?;
+        self.log_size += log.len();
+        Ok(())
+    }
+
+    fn read_log(&self, size: usize, offset: usize) -> Result<PageData, DiskManagerError> {
+        if size > self.page_size() {
+            return Err(DiskManagerError::InvalidLogReadSize(size));
+        }
+        // Without a log there's nothing to read, so any offset is past the end
+        let log_file = match &self.log_file {
+            Some(log_file) if offset < self.log_size => log_file,
+            _ => return Err(DiskManagerError::LogOffsetOutOfRange(offset)),
+        };
+
+        let read_size = usize::min(size, self.log_size - offset);
+        let mut log_data = vec![0u8; self.page_size()];
+        log_file.read_exact_at(&mut log_data[..read_size], offset as u64)?;
+        Ok(log_data)
+    }
+
+    fn sync_log(&mut self) -> Result<(), DiskManagerError> {
+        if let Some(log_file) = &self.log_file {
+            log_file.sync_data()?;
+        }
+        Ok(())
+    }
+
+    fn sync_pages(&mut self) -> Result<(), DiskManagerError> {
+        self.db_file.sync()
+    }
+
+    fn allocate_page(&mut self) -> Result<PageId, DiskManagerError> {
+        // Hand out a free page if there is one, before growing the file
+        if let Some(page_id) = self.superblock.free_list_head {
+            let next_free_page_id = self.read_next_free_page_id(page_id)?;
+            self.superblock.free_list_head = next_free_page_id;
+            self.write_superblock()?;
+            self.free_pages.remove(&page_id);
+            // The page is only zeroed once it's durably off the list, so a
+            // crash can't leave the list running through a zeroed page
+            self.db_file.sync()?;
+
+            // Pages are always handed out zeroed
+            self.write_slot(page_id, &vec![0u8; self.page_size()])?;
+            return Ok(page_id);
+        }
+
+        let page_id = self.superblock.next_page_id;
+
+        // Extend the file with a zeroed page, durably, before the superblock
+        // counts it, so the superblock never claims a page the file doesn't
+        // have
+        self.write_slot(page_id, &vec![0u8; self.page_size()])?;
+        self.db_file.sync()?;
+        self.superblock.next_page_id += 1;
+        self.write_superblock()?;
+        Ok(page_id)
+    }
+
+    fn deallocate_page(&mut self, page_id: PageId) -> Result<(), DiskManagerError> {
+        // Pages that aren't in use, including ones that are already free, are
+        // left alone
+        if self.check_allocated(page_id).is_err() {
+            return Ok(());
+        }
+
+        // The page is durably linked in before the head is moved to it, so the
+        // list is never left pointing at a page that isn't in it
+        self.write_free_page(page_id, self.superblock.free_list_head)?;
+        self.db_file.sync()?;
+        self.superblock.free_list_head = Some(page_id);
+        self.write_superblock()?;
+        self.free_pages.insert(page_id);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dbms::buffer::pool_manager::{
+        BufferPoolManager, BufferPoolManagerError, IBufferPoolManager,
+    };
+    use crate::dbms::buffer::replacer::clock_replacer::ClockReplacer;
+    use crate::dbms::storage::disk::FORMAT_VERSION;
+    use crate::dbms::types::{MAX_PAGE_SIZE, MIN_PAGE_SIZE};
+    use rstest::*;
+    use tempfile::tempdir;
+
+    /// Size of the pages in a file with the default page size
+    const PAGE_SIZE: usize = usable_page_size(DEFAULT_PAGE_SIZE);
+
+    #[rstest]
+    fn test_write_and_read_page() {
+        let dir = tempdir().unwrap();
+        let mut disk_manager = FileDiskManager::new(dir.path().join("test.db")).unwrap();
+
+        let page_id = disk_manager.allocate_page().unwrap();
+        let page = [1u8; PAGE_SIZE];
+        disk_manager.write_page(page_id, &page).unwrap();
+
+        assert_eq!(disk_manager.read_page(page_id).unwrap(), page);
+    }
+
+    #[rstest]
+    fn test_allocate_page() {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("test.db");
+        let mut disk_manager = FileDiskManager::new(&db_path).unwrap();
+
+        // Page 0 is the header page, so data pages start after it
+        assert_eq!(disk_manager.allocate_page().unwrap(), 1);
+        assert_eq!(disk_manager.allocate_page().unwrap(), 2);
+        assert_eq!(disk_manager.read_page(2).unwrap(), [0u8; PAGE_SIZE]);
+        assert_eq!(
+            std::fs::metadata(&db_path).unwrap().len(),
+            3 * DEFAULT_PAGE_SIZE as u64
+        );
+    }
+
+    #[rstest]
+    fn test_write_page_nonexistent() {
+        let dir = tempdir().unwrap();
+        let mut disk_manager = FileDiskManager::new(dir.path().join("test.db")).unwrap();
+
+        let page_id = disk_manager.allocate_page().unwrap();
+        let result = disk_manager.write_page(page_id + 1, &[1u8; PAGE_SIZE]);
+        assert!(matches!(result, Err(DiskManagerError::PageNotFound)));
+    }
+
+    #[rstest]
+    fn test_write_page_wrong_size() {
+        let dir = tempdir().unwrap();
+        let mut disk_manager = FileDiskManager::new(dir.path().join("test.db")).unwrap();
+
+        let page_id = disk_manager.allocate_page().unwrap();
+        let result = disk_manager.write_page(page_id, &[1u8; 10]);
+        assert!(matches!(result, Err(DiskManagerError::InvalidPageSize(10))));
+    }
+
+    #[rstest]
+    fn test_read_page_nonexistent() {
+        let dir = tempdir().unwrap();
+        let mut disk_manager = FileDiskManager::new(dir.path().join("test.db")).unwrap();
+
+        let page_id = disk_manager.allocate_page().unwrap();
+        let result = disk_manager.read_page(page_id + 1);
+        assert!(matches!(result, Err(DiskManagerError::PageNotFound)));
+    }
+
+    #[rstest]
+    fn test_pages_persist_across_reopen() {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("test.db");
+
+        {
+            let mut disk_manager = FileDiskManager::new(&db_path).unwrap();
+            for i in 1..4 {
+                let page_id = disk_manager.allocate_page().unwrap();
+                disk_manager.write_page(page_id, &[i; PAGE_SIZE]).unwrap();
+            }
+        }
+
+        let mut disk_manager = FileDiskManager::new(&db_path).unwrap();
+        for i in 1..4 {
+            assert_eq!(disk_manager.read_page(i as PageId).unwrap(), [i; PAGE_SIZE]);
+        }
+
+        // Next page ID carries on from where the last session left off
+        assert_eq!(disk_manager.allocate_page().unwrap(), 4);
+    }
+
+    #[rstest]
+    fn test_header_page_not_readable() {
+        let dir = tempdir().unwrap();
+        let mut disk_manager = FileDiskManager::new(dir.path().join("test.db")).unwrap();
+
+        assert!(matches!(
+            disk_manager.read_page(SUPERBLOCK_PAGE_ID),
+            Err(DiskManagerError::PageNotFound)
+        ));
+        assert!(matches!(
+            disk_manager.write_page(SUPERBLOCK_PAGE_ID, &[1u8; PAGE_SIZE]),
+            Err(DiskManagerError::PageNotFound)
+        ));
+    }
+
+    #[rstest]
+    fn test_deallocated_page_reused() {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("test.db");
+        let mut disk_manager = FileDiskManager::new(&db_path).unwrap();
+        for _ in 0..3 {
+            let page_id = disk_manager.allocate_page().unwrap();
+            disk_manager.write_page(page_id, &[1u8; PAGE_SIZE]).unwrap();
+        }
+
+        disk_manager.deallocate_page(1).unwrap();
+        disk_manager.deallocate_page(3).unwrap();
+
+        // Freed pages can't be used until they're allocated again
+        assert!(matches!(
+            disk_manager.read_page(1),
+            Err(DiskManagerError::PageNotFound)
+        ));
+        assert!(matches!(
+            disk_manager.write_page(3, &[1u8; PAGE_SIZE]),
+            Err(DiskManagerError::PageNotFound)
+        ));
+
+        // The most recently freed page is reused first, zeroed, without
+        // growing the file
+        assert_eq!(disk_manager.allocate_page().unwrap(), 3);
+        assert_eq!(disk_manager.read_page(3).unwrap(), [0u8; PAGE_SIZE]);
+        assert_eq!(disk_manager.allocate_page().unwrap(), 1);
+        assert_eq!(disk_manager.read_page(1).unwrap(), [0u8; PAGE_SIZE]);
+        assert_eq!(
+            std::fs::metadata(&db_path).unwrap().len(),
+            4 * DEFAULT_PAGE_SIZE as u64
+        );
+
+        assert_eq!(disk_manager.allocate_page().unwrap(), 4);
+    }
+
+    #[rstest]
+    #[case(1)]
+    #[case(2)]
+    #[case(10)]
+    fn test_deallocate_unallocated_page_ignored(#[case] page_id: PageId) {
+        let dir = tempdir().unwrap();
+        let mut disk_manager = FileDiskManager::new(dir.path().join("test.db")).unwrap();
+        disk_manager.allocate_page().unwrap();
+        disk_manager.allocate_page().unwrap();
+        disk_manager.deallocate_page(2).unwrap();
+
+        disk_manager.deallocate_page(page_id).unwrap();
+        disk_manager.deallocate_page(SUPERBLOCK_PAGE_ID).unwrap();
+
+        let expected = if page_id == 1 { vec![1, 2] } else { vec![2] };
+        let allocated = (0..expected.len())
+            .map(|_| disk_manager.allocate_page().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(allocated, expected);
+        assert_eq!(disk_manager.allocate_page().unwrap(), 3);
+    }
+
+    #[rstest]
+    fn test_free_list_persists_across_reopen() {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("test.db");
+
+        {
+            let mut disk_manager = FileDiskManager::new(&db_path).unwrap();
+            for _ in 0..4 {
+                let page_id = disk_manager.allocate_page().unwrap();
+                disk_manager.write_page(page_id, &[1u8; PAGE_SIZE]).unwrap();
+            }
+            disk_manager.deallocate_page(2).unwrap();
+            disk_manager.deallocate_page(4).unwrap();
+        }
+
+        let mut disk_manager = FileDiskManager::new(&db_path).unwrap();
+        assert!(matches!(
+            disk_manager.read_page(2),
+            Err(DiskManagerError::PageNotFound)
+        ));
+        assert_eq!(disk_manager.read_page(3).unwrap(), [1u8; PAGE_SIZE]);
+
+        assert_eq!(disk_manager.allocate_page().unwrap(), 4);
+        assert_eq!(disk_manager.allocate_page().unwrap(), 2);
+        assert_eq!(disk_manager.read_page(2).unwrap(), [0u8; PAGE_SIZE]);
+        assert_eq!(disk_manager.allocate_page().unwrap(), 5);
+    }
+
+    /// A write or sync reaching a `RecordingDbFile`, writes by the page
+    /// they're to
+    #[derive(Debug, PartialEq)]
+    enum FileOp {
+        Write(PageId),
+        Sync,
+    }
+
+    /// A database file that records the order writes and syncs reach it in
+    struct RecordingDbFile {
+        inner: PlainDbFile,
+        ops: Vec<FileOp>,
+    }
+
+    impl IDbFile for RecordingDbFile {
+        fn open(path: &Path) -> Result<Self, DiskManagerError> {
+            Ok(Self {
+                inner: PlainDbFile::open(path)?,
+                ops: Vec::new(),
+            })
+        }
+
+        fn size(&self) -> Result<u64, DiskManagerError> {
+            self.inner.size()
+        }
+
+        fn read_at(&self, offset: u64, buf: &mut [u8]) -> Result<(), DiskManagerError> {
+            self.inner.read_at(offset, buf)
+        }
+
+        fn write_at(&mut self, offset: u64, data: &[u8]) -> Result<(), DiskManagerError> {
+            self.ops
+                .push(FileOp::Write(offset / DEFAULT_PAGE_SIZE as u64));
+            self.inner.write_at(offset, data)
+        }
+
+        fn truncate(&mut self, size: u64) -> Result<(), DiskManagerError> {
+            self.inner.truncate(size)
+        }
+
+        fn sync(&mut self) -> Result<(), DiskManagerError> {
+            self.ops.push(FileOp::Sync);
+            self.inner.sync()
+        }
+    }
+
+    #[rstest]
+    fn test_allocation_synced_between_writes() {
+        use FileOp::{Sync, Write};
+
+        let dir = tempdir().unwrap();
+        let mut disk_manager =
+            FileDiskManager::<RecordingDbFile>::open(dir.path().join("test.db")).unwrap();
+        disk_manager.db_file.ops.clear();
+
+        disk_manager.allocate_page().unwrap();
+        disk_manager.allocate_page().unwrap();
+        disk_manager.deallocate_page(1).unwrap();
+        assert_eq!(disk_manager.allocate_page().unwrap(), 1);
+
+        assert_eq!(
+            disk_manager.db_file.ops,
+            vec![
+                // The file grows before the superblock counts the new page
+                Write(1),
+                Sync,
+                Write(0),
+                Write(2),
+                Sync,
+                Write(0),
+                // A freed page is linked in before the superblock points at it
+                Write(1),
+                Sync,
+                Write(0),
+                // And is taken off the list before it's zeroed
+                Write(0),
+                Sync,
+                Write(1),
+            ]
+        );
+    }
+
+    #[rstest]
+    #[case::past_end_of_file(3)]
+    #[case::header_page(SUPERBLOCK_PAGE_ID)]
+    fn test_open_invalid_free_list(#[case] bad_page_id: PageId) {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("test.db");
+
+        {
+            let mut disk_manager = FileDiskManager::new(&db_path).unwrap();
+            disk_manager.allocate_page().unwrap();
+            disk_manager.allocate_page().unwrap();
+            disk_manager.deallocate_page(1).unwrap();
+            // Point the freed page at a page that can't be on the free list
+            disk_manager.write_free_page(1, Some(bad_page_id)).unwrap();
+        }
+
+        let result = FileDiskManager::new(&db_path);
+        assert!(matches!(
+            result,
+            Err(DiskManagerError::InvalidFreeList(page_id)) if page_id == bad_page_id
+        ));
+    }
+
+    #[rstest]
+    fn test_open_free_list_cycle() {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("test.db");
+
+        {
+            let mut disk_manager = FileDiskManager::new(&db_path).unwrap();
+            disk_manager.allocate_page().unwrap();
+            disk_manager.allocate_page().unwrap();
+            disk_manager.deallocate_page(1).unwrap();
+            disk_manager.deallocate_page(2).unwrap();
+            // Page 2 is the head of the list, and page 1 points back to it
+            disk_manager.write_free_page(1, Some(2)).unwrap();
+        }
+
+        let result = FileDiskManager::new(&db_path);
+        assert!(matches!(result, Err(DiskManagerError::InvalidFreeList(2))));
+    }
+
+    #[rstest]
+    fn test_open_partial_page_file() {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("test.db");
+        FileDiskManager::new(&db_path).unwrap();
+        let mut bytes = std::fs::read(&db_path).unwrap();
+        bytes.extend_from_slice(&[0u8; 10]);
+        std::fs::write(&db_path, bytes).unwrap();
+
+        let result = FileDiskManager::new(&db_path);
+        assert!(matches!(
+            result,
+            Err(DiskManagerError::InvalidFileSize(size)) if size == DEFAULT_PAGE_SIZE as u64 + 10
+        ));
+    }
+
+    #[rstest]
+    fn test_open_truncated_file() {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("test.db");
+        {
+            let mut disk_manager = FileDiskManager::new(&db_path).unwrap();
+            disk_manager.allocate_page().unwrap();
+            disk_manager.allocate_page().unwrap();
+        }
+        // Lose the last page the superblock says is there
+        let bytes = std::fs::read(&db_path).unwrap();
+        std::fs::write(&db_path, &bytes[..2 * DEFAULT_PAGE_SIZE]).unwrap();
+
+        let result = FileDiskManager::new(&db_path);
+        assert!(matches!(
+            result,
+            Err(DiskManagerError::InvalidFileSize(size)) if size == 2 * DEFAULT_PAGE_SIZE as u64
+        ));
+    }
+
+    #[rstest]
+    fn test_open_file_with_unfinished_allocation() {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("test.db");
+        {
+            let mut disk_manager = FileDiskManager::new(&db_path).unwrap();
+            disk_manager.allocate_page().unwrap();
+        }
+        // A page written past the end of the file that the superblock never
+        // counted
+        let mut bytes = std::fs::read(&db_path).unwrap();
+        bytes.extend_from_slice(&[1u8; DEFAULT_PAGE_SIZE]);
+        std::fs::write(&db_path, bytes).unwrap();
+
+        let mut disk_manager = FileDiskManager::new(&db_path).unwrap();
+        assert!(matches!(
+            disk_manager.read_page(2),
+            Err(DiskManagerError::PageNotFound)
+        ));
+        assert_eq!(
+            std::fs::metadata(&db_path).unwrap().len(),
+            2 * DEFAULT_PAGE_SIZE as u64
+        );
+        assert_eq!(disk_manager.allocate_page().unwrap(), 2);
+        assert_eq!(disk_manager.read_page(2).unwrap(), [0u8; PAGE_SIZE]);
+    }
+
+    #[rstest]
+    #[case::empty_page(vec![0u8; DEFAULT_PAGE_SIZE])]
+    #[case::text(b"not a database".to_vec())]
+    fn test_open_non_database_file(#[case] bytes: Vec<u8>) {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("test.db");
+        std::fs::write(&db_path, bytes).unwrap();
+
+        let result = FileDiskManager::new(&db_path);
+        assert!(matches!(result, Err(DiskManagerError::InvalidMagic)));
+    }
+
+    /// Create a database file, then overwrite a `u32` in its superblock
+    fn create_file_with_superblock_u32(db_path: &Path, offset: usize, value: u32) {
+        FileDiskManager::new(db_path).unwrap();
+        let mut bytes = std::fs::read(db_path).unwrap();
+        bytes[offset..offset + 4].copy_from_slice(&value.to_be_bytes());
+        std::fs::write(db_path, bytes).unwrap();
+    }
+
+    #[rstest]
+    fn test_open_unsupported_version() {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("test.db");
+        create_file_with_superblock_u32(&db_path, 8, FORMAT_VERSION + 1);
+
+        let result = FileDiskManager::new(&db_path);
+        assert!(matches!(
+            result,
+            Err(DiskManagerError::UnsupportedVersion(v)) if v == FORMAT_VERSION + 1
+        ));
+    }
+
+    #[rstest]
+    fn test_open_unsupported_page_size() {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("test.db");
+        create_file_with_superblock_u32(&db_path, 12, 3 * DEFAULT_PAGE_SIZE as u32);
+
+        let result = FileDiskManager::new(&db_path);
+        assert!(matches!(
+            result,
+            Err(DiskManagerError::UnsupportedPageSize(size)) if size == 3 * DEFAULT_PAGE_SIZE
+        ));
+    }
+
+    #[rstest]
+    #[case(8192)]
+    #[case(MAX_PAGE_SIZE)]
+    fn test_page_size_chosen_at_creation(#[case] page_size: usize) {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("test.db");
+        {
+            let mut disk_manager = FileDiskManager::with_page_size(&db_path, page_size).unwrap();
+            assert_eq!(disk_manager.file_page_size(), page_size);
+            assert_eq!(disk_manager.page_size(), usable_page_size(page_size));
+            for i in 1..4u8 {
+                let page_id = disk_manager.allocate_page().unwrap();
+                disk_manager
+                    .write_page(page_id, &vec![i; usable_page_size(page_size)])
+                    .unwrap();
+            }
+            assert!(matches!(
+                disk_manager.write_page(1, &[1u8; PAGE_SIZE]),
+                Err(DiskManagerError::InvalidPageSize(PAGE_SIZE))
+            ));
+        }
+        assert_eq!(
+            std::fs::metadata(&db_path).unwrap().len(),
+            4 * page_size as u64
+        );
+
+        // Opening without asking for a page size uses the one it was created
+        // with
+        let disk_manager = FileDiskManager::new(&db_path).unwrap();
+        assert_eq!(disk_manager.file_page_size(), page_size);
+        for i in 1..4u8 {
+            assert_eq!(
+                disk_manager.read_page(i as PageId).unwrap(),
+                vec![i; usable_page_size(page_size)]
+            );
+        }
+    }
+
+    #[rstest]
+    #[case(DEFAULT_PAGE_SIZE)]
+    #[case(MAX_PAGE_SIZE)]
+    fn test_slots_aligned_to_page_size(#[case] page_size: usize) {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("test.db");
+        {
+            let mut disk_manager = FileDiskManager::with_page_size(&db_path, page_size).unwrap();
+            for i in 1..4u8 {
+                let page_id = disk_manager.allocate_page().unwrap();
+                disk_manager
+                    .write_page(page_id, &vec![i; usable_page_size(page_size)])
+                    .unwrap();
+            }
+        }
+
+        // Each page starts on a page size boundary, with its checksum in the
+        // last few bytes of its slot rather than pushing the next page along
+        let bytes = std::fs::read(&db_path).unwrap();
+        assert_eq!(bytes.len(), 4 * page_size);
+        for i in 1..4u8 {
+            let slot = &bytes[page_offset(i as PageId, page_size) as usize..][..page_size];
+            assert_eq!(
+                slot[..usable_page_size(page_size)],
+                vec![i; usable_page_size(page_size)]
+            );
+        }
+    }
+
+    #[rstest]
+    fn test_open_different_page_size() {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("test.db");
+        FileDiskManager::with_page_size(&db_path, 8192).unwrap();
+
+        assert!(matches!(
+            FileDiskManager::with_page_size(&db_path, DEFAULT_PAGE_SIZE),
+            Err(DiskManagerError::PageSizeMismatch(8192))
+        ));
+    }
+
+    #[rstest]
+    #[case(0)]
+    #[case(MIN_PAGE_SIZE / 2)]
+    #[case(MIN_PAGE_SIZE + 512)]
+    #[case(MAX_PAGE_SIZE * 2)]
+    fn test_create_unsupported_page_size(#[case] page_size: usize) {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("test.db");
+
+        assert!(matches!(
+            FileDiskManager::with_page_size(&db_path, page_size),
+            Err(DiskManagerError::UnsupportedPageSize(size)) if size == page_size
+        ));
+        assert!(!db_path.exists());
+    }
+
+    #[rstest]
+    fn test_new_file_superblock() {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("test.db");
+        FileDiskManager::new(&db_path).unwrap();
+
+        let bytes = std::fs::read(&db_path).unwrap();
+        assert_eq!(bytes.len(), DEFAULT_PAGE_SIZE);
+        let superblock_bytes = &bytes[..SUPERBLOCK_SIZE_BYTES];
+        assert_eq!(
+            Superblock::from_bytes(superblock_bytes.try_into().unwrap()).unwrap(),
+            Superblock::new(DEFAULT_PAGE_SIZE)
+        );
+    }
+
+    #[rstest]
+    fn test_catalog_root_persists_across_reopen() {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("test.db");
+
+        {
+            let mut disk_manager = FileDiskManager::new(&db_path).unwrap();
+            assert_eq!(disk_manager.catalog_root_page_id(), None);
+            let page_id = disk_manager.allocate_page().unwrap();
+            disk_manager
+                .set_catalog_root_page_id(Some(page_id))
+                .unwrap();
+            assert_eq!(disk_manager.catalog_root_page_id(), Some(page_id));
+        }
+
+        let mut disk_manager = FileDiskManager::new(&db_path).unwrap();
+        assert_eq!(disk_manager.catalog_root_page_id(), Some(1));
+
+        disk_manager.set_catalog_root_page_id(None).unwrap();
+        let disk_manager = FileDiskManager::new(&db_path).unwrap();
+        assert_eq!(disk_manager.catalog_root_page_id(), None);
+    }
+
+    #[rstest]
+    #[case::superblock(SUPERBLOCK_PAGE_ID)]
+    #[case::unallocated(5)]
+    fn test_set_catalog_root_unallocated_page(#[case] page_id: PageId) {
+        let dir = tempdir().unwrap();
+        let mut disk_manager = FileDiskManager::new(dir.path().join("test.db")).unwrap();
+        disk_manager.allocate_page().unwrap();
+
+        let result = disk_manager.set_catalog_root_page_id(Some(page_id));
+        assert!(matches!(result, Err(DiskManagerError::PageNotFound)));
+        assert_eq!(disk_manager.catalog_root_page_id(), None);
+    }
+
+    #[rstest]
+    fn test_open_directory() {
+        let dir = tempdir().unwrap();
+
+        let result = FileDiskManager::new(dir.path());
+        assert!(matches!(result, Err(DiskManagerError::IoError(_))));
+    }
+
+    #[rstest]
+    fn test_write_and_read_log() {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("test.db");
+        let mut disk_manager = FileDiskManager::new(&db_path).unwrap();
+
+        disk_manager.write_log(&[1, 2, 3]).unwrap();
+        disk_manager.write_log(&[4, 5]).unwrap();
+        disk_manager.sync_log().unwrap();
+
+        let log_data = disk_manager.read_log(3, 1).unwrap();
+        assert_eq!(log_data[..3], [2, 3, 4]);
+        assert_eq!(log_data[3..], [0u8; PAGE_SIZE - 3]);
+
+        // The log is kept separately from the pages
+        assert_eq!(
+            std::fs::read(dir.path().join("test.db.log")).unwrap(),
+            vec![1, 2, 3, 4, 5]
+        );
+        assert_eq!(
+            std::fs::metadata(&db_path).unwrap().len(),
+            DEFAULT_PAGE_SIZE as u64
+        );
+    }
+
+    #[rstest]
+    fn test_read_log_past_end() {
+        let dir = tempdir().unwrap();
+        let mut disk_manager = FileDiskManager::new(dir.path().join("test.db")).unwrap();
+
+        assert!(matches!(
+            disk_manager.read_log(1, 0),
+            Err(DiskManagerError::LogOffsetOutOfRange(0))
+        ));
+
+        disk_manager.write_log(&[1, 2, 3]).unwrap();
+
+        // Reads running off the end of the log are zero-padded
+        let log_data = disk_manager.read_log(10, 1).unwrap();
+        assert_eq!(log_data[..2], [2, 3]);
+        assert_eq!(log_data[2..], [0u8; PAGE_SIZE - 2]);
+
+        // Reads starting at or after the end of the log are an error
+        assert!(matches!(
+            disk_manager.read_log(1, 3),
+            Err(DiskManagerError::LogOffsetOutOfRange(3))
+        ));
+    }
+
+    #[rstest]
+    fn test_read_log_too_large() {
+        let dir = tempdir().unwrap();
+        let mut disk_manager = FileDiskManager::new(dir.path().join("test.db")).unwrap();
+        disk_manager
+            .write_log(&[1u8; DEFAULT_PAGE_SIZE * 2])
+            .unwrap();
+
+        let result = disk_manager.read_log(PAGE_SIZE + 1, 0);
+        assert!(matches!(
+            result,
+            Err(DiskManagerError::InvalidLogReadSize(size)) if size == PAGE_SIZE + 1
+        ));
+    }
+
+    #[rstest]
+    #[case::log_extension("test.log", "test.log.log")]
+    #[case::no_extension("test", "test.log")]
+    fn test_log_path(#[case] db_name: &str, #[case] log_name: &str) {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join(db_name);
+        let mut disk_manager = FileDiskManager::new(&db_path).unwrap();
+        let page_id = disk_manager.allocate_page().unwrap();
+        disk_manager.write_page(page_id, &[7u8; PAGE_SIZE]).unwrap();
+        disk_manager.write_log(&[1, 2, 3]).unwrap();
+        disk_manager.sync_log().unwrap();
+        drop(disk_manager);
+
+        assert_eq!(
+            std::fs::read(dir.path().join(log_name)).unwrap(),
+            vec![1, 2, 3]
+        );
+        let disk_manager = FileDiskManager::new(&db_path).unwrap();
+        assert_eq!(disk_manager.read_page(page_id).unwrap(), [7u8; PAGE_SIZE]);
+        assert_eq!(disk_manager.read_log(3, 0).unwrap()[..3], [1, 2, 3]);
+    }
+
+    #[rstest]
+    fn test_open_without_log() {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("test.db");
+        let mut disk_manager = FileDiskManager::without_log(&db_path, DEFAULT_PAGE_SIZE).unwrap();
+
+        let page_id = disk_manager.allocate_page().unwrap();
+        disk_manager.write_page(page_id, &[1u8; PAGE_SIZE]).unwrap();
+        assert_eq!(disk_manager.read_page(page_id).unwrap(), [1u8; PAGE_SIZE]);
+
+        assert!(matches!(
+            disk_manager.write_log(&[1, 2, 3]),
+            Err(DiskManagerError::NoLog)
+        ));
+        assert!(matches!(
+            disk_manager.read_log(1, 0),
+            Err(DiskManagerError::LogOffsetOutOfRange(0))
+        ));
+        disk_manager.sync_log().unwrap();
+        assert!(!dir.path().join("test.db.log").exists());
+    }
+
+    #[rstest]
+    fn test_log_persists_across_reopen() {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("test.db");
+
+        {
+            let mut disk_manager = FileDiskManager::new(&db_path).unwrap();
+            disk_manager.write_log(&[1, 2, 3]).unwrap();
+            disk_manager.sync_log().unwrap();
+        }
+
+        let mut disk_manager = FileDiskManager::new(&db_path).unwrap();
+        disk_manager.write_log(&[4, 5]).unwrap();
+
+        let log_data = disk_manager.read_log(5, 0).unwrap();
+        assert_eq!(log_data[..5], [1, 2, 3, 4, 5]);
+    }
+
+    #[rstest]
+    fn test_buffer_pool_pages_survive_restart() {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("test.db");
+
+        let page_id: PageId;
+        {
+            let disk_manager = FileDiskManager::new(&db_path).unwrap();
+            let buffer_pool_manager = BufferPoolManager::new(
+                10,
+                Box::new(ClockReplacer::new(10)),
+                Box::new(disk_manager),
+            );
+
+            {
+                let mut page = buffer_pool_manager.new_page().unwrap();
+                page_id = page.get_page_id().unwrap().unwrap();
+                page.write_data(15, &[42]).unwrap();
+            }
+            buffer_pool_manager.unpin_page(page_id, true).unwrap();
+            buffer_pool_manager.flush_all_pages().unwrap();
+        }
+
+        let disk_manager = FileDiskManager::new(&db_path).unwrap();
+        let buffer_pool_manager =
+            BufferPoolManager::new(10, Box::new(ClockReplacer::new(10)), Box::new(disk_manager));
+
+        let page = buffer_pool_manager.fetch_page(page_id).unwrap();
+        assert_eq!(page.get_data().unwrap()[15], 42);
+    }
+
+    /// Flip a byte of a page in the file, as if it had been corrupted on disk
+    fn corrupt_page(db_path: &Path, page_id: PageId, offset: usize) {
+        let mut bytes = std::fs::read(db_path).unwrap();
+        let offset = page_offset(page_id, DEFAULT_PAGE_SIZE) as usize + offset;
+        bytes[offset] ^= 0xff;
+        std::fs::write(db_path, bytes).unwrap();
+    }
+
+    #[rstest]
+    #[case::start_of_page(0)]
+    #[case::end_of_page(PAGE_SIZE - 1)]
+    #[case::checksum(PAGE_SIZE)]
+    fn test_read_corrupted_page(#[case] offset: usize) {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("test.db");
+        {
+            let mut disk_manager = FileDiskManager::new(&db_path).unwrap();
+            for _ in 0..2 {
+                let page_id = disk_manager.allocate_page().unwrap();
+                disk_manager.write_page(page_id, &[7u8; PAGE_SIZE]).unwrap();
+            }
+        }
+        corrupt_page(&db_path, 2, offset);
+
+        let disk_manager = FileDiskManager::new(&db_path).unwrap();
+        assert!(matches!(
+            disk_manager.read_page(2),
+            Err(DiskManagerError::ChecksumMismatch { page_id: 2 })
+        ));
+        // Other pages are unaffected
+        assert_eq!(disk_manager.read_page(1).unwrap(), [7u8; PAGE_SIZE]);
+    }
+
+    #[rstest]
+    fn test_read_page_written_to_wrong_place() {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("test.db");
+        {
+            let mut disk_manager = FileDiskManager::new(&db_path).unwrap();
+            for i in 1..3 {
+                let page_id = disk_manager.allocate_page().unwrap();
+                disk_manager.write_page(page_id, &[i; PAGE_SIZE]).unwrap();
+            }
+        }
+        // Copy page 1, trailer and all, over page 2
+        let mut bytes = std::fs::read(&db_path).unwrap();
+        let (first, second) = (
+            page_offset(1, DEFAULT_PAGE_SIZE) as usize,
+            page_offset(2, DEFAULT_PAGE_SIZE) as usize,
+        );
+        bytes.copy_within(first..second, second);
+        std::fs::write(&db_path, bytes).unwrap();
+
+        let disk_manager = FileDiskManager::new(&db_path).unwrap();
+        assert_eq!(disk_manager.read_page(1).unwrap(), [1u8; PAGE_SIZE]);
+        assert!(matches!(
+            disk_manager.read_page(2),
+            Err(DiskManagerError::ChecksumMismatch { page_id: 2 })
+        ));
+    }
+
+    #[rstest]
+    fn test_open_corrupted_superblock() {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("test.db");
+        FileDiskManager::new(&db_path).unwrap();
+        // Past the superblock's fields, so only the checksum catches it
+        corrupt_page(&db_path, SUPERBLOCK_PAGE_ID, SUPERBLOCK_SIZE_BYTES);
+
+        let result = FileDiskManager::new(&db_path);
+        assert!(matches!(
+            result,
+            Err(DiskManagerError::ChecksumMismatch {
+                page_id: SUPERBLOCK_PAGE_ID
+            })
+        ));
+    }
+
+    #[rstest]
+    fn test_open_corrupted_free_page() {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("test.db");
+        {
+            let mut disk_manager = FileDiskManager::new(&db_path).unwrap();
+            disk_manager.allocate_page().unwrap();
+            disk_manager.deallocate_page(1).unwrap();
+        }
+        corrupt_page(&db_path, 1, 0);
+
+        let result = FileDiskManager::new(&db_path);
+        assert!(matches!(
+            result,
+            Err(DiskManagerError::ChecksumMismatch { page_id: 1 })
+        ));
+    }
+
+    #[rstest]
+    fn test_buffer_pool_fetch_corrupted_page() {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("test.db");
+        let page_id = {
+            let mut disk_manager = FileDiskManager::new(&db_path).unwrap();
+            let page_id = disk_manager.allocate_page().unwrap();
+            disk_manager.write_page(page_id, &[3u8; PAGE_SIZE]).unwrap();
+            page_id
+        };
+        corrupt_page(&db_path, page_id, 100);
+
+        let disk_manager = FileDiskManager::new(&db_path).unwrap();
+        let buffer_pool_manager =
+            BufferPoolManager::new(1, Box::new(ClockReplacer::new(1)), Box::new(disk_manager));
+
+        let result = buffer_pool_manager.fetch_page(page_id);
+        assert!(matches!(
+            result,
+            Err(BufferPoolManagerError::DiskManagerError(
+                DiskManagerError::ChecksumMismatch { page_id: id }
+            )) if id == page_id
+        ));
+        drop(result);
+
+        // The frame the page was being read into is still usable
+        let page = buffer_pool_manager.new_page().unwrap();
+        assert!(page.get_page_id().unwrap().is_some());
+    }
+}
@@ -0,0 +1,536 @@
+use std::fs::{File, OpenOptions};
+use std::io::ErrorKind;
+use std::path::Path;
+
+use memmap2::MmapMut;
+
+use super::{DiskManagerError, FileDiskManager, IDbFile};
+
+/// A disk manager that reads and writes its database file through a memory
+/// mapping rather than with file I/O, which saves a system call and a copy
+/// into the kernel on every page read. Pages, the superblock and the free
+/// list are laid out exactly as for a `FileDiskManager`, so a file written by
+/// either can be opened by the other. The log is still a plain file.
+///
+/// Open one with `MmapDiskManager::open`, or `MmapDiskManager::open_with_page_size`
+/// to create it with a page size other than the default.
+pub type MmapDiskManager = FileDiskManager<MappedDbFile>;
+
+/// Most the file is grown by at once, once it's large enough that doubling
+/// it would be a waste
+const MAX_GROWTH_BYTES: u64 = 64 << 20;
+
+/// A database file accessed through a memory mapping of the whole file.
+///
+/// When a write runs past the end of the mapping, as it does when a page is
+/// allocated, the file is extended and mapped again. It's grown ahead of the
+/// write, doubling in size up to `MAX_GROWTH_BYTES` at a time, so allocating
+/// page after page only remaps it every so often. The space grown into ahead
+/// of time isn't part of the file as far as anyone reading it is concerned,
+/// and is cut off again when the file is closed. If that fails, or the file
+/// is never closed, it's cut off when the file is next opened. The file
+/// mustn't be truncated by anything else while it's mapped.
+pub struct MappedDbFile {
+    file: File,
+    /// Mapping of the whole file, or `None` while the file is empty, as an
+    /// empty file can't be mapped
+    mmap: Option<MmapMut>,
+    /// Size of the file as far as it's been written, which the mapping may
+    /// run past
+    len: u64,
+}
+
+impl MappedDbFile {
+    fn map(file: &File) -> Result<Option<MmapMut>, DiskManagerError> {
+        if file.metadata()?.len() == 0 {
+            return Ok(None);
+        }
+        // Safety: the mapping is only ever accessed through this struct, which
+        // owns the file, and the file is only ever grown while mapped
+        let mmap = unsafe { MmapMut::map_mut(file)? };
+        Ok(Some(mmap))
+    }
+
+    fn mapped(&self) -> &[u8] {
+        self.mmap.as_deref().unwrap_or(&[])
+    }
+
+    /// Extend the file to at least the given size, and map it again to cover
+    /// it all
+    fn grow(&mut self, size: u64) -> Result<(), DiskManagerError> {
+        let mapped = self.mapped().len() as u64;
+        let size = u64::max(size, mapped + u64::min(mapped, MAX_GROWTH_BYTES));
+        // The old mapping is dropped first, as it doesn't cover the new end
+        self.mmap = None;
+        self.file.set_len(size)?;
+        self.mmap = Self::map(&self.file)?;
+        Ok(())
+    }
+
+    /// Byte range of the mapping covering `len` bytes from `offset`, if the
+    /// mapping is that long
+    fn range(&self, offset: u64, len: usize) -> Option<std::ops::Range<usize>> {
+        let start = usize::try_from(offset).ok()?;
+        let end = start.checked_add(len)?;
+        (end <= self.mapped().len()).then_some(start..end)
+    }
+}
+
+impl Drop for MappedDbFile {
+    /// Cut off the space grown into ahead of time, so the file's left the
+    /// size of what's been written to it
+    fn drop(&mut self) {
+        self.mmap = None;
+        // Nothing can be done about it failing here, but the space left on
+        // the end is past the last page, and is cut off on the next open.
+        // `MmapDiskManager::close` reports it instead.
+        let _ = self.file.set_len(self.len);
+    }
+}
+
+impl IDbFile for MappedDbFile {
+    fn open(path: &Path) -> Result<Self, DiskManagerError> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)?;
+        let mmap = Self::map(&file)?;
+        let len = file.metadata()?.len();
+        Ok(Self { file, mmap, len })
+    }
+
+    fn size(&self) -> Result<u64, DiskManagerError> {
+        Ok(self.len)
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> Result<(), DiskManagerError> {
+        // Same as running off the end of the file with `read_exact`
+        let range = self
+            .range(offset, buf.len())
+            .filter(|range| range.end as u64 <= self.len)
+            .ok_or_else(|| std::io::Error::from(ErrorKind::UnexpectedEof))?;
+        buf.copy_from_slice(&self.mapped()[range]);
+        Ok(())
+    }
+
+    fn write_at(&mut self, offset: u64, data: &[u8]) -> Result<(), DiskManagerError> {
+        let end = offset + data.len() as u64;
+        if self.range(offset, data.len()).is_none() {
+            self.grow(end)?;
+        }
+        let range = self.range(offset, data.len()).unwrap();
+        // The mapping is always there after growing, as the file isn't empty
+        self.mmap.as_mut().unwrap()[range].copy_from_slice(data);
+        self.len = u64::max(self.len, end);
+        Ok(())
+    }
+
+    fn truncate(&mut self, size: u64) -> Result<(), DiskManagerError> {
+        self.mmap = None;
+        self.file.set_len(size)?;
+        self.mmap = Self::map(&self.file)?;
+        self.len = size;
+        Ok(())
+    }
+
+    fn sync(&mut self) -> Result<(), DiskManagerError> {
+        // Writes through the mapping have to be flushed out of it, and the
+        // file's new size made durable along with them
+        if let Some(mmap) = &self.mmap {
+            mmap.flush()?;
+        }
+        self.file.sync_data()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::*;
+    use crate::dbms::buffer::pool_manager::{BufferPoolManager, IBufferPoolManager};
+    use crate::dbms::buffer::replacer::clock_replacer::ClockReplacer;
+    use crate::dbms::storage::disk::file_disk_manager::usable_page_size;
+    use crate::dbms::storage::disk::{IDiskManager, PlainDbFile, SUPERBLOCK_PAGE_ID};
+    use crate::dbms::types::{PageId, DEFAULT_PAGE_SIZE, MAX_PAGE_SIZE};
+    use rstest::*;
+    use tempfile::tempdir;
+
+    /// Size of the pages in a file with the default page size
+    const PAGE_SIZE: usize = usable_page_size(DEFAULT_PAGE_SIZE);
+
+    #[rstest]
+    fn test_write_and_read_page() {
+        let dir = tempdir().unwrap();
+        let mut disk_manager = MmapDiskManager::open(dir.path().join("test.db")).unwrap();
+
+        let page_id = disk_manager.allocate_page().unwrap();
+        let page = [1u8; PAGE_SIZE];
+        disk_manager.write_page(page_id, &page).unwrap();
+
+        assert_eq!(disk_manager.read_page(page_id).unwrap(), page);
+    }
+
+    #[rstest]
+    fn test_allocate_page_grows_mapping() {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("test.db");
+        let mut disk_manager = MmapDiskManager::open(&db_path).unwrap();
+
+        for i in 1..=20 {
+            assert_eq!(disk_manager.allocate_page().unwrap(), i);
+            disk_manager.write_page(i, &[i as u8; PAGE_SIZE]).unwrap();
+        }
+
+        // Earlier pages are still there after the file's been mapped again
+        for i in 1..=20 {
+            assert_eq!(disk_manager.read_page(i).unwrap(), [i as u8; PAGE_SIZE]);
+        }
+        // The file doubled as it grew, rather than growing a page at a time
+        assert_eq!(
+            std::fs::metadata(&db_path).unwrap().len(),
+            32 * DEFAULT_PAGE_SIZE as u64
+        );
+
+        // And the space grown into ahead of time is gone once it's closed
+        disk_manager.close().unwrap();
+        assert_eq!(
+            std::fs::metadata(&db_path).unwrap().len(),
+            21 * DEFAULT_PAGE_SIZE as u64
+        );
+    }
+
+    #[rstest]
+    fn test_reopen_file_grown_ahead() {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("test.db");
+        {
+            let mut disk_manager = MmapDiskManager::open(&db_path).unwrap();
+            for i in 1..=5 {
+                disk_manager.allocate_page().unwrap();
+                disk_manager.write_page(i, &[i as u8; PAGE_SIZE]).unwrap();
+            }
+            disk_manager.sync_pages().unwrap();
+            // As if it crashed, or cutting the file back down failed
+            std::mem::forget(disk_manager);
+        }
+        assert_eq!(
+            std::fs::metadata(&db_path).unwrap().len(),
+            8 * DEFAULT_PAGE_SIZE as u64
+        );
+
+        // The zeroed space on the end is past the last page, so it's cut off
+        // when the file's opened again
+        let mut disk_manager = MmapDiskManager::open(&db_path).unwrap();
+        assert_eq!(
+            std::fs::metadata(&db_path).unwrap().len(),
+            6 * DEFAULT_PAGE_SIZE as u64
+        );
+        for i in 1..=5 {
+            assert_eq!(disk_manager.read_page(i).unwrap(), [i as u8; PAGE_SIZE]);
+        }
+        assert_eq!(disk_manager.allocate_page().unwrap(), 6);
+        assert_eq!(disk_manager.read_page(6).unwrap(), [0u8; PAGE_SIZE]);
+    }
+
+    #[rstest]
+    fn test_unallocated_pages_not_found() {
+        let dir = tempdir().unwrap();
+        let mut disk_manager = MmapDiskManager::open(dir.path().join("test.db")).unwrap();
+        let page_id = disk_manager.allocate_page().unwrap();
+
+        assert!(matches!(
+            disk_manager.read_page(page_id + 1),
+            Err(DiskManagerError::PageNotFound)
+        ));
+        assert!(matches!(
+            disk_manager.write_page(SUPERBLOCK_PAGE_ID, &[1u8; PAGE_SIZE]),
+            Err(DiskManagerError::PageNotFound)
+        ));
+    }
+
+    #[rstest]
+    fn test_deallocated_page_reused() {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("test.db");
+        let mut disk_manager = MmapDiskManager::open(&db_path).unwrap();
+        for _ in 0..3 {
+            let page_id = disk_manager.allocate_page().unwrap();
+            disk_manager.write_page(page_id, &[1u8; PAGE_SIZE]).unwrap();
+        }
+
+        disk_manager.deallocate_page(2).unwrap();
+
+        assert_eq!(disk_manager.allocate_page().unwrap(), 2);
+        assert_eq!(disk_manager.read_page(2).unwrap(), [0u8; PAGE_SIZE]);
+        assert_eq!(
+            std::fs::metadata(&db_path).unwrap().len(),
+            4 * DEFAULT_PAGE_SIZE as u64
+        );
+    }
+
+    #[rstest]
+    fn test_pages_persist_across_reopen() {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("test.db");
+
+        {
+            let mut disk_manager = MmapDiskManager::open(&db_path).unwrap();
+            for i in 1..4 {
+                let page_id = disk_manager.allocate_page().unwrap();
+                disk_manager.write_page(page_id, &[i; PAGE_SIZE]).unwrap();
+            }
+            disk_manager.deallocate_page(1).unwrap();
+        }
+
+        let mut disk_manager = MmapDiskManager::open(&db_path).unwrap();
+        for i in 2..4 {
+            assert_eq!(disk_manager.read_page(i as PageId).unwrap(), [i; PAGE_SIZE]);
+        }
+        assert_eq!(disk_manager.allocate_page().unwrap(), 1);
+        assert_eq!(disk_manager.allocate_page().unwrap(), 4);
+    }
+
+    #[rstest]
+    #[case::mapped_then_plain(true)]
+    #[case::plain_then_mapped(false)]
+    fn test_same_file_format_as_file_disk_manager(#[case] mapped_first: bool) {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("test.db");
+
+        fn fill(disk_manager: &mut impl IDiskManager) {
+            for i in 1..4 {
+                let page_id = disk_manager.allocate_page().unwrap();
+                disk_manager.write_page(page_id, &[i; PAGE_SIZE]).unwrap();
+            }
+            disk_manager.deallocate_page(2).unwrap();
+            disk_manager.write_log(&[1, 2, 3]).unwrap();
+        }
+
+        fn check(disk_manager: &mut impl IDiskManager) {
+            assert_eq!(disk_manager.read_page(1).unwrap(), [1u8; PAGE_SIZE]);
+            assert_eq!(disk_manager.read_page(3).unwrap(), [3u8; PAGE_SIZE]);
+            assert!(matches!(
+                disk_manager.read_page(2),
+                Err(DiskManagerError::PageNotFound)
+            ));
+            assert_eq!(disk_manager.read_log(3, 0).unwrap()[..3], [1, 2, 3]);
+            assert_eq!(disk_manager.allocate_page().unwrap(), 2);
+            assert_eq!(disk_manager.allocate_page().unwrap(), 4);
+        }
+
+        if mapped_first {
+            fill(&mut MmapDiskManager::open(&db_path).unwrap());
+            check(&mut FileDiskManager::new(&db_path).unwrap());
+        } else {
+            fill(&mut FileDiskManager::new(&db_path).unwrap());
+            check(&mut MmapDiskManager::open(&db_path).unwrap());
+        }
+    }
+
+    #[rstest]
+    fn test_page_size_shared_with_file_disk_manager() {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("test.db");
+        {
+            let mut disk_manager =
+                MmapDiskManager::open_with_page_size(&db_path, MAX_PAGE_SIZE).unwrap();
+            let page_id = disk_manager.allocate_page().unwrap();
+            disk_manager
+                .write_page(page_id, &vec![5u8; usable_page_size(MAX_PAGE_SIZE)])
+                .unwrap();
+        }
+        assert_eq!(
+            std::fs::metadata(&db_path).unwrap().len(),
+            2 * MAX_PAGE_SIZE as u64
+        );
+
+        // The page size comes from the file, whichever way it's opened
+        let disk_manager = FileDiskManager::new(&db_path).unwrap();
+        assert_eq!(disk_manager.file_page_size(), MAX_PAGE_SIZE);
+        assert_eq!(
+            disk_manager.read_page(1).unwrap(),
+            vec![5u8; usable_page_size(MAX_PAGE_SIZE)]
+        );
+        drop(disk_manager);
+        assert!(matches!(
+            MmapDiskManager::open_with_page_size(&db_path, DEFAULT_PAGE_SIZE),
+            Err(DiskManagerError::PageSizeMismatch(MAX_PAGE_SIZE))
+        ));
+    }
+
+    #[rstest]
+    fn test_read_corrupted_page() {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("test.db");
+        {
+            let mut disk_manager = MmapDiskManager::open(&db_path).unwrap();
+            let page_id = disk_manager.allocate_page().unwrap();
+            disk_manager.write_page(page_id, &[7u8; PAGE_SIZE]).unwrap();
+        }
+        let mut bytes = std::fs::read(&db_path).unwrap();
+        bytes[DEFAULT_PAGE_SIZE] ^= 0xff;
+        std::fs::write(&db_path, bytes).unwrap();
+
+        let disk_manager = MmapDiskManager::open(&db_path).unwrap();
+        assert!(matches!(
+            disk_manager.read_page(1),
+            Err(DiskManagerError::ChecksumMismatch { page_id: 1 })
+        ));
+    }
+
+    #[rstest]
+    #[case::empty_page(vec![0u8; DEFAULT_PAGE_SIZE])]
+    #[case::text(b"not a database".to_vec())]
+    fn test_open_non_database_file(#[case] bytes: Vec<u8>) {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("test.db");
+        std::fs::write(&db_path, bytes).unwrap();
+
+        let result = MmapDiskManager::open(&db_path);
+        assert!(matches!(result, Err(DiskManagerError::InvalidMagic)));
+    }
+
+    #[rstest]
+    fn test_read_past_end_of_file() {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("test.db");
+        let mut db_file = MappedDbFile::open(&db_path).unwrap();
+        let mut buf = [0u8; 4];
+
+        // Nothing is mapped while the file is empty
+        assert_eq!(db_file.size().unwrap(), 0);
+        assert!(matches!(
+            db_file.read_at(0, &mut buf),
+            Err(DiskManagerError::IoError(e)) if e.kind() == ErrorKind::UnexpectedEof
+        ));
+
+        db_file.write_at(2, &[1, 2, 3]).unwrap();
+        assert_eq!(db_file.size().unwrap(), 5);
+        db_file.read_at(1, &mut buf).unwrap();
+        assert_eq!(buf, [0, 1, 2, 3]);
+        assert!(matches!(
+            db_file.read_at(2, &mut buf),
+            Err(DiskManagerError::IoError(e)) if e.kind() == ErrorKind::UnexpectedEof
+        ));
+
+        // The mapping's grown ahead of the write, but only what's been
+        // written can be read
+        db_file.write_at(5, &[4]).unwrap();
+        assert_eq!(db_file.size().unwrap(), 6);
+        assert_eq!(db_file.mapped().len(), 10);
+        db_file.read_at(2, &mut buf).unwrap();
+        assert_eq!(buf, [1, 2, 3, 4]);
+        assert!(matches!(
+            db_file.read_at(3, &mut buf),
+            Err(DiskManagerError::IoError(e)) if e.kind() == ErrorKind::UnexpectedEof
+        ));
+        assert!(matches!(
+            db_file.read_at(u64::MAX, &mut buf),
+            Err(DiskManagerError::IoError(e)) if e.kind() == ErrorKind::UnexpectedEof
+        ));
+    }
+
+    #[rstest]
+    fn test_buffer_pool_pages_survive_restart() {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("test.db");
+
+        let page_id: PageId;
+        {
+            let disk_manager = MmapDiskManager::open(&db_path).unwrap();
+            let buffer_pool_manager = BufferPoolManager::new(
+                10,
+                Box::new(ClockReplacer::new(10)),
+                Box::new(disk_manager),
+            );
+
+            {
+                let mut page = buffer_pool_manager.new_page().unwrap();
+                page_id = page.get_page_id().unwrap().unwrap();
+                page.write_data(15, &[42]).unwrap();
+            }
+            buffer_pool_manager.unpin_page(page_id, true).unwrap();
+            buffer_pool_manager.flush_all_pages().unwrap();
+        }
+
+        let disk_manager = MmapDiskManager::open(&db_path).unwrap();
+        let buffer_pool_manager =
+            BufferPoolManager::new(10, Box::new(ClockReplacer::new(10)), Box::new(disk_manager));
+
+        let page = buffer_pool_manager.fetch_page(page_id).unwrap();
+        assert_eq!(page.get_data().unwrap()[15], 42);
+    }
+
+    const BENCH_PAGES: PageId = 2000;
+    const BENCH_POOL_SIZE: usize = 64;
+    const BENCH_FETCHES: usize = 100_000;
+
+    /// Fill a database with pages through a buffer pool, then fetch pages at
+    /// random through a pool much smaller than the database, so most fetches
+    /// read from disk. Returns the time taken to fill and to fetch.
+    fn bench_buffer_pool<F>(db_path: &Path) -> (Duration, Duration)
+    where
+        F: IDbFile + Send + Sync + 'static,
+    {
+        let disk_manager = FileDiskManager::<F>::open(db_path).unwrap();
+        let buffer_pool_manager = BufferPoolManager::new(
+            BENCH_POOL_SIZE,
+            Box::new(ClockReplacer::new(BENCH_POOL_SIZE)),
+            Box::new(disk_manager),
+        );
+
+        let start = Instant::now();
+        for i in 0..BENCH_PAGES {
+            let page_id = {
+                let mut page = buffer_pool_manager.new_page().unwrap();
+                page.write_data(0, &i.to_be_bytes()).unwrap();
+                page.get_page_id().unwrap().unwrap()
+            };
+            buffer_pool_manager.unpin_page(page_id, true).unwrap();
+        }
+        buffer_pool_manager.flush_all_pages().unwrap();
+        let fill_time = start.elapsed();
+
+        // A fixed linear congruential generator, so both runs fetch the same
+        // pages in the same order
+        let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+        let start = Instant::now();
+        for _ in 0..BENCH_FETCHES {
+            state = state
+                .wrapping_mul(6_364_136_223_846_793_005)
+                .wrapping_add(1_442_695_040_888_963_407);
+            let page_id = 1 + (state >> 33) % BENCH_PAGES;
+            {
+                let page = buffer_pool_manager.fetch_page(page_id).unwrap();
+                let data = page.get_data().unwrap();
+                assert_eq!(data[..8], (page_id - 1).to_be_bytes());
+            }
+            buffer_pool_manager.unpin_page(page_id, false).unwrap();
+        }
+        (fill_time, start.elapsed())
+    }
+
+    /// Compare the plain and memory-mapped disk managers under the same
+    /// buffer pool. Timings are too noisy to say which is faster, so this only
+    /// checks the mapping isn't far behind on either filling or fetching. Run
+    /// with `cargo test --release bench_ -- --ignored`.
+    #[rstest]
+    #[ignore = "benchmark"]
+    fn bench_mmap_against_file_disk_manager() {
+        let dir = tempdir().unwrap();
+
+        let (file_fill, file_fetch) = bench_buffer_pool::<PlainDbFile>(&dir.path().join("file.db"));
+        let (mmap_fill, mmap_fetch) =
+            bench_buffer_pool::<MappedDbFile>(&dir.path().join("mmap.db"));
+
+        assert!(
+            mmap_fill < 2 * file_fill && mmap_fetch < 2 * file_fetch,
+            "file: fill {file_fill:?}, fetch {file_fetch:?}; \
+             mmap: fill {mmap_fill:?}, fetch {mmap_fetch:?}"
+        );
+    }
+}
@@ -0,0 +1,213 @@
+use crate::dbms::types::{is_valid_page_size, PageId, INVALID_PAGE_ID};
+
+use super::DiskManagerError;
+
+/// Marks a file as a k2db database file
+pub const SUPERBLOCK_MAGIC: [u8; 8] = *b"K2DBFILE";
+/// Version of the file format written by this build. Files with any other
+/// version are rejected when opened.
+pub const FORMAT_VERSION: u32 = 4;
+
+/// The superblock always lives in the file's first page
+pub const SUPERBLOCK_PAGE_ID: PageId = 0;
+
+const MAGIC_OFFSET_BYTES: usize = 0;
+const VERSION_OFFSET_BYTES: usize = MAGIC_OFFSET_BYTES + SUPERBLOCK_MAGIC.len();
+const PAGE_SIZE_OFFSET_BYTES: usize = VERSION_OFFSET_BYTES + U32_SIZE_BYTES;
+const NEXT_PAGE_ID_OFFSET_BYTES: usize = PAGE_SIZE_OFFSET_BYTES + U32_SIZE_BYTES;
+const FREE_LIST_HEAD_OFFSET_BYTES: usize = NEXT_PAGE_ID_OFFSET_BYTES + PAGE_ID_SIZE_BYTES;
+const CATALOG_ROOT_OFFSET_BYTES: usize = FREE_LIST_HEAD_OFFSET_BYTES + PAGE_ID_SIZE_BYTES;
+/// Bytes of the first page used by the superblock. The rest is zeroed.
+pub const SUPERBLOCK_SIZE_BYTES: usize = CATALOG_ROOT_OFFSET_BYTES + PAGE_ID_SIZE_BYTES;
+
+const U32_SIZE_BYTES: usize = std::mem::size_of::<u32>();
+const PAGE_ID_SIZE_BYTES: usize = std::mem::size_of::<PageId>();
+
+/// Describes a database file: what built it, and where to find the things
+/// that have to be found before anything else can be read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Superblock {
+    /// Size of every page's slot in the file, chosen when it was created
+    pub page_size: usize,
+    /// The page ID the file will grow into next, which is also the number of
+    /// pages in use, including the superblock's own page
+    pub next_page_id: PageId,
+    /// First page of the list of deallocated pages, if there are any
+    pub free_list_head: Option<PageId>,
+    /// Root page of the database's catalog, once it has one
+    pub catalog_root_page_id: Option<PageId>,
+}
+
+impl Superblock {
+    /// The superblock for a new file with the given page size, which only has
+    /// the superblock's page
+    pub fn new(page_size: usize) -> Self {
+        Self {
+            page_size,
+            next_page_id: SUPERBLOCK_PAGE_ID + 1,
+            free_list_head: None,
+            catalog_root_page_id: None,
+        }
+    }
+
+    pub fn to_bytes(self) -> [u8; SUPERBLOCK_SIZE_BYTES] {
+        let mut bytes = [0u8; SUPERBLOCK_SIZE_BYTES];
+        bytes[MAGIC_OFFSET_BYTES..VERSION_OFFSET_BYTES].copy_from_slice(&SUPERBLOCK_MAGIC);
+        write_u32(&mut bytes, VERSION_OFFSET_BYTES, FORMAT_VERSION);
+        write_u32(&mut bytes, PAGE_SIZE_OFFSET_BYTES, self.page_size as u32);
+        write_page_id(&mut bytes, NEXT_PAGE_ID_OFFSET_BYTES, self.next_page_id);
+        write_page_id(
+            &mut bytes,
+            FREE_LIST_HEAD_OFFSET_BYTES,
+            self.free_list_head.unwrap_or(INVALID_PAGE_ID),
+        );
+        write_page_id(
+            &mut bytes,
+            CATALOG_ROOT_OFFSET_BYTES,
+            self.catalog_root_page_id.unwrap_or(INVALID_PAGE_ID),
+        );
+        bytes
+    }
+
+    /// Read a superblock, checking it was written by a compatible build with
+    /// a page size a database can have. The magic number is checked first, so
+    /// a file that isn't a database at all isn't mistaken for one with the
+    /// wrong version or page size.
+    pub fn from_bytes(bytes: &[u8; SUPERBLOCK_SIZE_BYTES]) -> Result<Self, DiskManagerError> {
+        if bytes[MAGIC_OFFSET_BYTES..VERSION_OFFSET_BYTES] != SUPERBLOCK_MAGIC {
+            return Err(DiskManagerError::InvalidMagic);
+        }
+        let version = read_u32(bytes, VERSION_OFFSET_BYTES);
+        if version != FORMAT_VERSION {
+            return Err(DiskManagerError::UnsupportedVersion(version));
+        }
+        let page_size = read_u32(bytes, PAGE_SIZE_OFFSET_BYTES) as usize;
+        if !is_valid_page_size(page_size) {
+            return Err(DiskManagerError::UnsupportedPageSize(page_size));
+        }
+
+        Ok(Self {
+            page_size,
+            next_page_id: read_u64(bytes, NEXT_PAGE_ID_OFFSET_BYTES),
+            free_list_head: read_page_id(bytes, FREE_LIST_HEAD_OFFSET_BYTES),
+            catalog_root_page_id: read_page_id(bytes, CATALOG_ROOT_OFFSET_BYTES),
+        })
+    }
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    // Offsets are all constants inside the superblock, so this can't fail
+    u32::from_be_bytes(bytes[offset..offset + U32_SIZE_BYTES].try_into().unwrap())
+}
+
+fn read_u64(bytes: &[u8], offset: usize) -> u64 {
+    u64::from_be_bytes(
+        bytes[offset..offset + PAGE_ID_SIZE_BYTES]
+            .try_into()
+            .unwrap(),
+    )
+}
+
+fn read_page_id(bytes: &[u8], offset: usize) -> Option<PageId> {
+    match read_u64(bytes, offset) {
+        INVALID_PAGE_ID => None,
+        page_id => Some(page_id),
+    }
+}
+
+fn write_u32(bytes: &mut [u8], offset: usize, value: u32) {
+    bytes[offset..offset + U32_SIZE_BYTES].copy_from_slice(&value.to_be_bytes());
+}
+
+fn write_page_id(bytes: &mut [u8], offset: usize, page_id: PageId) {
+    bytes[offset..offset + PAGE_ID_SIZE_BYTES].copy_from_slice(&page_id.to_be_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dbms::types::{DEFAULT_PAGE_SIZE, MAX_PAGE_SIZE};
+    use rstest::*;
+
+    #[rstest]
+    #[case(Superblock::new(DEFAULT_PAGE_SIZE))]
+    #[case(Superblock {
+        page_size: MAX_PAGE_SIZE,
+        next_page_id: 1 << 40,
+        free_list_head: Some(5),
+        catalog_root_page_id: Some(1),
+    })]
+    fn test_round_trip(#[case] superblock: Superblock) {
+        let bytes = superblock.to_bytes();
+
+        assert_eq!(Superblock::from_bytes(&bytes).unwrap(), superblock);
+    }
+
+    #[rstest]
+    fn test_layout() {
+        let superblock = Superblock {
+            page_size: 8192,
+            next_page_id: 3,
+            free_list_head: Some(2),
+            catalog_root_page_id: None,
+        };
+
+        let bytes = superblock.to_bytes();
+
+        assert_eq!(bytes[..8], *b"K2DBFILE");
+        assert_eq!(bytes[8..12], FORMAT_VERSION.to_be_bytes());
+        assert_eq!(bytes[12..16], 8192u32.to_be_bytes());
+        assert_eq!(bytes[16..24], 3u64.to_be_bytes());
+        assert_eq!(bytes[24..32], 2u64.to_be_bytes());
+        assert_eq!(bytes[32..40], INVALID_PAGE_ID.to_be_bytes());
+    }
+
+    #[rstest]
+    fn test_invalid_magic() {
+        let mut bytes = Superblock::new(DEFAULT_PAGE_SIZE).to_bytes();
+        bytes[0] = b'X';
+
+        assert!(matches!(
+            Superblock::from_bytes(&bytes),
+            Err(DiskManagerError::InvalidMagic)
+        ));
+    }
+
+    #[rstest]
+    #[case(0)]
+    #[case(FORMAT_VERSION + 1)]
+    fn test_unsupported_version(#[case] version: u32) {
+        let mut bytes = Superblock::new(DEFAULT_PAGE_SIZE).to_bytes();
+        write_u32(&mut bytes, VERSION_OFFSET_BYTES, version);
+
+        assert!(matches!(
+            Superblock::from_bytes(&bytes),
+            Err(DiskManagerError::UnsupportedVersion(v)) if v == version
+        ));
+    }
+
+    #[rstest]
+    #[case(0)]
+    #[case(DEFAULT_PAGE_SIZE / 2)]
+    #[case(DEFAULT_PAGE_SIZE + 1)]
+    #[case(MAX_PAGE_SIZE * 2)]
+    fn test_unsupported_page_size(#[case] page_size: usize) {
+        let mut bytes = Superblock::new(DEFAULT_PAGE_SIZE).to_bytes();
+        write_u32(&mut bytes, PAGE_SIZE_OFFSET_BYTES, page_size as u32);
+
+        assert!(matches!(
+            Superblock::from_bytes(&bytes),
+            Err(DiskManagerError::UnsupportedPageSize(size)) if size == page_size
+        ));
+    }
+
+    #[rstest]
+    fn test_magic_checked_before_version() {
+        let bytes = [0u8; SUPERBLOCK_SIZE_BYTES];
+
+        assert!(matches!(
+            Superblock::from_bytes(&bytes),
+            Err(DiskManagerError::InvalidMagic)
+        ));
+    }
+}
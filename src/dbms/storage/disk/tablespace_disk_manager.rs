@@ -0,0 +1,553 @@
+use std::collections::btree_map::Entry;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::dbms::types::{
+    is_valid_page_size, make_page_id, page_file_id, page_number, FileId, PageData, PageId,
+    MAX_FILE_ID, MAX_PAGE_NUMBER, SYSTEM_FILE_ID,
+};
+
+use super::file_disk_manager::log_path;
+use super::{DiskManagerError, FileDiskManager, IDiskManager};
+
+/// A disk manager that keeps pages in any number of files in one directory,
+/// so each table or index can have a file of its own, which can be dropped,
+/// copied or moved without touching the others.
+///
+/// Each file is a database file in the same format as a `FileDiskManager`'s,
+/// named after its file ID, e.g. `3.db`, with its own superblock and free
+/// list. A page's ID is made up of its file's ID and its page number within
+/// that file. A file copied into another database's directory under the same
+/// file ID, if nothing there is using it, is part of that database when it's
+/// next opened. It can't be given a different ID, though: pages refer to
+/// each other by full page ID, e.g. an index's nodes, and those would still
+/// point into the file's old ID.
+///
+/// The system file, `0.db`, is always there. Pages are allocated in it
+/// unless another file is asked for, and it keeps the log. The other files
+/// are opened without logs. Every file has the system file's page size.
+pub struct TablespaceDiskManager {
+    dir: PathBuf,
+    files: BTreeMap<FileId, FileDiskManager>,
+}
+
+impl TablespaceDiskManager {
+    /// Open the database in the given directory, creating the directory and
+    /// the system file with the default page size if they don't exist yet
+    #[allow(dead_code)]
+    pub fn new(dir: impl AsRef<Path>) -> Result<Self, DiskManagerError> {
+        Self::open_dir(dir.as_ref(), None)
+    }
+
+    /// Open the database in the given directory, creating the directory and
+    /// the system file with the given page size if they don't exist yet. An
+    /// existing database must have been created with the same page size.
+    #[allow(dead_code)]
+    pub fn with_page_size(
+        dir: impl AsRef<Path>,
+        page_size: usize,
+    ) -> Result<Self, DiskManagerError> {
+        Self::open_dir(dir.as_ref(), Some(page_size))
+    }
+
+    fn open_dir(dir: &Path, page_size: Option<usize>) -> Result<Self, DiskManagerError> {
+        if let Some(page_size) = page_size.filter(|&size| !is_valid_page_size(size)) {
+            return Err(DiskManagerError::UnsupportedPageSize(page_size));
+        }
+        let dir = dir.to_path_buf();
+        std::fs::create_dir_all(&dir)?;
+
+        // The system file decides the page size of an existing database, so
+        // it's opened first
+        let system_path = Self::file_path_in(&dir, SYSTEM_FILE_ID);
+        let system_file = match page_size {
+            Some(page_size) => FileDiskManager::with_page_size(&system_path, page_size)?,
+            None => FileDiskManager::new(&system_path)?,
+        };
+        let page_size = system_file.file_page_size();
+
+        let mut files = BTreeMap::new();
+        files.insert(SYSTEM_FILE_ID, system_file);
+        for entry in std::fs::read_dir(&dir)? {
+            let path = entry?.path();
+            if let Some(file_id) = Self::parse_file_id(&path) {
+                if let Entry::Vacant(entry) = files.entry(file_id) {
+                    entry.insert(FileDiskManager::without_log(&path, page_size)?);
+                }
+            }
+        }
+
+        Ok(Self { dir, files })
+    }
+
+    /// IDs of every file in the database, in order
+    #[allow(dead_code)]
+    pub fn file_ids(&self) -> Vec<FileId> {
+        self.files.keys().copied().collect()
+    }
+
+    /// Where the given file is kept
+    #[allow(dead_code)]
+    pub fn file_path(&self, file_id: FileId) -> PathBuf {
+        Self::file_path_in(&self.dir, file_id)
+    }
+
+    fn file_path_in(dir: &Path, file_id: FileId) -> PathBuf {
+        dir.join(format!("{file_id}.db"))
+    }
+
+    /// The file ID a path in the database's directory is for, if it's one
+    /// of its files
+    fn parse_file_id(path: &Path) -> Option<FileId> {
+        if path.extension()? != "db" {
+            return None;
+        }
+        let stem = path.file_stem()?.to_str()?;
+        let file_id = stem.parse::<FileId>().ok()?;
+        // Anything that wouldn't be written back with the same name isn't
+        // one of ours, e.g. `007.db`
+        (file_id <= MAX_FILE_ID && stem == file_id.to_string()).then_some(file_id)
+    }
+
+    fn file(&self, file_id: FileId) -> Result<&FileDiskManager, DiskManagerError> {
+        self.files
+            .get(&file_id)
+            .ok_or(DiskManagerError::FileNotFound(file_id))
+    }
+
+    fn file_mut(&mut self, file_id: FileId) -> Result<&mut FileDiskManager, DiskManagerError> {
+        self.files
+            .get_mut(&file_id)
+            .ok_or(DiskManagerError::FileNotFound(file_id))
+    }
+
+    fn system_file(&self) -> &FileDiskManager {
+        &self.files[&SYSTEM_FILE_ID]
+    }
+
+    fn system_file_mut(&mut self) -> &mut FileDiskManager {
+        self.files.get_mut(&SYSTEM_FILE_ID).unwrap()
+    }
+}
+
+impl IDiskManager for TablespaceDiskManager {
+    fn page_size(&self) -> usize {
+        self.system_file().page_size()
+    }
+
+    fn write_page(&mut self, page_id: PageId, page: &[u8]) -> Result<(), DiskManagerError> {
+        self.file_mut(page_file_id(page_id))?
+            .write_page(page_number(page_id), page)
+    }
+
+    fn read_page(&self, page_id: PageId) -> Result<PageData, DiskManagerError> {
+        self.file(page_file_id(page_id))?
+            .read_page(page_number(page_id))
+    }
+
+    fn write_log(&mut self, log: &[u8]) -> Result<(), DiskManagerError> {
+        self.system_file_mut().write_log(log)
+    }
+
+    fn read_log(&self, size: usize, offset: usize) -> Result<PageData, DiskManagerError> {
+        self.system_file().read_log(size, offset)
+    }
+
+    fn sync_log(&mut self) -> Result<(), DiskManagerError> {
+        self.system_file_mut().sync_log()
+    }
+
+    fn sync_pages(&mut self) -> Result<(), DiskManagerError> {
+        for file in self.files.values_mut() {
+            file.sync_pages()?;
+        }
+        Ok(())
+    }
+
+    fn allocate_page(&mut self) -> Result<PageId, DiskManagerError> {
+        self.allocate_page_in(SYSTEM_FILE_ID)
+    }
+
+    fn deallocate_page(&mut self, page_id: PageId) -> Result<(), DiskManagerError> {
+        // Pages in files that aren't there aren't allocated either
+        match self.files.get_mut(&page_file_id(page_id)) {
+            Some(file) => file.deallocate_page(page_number(page_id)),
+            None => Ok(()),
+        }
+    }
+
+    fn allocate_page_in(&mut self, file_id: FileId) -> Result<PageId, DiskManagerError> {
+        let file = self.file_mut(file_id)?;
+        let page_number = file.allocate_page()?;
+        if page_number > MAX_PAGE_NUMBER {
+            // Its ID would spill over into the next file's
+            file.deallocate_page(page_number)?;
+            return Err(DiskManagerError::FileFull(file_id));
+        }
+        Ok(make_page_id(file_id, page_number))
+    }
+
+    fn create_file(&mut self) -> Result<FileId, DiskManagerError> {
+        let file_id = (SYSTEM_FILE_ID + 1..=MAX_FILE_ID)
+            .find(|file_id| !self.files.contains_key(file_id))
+            .ok_or(DiskManagerError::TooManyFiles)?;
+        let file = FileDiskManager::without_log(
+            self.file_path(file_id),
+            self.system_file().file_page_size(),
+        )?;
+        self.files.insert(file_id, file);
+        Ok(file_id)
+    }
+
+    fn drop_file(&mut self, file_id: FileId) -> Result<(), DiskManagerError> {
+        if file_id == SYSTEM_FILE_ID {
+            return Err(DiskManagerError::SystemFile);
+        }
+        // Closed before it's deleted
+        drop(
+            self.files
+                .remove(&file_id)
+                .ok_or(DiskManagerError::FileNotFound(file_id))?,
+        );
+
+        let path = self.file_path(file_id);
+        std::fs::remove_file(&path)?;
+        // Only there if the file was ever opened with a log outside the
+        // tablespace, so it's fine for it to be missing
+        match std::fs::remove_file(log_path(&path)) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dbms::buffer::pool_manager::{
+        BufferPoolManager, BufferPoolManagerError, IBufferPoolManager,
+    };
+    use crate::dbms::buffer::replacer::clock_replacer::ClockReplacer;
+    use crate::dbms::storage::disk::file_disk_manager::usable_page_size;
+    use crate::dbms::types::DEFAULT_PAGE_SIZE;
+    use rstest::*;
+    use tempfile::tempdir;
+
+    /// Size of the pages in a tablespace with the default page size
+    const PAGE_SIZE: usize = usable_page_size(DEFAULT_PAGE_SIZE);
+
+    #[rstest]
+    fn test_new_database_has_system_file() {
+        let dir = tempdir().unwrap();
+        let db_dir = dir.path().join("db");
+
+        let disk_manager = TablespaceDiskManager::new(&db_dir).unwrap();
+
+        assert_eq!(disk_manager.file_ids(), vec![SYSTEM_FILE_ID]);
+        assert!(db_dir.join("0.db").exists());
+    }
+
+    #[rstest]
+    fn test_pages_in_different_files() {
+        let dir = tempdir().unwrap();
+        let mut disk_manager = TablespaceDiskManager::new(dir.path()).unwrap();
+        let file_id = disk_manager.create_file().unwrap();
+        assert_eq!(file_id, 1);
+
+        let system_page_id = disk_manager.allocate_page().unwrap();
+        let page_id = disk_manager.allocate_page_in(file_id).unwrap();
+        disk_manager
+            .write_page(system_page_id, &[1u8; PAGE_SIZE])
+            .unwrap();
+        disk_manager.write_page(page_id, &[2u8; PAGE_SIZE]).unwrap();
+
+        // Both files number their pages from the start
+        assert_eq!(page_number(system_page_id), page_number(page_id));
+        assert_eq!(page_file_id(system_page_id), SYSTEM_FILE_ID);
+        assert_eq!(page_file_id(page_id), file_id);
+        assert_eq!(
+            disk_manager.read_page(system_page_id).unwrap(),
+            [1u8; PAGE_SIZE]
+        );
+        assert_eq!(disk_manager.read_page(page_id).unwrap(), [2u8; PAGE_SIZE]);
+    }
+
+    #[rstest]
+    fn test_files_persist_across_reopen() {
+        let dir = tempdir().unwrap();
+
+        let page_ids = {
+            let mut disk_manager = TablespaceDiskManager::new(dir.path()).unwrap();
+            let page_ids = (0..3)
+                .map(|i| {
+                    let file_id = disk_manager.create_file().unwrap();
+                    let page_id = disk_manager.allocate_page_in(file_id).unwrap();
+                    disk_manager.write_page(page_id, &[i; PAGE_SIZE]).unwrap();
+                    page_id
+                })
+                .collect::<Vec<_>>();
+            disk_manager.write_log(&[1, 2, 3]).unwrap();
+            page_ids
+        };
+
+        let mut disk_manager = TablespaceDiskManager::new(dir.path()).unwrap();
+        assert_eq!(disk_manager.file_ids(), vec![0, 1, 2, 3]);
+        for (i, page_id) in page_ids.into_iter().enumerate() {
+            assert_eq!(
+                disk_manager.read_page(page_id).unwrap(),
+                [i as u8; PAGE_SIZE]
+            );
+        }
+        assert_eq!(disk_manager.read_log(3, 0).unwrap()[..3], [1, 2, 3]);
+        assert_eq!(disk_manager.create_file().unwrap(), 4);
+    }
+
+    #[rstest]
+    fn test_drop_file() {
+        let dir = tempdir().unwrap();
+        let mut disk_manager = TablespaceDiskManager::new(dir.path()).unwrap();
+        let dropped = disk_manager.create_file().unwrap();
+        let kept = disk_manager.create_file().unwrap();
+        let dropped_page_id = disk_manager.allocate_page_in(dropped).unwrap();
+        let kept_page_id = disk_manager.allocate_page_in(kept).unwrap();
+        disk_manager
+            .write_page(kept_page_id, &[5u8; PAGE_SIZE])
+            .unwrap();
+        // Only the system file has a log
+        assert!(!dir.path().join("1.db.log").exists());
+
+        disk_manager.drop_file(dropped).unwrap();
+
+        assert!(!disk_manager.file_path(dropped).exists());
+        assert!(matches!(
+            disk_manager.read_page(dropped_page_id),
+            Err(DiskManagerError::FileNotFound(1))
+        ));
+        assert!(matches!(
+            disk_manager.allocate_page_in(dropped),
+            Err(DiskManagerError::FileNotFound(1))
+        ));
+        // Deallocating a page in a dropped file does nothing, like any other
+        // page that isn't allocated
+        disk_manager.deallocate_page(dropped_page_id).unwrap();
+        assert_eq!(
+            disk_manager.read_page(kept_page_id).unwrap(),
+            [5u8; PAGE_SIZE]
+        );
+
+        // The dropped file's ID is free to use again, for an empty file
+        assert_eq!(disk_manager.create_file().unwrap(), dropped);
+        assert!(matches!(
+            disk_manager.read_page(dropped_page_id),
+            Err(DiskManagerError::PageNotFound)
+        ));
+    }
+
+    #[rstest]
+    fn test_drop_file_with_leftover_log() {
+        let dir = tempdir().unwrap();
+        let mut disk_manager = TablespaceDiskManager::new(dir.path()).unwrap();
+        let file_id = disk_manager.create_file().unwrap();
+        // Left behind by opening the file on its own, outside the tablespace
+        std::fs::write(dir.path().join("1.db.log"), [1, 2, 3]).unwrap();
+
+        disk_manager.drop_file(file_id).unwrap();
+
+        assert!(!disk_manager.file_path(file_id).exists());
+        assert!(!dir.path().join("1.db.log").exists());
+        assert!(dir.path().join("0.db.log").exists());
+    }
+
+    #[rstest]
+    #[case::system_file(SYSTEM_FILE_ID)]
+    #[case::missing_file(3)]
+    fn test_drop_file_not_droppable(#[case] file_id: FileId) {
+        let dir = tempdir().unwrap();
+        let mut disk_manager = TablespaceDiskManager::new(dir.path()).unwrap();
+        disk_manager.create_file().unwrap();
+
+        let result = disk_manager.drop_file(file_id);
+        if file_id == SYSTEM_FILE_ID {
+            assert!(matches!(result, Err(DiskManagerError::SystemFile)));
+        } else {
+            assert!(matches!(result, Err(DiskManagerError::FileNotFound(3))));
+        }
+        assert_eq!(disk_manager.file_ids(), vec![0, 1]);
+    }
+
+    #[rstest]
+    fn test_copied_file_attached_on_open() {
+        let dir = tempdir().unwrap();
+        let (source_dir, target_dir) = (dir.path().join("source"), dir.path().join("target"));
+
+        let page_id = {
+            let mut disk_manager = TablespaceDiskManager::new(&source_dir).unwrap();
+            let file_id = disk_manager.create_file().unwrap();
+            let page_id = disk_manager.allocate_page_in(file_id).unwrap();
+            disk_manager.write_page(page_id, &[7u8; PAGE_SIZE]).unwrap();
+            page_id
+        };
+        TablespaceDiskManager::new(&target_dir).unwrap();
+        // Under the same file ID, so its page IDs stay the same
+        std::fs::copy(source_dir.join("1.db"), target_dir.join("1.db")).unwrap();
+
+        let disk_manager = TablespaceDiskManager::new(&target_dir).unwrap();
+        assert_eq!(disk_manager.file_ids(), vec![0, 1]);
+        assert_eq!(disk_manager.read_page(page_id).unwrap(), [7u8; PAGE_SIZE]);
+    }
+
+    #[rstest]
+    fn test_page_size_chosen_at_creation() {
+        let dir = tempdir().unwrap();
+        let page_size = 16384;
+        let page_id = {
+            let mut disk_manager =
+                TablespaceDiskManager::with_page_size(dir.path(), page_size).unwrap();
+            let file_id = disk_manager.create_file().unwrap();
+            let page_id = disk_manager.allocate_page_in(file_id).unwrap();
+            disk_manager
+                .write_page(page_id, &vec![3u8; usable_page_size(page_size)])
+                .unwrap();
+            page_id
+        };
+
+        // Reopened with the page size the system file was created with
+        let disk_manager = TablespaceDiskManager::new(dir.path()).unwrap();
+        assert_eq!(disk_manager.page_size(), usable_page_size(page_size));
+        assert_eq!(
+            disk_manager.read_page(page_id).unwrap(),
+            vec![3u8; usable_page_size(page_size)]
+        );
+        drop(disk_manager);
+        assert!(matches!(
+            TablespaceDiskManager::with_page_size(dir.path(), DEFAULT_PAGE_SIZE),
+            Err(DiskManagerError::PageSizeMismatch(size)) if size == page_size
+        ));
+    }
+
+    #[rstest]
+    fn test_copied_file_with_different_page_size() {
+        let dir = tempdir().unwrap();
+        let (source_dir, target_dir) = (dir.path().join("source"), dir.path().join("target"));
+        TablespaceDiskManager::with_page_size(&source_dir, 8192)
+            .unwrap()
+            .create_file()
+            .unwrap();
+        TablespaceDiskManager::new(&target_dir).unwrap();
+        std::fs::copy(source_dir.join("1.db"), target_dir.join("1.db")).unwrap();
+
+        let result = TablespaceDiskManager::new(&target_dir);
+        assert!(matches!(
+            result,
+            Err(DiskManagerError::PageSizeMismatch(8192))
+        ));
+    }
+
+    #[rstest]
+    fn test_unsupported_page_size() {
+        let dir = tempdir().unwrap();
+        let db_dir = dir.path().join("db");
+
+        let result = TablespaceDiskManager::with_page_size(&db_dir, 5000);
+        assert!(matches!(
+            result,
+            Err(DiskManagerError::UnsupportedPageSize(5000))
+        ));
+        assert!(!db_dir.exists());
+    }
+
+    #[rstest]
+    #[case::not_a_database("notes.txt")]
+    #[case::leading_zero("01.db")]
+    #[case::out_of_range("255.db")]
+    #[case::not_a_number("main.db")]
+    fn test_other_files_ignored(#[case] name: &str) {
+        let dir = tempdir().unwrap();
+        std::fs::write(dir.path().join(name), b"not a database").unwrap();
+
+        let disk_manager = TablespaceDiskManager::new(dir.path()).unwrap();
+        assert_eq!(disk_manager.file_ids(), vec![SYSTEM_FILE_ID]);
+    }
+
+    #[rstest]
+    fn test_open_invalid_file() {
+        let dir = tempdir().unwrap();
+        std::fs::write(dir.path().join("2.db"), b"not a database").unwrap();
+
+        let result = TablespaceDiskManager::new(dir.path());
+        assert!(matches!(result, Err(DiskManagerError::InvalidMagic)));
+    }
+
+    #[rstest]
+    fn test_buffer_pool_caches_pages_from_many_files() {
+        let dir = tempdir().unwrap();
+        let disk_manager = TablespaceDiskManager::new(dir.path()).unwrap();
+        let buffer_pool_manager =
+            BufferPoolManager::new(4, Box::new(ClockReplacer::new(4)), Box::new(disk_manager));
+
+        let file_ids = (0..3)
+            .map(|_| buffer_pool_manager.create_file().unwrap())
+            .collect::<Vec<_>>();
+        let mut page_ids = Vec::new();
+        for (i, &file_id) in file_ids.iter().cycle().take(12).enumerate() {
+            let mut page = buffer_pool_manager.new_page_in(file_id).unwrap();
+            let page_id = page.get_page_id().unwrap().unwrap();
+            page.write_data(0, &[i as u8]).unwrap();
+            drop(page);
+            buffer_pool_manager.unpin_page(page_id, true).unwrap();
+            page_ids.push(page_id);
+        }
+
+        // More pages than the pool holds, so most were written out and read
+        // back in from their own files
+        for (i, &page_id) in page_ids.iter().enumerate() {
+            assert_eq!(page_file_id(page_id), file_ids[i % 3]);
+            let page = buffer_pool_manager.fetch_page(page_id).unwrap();
+            assert_eq!(page.get_data().unwrap()[0], i as u8);
+            drop(page);
+            buffer_pool_manager.unpin_page(page_id, false).unwrap();
+        }
+
+        // Dropping a file drops its pages from the pool too
+        buffer_pool_manager.drop_file(file_ids[0]).unwrap();
+        assert!(matches!(
+            buffer_pool_manager.fetch_page(page_ids[0]),
+            Err(BufferPoolManagerError::DiskManagerError(
+                DiskManagerError::FileNotFound(_)
+            ))
+        ));
+        assert!(matches!(
+            buffer_pool_manager.fetch_page(page_ids[1]),
+            Ok(page) if page.get_data().unwrap()[0] == 1
+        ));
+    }
+
+    #[rstest]
+    fn test_buffer_pool_drop_file_in_use() {
+        let dir = tempdir().unwrap();
+        let disk_manager = TablespaceDiskManager::new(dir.path()).unwrap();
+        let buffer_pool_manager =
+            BufferPoolManager::new(4, Box::new(ClockReplacer::new(4)), Box::new(disk_manager));
+        let file_id = buffer_pool_manager.create_file().unwrap();
+        let page = buffer_pool_manager.new_page_in(file_id).unwrap();
+        let page_id = page.get_page_id().unwrap().unwrap();
+        drop(page);
+
+        assert!(matches!(
+            buffer_pool_manager.drop_file(file_id),
+            Err(BufferPoolManagerError::PageInUse)
+        ));
+        assert!(matches!(
+            buffer_pool_manager.drop_file(SYSTEM_FILE_ID),
+            Err(BufferPoolManagerError::DiskManagerError(
+                DiskManagerError::SystemFile
+            ))
+        ));
+
+        // Once it's not in use it can be dropped, without being written out
+        buffer_pool_manager.unpin_page(page_id, true).unwrap();
+        buffer_pool_manager.drop_file(file_id).unwrap();
+        buffer_pool_manager.flush_all_pages().unwrap();
+        assert!(!dir.path().join("1.db").exists());
+    }
+}
@@ -1,72 +1,361 @@
 use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
 
-use crate::dbms::types::{PageData, PageId, PAGE_SIZE};
+use crate::dbms::types::{PageData, PageId, DEFAULT_PAGE_SIZE};
 
 use super::{DiskManagerError, IDiskManager};
 
 /// A purely in-memory implementation of the DiskManager trait for testing purposes.
 /// Also exposes the underlying data structures for inspection in tests.
+#[derive(Clone)]
 pub struct InMemoryDiskManager {
     /// page_id -> page_data
     pub pages: HashMap<PageId, Vec<u8>>,
-    /// log_id -> log_data
-    pub logs: HashMap<usize, Vec<u8>>,
+    /// Append-only log
+    pub log: Vec<u8>,
     pub next_page_id: PageId,
+    /// Deallocated page IDs, handed out again last in first out
+    pub free_list: Vec<PageId>,
+    pub page_size: usize,
 }
 
 impl InMemoryDiskManager {
     #[cfg(test)]
     pub fn new() -> Self {
+        Self::with_page_size(DEFAULT_PAGE_SIZE)
+    }
+
+    #[cfg(test)]
+    pub fn with_page_size(page_size: usize) -> Self {
         Self {
             pages: HashMap::new(),
-            logs: HashMap::new(),
+            log: Vec::new(),
             next_page_id: 0,
+            free_list: Vec::new(),
+            page_size,
         }
     }
 }
 
 impl IDiskManager for InMemoryDiskManager {
+    fn page_size(&self) -> usize {
+        self.page_size
+    }
+
     fn write_page(&mut self, page_id: PageId, page: &[u8]) -> Result<(), DiskManagerError> {
         // Must allocate page before writing to it
         if !self.pages.contains_key(&page_id) {
             return Err(DiskManagerError::PageNotFound);
         }
+        if page.len() != self.page_size {
+            return Err(DiskManagerError::InvalidPageSize(page.len()));
+        }
         self.pages.insert(page_id, page.to_vec());
         Ok(())
     }
 
     fn read_page(&self, page_id: PageId) -> Result<PageData, DiskManagerError> {
-        let page = match self.pages.get(&page_id) {
-            Some(page) => page,
-            None => return Err(DiskManagerError::PageNotFound),
-        };
-        let mut page_data = [0u8; PAGE_SIZE];
-        page_data.copy_from_slice(page);
-        Ok(page_data)
+        match self.pages.get(&page_id) {
+            Some(page) => Ok(page.clone()),
+            None => Err(DiskManagerError::PageNotFound),
+        }
+    }
+
+    fn write_log(&mut self, log: &[u8]) -> Result<(), DiskManagerError> {
+        self.log.extend_from_slice(log);
+        Ok(())
+    }
+
+    fn read_log(&self, size: usize, offset: usize) -> Result<PageData, DiskManagerError> {
+        if size > self.page_size {
+            return Err(DiskManagerError::InvalidLogReadSize(size));
+        }
+        if offset >= self.log.len() {
+            return Err(DiskManagerError::LogOffsetOutOfRange(offset));
+        }
+
+        let end = usize::min(offset + size, self.log.len());
+        let mut log_data = vec![0u8; self.page_size];
+        log_data[..end - offset].copy_from_slice(&self.log[offset..end]);
+        Ok(log_data)
     }
 
-    fn write_log(&mut self, _log: &[u8]) -> Result<(), DiskManagerError> {
-        todo!()
+    fn sync_log(&mut self) -> Result<(), DiskManagerError> {
+        // Nothing to make durable in memory
+        Ok(())
     }
 
-    fn read_log(&self, _size: usize, _offset: usize) -> Result<PageData, DiskManagerError> {
-        todo!()
+    fn sync_pages(&mut self) -> Result<(), DiskManagerError> {
+        Ok(())
     }
 
     fn allocate_page(&mut self) -> Result<PageId, DiskManagerError> {
-        let page_id = self.next_page_id;
-        self.next_page_id += 1;
-        self.pages.insert(page_id, vec![0u8; PAGE_SIZE]);
+        let page_id = match self.free_list.pop() {
+            Some(page_id) => page_id,
+            None => {
+                self.next_page_id += 1;
+                self.next_page_id - 1
+            }
+        };
+        self.pages.insert(page_id, vec![0u8; self.page_size]);
         Ok(page_id)
     }
 
     fn deallocate_page(&mut self, page_id: PageId) -> Result<(), DiskManagerError> {
-        self.pages.remove(&page_id);
+        if self.pages.remove(&page_id).is_some() {
+            self.free_list.push(page_id);
+        }
         Ok(())
     }
 }
 
+/// A disk manager operation that a fault can be injected into
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DiskOperation {
+    ReadPage,
+    WritePage,
+    ReadLog,
+    WriteLog,
+    SyncLog,
+    SyncPages,
+    AllocatePage,
+    DeallocatePage,
+}
+
+/// What goes wrong with an operation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fault {
+    /// Fail with an I/O error, without doing anything
+    Fail,
+    /// Only read or write the first given number of bytes, but report success.
+    /// A short page read zeroes the rest of the page, and a torn page write
+    /// leaves the rest of the page as it was. Operations without any data to
+    /// cut short just fail.
+    Partial(usize),
+    /// Only write the first given number of bytes, then fail with an I/O
+    /// error. Reads, and operations without any data to cut short, just fail.
+    PartialFail(usize),
+}
+
+struct FaultPlan<D> {
+    /// How many times each operation has been called
+    counts: HashMap<DiskOperation, usize>,
+    /// Faults to inject, by operation and which call of it to inject into
+    faults: HashMap<(DiskOperation, usize), Fault>,
+    /// The inner disk manager as it was when the log was last synced
+    synced: D,
+}
+
+/// Scripts the faults a `FaultInjectingDiskManager` injects. It's shared with
+/// the disk manager, so faults can still be scripted once the disk manager has
+/// been handed to a buffer pool.
+pub struct FaultInjector<D> {
+    plan: Arc<Mutex<FaultPlan<D>>>,
+}
+
+impl<D> Clone for FaultInjector<D> {
+    fn clone(&self) -> Self {
+        Self {
+            plan: self.plan.clone(),
+        }
+    }
+}
+
+impl<D: Clone> FaultInjector<D> {
+    /// Inject a fault into the `n`th call of an operation from now, counting
+    /// from 1
+    pub fn inject(&self, operation: DiskOperation, n: usize, fault: Fault) {
+        assert!(n > 0, "calls are counted from 1");
+        let mut plan = self.plan.lock().unwrap();
+        let call = plan.counts.get(&operation).copied().unwrap_or(0) + n;
+        plan.faults.insert((operation, call), fault);
+    }
+
+    /// Fail the `n`th page write from now
+    pub fn fail_write(&self, n: usize) {
+        self.inject(DiskOperation::WritePage, n, Fault::Fail);
+    }
+
+    /// Fail the `n`th page read from now
+    pub fn fail_read(&self, n: usize) {
+        self.inject(DiskOperation::ReadPage, n, Fault::Fail);
+    }
+
+    /// Only write the first `len` bytes of the `n`th page write from now
+    pub fn tear_write(&self, n: usize, len: usize) {
+        self.inject(DiskOperation::WritePage, n, Fault::Partial(len));
+    }
+
+    /// Only read the first `len` bytes of the `n`th page read from now
+    pub fn short_read(&self, n: usize, len: usize) {
+        self.inject(DiskOperation::ReadPage, n, Fault::Partial(len));
+    }
+
+    /// Forget about any faults that haven't been injected yet
+    pub fn clear(&self) {
+        self.plan.lock().unwrap().faults.clear();
+    }
+
+    /// How many times an operation has been called, including calls that
+    /// had a fault injected
+    pub fn count(&self, operation: DiskOperation) -> usize {
+        let plan = self.plan.lock().unwrap();
+        plan.counts.get(&operation).copied().unwrap_or(0)
+    }
+
+    /// What would survive a crash right now: the inner disk manager as it was
+    /// when it was last synced, dropping everything written since.
+    ///
+    /// The inner disk manager can only be kept as a whole, so `sync_log` and
+    /// `sync_pages` both make everything written so far durable, pages and
+    /// log alike.
+    pub fn crash(&self) -> D {
+        self.plan.lock().unwrap().synced.clone()
+    }
+}
+
+/// A disk manager for testing how failures are handled, that passes operations
+/// on to another disk manager but can be scripted to fail them, cut reads and
+/// writes short, or lose whatever hasn't been synced.
+pub struct FaultInjectingDiskManager<D> {
+    inner: D,
+    injector: FaultInjector<D>,
+}
+
+impl<D: IDiskManager + Clone> FaultInjectingDiskManager<D> {
+    #[cfg(test)]
+    pub fn new(inner: D) -> Self {
+        let plan = FaultPlan {
+            counts: HashMap::new(),
+            faults: HashMap::new(),
+            synced: inner.clone(),
+        };
+        Self {
+            inner,
+            injector: FaultInjector {
+                plan: Arc::new(Mutex::new(plan)),
+            },
+        }
+    }
+
+    /// Get a handle to script the faults this disk manager injects
+    pub fn injector(&self) -> FaultInjector<D> {
+        self.injector.clone()
+    }
+
+    /// Count a call of an operation, and get the fault to inject into it, if
+    /// there is one
+    fn next_fault(&self, operation: DiskOperation) -> Option<Fault> {
+        let mut plan = self.injector.plan.lock().unwrap();
+        let count = plan.counts.entry(operation).or_insert(0);
+        *count += 1;
+        let call = *count;
+        plan.faults.remove(&(operation, call))
+    }
+
+    /// Write only the first `len` bytes of a page, leaving the rest as it was
+    fn write_torn_page(
+        &mut self,
+        page_id: PageId,
+        page: &[u8],
+        len: usize,
+    ) -> Result<(), DiskManagerError> {
+        let mut torn_page = self.inner.read_page(page_id)?;
+        let len = usize::min(len, page.len());
+        torn_page[..len].copy_from_slice(&page[..len]);
+        self.inner.write_page(page_id, &torn_page)
+    }
+
+    /// Fail an operation if a fault is injected into it, otherwise carry on
+    fn check_fault(&self, operation: DiskOperation) -> Result<(), DiskManagerError> {
+        match self.next_fault(operation) {
+            Some(_) => Err(injected_error(operation)),
+            None => Ok(()),
+        }
+    }
+}
+
+fn injected_error(operation: DiskOperation) -> DiskManagerError {
+    DiskManagerError::IoError(std::io::Error::other(format!(
+        "injected fault in {operation:?}"
+    )))
+}
+
+impl<D: IDiskManager + Clone> IDiskManager for FaultInjectingDiskManager<D> {
+    fn page_size(&self) -> usize {
+        self.inner.page_size()
+    }
+
+    fn write_page(&mut self, page_id: PageId, page: &[u8]) -> Result<(), DiskManagerError> {
+        match self.next_fault(DiskOperation::WritePage) {
+            None => self.inner.write_page(page_id, page),
+            Some(Fault::Fail) => Err(injected_error(DiskOperation::WritePage)),
+            Some(Fault::Partial(len)) => self.write_torn_page(page_id, page, len),
+            Some(Fault::PartialFail(len)) => {
+                self.write_torn_page(page_id, page, len)?;
+                Err(injected_error(DiskOperation::WritePage))
+            }
+        }
+    }
+
+    fn read_page(&self, page_id: PageId) -> Result<PageData, DiskManagerError> {
+        match self.next_fault(DiskOperation::ReadPage) {
+            None => self.inner.read_page(page_id),
+            Some(Fault::Fail | Fault::PartialFail(_)) => {
+                Err(injected_error(DiskOperation::ReadPage))
+            }
+            Some(Fault::Partial(len)) => {
+                let mut page = self.inner.read_page(page_id)?;
+                page[usize::min(len, self.inner.page_size())..].fill(0);
+                Ok(page)
+            }
+        }
+    }
+
+    fn write_log(&mut self, log: &[u8]) -> Result<(), DiskManagerError> {
+        match self.next_fault(DiskOperation::WriteLog) {
+            None => self.inner.write_log(log),
+            Some(Fault::Fail) => Err(injected_error(DiskOperation::WriteLog)),
+            Some(Fault::Partial(len)) => self.inner.write_log(&log[..usize::min(len, log.len())]),
+            Some(Fault::PartialFail(len)) => {
+                self.inner.write_log(&log[..usize::min(len, log.len())])?;
+                Err(injected_error(DiskOperation::WriteLog))
+            }
+        }
+    }
+
+    fn read_log(&self, size: usize, offset: usize) -> Result<PageData, DiskManagerError> {
+        self.check_fault(DiskOperation::ReadLog)?;
+        self.inner.read_log(size, offset)
+    }
+
+    fn sync_log(&mut self) -> Result<(), DiskManagerError> {
+        self.check_fault(DiskOperation::SyncLog)?;
+        self.inner.sync_log()?;
+        self.injector.plan.lock().unwrap().synced = self.inner.clone();
+        Ok(())
+    }
+
+    fn sync_pages(&mut self) -> Result<(), DiskManagerError> {
+        self.check_fault(DiskOperation::SyncPages)?;
+        self.inner.sync_pages()?;
+        self.injector.plan.lock().unwrap().synced = self.inner.clone();
+        Ok(())
+    }
+
+    fn allocate_page(&mut self) -> Result<PageId, DiskManagerError> {
+        self.check_fault(DiskOperation::AllocatePage)?;
+        self.inner.allocate_page()
+    }
+
+    fn deallocate_page(&mut self, page_id: PageId) -> Result<(), DiskManagerError> {
+        self.check_fault(DiskOperation::DeallocatePage)?;
+        self.inner.deallocate_page(page_id)
+    }
+}
+
 #[cfg(test)]
+#[allow(clippy::unnecessary_get_then_check)]
 mod tests {
     use super::*;
     use rstest::*;
@@ -75,7 +364,7 @@ mod tests {
     fn test_write_page() {
         let mut disk_manager = InMemoryDiskManager::new();
         let page_id = disk_manager.allocate_page().unwrap();
-        let page = [1u8; PAGE_SIZE];
+        let page = [1u8; DEFAULT_PAGE_SIZE];
         disk_manager.write_page(page_id, &page).unwrap();
         assert_eq!(disk_manager.pages.get(&page_id).unwrap(), &page);
     }
@@ -84,7 +373,7 @@ mod tests {
     fn test_write_page_nonexistent() {
         let mut disk_manager = InMemoryDiskManager::new();
         let page_id = disk_manager.allocate_page().unwrap();
-        let page = [1u8; PAGE_SIZE];
+        let page = [1u8; DEFAULT_PAGE_SIZE];
         let result = disk_manager.write_page(page_id + 1, &page);
         assert!(result.is_err());
     }
@@ -93,7 +382,7 @@ mod tests {
     fn test_read_page() {
         let mut disk_manager = InMemoryDiskManager::new();
         let page_id = disk_manager.allocate_page().unwrap();
-        let page = [1u8; PAGE_SIZE];
+        let page = [1u8; DEFAULT_PAGE_SIZE];
         disk_manager.write_page(page_id, &page).unwrap();
         let read_page = disk_manager.read_page(page_id).unwrap();
         assert_eq!(read_page, page);
@@ -142,19 +431,289 @@ mod tests {
     }
 
     #[rstest]
-    fn test_deallocated_page_ids_not_reused() {
+    fn test_deallocated_page_ids_reused() {
         let mut disk_manager = InMemoryDiskManager::new();
         disk_manager.allocate_page().unwrap();
         disk_manager.allocate_page().unwrap();
         disk_manager.allocate_page().unwrap();
-        assert_eq!(disk_manager.pages.len(), 3);
+        disk_manager
+            .write_page(1, &[1u8; DEFAULT_PAGE_SIZE])
+            .unwrap();
         disk_manager.deallocate_page(1).unwrap();
-        assert_eq!(disk_manager.pages.len(), 2);
-        assert!(disk_manager.pages.get(&3).is_none());
+        assert_eq!(disk_manager.free_list, vec![1]);
+
         let page_id = disk_manager.allocate_page().unwrap();
-        assert_eq!(page_id, 3);
+        assert_eq!(page_id, 1);
         assert_eq!(disk_manager.pages.len(), 3);
-        assert!(disk_manager.pages.get(&3).is_some());
-        assert!(disk_manager.pages.get(&1).is_none());
+        assert!(disk_manager.free_list.is_empty());
+        // Reused pages are zeroed like new ones
+        assert_eq!(disk_manager.read_page(1).unwrap(), [0u8; DEFAULT_PAGE_SIZE]);
+
+        // Once the free list is empty, new IDs carry on from the last one
+        assert_eq!(disk_manager.allocate_page().unwrap(), 3);
+    }
+
+    #[rstest]
+    fn test_deallocated_page_ids_reused_last_first() {
+        let mut disk_manager = InMemoryDiskManager::new();
+        for _ in 0..5 {
+            disk_manager.allocate_page().unwrap();
+        }
+        disk_manager.deallocate_page(1).unwrap();
+        disk_manager.deallocate_page(3).unwrap();
+        // Deallocating twice, or a page that was never allocated, does nothing
+        disk_manager.deallocate_page(3).unwrap();
+        disk_manager.deallocate_page(9).unwrap();
+
+        assert_eq!(disk_manager.allocate_page().unwrap(), 3);
+        assert_eq!(disk_manager.allocate_page().unwrap(), 1);
+        assert_eq!(disk_manager.allocate_page().unwrap(), 5);
+    }
+
+    #[rstest]
+    fn test_write_and_read_log() {
+        let mut disk_manager = InMemoryDiskManager::new();
+        disk_manager.write_log(&[1, 2, 3]).unwrap();
+        disk_manager.write_log(&[4, 5]).unwrap();
+        assert_eq!(disk_manager.log, vec![1, 2, 3, 4, 5]);
+
+        let log_data = disk_manager.read_log(3, 1).unwrap();
+        assert_eq!(log_data[..3], [2, 3, 4]);
+        assert_eq!(log_data[3..], [0u8; DEFAULT_PAGE_SIZE - 3]);
+    }
+
+    #[rstest]
+    fn test_read_log_past_end() {
+        let mut disk_manager = InMemoryDiskManager::new();
+        disk_manager.write_log(&[1, 2, 3]).unwrap();
+
+        // Reads running off the end of the log are zero-padded
+        let log_data = disk_manager.read_log(10, 1).unwrap();
+        assert_eq!(log_data[..2], [2, 3]);
+        assert_eq!(log_data[2..], [0u8; DEFAULT_PAGE_SIZE - 2]);
+
+        // Reads starting at or after the end of the log are an error
+        assert!(matches!(
+            disk_manager.read_log(1, 3),
+            Err(DiskManagerError::LogOffsetOutOfRange(3))
+        ));
+        assert!(matches!(
+            disk_manager.read_log(1, 10),
+            Err(DiskManagerError::LogOffsetOutOfRange(10))
+        ));
+    }
+
+    #[rstest]
+    fn test_read_log_too_large() {
+        let mut disk_manager = InMemoryDiskManager::new();
+        disk_manager
+            .write_log(&[1u8; DEFAULT_PAGE_SIZE * 2])
+            .unwrap();
+
+        let result = disk_manager.read_log(DEFAULT_PAGE_SIZE + 1, 0);
+        assert!(matches!(
+            result,
+            Err(DiskManagerError::InvalidLogReadSize(size)) if size == DEFAULT_PAGE_SIZE + 1
+        ));
+    }
+
+    fn create_faulty_disk_manager() -> (
+        FaultInjectingDiskManager<InMemoryDiskManager>,
+        FaultInjector<InMemoryDiskManager>,
+    ) {
+        let disk_manager = FaultInjectingDiskManager::new(InMemoryDiskManager::new());
+        let injector = disk_manager.injector();
+        (disk_manager, injector)
+    }
+
+    fn is_injected_error(result: Result<impl Sized, DiskManagerError>) -> bool {
+        matches!(result, Err(DiskManagerError::IoError(e)) if e.kind() == std::io::ErrorKind::Other)
+    }
+
+    #[rstest]
+    fn test_fail_nth_write() {
+        let (mut disk_manager, injector) = create_faulty_disk_manager();
+        let page_id = disk_manager.allocate_page().unwrap();
+        injector.fail_write(3);
+
+        disk_manager
+            .write_page(page_id, &[1u8; DEFAULT_PAGE_SIZE])
+            .unwrap();
+        disk_manager
+            .write_page(page_id, &[2u8; DEFAULT_PAGE_SIZE])
+            .unwrap();
+        assert!(is_injected_error(
+            disk_manager.write_page(page_id, &[3u8; DEFAULT_PAGE_SIZE])
+        ));
+        // The failed write didn't happen, and later writes are fine
+        assert_eq!(
+            disk_manager.read_page(page_id).unwrap(),
+            [2u8; DEFAULT_PAGE_SIZE]
+        );
+        disk_manager
+            .write_page(page_id, &[4u8; DEFAULT_PAGE_SIZE])
+            .unwrap();
+        assert_eq!(
+            disk_manager.read_page(page_id).unwrap(),
+            [4u8; DEFAULT_PAGE_SIZE]
+        );
+        assert_eq!(injector.count(DiskOperation::WritePage), 4);
+    }
+
+    #[rstest]
+    fn test_faults_counted_from_when_injected() {
+        let (mut disk_manager, injector) = create_faulty_disk_manager();
+        let page_id = disk_manager.allocate_page().unwrap();
+        for _ in 0..5 {
+            disk_manager.read_page(page_id).unwrap();
+        }
+
+        injector.fail_read(1);
+
+        assert!(is_injected_error(disk_manager.read_page(page_id)));
+        disk_manager.read_page(page_id).unwrap();
+    }
+
+    #[rstest]
+    fn test_tear_write() {
+        let (mut disk_manager, injector) = create_faulty_disk_manager();
+        let page_id = disk_manager.allocate_page().unwrap();
+        disk_manager
+            .write_page(page_id, &[1u8; DEFAULT_PAGE_SIZE])
+            .unwrap();
+        injector.tear_write(1, 100);
+
+        // A torn write looks like it worked
+        disk_manager
+            .write_page(page_id, &[2u8; DEFAULT_PAGE_SIZE])
+            .unwrap();
+
+        let page = disk_manager.read_page(page_id).unwrap();
+        assert_eq!(page[..100], [2u8; 100]);
+        assert_eq!(page[100..], [1u8; DEFAULT_PAGE_SIZE - 100]);
+    }
+
+    #[rstest]
+    fn test_short_read() {
+        let (mut disk_manager, injector) = create_faulty_disk_manager();
+        let page_id = disk_manager.allocate_page().unwrap();
+        disk_manager
+            .write_page(page_id, &[1u8; DEFAULT_PAGE_SIZE])
+            .unwrap();
+        injector.short_read(2, 10);
+
+        assert_eq!(
+            disk_manager.read_page(page_id).unwrap(),
+            [1u8; DEFAULT_PAGE_SIZE]
+        );
+        let page = disk_manager.read_page(page_id).unwrap();
+        assert_eq!(page[..10], [1u8; 10]);
+        assert_eq!(page[10..], [0u8; DEFAULT_PAGE_SIZE - 10]);
+        // Only the one read is cut short
+        assert_eq!(
+            disk_manager.read_page(page_id).unwrap(),
+            [1u8; DEFAULT_PAGE_SIZE]
+        );
+    }
+
+    #[rstest]
+    #[case(DiskOperation::ReadLog)]
+    #[case(DiskOperation::WriteLog)]
+    #[case(DiskOperation::SyncLog)]
+    #[case(DiskOperation::SyncPages)]
+    #[case(DiskOperation::AllocatePage)]
+    #[case(DiskOperation::DeallocatePage)]
+    fn test_fail_other_operations(#[case] operation: DiskOperation) {
+        let (mut disk_manager, injector) = create_faulty_disk_manager();
+        disk_manager.write_log(&[1]).unwrap();
+        let page_id = disk_manager.allocate_page().unwrap();
+        injector.inject(operation, 1, Fault::Fail);
+
+        let failed = match operation {
+            DiskOperation::ReadLog => is_injected_error(disk_manager.read_log(1, 0)),
+            DiskOperation::WriteLog => is_injected_error(disk_manager.write_log(&[2])),
+            DiskOperation::SyncLog => is_injected_error(disk_manager.sync_log()),
+            DiskOperation::SyncPages => is_injected_error(disk_manager.sync_pages()),
+            DiskOperation::AllocatePage => is_injected_error(disk_manager.allocate_page()),
+            DiskOperation::DeallocatePage => {
+                is_injected_error(disk_manager.deallocate_page(page_id))
+            }
+            DiskOperation::ReadPage | DiskOperation::WritePage => unreachable!(),
+        };
+        assert!(failed);
+        // Nothing was done
+        assert_eq!(disk_manager.inner.log, vec![1]);
+        assert_eq!(disk_manager.inner.pages.len(), 1);
+    }
+
+    #[rstest]
+    fn test_torn_log_write() {
+        let (mut disk_manager, injector) = create_faulty_disk_manager();
+        injector.inject(DiskOperation::WriteLog, 1, Fault::Partial(2));
+
+        disk_manager.write_log(&[1, 2, 3, 4]).unwrap();
+
+        assert_eq!(disk_manager.inner.log, vec![1, 2]);
+    }
+
+    #[rstest]
+    fn test_torn_log_write_failed() {
+        let (mut disk_manager, injector) = create_faulty_disk_manager();
+        injector.inject(DiskOperation::WriteLog, 1, Fault::PartialFail(2));
+
+        assert!(disk_manager.write_log(&[1, 2, 3, 4]).is_err());
+
+        assert_eq!(disk_manager.inner.log, vec![1, 2]);
+    }
+
+    #[rstest]
+    fn test_clear_faults() {
+        let (mut disk_manager, injector) = create_faulty_disk_manager();
+        let page_id = disk_manager.allocate_page().unwrap();
+        injector.fail_write(1);
+
+        injector.clear();
+
+        disk_manager
+            .write_page(page_id, &[1u8; DEFAULT_PAGE_SIZE])
+            .unwrap();
+    }
+
+    #[rstest]
+    fn test_crash_drops_unsynced_data() {
+        let (mut disk_manager, injector) = create_faulty_disk_manager();
+        let page_id = disk_manager.allocate_page().unwrap();
+        disk_manager
+            .write_page(page_id, &[1u8; DEFAULT_PAGE_SIZE])
+            .unwrap();
+        disk_manager.write_log(&[1, 2]).unwrap();
+        disk_manager.sync_log().unwrap();
+
+        disk_manager
+            .write_page(page_id, &[2u8; DEFAULT_PAGE_SIZE])
+            .unwrap();
+        disk_manager.allocate_page().unwrap();
+        disk_manager.write_log(&[3]).unwrap();
+
+        let recovered = injector.crash();
+        assert_eq!(recovered.log, vec![1, 2]);
+        assert_eq!(recovered.pages.len(), 1);
+        assert_eq!(
+            recovered.read_page(page_id).unwrap(),
+            [1u8; DEFAULT_PAGE_SIZE]
+        );
+    }
+
+    #[rstest]
+    fn test_crash_before_any_sync() {
+        let (mut disk_manager, injector) = create_faulty_disk_manager();
+        disk_manager.allocate_page().unwrap();
+        disk_manager.write_log(&[1]).unwrap();
+        injector.inject(DiskOperation::SyncLog, 1, Fault::Fail);
+        assert!(disk_manager.sync_log().is_err());
+
+        let recovered = injector.crash();
+        assert!(recovered.pages.is_empty());
+        assert!(recovered.log.is_empty());
     }
 }
@@ -1,4 +1,6 @@
+pub mod b_plus_tree;
 pub mod hash_table;
 mod page_type;
+pub mod table;
 
 pub use page_type::*;
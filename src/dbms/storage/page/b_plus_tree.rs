@@ -0,0 +1,4 @@
+pub mod header;
+pub mod internal;
+pub mod leaf;
+pub mod node;
@@ -0,0 +1,175 @@
+use crate::dbms::{
+    buffer::types::{ReadOnlyPage, WritablePage},
+    types::PageId,
+};
+
+use super::node::{
+    read_page_id_at_offset, read_u32_at_offset, read_u64_at_offset, write_page_id_at_offset,
+    write_u32_at_offset, write_u64_at_offset, BPlusTreePageError, PAGE_ENTRY_SIZE_BYTES,
+    U32_SIZE_BYTES,
+};
+
+const PAGE_ID_OFFSET_BYTES: usize = 0;
+const ROOT_PAGE_ID_OFFSET_BYTES: usize = PAGE_ENTRY_SIZE_BYTES;
+const LEAF_MAX_SIZE_OFFSET_BYTES: usize = 2 * PAGE_ENTRY_SIZE_BYTES;
+const INTERNAL_MAX_SIZE_OFFSET_BYTES: usize = LEAF_MAX_SIZE_OFFSET_BYTES + U32_SIZE_BYTES;
+
+/// Interact with a page as a B+ tree header page, which records where the
+/// tree's root is and how large its nodes can get.
+pub trait IBPlusTreeHeaderPageRead {
+    /// Get the page ID
+    fn get_page_id(&self) -> Result<PageId, BPlusTreePageError>;
+    /// The root node's page ID, if the tree isn't empty
+    fn get_root_page_id(&self) -> Result<Option<PageId>, BPlusTreePageError>;
+    /// Max size to give new leaf nodes
+    fn get_leaf_max_size(&self) -> Result<usize, BPlusTreePageError>;
+    /// Max size to give new internal nodes
+    fn get_internal_max_size(&self) -> Result<usize, BPlusTreePageError>;
+}
+
+/// Interact with a page as a B+ tree header page.
+pub trait IBPlusTreeHeaderPageWrite: IBPlusTreeHeaderPageRead {
+    /// Set the root node's page ID
+    fn set_root_page_id(&mut self, page_id: Option<PageId>) -> Result<(), BPlusTreePageError>;
+}
+
+pub struct ReadOnlyBPlusTreeHeaderPage<'a> {
+    page: ReadOnlyPage<'a>,
+}
+
+impl<'a> ReadOnlyBPlusTreeHeaderPage<'a> {
+    pub fn new(page: ReadOnlyPage<'a>) -> Self {
+        Self { page }
+    }
+}
+
+impl IBPlusTreeHeaderPageRead for ReadOnlyBPlusTreeHeaderPage<'_> {
+    fn get_page_id(&self) -> Result<PageId, BPlusTreePageError> {
+        read_u64_at_offset(&self.page, PAGE_ID_OFFSET_BYTES)
+    }
+
+    fn get_root_page_id(&self) -> Result<Option<PageId>, BPlusTreePageError> {
+        read_page_id_at_offset(&self.page, ROOT_PAGE_ID_OFFSET_BYTES)
+    }
+
+    fn get_leaf_max_size(&self) -> Result<usize, BPlusTreePageError> {
+        Ok(read_u32_at_offset(&self.page, LEAF_MAX_SIZE_OFFSET_BYTES)? as usize)
+    }
+
+    fn get_internal_max_size(&self) -> Result<usize, BPlusTreePageError> {
+        Ok(read_u32_at_offset(&self.page, INTERNAL_MAX_SIZE_OFFSET_BYTES)? as usize)
+    }
+}
+
+pub struct WritableBPlusTreeHeaderPage<'a> {
+    page: WritablePage<'a>,
+}
+
+impl<'a> WritableBPlusTreeHeaderPage<'a> {
+    pub fn new(page: WritablePage<'a>) -> Self {
+        Self { page }
+    }
+
+    /// Initialize a header page for an empty tree, with the node sizes to use
+    pub fn initialize(
+        &mut self,
+        leaf_max_size: usize,
+        internal_max_size: usize,
+    ) -> Result<(), BPlusTreePageError> {
+        let page_id = self
+            .page
+            .get_page_id()?
+            .ok_or(BPlusTreePageError::NoPageId)?;
+        write_u64_at_offset(&mut self.page, PAGE_ID_OFFSET_BYTES, page_id)?;
+        self.set_root_page_id(None)?;
+        write_u32_at_offset(
+            &mut self.page,
+            LEAF_MAX_SIZE_OFFSET_BYTES,
+            leaf_max_size as u32,
+        )?;
+        write_u32_at_offset(
+            &mut self.page,
+            INTERNAL_MAX_SIZE_OFFSET_BYTES,
+            internal_max_size as u32,
+        )
+    }
+}
+
+impl IBPlusTreeHeaderPageRead for WritableBPlusTreeHeaderPage<'_> {
+    fn get_page_id(&self) -> Result<PageId, BPlusTreePageError> {
+        read_u64_at_offset(&self.page, PAGE_ID_OFFSET_BYTES)
+    }
+
+    fn get_root_page_id(&self) -> Result<Option<PageId>, BPlusTreePageError> {
+        read_page_id_at_offset(&self.page, ROOT_PAGE_ID_OFFSET_BYTES)
+    }
+
+    fn get_leaf_max_size(&self) -> Result<usize, BPlusTreePageError> {
+        Ok(read_u32_at_offset(&self.page, LEAF_MAX_SIZE_OFFSET_BYTES)? as usize)
+    }
+
+    fn get_internal_max_size(&self) -> Result<usize, BPlusTreePageError> {
+        Ok(read_u32_at_offset(&self.page, INTERNAL_MAX_SIZE_OFFSET_BYTES)? as usize)
+    }
+}
+
+impl IBPlusTreeHeaderPageWrite for WritableBPlusTreeHeaderPage<'_> {
+    fn set_root_page_id(&mut self, page_id: Option<PageId>) -> Result<(), BPlusTreePageError> {
+        write_page_id_at_offset(&mut self.page, ROOT_PAGE_ID_OFFSET_BYTES, page_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dbms::buffer::pool_manager::{
+        testing::create_testing_pool_manager, IBufferPoolManager,
+    };
+
+    use super::*;
+    use rstest::*;
+
+    #[rstest]
+    fn test_initialize() {
+        let pool_manager = create_testing_pool_manager(10);
+        let mut header = WritableBPlusTreeHeaderPage::new(pool_manager.new_page().unwrap());
+
+        header.initialize(10, 20).unwrap();
+
+        assert_eq!(header.get_page_id().unwrap(), 0);
+        assert_eq!(header.get_root_page_id().unwrap(), None);
+        assert_eq!(header.get_leaf_max_size().unwrap(), 10);
+        assert_eq!(header.get_internal_max_size().unwrap(), 20);
+    }
+
+    #[rstest]
+    fn test_set_root_page_id() {
+        let pool_manager = create_testing_pool_manager(10);
+        let mut header = WritableBPlusTreeHeaderPage::new(pool_manager.new_page().unwrap());
+        header.initialize(10, 20).unwrap();
+
+        header.set_root_page_id(Some(0)).unwrap();
+        assert_eq!(header.get_root_page_id().unwrap(), Some(0));
+
+        header.set_root_page_id(None).unwrap();
+        assert_eq!(header.get_root_page_id().unwrap(), None);
+    }
+
+    #[rstest]
+    fn test_read_after_flush() {
+        let pool_manager = create_testing_pool_manager(10);
+        {
+            let mut header = WritableBPlusTreeHeaderPage::new(pool_manager.new_page().unwrap());
+            header.initialize(10, 20).unwrap();
+            header.set_root_page_id(Some(123)).unwrap();
+        }
+        pool_manager.unpin_page(0, true).unwrap();
+        pool_manager.flush_all_pages().unwrap();
+
+        let header = ReadOnlyBPlusTreeHeaderPage::new(pool_manager.fetch_page(0).unwrap());
+
+        assert_eq!(header.get_page_id().unwrap(), 0);
+        assert_eq!(header.get_root_page_id().unwrap(), Some(123));
+        assert_eq!(header.get_leaf_max_size().unwrap(), 10);
+        assert_eq!(header.get_internal_max_size().unwrap(), 20);
+    }
+}
@@ -0,0 +1,420 @@
+use std::marker::PhantomData;
+
+use crate::dbms::{
+    buffer::types::{PageGeneric, ReadOnlyPage, WritablePage},
+    storage::serialize::BytesSerialize,
+    types::PageId,
+};
+
+use super::node::{
+    check_page_type, clear_page_type, initialize_node, read_max_size, read_page_id, read_size,
+    read_u64_at_offset, write_size, write_u64_at_offset, BPlusTreePageError, BPlusTreePageType,
+    NODE_HEADER_SIZE_BYTES, PAGE_ENTRY_SIZE_BYTES,
+};
+
+const ENTRIES_START_OFFSET_BYTES: usize = NODE_HEADER_SIZE_BYTES;
+
+/// Smallest max size an internal node can have and still be split in two
+pub const MIN_INTERNAL_MAX_SIZE: usize = 3;
+
+/// Most children an internal page of the given size has room for with the
+/// given key type
+pub fn internal_page_capacity<KeyType: BytesSerialize>(page_size: usize) -> usize {
+    (page_size - ENTRIES_START_OFFSET_BYTES) / (KeyType::serialized_size() + PAGE_ENTRY_SIZE_BYTES)
+}
+
+/// Interact with a page as a B+ tree internal page.
+///
+/// An internal node with `n` children holds `n - 1` separator keys. Child `i`
+/// covers the keys from separator `i` (inclusive) up to separator `i + 1`
+/// (exclusive), so the first child has no separator of its own and key index
+/// 0 is never used.
+pub trait IBPlusTreeInternalPageRead<KeyType: BytesSerialize> {
+    /// Get the page ID
+    fn get_page_id(&self) -> Result<PageId, BPlusTreePageError>;
+    /// Number of children of the node
+    fn get_size(&self) -> Result<usize, BPlusTreePageError>;
+    /// Most children the node can have before it has to be split
+    fn get_max_size(&self) -> Result<usize, BPlusTreePageError>;
+    /// The separator key for the child at the index, from index 1 on
+    fn key_at(&self, index: usize) -> Result<KeyType, BPlusTreePageError>;
+    fn child_at(&self, index: usize) -> Result<PageId, BPlusTreePageError>;
+
+    /// The first child, then every later child with its separator key
+    fn children(&self) -> Result<(PageId, Vec<(KeyType, PageId)>), BPlusTreePageError> {
+        let entries = (1..self.get_size()?)
+            .map(|index| Ok((self.key_at(index)?, self.child_at(index)?)))
+            .collect::<Result<Vec<_>, BPlusTreePageError>>()?;
+        Ok((self.child_at(0)?, entries))
+    }
+}
+
+/// Interact with a page as a B+ tree internal page.
+pub trait IBPlusTreeInternalPageWrite<KeyType: BytesSerialize>:
+    IBPlusTreeInternalPageRead<KeyType>
+{
+    /// Set the separator key for the child at the index, from index 1 on
+    fn set_key_at(&mut self, index: usize, key: KeyType) -> Result<(), BPlusTreePageError>;
+    /// Replace every child of the node
+    fn set_children(
+        &mut self,
+        first_child: PageId,
+        entries: &[(KeyType, PageId)],
+    ) -> Result<(), BPlusTreePageError>;
+}
+
+fn entry_address<KeyType: BytesSerialize>(index: usize) -> usize {
+    ENTRIES_START_OFFSET_BYTES + index * (KeyType::serialized_size() + PAGE_ENTRY_SIZE_BYTES)
+}
+
+fn read_key<KeyType: BytesSerialize>(
+    page: &PageGeneric,
+    index: usize,
+) -> Result<KeyType, BPlusTreePageError> {
+    if index == 0 || index >= read_size(page)? {
+        return Err(BPlusTreePageError::IndexOutOfRange(index));
+    }
+    let bytes = page.read_data(entry_address::<KeyType>(index), KeyType::serialized_size())?;
+    Ok(KeyType::from_bytes(bytes)?)
+}
+
+fn read_child<KeyType: BytesSerialize>(
+    page: &PageGeneric,
+    index: usize,
+) -> Result<PageId, BPlusTreePageError> {
+    if index >= read_size(page)? {
+        return Err(BPlusTreePageError::IndexOutOfRange(index));
+    }
+    read_u64_at_offset(
+        page,
+        entry_address::<KeyType>(index) + KeyType::serialized_size(),
+    )
+}
+
+pub struct ReadOnlyBPlusTreeInternalPage<'a, KeyType: BytesSerialize> {
+    page: ReadOnlyPage<'a>,
+
+    _phantom: PhantomData<KeyType>,
+}
+
+impl<'a, KeyType: BytesSerialize> ReadOnlyBPlusTreeInternalPage<'a, KeyType> {
+    /// Wrap a page holding an internal node, failing if it holds something
+    /// else.
+    pub fn new(page: ReadOnlyPage<'a>) -> Result<Self, BPlusTreePageError> {
+        check_page_type(&page, BPlusTreePageType::Internal)?;
+        Ok(Self {
+            page,
+            _phantom: PhantomData,
+        })
+    }
+}
+
+impl<KeyType: BytesSerialize> IBPlusTreeInternalPageRead<KeyType>
+    for ReadOnlyBPlusTreeInternalPage<'_, KeyType>
+{
+    fn get_page_id(&self) -> Result<PageId, BPlusTreePageError> {
+        read_page_id(&self.page)
+    }
+
+    fn get_size(&self) -> Result<usize, BPlusTreePageError> {
+        read_size(&self.page)
+    }
+
+    fn get_max_size(&self) -> Result<usize, BPlusTreePageError> {
+        read_max_size(&self.page)
+    }
+
+    fn key_at(&self, index: usize) -> Result<KeyType, BPlusTreePageError> {
+        read_key(&self.page, index)
+    }
+
+    fn child_at(&self, index: usize) -> Result<PageId, BPlusTreePageError> {
+        read_child::<KeyType>(&self.page, index)
+    }
+}
+
+pub struct WritableBPlusTreeInternalPage<'a, KeyType: BytesSerialize> {
+    page: WritablePage<'a>,
+
+    _phantom: PhantomData<KeyType>,
+}
+
+impl<'a, KeyType: BytesSerialize> WritableBPlusTreeInternalPage<'a, KeyType> {
+    /// Wrap a page holding an internal node, failing if it holds something
+    /// else.
+    pub fn new(page: WritablePage<'a>) -> Result<Self, BPlusTreePageError> {
+        check_page_type(&page, BPlusTreePageType::Internal)?;
+        Ok(Self {
+            page,
+            _phantom: PhantomData,
+        })
+    }
+
+    /// Set up a new page as an internal node with the given max size. It has
+    /// no children until they're set.
+    pub fn initialize(
+        mut page: WritablePage<'a>,
+        max_size: usize,
+    ) -> Result<Self, BPlusTreePageError> {
+        let capacity = internal_page_capacity::<KeyType>(page.get_page_size()?);
+        initialize_node(
+            &mut page,
+            BPlusTreePageType::Internal,
+            max_size,
+            capacity,
+            MIN_INTERNAL_MAX_SIZE,
+        )?;
+        Self::new(page)
+    }
+
+    /// Mark the page as no longer holding a internal node, once it's been removed from
+    /// the tree.
+    pub fn mark_removed(&mut self) -> Result<(), BPlusTreePageError> {
+        clear_page_type(&mut self.page)
+    }
+
+    fn write_key(&mut self, index: usize, key: &KeyType) -> Result<(), BPlusTreePageError> {
+        self.page
+            .write_data(entry_address::<KeyType>(index), &key.to_bytes()?)?;
+        Ok(())
+    }
+
+    fn write_child(&mut self, index: usize, child: PageId) -> Result<(), BPlusTreePageError> {
+        write_u64_at_offset(
+            &mut self.page,
+            entry_address::<KeyType>(index) + KeyType::serialized_size(),
+            child,
+        )
+    }
+}
+
+impl<KeyType: BytesSerialize> IBPlusTreeInternalPageRead<KeyType>
+    for WritableBPlusTreeInternalPage<'_, KeyType>
+{
+    fn get_page_id(&self) -> Result<PageId, BPlusTreePageError> {
+        read_page_id(&self.page)
+    }
+
+    fn get_size(&self) -> Result<usize, BPlusTreePageError> {
+        read_size(&self.page)
+    }
+
+    fn get_max_size(&self) -> Result<usize, BPlusTreePageError> {
+        read_max_size(&self.page)
+    }
+
+    fn key_at(&self, index: usize) -> Result<KeyType, BPlusTreePageError> {
+        read_key(&self.page, index)
+    }
+
+    fn child_at(&self, index: usize) -> Result<PageId, BPlusTreePageError> {
+        read_child::<KeyType>(&self.page, index)
+    }
+}
+
+impl<KeyType: BytesSerialize> IBPlusTreeInternalPageWrite<KeyType>
+    for WritableBPlusTreeInternalPage<'_, KeyType>
+{
+    fn set_key_at(&mut self, index: usize, key: KeyType) -> Result<(), BPlusTreePageError> {
+        if index == 0 || index >= self.get_size()? {
+            return Err(BPlusTreePageError::IndexOutOfRange(index));
+        }
+        self.write_key(index, &key)
+    }
+
+    fn set_children(
+        &mut self,
+        first_child: PageId,
+        entries: &[(KeyType, PageId)],
+    ) -> Result<(), BPlusTreePageError> {
+        if entries.len() + 1 > self.get_max_size()? {
+            return Err(BPlusTreePageError::NodeFull);
+        }
+
+        self.write_child(0, first_child)?;
+        for (i, (key, child)) in entries.iter().enumerate() {
+            self.write_key(i + 1, key)?;
+            self.write_child(i + 1, *child)?;
+        }
+        write_size(&mut self.page, entries.len() + 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dbms::buffer::pool_manager::{
+        testing::create_testing_pool_manager, IBufferPoolManager,
+    };
+    use crate::dbms::storage::page::b_plus_tree::leaf::WritableBPlusTreeLeafPage;
+    use crate::dbms::types::DEFAULT_PAGE_SIZE;
+    use crate::{tuple, tuple_type};
+
+    use super::*;
+    use rstest::*;
+
+    type TestInternal<'a> = WritableBPlusTreeInternalPage<'a, tuple_type![u32, u8]>;
+
+    #[rstest]
+    fn test_initialize() {
+        let pool_manager = create_testing_pool_manager(10);
+        let node = TestInternal::initialize(pool_manager.new_page().unwrap(), 10).unwrap();
+
+        assert_eq!(node.get_page_id().unwrap(), 0);
+        assert_eq!(node.get_size().unwrap(), 0);
+        assert_eq!(node.get_max_size().unwrap(), 10);
+    }
+
+    #[rstest]
+    #[case(2)]
+    #[case(454)]
+    fn test_initialize_invalid_max_size(#[case] max_size: usize) {
+        let pool_manager = create_testing_pool_manager(10);
+
+        let result = TestInternal::initialize(pool_manager.new_page().unwrap(), max_size);
+
+        assert!(matches!(
+            result,
+            Err(BPlusTreePageError::InvalidMaxSize(size)) if size == max_size
+        ));
+    }
+
+    #[rstest]
+    #[case(DEFAULT_PAGE_SIZE, 313)]
+    #[case(65536, 5039)]
+    fn test_capacity(#[case] page_size: usize, #[case] expected: usize) {
+        assert_eq!(
+            internal_page_capacity::<tuple_type![u32, u8]>(page_size),
+            expected
+        );
+    }
+
+    #[rstest]
+    fn test_wrap_leaf_page() {
+        let pool_manager = create_testing_pool_manager(10);
+        WritableBPlusTreeLeafPage::<u32, u32>::initialize(pool_manager.new_page().unwrap(), 10)
+            .unwrap();
+        pool_manager.unpin_page(0, true).unwrap();
+
+        let result = TestInternal::new(pool_manager.fetch_page_writable(0).unwrap());
+
+        assert!(matches!(result, Err(BPlusTreePageError::WrongPageType)));
+    }
+
+    #[rstest]
+    fn test_mark_removed() {
+        let pool_manager = create_testing_pool_manager(10);
+        let mut node = TestInternal::initialize(pool_manager.new_page().unwrap(), 10).unwrap();
+
+        node.mark_removed().unwrap();
+        drop(node);
+        pool_manager.unpin_page(0, true).unwrap();
+
+        let result = TestInternal::new(pool_manager.fetch_page_writable(0).unwrap());
+        assert!(matches!(result, Err(BPlusTreePageError::WrongPageType)));
+    }
+
+    #[rstest]
+    fn test_set_and_get_children() {
+        let pool_manager = create_testing_pool_manager(10);
+        let mut node = TestInternal::initialize(pool_manager.new_page().unwrap(), 10).unwrap();
+        let entries = vec![(tuple![10, 1], 21), (tuple![20, 2], 22)];
+
+        node.set_children(20, &entries).unwrap();
+
+        assert_eq!(node.get_size().unwrap(), 3);
+        assert_eq!(node.children().unwrap(), (20, entries));
+        assert_eq!(node.child_at(0).unwrap(), 20);
+        assert_eq!(node.child_at(2).unwrap(), 22);
+        assert_eq!(node.key_at(1).unwrap(), tuple![10, 1]);
+    }
+
+    #[rstest]
+    fn test_out_of_range() {
+        let pool_manager = create_testing_pool_manager(10);
+        let mut node = TestInternal::initialize(pool_manager.new_page().unwrap(), 10).unwrap();
+        node.set_children(20, &[(tuple![10, 1], 21)]).unwrap();
+
+        // The first child has no key
+        assert_eq!(node.key_at(0), Err(BPlusTreePageError::IndexOutOfRange(0)));
+        assert_eq!(node.key_at(2), Err(BPlusTreePageError::IndexOutOfRange(2)));
+        assert_eq!(
+            node.child_at(2),
+            Err(BPlusTreePageError::IndexOutOfRange(2))
+        );
+        assert_eq!(
+            node.set_key_at(0, tuple![1, 1]),
+            Err(BPlusTreePageError::IndexOutOfRange(0))
+        );
+    }
+
+    #[rstest]
+    fn test_set_key_at() {
+        let pool_manager = create_testing_pool_manager(10);
+        let mut node = TestInternal::initialize(pool_manager.new_page().unwrap(), 10).unwrap();
+        node.set_children(20, &[(tuple![10, 1], 21), (tuple![20, 2], 22)])
+            .unwrap();
+
+        node.set_key_at(2, tuple![15, 5]).unwrap();
+
+        assert_eq!(
+            node.children().unwrap(),
+            (20, vec![(tuple![10, 1], 21), (tuple![15, 5], 22)])
+        );
+    }
+
+    #[rstest]
+    fn test_set_too_many_children() {
+        let pool_manager = create_testing_pool_manager(10);
+        let mut node = TestInternal::initialize(pool_manager.new_page().unwrap(), 3).unwrap();
+        let entries = vec![
+            (tuple![10, 1], 21),
+            (tuple![20, 2], 22),
+            (tuple![30, 3], 23),
+        ];
+
+        assert_eq!(
+            node.set_children(20, &entries),
+            Err(BPlusTreePageError::NodeFull)
+        );
+        node.set_children(20, &entries[..2]).unwrap();
+        assert_eq!(node.get_size().unwrap(), 3);
+    }
+
+    #[rstest]
+    fn test_threaded_read_children() {
+        let pool_manager = create_testing_pool_manager(100);
+
+        {
+            for i in 0..11 {
+                let mut node =
+                    TestInternal::initialize(pool_manager.new_page().unwrap(), 10).unwrap();
+                node.set_children(i * 2, &[(tuple![i as u32, 0], i * 2 + 1)])
+                    .unwrap();
+            }
+        }
+
+        pool_manager.flush_all_pages().unwrap();
+
+        // Relying on the test logic that page IDs in the test pool manager count up from 0
+        let mut read_threads = Vec::new();
+        for i in 0..11 {
+            let buffer_pool_manager = pool_manager.clone();
+            read_threads.push(std::thread::spawn(move || {
+                let node = ReadOnlyBPlusTreeInternalPage::<tuple_type![u32, u8]>::new(
+                    buffer_pool_manager.fetch_page(i).unwrap(),
+                )
+                .unwrap();
+
+                assert_eq!(node.get_page_id().unwrap(), i);
+                assert_eq!(
+                    node.children().unwrap(),
+                    (i * 2, vec![(tuple![i as u32, 0], i * 2 + 1)])
+                );
+            }));
+        }
+
+        for thread in read_threads {
+            thread.join().unwrap();
+        }
+    }
+}
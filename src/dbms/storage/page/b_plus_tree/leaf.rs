@@ -0,0 +1,563 @@
+use std::marker::PhantomData;
+
+use crate::dbms::{
+    buffer::types::{PageGeneric, ReadOnlyPage, WritablePage},
+    storage::serialize::BytesSerialize,
+    types::PageId,
+};
+
+use super::node::{
+    check_page_type, clear_page_type, initialize_node, read_max_size, read_page_id,
+    read_page_id_at_offset, read_size, write_page_id_at_offset, write_size, BPlusTreePageError,
+    BPlusTreePageType, NODE_HEADER_SIZE_BYTES, PAGE_ENTRY_SIZE_BYTES,
+};
+
+const PREV_PAGE_ID_OFFSET_BYTES: usize = NODE_HEADER_SIZE_BYTES;
+const NEXT_PAGE_ID_OFFSET_BYTES: usize = NODE_HEADER_SIZE_BYTES + PAGE_ENTRY_SIZE_BYTES;
+const ENTRIES_START_OFFSET_BYTES: usize = NODE_HEADER_SIZE_BYTES + 2 * PAGE_ENTRY_SIZE_BYTES;
+
+/// Smallest max size a leaf can have and still be split in two
+pub const MIN_LEAF_MAX_SIZE: usize = 2;
+
+/// Most entries a leaf page of the given size has room for with the given key
+/// and value types
+pub fn leaf_page_capacity<KeyType: BytesSerialize, ValueType: BytesSerialize>(
+    page_size: usize,
+) -> usize {
+    (page_size - ENTRIES_START_OFFSET_BYTES)
+        / (KeyType::serialized_size() + ValueType::serialized_size())
+}
+
+/// Interact with a page as a B+ tree leaf page, holding key-value entries in
+/// key order.
+pub trait IBPlusTreeLeafPageRead<KeyType: BytesSerialize, ValueType: BytesSerialize> {
+    /// Get the page ID
+    fn get_page_id(&self) -> Result<PageId, BPlusTreePageError>;
+    /// Number of entries in the leaf
+    fn get_size(&self) -> Result<usize, BPlusTreePageError>;
+    /// Most entries the leaf can hold before it has to be split
+    fn get_max_size(&self) -> Result<usize, BPlusTreePageError>;
+    /// The previous leaf in key order, if any
+    fn get_prev_page_id(&self) -> Result<Option<PageId>, BPlusTreePageError>;
+    /// The next leaf along in key order, if any
+    fn get_next_page_id(&self) -> Result<Option<PageId>, BPlusTreePageError>;
+    fn key_at(&self, index: usize) -> Result<KeyType, BPlusTreePageError>;
+    fn value_at(&self, index: usize) -> Result<ValueType, BPlusTreePageError>;
+
+    /// Every entry in the leaf, in order
+    fn entries(&self) -> Result<Vec<(KeyType, ValueType)>, BPlusTreePageError> {
+        (0..self.get_size()?)
+            .map(|index| Ok((self.key_at(index)?, self.value_at(index)?)))
+            .collect()
+    }
+}
+
+/// Interact with a page as a B+ tree leaf page, holding key-value entries in
+/// key order.
+pub trait IBPlusTreeLeafPageWrite<KeyType: BytesSerialize, ValueType: BytesSerialize>:
+    IBPlusTreeLeafPageRead<KeyType, ValueType>
+{
+    /// Set the previous leaf in key order
+    fn set_prev_page_id(&mut self, page_id: Option<PageId>) -> Result<(), BPlusTreePageError>;
+    /// Set the next leaf along in key order
+    fn set_next_page_id(&mut self, page_id: Option<PageId>) -> Result<(), BPlusTreePageError>;
+    /// Insert an entry at the index, shifting the later entries along
+    fn insert_at(
+        &mut self,
+        index: usize,
+        key: KeyType,
+        value: ValueType,
+    ) -> Result<(), BPlusTreePageError>;
+    /// Remove the entry at the index, shifting the later entries back
+    fn remove_at(&mut self, index: usize) -> Result<(), BPlusTreePageError>;
+    /// Replace every entry in the leaf
+    fn set_entries(&mut self, entries: &[(KeyType, ValueType)]) -> Result<(), BPlusTreePageError>;
+}
+
+fn entry_size<KeyType: BytesSerialize, ValueType: BytesSerialize>() -> usize {
+    KeyType::serialized_size() + ValueType::serialized_size()
+}
+
+fn entry_address<KeyType: BytesSerialize, ValueType: BytesSerialize>(index: usize) -> usize {
+    ENTRIES_START_OFFSET_BYTES + index * entry_size::<KeyType, ValueType>()
+}
+
+fn read_key<KeyType: BytesSerialize, ValueType: BytesSerialize>(
+    page: &PageGeneric,
+    index: usize,
+) -> Result<KeyType, BPlusTreePageError> {
+    if index >= read_size(page)? {
+        return Err(BPlusTreePageError::IndexOutOfRange(index));
+    }
+    let bytes = page.read_data(
+        entry_address::<KeyType, ValueType>(index),
+        KeyType::serialized_size(),
+    )?;
+    Ok(KeyType::from_bytes(bytes)?)
+}
+
+fn read_value<KeyType: BytesSerialize, ValueType: BytesSerialize>(
+    page: &PageGeneric,
+    index: usize,
+) -> Result<ValueType, BPlusTreePageError> {
+    if index >= read_size(page)? {
+        return Err(BPlusTreePageError::IndexOutOfRange(index));
+    }
+    let bytes = page.read_data(
+        entry_address::<KeyType, ValueType>(index) + KeyType::serialized_size(),
+        ValueType::serialized_size(),
+    )?;
+    Ok(ValueType::from_bytes(bytes)?)
+}
+
+pub struct ReadOnlyBPlusTreeLeafPage<'a, KeyType: BytesSerialize, ValueType: BytesSerialize> {
+    page: ReadOnlyPage<'a>,
+
+    _phantom: PhantomData<KeyType>,
+    _phantom2: PhantomData<ValueType>,
+}
+
+impl<'a, KeyType: BytesSerialize, ValueType: BytesSerialize>
+    ReadOnlyBPlusTreeLeafPage<'a, KeyType, ValueType>
+{
+    /// Wrap a page holding a leaf node, failing if it holds something else.
+    pub fn new(page: ReadOnlyPage<'a>) -> Result<Self, BPlusTreePageError> {
+        check_page_type(&page, BPlusTreePageType::Leaf)?;
+        Ok(Self {
+            page,
+            _phantom: PhantomData,
+            _phantom2: PhantomData,
+        })
+    }
+}
+
+impl<KeyType: BytesSerialize, ValueType: BytesSerialize> IBPlusTreeLeafPageRead<KeyType, ValueType>
+    for ReadOnlyBPlusTreeLeafPage<'_, KeyType, ValueType>
+{
+    fn get_page_id(&self) -> Result<PageId, BPlusTreePageError> {
+        read_page_id(&self.page)
+    }
+
+    fn get_size(&self) -> Result<usize, BPlusTreePageError> {
+        read_size(&self.page)
+    }
+
+    fn get_max_size(&self) -> Result<usize, BPlusTreePageError> {
+        read_max_size(&self.page)
+    }
+
+    fn get_prev_page_id(&self) -> Result<Option<PageId>, BPlusTreePageError> {
+        read_page_id_at_offset(&self.page, PREV_PAGE_ID_OFFSET_BYTES)
+    }
+
+    fn get_next_page_id(&self) -> Result<Option<PageId>, BPlusTreePageError> {
+        read_page_id_at_offset(&self.page, NEXT_PAGE_ID_OFFSET_BYTES)
+    }
+
+    fn key_at(&self, index: usize) -> Result<KeyType, BPlusTreePageError> {
+        read_key::<KeyType, ValueType>(&self.page, index)
+    }
+
+    fn value_at(&self, index: usize) -> Result<ValueType, BPlusTreePageError> {
+        read_value::<KeyType, ValueType>(&self.page, index)
+    }
+}
+
+pub struct WritableBPlusTreeLeafPage<'a, KeyType: BytesSerialize, ValueType: BytesSerialize> {
+    page: WritablePage<'a>,
+
+    _phantom: PhantomData<KeyType>,
+    _phantom2: PhantomData<ValueType>,
+}
+
+impl<'a, KeyType: BytesSerialize, ValueType: BytesSerialize>
+    WritableBPlusTreeLeafPage<'a, KeyType, ValueType>
+{
+    /// Wrap a page holding a leaf node, failing if it holds something else.
+    pub fn new(page: WritablePage<'a>) -> Result<Self, BPlusTreePageError> {
+        check_page_type(&page, BPlusTreePageType::Leaf)?;
+        Ok(Self {
+            page,
+            _phantom: PhantomData,
+            _phantom2: PhantomData,
+        })
+    }
+
+    /// Set up a new page as an empty leaf with the given max size.
+    pub fn initialize(
+        mut page: WritablePage<'a>,
+        max_size: usize,
+    ) -> Result<Self, BPlusTreePageError> {
+        let capacity = leaf_page_capacity::<KeyType, ValueType>(page.get_page_size()?);
+        initialize_node(
+            &mut page,
+            BPlusTreePageType::Leaf,
+            max_size,
+            capacity,
+            MIN_LEAF_MAX_SIZE,
+        )?;
+        write_page_id_at_offset(&mut page, PREV_PAGE_ID_OFFSET_BYTES, None)?;
+        write_page_id_at_offset(&mut page, NEXT_PAGE_ID_OFFSET_BYTES, None)?;
+        Self::new(page)
+    }
+
+    /// Mark the page as no longer holding a leaf, once it's been removed from
+    /// the tree.
+    pub fn mark_removed(&mut self) -> Result<(), BPlusTreePageError> {
+        clear_page_type(&mut self.page)
+    }
+
+    fn write_entry(
+        &mut self,
+        index: usize,
+        key: &KeyType,
+        value: &ValueType,
+    ) -> Result<(), BPlusTreePageError> {
+        let address = entry_address::<KeyType, ValueType>(index);
+        self.page.write_data(address, &key.to_bytes()?)?;
+        self.page
+            .write_data(address + KeyType::serialized_size(), &value.to_bytes()?)?;
+        Ok(())
+    }
+
+    /// Move the entries from `from` onwards so they start at `to` instead
+    fn shift_entries(
+        &mut self,
+        from: usize,
+        to: usize,
+        size: usize,
+    ) -> Result<(), BPlusTreePageError> {
+        if from >= size {
+            return Ok(());
+        }
+        let start = entry_address::<KeyType, ValueType>(from);
+        let end = entry_address::<KeyType, ValueType>(size);
+        let bytes = self.page.read_data(start, end - start)?;
+        self.page
+            .write_data(entry_address::<KeyType, ValueType>(to), &bytes)?;
+        Ok(())
+    }
+}
+
+impl<KeyType: BytesSerialize, ValueType: BytesSerialize> IBPlusTreeLeafPageRead<KeyType, ValueType>
+    for WritableBPlusTreeLeafPage<'_, KeyType, ValueType>
+{
+    fn get_page_id(&self) -> Result<PageId, BPlusTreePageError> {
+        read_page_id(&self.page)
+    }
+
+    fn get_size(&self) -> Result<usize, BPlusTreePageError> {
+        read_size(&self.page)
+    }
+
+    fn get_max_size(&self) -> Result<usize, BPlusTreePageError> {
+        read_max_size(&self.page)
+    }
+
+    fn get_prev_page_id(&self) -> Result<Option<PageId>, BPlusTreePageError> {
+        read_page_id_at_offset(&self.page, PREV_PAGE_ID_OFFSET_BYTES)
+    }
+
+    fn get_next_page_id(&self) -> Result<Option<PageId>, BPlusTreePageError> {
+        read_page_id_at_offset(&self.page, NEXT_PAGE_ID_OFFSET_BYTES)
+    }
+
+    fn key_at(&self, index: usize) -> Result<KeyType, BPlusTreePageError> {
+        read_key::<KeyType, ValueType>(&self.page, index)
+    }
+
+    fn value_at(&self, index: usize) -> Result<ValueType, BPlusTreePageError> {
+        read_value::<KeyType, ValueType>(&self.page, index)
+    }
+}
+
+impl<KeyType: BytesSerialize, ValueType: BytesSerialize> IBPlusTreeLeafPageWrite<KeyType, ValueType>
+    for WritableBPlusTreeLeafPage<'_, KeyType, ValueType>
+{
+    fn set_prev_page_id(&mut self, page_id: Option<PageId>) -> Result<(), BPlusTreePageError> {
+        write_page_id_at_offset(&mut self.page, PREV_PAGE_ID_OFFSET_BYTES, page_id)
+    }
+
+    fn set_next_page_id(&mut self, page_id: Option<PageId>) -> Result<(), BPlusTreePageError> {
+        write_page_id_at_offset(&mut self.page, NEXT_PAGE_ID_OFFSET_BYTES, page_id)
+    }
+
+    fn insert_at(
+        &mut self,
+        index: usize,
+        key: KeyType,
+        value: ValueType,
+    ) -> Result<(), BPlusTreePageError> {
+        let size = self.get_size()?;
+        if index > size {
+            return Err(BPlusTreePageError::IndexOutOfRange(index));
+        }
+        if size >= self.get_max_size()? {
+            return Err(BPlusTreePageError::NodeFull);
+        }
+
+        self.shift_entries(index, index + 1, size)?;
+        self.write_entry(index, &key, &value)?;
+        write_size(&mut self.page, size + 1)
+    }
+
+    fn remove_at(&mut self, index: usize) -> Result<(), BPlusTreePageError> {
+        let size = self.get_size()?;
+        if index >= size {
+            return Err(BPlusTreePageError::IndexOutOfRange(index));
+        }
+
+        self.shift_entries(index + 1, index, size)?;
+        write_size(&mut self.page, size - 1)
+    }
+
+    fn set_entries(&mut self, entries: &[(KeyType, ValueType)]) -> Result<(), BPlusTreePageError> {
+        if entries.len() > self.get_max_size()? {
+            return Err(BPlusTreePageError::NodeFull);
+        }
+
+        for (index, (key, value)) in entries.iter().enumerate() {
+            self.write_entry(index, key, value)?;
+        }
+        write_size(&mut self.page, entries.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dbms::buffer::pool_manager::{
+        testing::create_testing_pool_manager, IBufferPoolManager,
+    };
+    use crate::dbms::types::DEFAULT_PAGE_SIZE;
+    use crate::{tuple, tuple_type};
+
+    use super::*;
+    use rstest::*;
+
+    type TestLeaf<'a> = WritableBPlusTreeLeafPage<'a, tuple_type![u32], tuple_type![bool, f64]>;
+
+    #[rstest]
+    fn test_initialize() {
+        let pool_manager = create_testing_pool_manager(10);
+        let leaf = TestLeaf::initialize(pool_manager.new_page().unwrap(), 10).unwrap();
+
+        assert_eq!(leaf.get_page_id().unwrap(), 0);
+        assert_eq!(leaf.get_size().unwrap(), 0);
+        assert_eq!(leaf.get_max_size().unwrap(), 10);
+        assert_eq!(leaf.get_prev_page_id().unwrap(), None);
+        assert_eq!(leaf.get_next_page_id().unwrap(), None);
+        assert_eq!(leaf.entries().unwrap(), vec![]);
+    }
+
+    #[rstest]
+    #[case(1)]
+    #[case(316)]
+    fn test_initialize_invalid_max_size(#[case] max_size: usize) {
+        let pool_manager = create_testing_pool_manager(10);
+
+        let result = TestLeaf::initialize(pool_manager.new_page().unwrap(), max_size);
+
+        assert!(matches!(
+            result,
+            Err(BPlusTreePageError::InvalidMaxSize(size)) if size == max_size
+        ));
+    }
+
+    #[rstest]
+    #[case(DEFAULT_PAGE_SIZE, 312)]
+    #[case(65536, 5038)]
+    fn test_capacity(#[case] page_size: usize, #[case] expected: usize) {
+        assert_eq!(
+            leaf_page_capacity::<tuple_type![u32], tuple_type![bool, f64]>(page_size),
+            expected
+        );
+    }
+
+    #[rstest]
+    fn test_wrap_wrong_page_type() {
+        let pool_manager = create_testing_pool_manager(10);
+
+        let result = TestLeaf::new(pool_manager.new_page().unwrap());
+
+        assert!(matches!(result, Err(BPlusTreePageError::WrongPageType)));
+    }
+
+    #[rstest]
+    fn test_mark_removed() {
+        let pool_manager = create_testing_pool_manager(10);
+        let mut leaf = TestLeaf::initialize(pool_manager.new_page().unwrap(), 10).unwrap();
+
+        leaf.mark_removed().unwrap();
+        drop(leaf);
+        pool_manager.unpin_page(0, true).unwrap();
+
+        let result = TestLeaf::new(pool_manager.fetch_page_writable(0).unwrap());
+        assert!(matches!(result, Err(BPlusTreePageError::WrongPageType)));
+    }
+
+    #[rstest]
+    fn test_insert_at() {
+        let pool_manager = create_testing_pool_manager(10);
+        let mut leaf = TestLeaf::initialize(pool_manager.new_page().unwrap(), 10).unwrap();
+
+        leaf.insert_at(0, 3, tuple![true, 3.0]).unwrap();
+        leaf.insert_at(0, 1, tuple![true, 1.0]).unwrap();
+        leaf.insert_at(1, 2, tuple![false, 2.0]).unwrap();
+        leaf.insert_at(3, 4, tuple![true, 4.0]).unwrap();
+
+        assert_eq!(
+            leaf.entries().unwrap(),
+            vec![
+                (1, tuple![true, 1.0]),
+                (2, tuple![false, 2.0]),
+                (3, tuple![true, 3.0]),
+                (4, tuple![true, 4.0]),
+            ]
+        );
+        assert_eq!(leaf.key_at(2).unwrap(), 3);
+        assert_eq!(leaf.value_at(2).unwrap(), tuple![true, 3.0]);
+    }
+
+    #[rstest]
+    fn test_insert_at_out_of_range() {
+        let pool_manager = create_testing_pool_manager(10);
+        let mut leaf = TestLeaf::initialize(pool_manager.new_page().unwrap(), 10).unwrap();
+
+        let result = leaf.insert_at(1, 1, tuple![true, 1.0]);
+
+        assert_eq!(result, Err(BPlusTreePageError::IndexOutOfRange(1)));
+    }
+
+    #[rstest]
+    fn test_insert_at_full() {
+        let pool_manager = create_testing_pool_manager(10);
+        let mut leaf = TestLeaf::initialize(pool_manager.new_page().unwrap(), 2).unwrap();
+        leaf.insert_at(0, 1, tuple![true, 1.0]).unwrap();
+        leaf.insert_at(1, 2, tuple![true, 2.0]).unwrap();
+
+        let result = leaf.insert_at(2, 3, tuple![true, 3.0]);
+
+        assert_eq!(result, Err(BPlusTreePageError::NodeFull));
+        assert_eq!(leaf.get_size().unwrap(), 2);
+    }
+
+    #[rstest]
+    fn test_remove_at() {
+        let pool_manager = create_testing_pool_manager(10);
+        let mut leaf = TestLeaf::initialize(pool_manager.new_page().unwrap(), 10).unwrap();
+        for i in 0..4 {
+            leaf.insert_at(i, i as u32, tuple![true, i as f64]).unwrap();
+        }
+
+        leaf.remove_at(1).unwrap();
+        leaf.remove_at(2).unwrap();
+
+        assert_eq!(
+            leaf.entries().unwrap(),
+            vec![(0, tuple![true, 0.0]), (2, tuple![true, 2.0])]
+        );
+        assert_eq!(leaf.key_at(2), Err(BPlusTreePageError::IndexOutOfRange(2)));
+        assert_eq!(
+            leaf.remove_at(2),
+            Err(BPlusTreePageError::IndexOutOfRange(2))
+        );
+    }
+
+    #[rstest]
+    fn test_set_entries() {
+        let pool_manager = create_testing_pool_manager(10);
+        let mut leaf = TestLeaf::initialize(pool_manager.new_page().unwrap(), 3).unwrap();
+        let entries = vec![(5, tuple![true, 0.5]), (6, tuple![false, 0.6])];
+
+        leaf.set_entries(&entries).unwrap();
+        assert_eq!(leaf.entries().unwrap(), entries);
+
+        leaf.set_entries(&entries[1..]).unwrap();
+        assert_eq!(leaf.entries().unwrap(), entries[1..]);
+
+        let too_many = vec![(1, tuple![true, 0.0]); 4];
+        assert_eq!(
+            leaf.set_entries(&too_many),
+            Err(BPlusTreePageError::NodeFull)
+        );
+    }
+
+    #[rstest]
+    fn test_fill_page() {
+        let pool_manager = create_testing_pool_manager(10);
+        let capacity =
+            leaf_page_capacity::<tuple_type![u32], tuple_type![bool, f64]>(DEFAULT_PAGE_SIZE);
+        let mut leaf = TestLeaf::initialize(pool_manager.new_page().unwrap(), capacity).unwrap();
+
+        for i in 0..capacity {
+            leaf.insert_at(i, i as u32, tuple![true, i as f64 / 3.0])
+                .unwrap();
+        }
+
+        for i in 0..capacity {
+            assert_eq!(leaf.key_at(i).unwrap(), i as u32);
+            assert_eq!(leaf.value_at(i).unwrap(), tuple![true, i as f64 / 3.0]);
+        }
+    }
+
+    #[rstest]
+    fn test_next_page_id() {
+        let pool_manager = create_testing_pool_manager(10);
+        let mut leaf = TestLeaf::initialize(pool_manager.new_page().unwrap(), 10).unwrap();
+
+        leaf.set_next_page_id(Some(7)).unwrap();
+        assert_eq!(leaf.get_next_page_id().unwrap(), Some(7));
+
+        leaf.set_next_page_id(None).unwrap();
+        assert_eq!(leaf.get_next_page_id().unwrap(), None);
+    }
+
+    #[rstest]
+    fn test_prev_page_id() {
+        let pool_manager = create_testing_pool_manager(10);
+        let mut leaf = TestLeaf::initialize(pool_manager.new_page().unwrap(), 10).unwrap();
+
+        leaf.set_prev_page_id(Some(7)).unwrap();
+        leaf.set_next_page_id(Some(8)).unwrap();
+        assert_eq!(leaf.get_prev_page_id().unwrap(), Some(7));
+        assert_eq!(leaf.get_next_page_id().unwrap(), Some(8));
+
+        leaf.set_prev_page_id(None).unwrap();
+        assert_eq!(leaf.get_prev_page_id().unwrap(), None);
+        assert_eq!(leaf.get_next_page_id().unwrap(), Some(8));
+    }
+
+    #[rstest]
+    fn test_threaded_read_entries() {
+        let pool_manager = create_testing_pool_manager(100);
+
+        {
+            for i in 0..11 {
+                let mut leaf = TestLeaf::initialize(pool_manager.new_page().unwrap(), 10).unwrap();
+                leaf.insert_at(0, i, tuple![true, 1.5]).unwrap();
+            }
+        }
+
+        pool_manager.flush_all_pages().unwrap();
+
+        // Relying on the test logic that page IDs in the test pool manager count up from 0
+        let mut read_threads = Vec::new();
+        for i in 0..11 {
+            let buffer_pool_manager = pool_manager.clone();
+            read_threads.push(std::thread::spawn(move || {
+                let leaf =
+                    ReadOnlyBPlusTreeLeafPage::<tuple_type![u32], tuple_type![bool, f64]>::new(
+                        buffer_pool_manager.fetch_page(i).unwrap(),
+                    )
+                    .unwrap();
+
+                assert_eq!(leaf.get_page_id().unwrap(), i);
+                assert_eq!(leaf.entries().unwrap(), vec![(i as u32, tuple![true, 1.5])]);
+            }));
+        }
+
+        for thread in read_threads {
+            thread.join().unwrap();
+        }
+    }
+}
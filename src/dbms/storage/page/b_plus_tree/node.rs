@@ -0,0 +1,249 @@
+use crate::dbms::{
+    buffer::types::PageGeneric,
+    storage::{page::PageError, serialize::SerializeError},
+    types::{PageId, INVALID_PAGE_ID},
+};
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum BPlusTreePageError {
+    /// Provided page ID is not set
+    NoPageId,
+    /// Entry index is past the end of the node
+    IndexOutOfRange(usize),
+    /// More entries than the node's max size
+    NodeFull,
+    /// Max size is more than fits in a page, or too small to split
+    InvalidMaxSize(usize),
+    /// The page isn't a tree node of the expected kind
+    WrongPageType,
+    PageError(PageError),
+    SerializeError(SerializeError),
+}
+
+impl From<PageError> for BPlusTreePageError {
+    fn from(e: PageError) -> Self {
+        BPlusTreePageError::PageError(e)
+    }
+}
+
+impl From<SerializeError> for BPlusTreePageError {
+    fn from(e: SerializeError) -> Self {
+        BPlusTreePageError::SerializeError(e)
+    }
+}
+
+/// The kind of tree node stored in a page
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BPlusTreePageType {
+    Leaf,
+    Internal,
+}
+
+impl BPlusTreePageType {
+    fn to_u32(self) -> u32 {
+        match self {
+            BPlusTreePageType::Leaf => 1,
+            BPlusTreePageType::Internal => 2,
+        }
+    }
+
+    fn from_u32(value: u32) -> Result<Self, BPlusTreePageError> {
+        match value {
+            1 => Ok(BPlusTreePageType::Leaf),
+            2 => Ok(BPlusTreePageType::Internal),
+            _ => Err(BPlusTreePageError::WrongPageType),
+        }
+    }
+}
+
+// Every node starts with the same header, so the page type can be read before
+// knowing which kind of node a page holds
+pub(super) const PAGE_ENTRY_SIZE_BYTES: usize = (PageId::BITS / 8) as usize;
+pub(super) const U32_SIZE_BYTES: usize = (u32::BITS / 8) as usize;
+const PAGE_TYPE_OFFSET_BYTES: usize = 0;
+const PAGE_ID_OFFSET_BYTES: usize = PAGE_TYPE_OFFSET_BYTES + U32_SIZE_BYTES;
+const SIZE_OFFSET_BYTES: usize = PAGE_ID_OFFSET_BYTES + PAGE_ENTRY_SIZE_BYTES;
+const MAX_SIZE_OFFSET_BYTES: usize = SIZE_OFFSET_BYTES + U32_SIZE_BYTES;
+pub(super) const NODE_HEADER_SIZE_BYTES: usize = MAX_SIZE_OFFSET_BYTES + U32_SIZE_BYTES;
+
+/// Read which kind of tree node a page holds.
+pub fn read_page_type(page: &PageGeneric) -> Result<BPlusTreePageType, BPlusTreePageError> {
+    BPlusTreePageType::from_u32(read_u32_at_offset(page, PAGE_TYPE_OFFSET_BYTES)?)
+}
+
+pub(super) fn read_u32_at_offset(
+    page: &PageGeneric,
+    offset_bytes: usize,
+) -> Result<u32, BPlusTreePageError> {
+    let data = page.read_data(offset_bytes, U32_SIZE_BYTES)?;
+    Ok(u32::from_be_bytes(data.as_slice().try_into().unwrap()))
+}
+
+pub(super) fn write_u32_at_offset(
+    page: &mut PageGeneric,
+    offset_bytes: usize,
+    value: u32,
+) -> Result<(), BPlusTreePageError> {
+    page.write_data(offset_bytes, &value.to_be_bytes())?;
+    Ok(())
+}
+
+pub(super) fn read_u64_at_offset(
+    page: &PageGeneric,
+    offset_bytes: usize,
+) -> Result<u64, BPlusTreePageError> {
+    let data = page.read_data(offset_bytes, PAGE_ENTRY_SIZE_BYTES)?;
+    Ok(u64::from_be_bytes(data.as_slice().try_into().unwrap()))
+}
+
+pub(super) fn write_u64_at_offset(
+    page: &mut PageGeneric,
+    offset_bytes: usize,
+    value: u64,
+) -> Result<(), BPlusTreePageError> {
+    page.write_data(offset_bytes, &value.to_be_bytes())?;
+    Ok(())
+}
+
+pub(super) fn read_page_id_at_offset(
+    page: &PageGeneric,
+    offset_bytes: usize,
+) -> Result<Option<PageId>, BPlusTreePageError> {
+    match read_u64_at_offset(page, offset_bytes)? {
+        INVALID_PAGE_ID => Ok(None),
+        page_id => Ok(Some(page_id)),
+    }
+}
+
+pub(super) fn write_page_id_at_offset(
+    page: &mut PageGeneric,
+    offset_bytes: usize,
+    page_id: Option<PageId>,
+) -> Result<(), BPlusTreePageError> {
+    write_u64_at_offset(page, offset_bytes, page_id.unwrap_or(INVALID_PAGE_ID))
+}
+
+pub(super) fn read_page_id(page: &PageGeneric) -> Result<PageId, BPlusTreePageError> {
+    read_u64_at_offset(page, PAGE_ID_OFFSET_BYTES)
+}
+
+pub(super) fn read_size(page: &PageGeneric) -> Result<usize, BPlusTreePageError> {
+    Ok(read_u32_at_offset(page, SIZE_OFFSET_BYTES)? as usize)
+}
+
+pub(super) fn write_size(page: &mut PageGeneric, size: usize) -> Result<(), BPlusTreePageError> {
+    write_u32_at_offset(page, SIZE_OFFSET_BYTES, size as u32)
+}
+
+pub(super) fn read_max_size(page: &PageGeneric) -> Result<usize, BPlusTreePageError> {
+    Ok(read_u32_at_offset(page, MAX_SIZE_OFFSET_BYTES)? as usize)
+}
+
+/// Check the page holds the expected kind of node.
+pub(super) fn check_page_type(
+    page: &PageGeneric,
+    page_type: BPlusTreePageType,
+) -> Result<(), BPlusTreePageError> {
+    if read_page_type(page)? != page_type {
+        return Err(BPlusTreePageError::WrongPageType);
+    }
+    Ok(())
+}
+
+/// Mark a page as no longer holding a tree node, so it's not mistaken for one
+/// after it's been removed from the tree.
+pub(super) fn clear_page_type(page: &mut PageGeneric) -> Result<(), BPlusTreePageError> {
+    write_u32_at_offset(page, PAGE_TYPE_OFFSET_BYTES, 0)
+}
+
+/// Write a fresh node header, with no entries.
+pub(super) fn initialize_node(
+    page: &mut PageGeneric,
+    page_type: BPlusTreePageType,
+    max_size: usize,
+    capacity: usize,
+    min_max_size: usize,
+) -> Result<(), BPlusTreePageError> {
+    if max_size < min_max_size || max_size > capacity {
+        return Err(BPlusTreePageError::InvalidMaxSize(max_size));
+    }
+    let page_id = page.get_page_id()?.ok_or(BPlusTreePageError::NoPageId)?;
+    write_u32_at_offset(page, PAGE_TYPE_OFFSET_BYTES, page_type.to_u32())?;
+    write_u64_at_offset(page, PAGE_ID_OFFSET_BYTES, page_id)?;
+    write_size(page, 0)?;
+    write_u32_at_offset(page, MAX_SIZE_OFFSET_BYTES, max_size as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dbms::buffer::pool_manager::{
+        testing::create_testing_pool_manager, IBufferPoolManager,
+    };
+
+    use super::*;
+    use rstest::*;
+
+    #[rstest]
+    #[case(BPlusTreePageType::Leaf)]
+    #[case(BPlusTreePageType::Internal)]
+    fn test_initialize_node(#[case] page_type: BPlusTreePageType) {
+        let pool_manager = create_testing_pool_manager(10);
+        let mut page = pool_manager.new_page().unwrap();
+
+        initialize_node(&mut page, page_type, 10, 20, 2).unwrap();
+
+        assert_eq!(read_page_type(&page), Ok(page_type));
+        assert_eq!(read_page_id(&page), Ok(0));
+        assert_eq!(read_size(&page), Ok(0));
+        assert_eq!(read_max_size(&page), Ok(10));
+    }
+
+    #[rstest]
+    #[case(1)]
+    #[case(21)]
+    fn test_initialize_node_invalid_max_size(#[case] max_size: usize) {
+        let pool_manager = create_testing_pool_manager(10);
+        let mut page = pool_manager.new_page().unwrap();
+
+        let result = initialize_node(&mut page, BPlusTreePageType::Leaf, max_size, 20, 2);
+
+        assert_eq!(result, Err(BPlusTreePageError::InvalidMaxSize(max_size)));
+    }
+
+    #[rstest]
+    fn test_read_page_type_of_blank_page() {
+        let pool_manager = create_testing_pool_manager(10);
+        let page = pool_manager.new_page().unwrap();
+
+        assert_eq!(
+            read_page_type(&page),
+            Err(BPlusTreePageError::WrongPageType)
+        );
+    }
+
+    #[rstest]
+    fn test_clear_page_type() {
+        let pool_manager = create_testing_pool_manager(10);
+        let mut page = pool_manager.new_page().unwrap();
+        initialize_node(&mut page, BPlusTreePageType::Leaf, 10, 20, 2).unwrap();
+
+        clear_page_type(&mut page).unwrap();
+
+        assert_eq!(
+            read_page_type(&page),
+            Err(BPlusTreePageError::WrongPageType)
+        );
+    }
+
+    #[rstest]
+    fn test_page_id_at_offset() {
+        let pool_manager = create_testing_pool_manager(10);
+        let mut page = pool_manager.new_page().unwrap();
+
+        write_page_id_at_offset(&mut page, 100, Some(123)).unwrap();
+        write_page_id_at_offset(&mut page, 108, None).unwrap();
+
+        assert_eq!(read_page_id_at_offset(&page, 100), Ok(Some(123)));
+        assert_eq!(read_page_id_at_offset(&page, 108), Ok(None));
+    }
+}
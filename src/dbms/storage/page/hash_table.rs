@@ -1,3 +1,5 @@
 pub mod block;
+pub mod bucket;
+pub mod directory;
 pub mod header;
-mod util;
+pub mod util;
@@ -66,9 +66,11 @@ impl<'a, KeyType: BytesSerialize, ValueType: BytesSerialize>
 {
     #[allow(dead_code)]
     pub fn new(page: ReadOnlyPage<'a>) -> Self {
-        let layout =
-            calculate_block_page_layout(KeyType::serialized_size() + ValueType::serialized_size())
-                .unwrap(); // TODO: Handle error
+        let layout = calculate_block_page_layout(
+            KeyType::serialized_size() + ValueType::serialized_size(),
+            page.get_page_size().unwrap(),
+        )
+        .unwrap(); // TODO: Handle error
 
         Self {
             page,
@@ -177,9 +179,11 @@ impl<'a, KeyType: BytesSerialize, ValueType: BytesSerialize>
 {
     #[allow(dead_code)]
     pub fn new(page: WritablePage<'a>) -> Self {
-        let layout =
-            calculate_block_page_layout(KeyType::serialized_size() + ValueType::serialized_size())
-                .unwrap(); // TODO: Handle error
+        let layout = calculate_block_page_layout(
+            KeyType::serialized_size() + ValueType::serialized_size(),
+            page.get_page_size().unwrap(),
+        )
+        .unwrap(); // TODO: Handle error
 
         Self {
             page,
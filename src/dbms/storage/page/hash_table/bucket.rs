@@ -0,0 +1,377 @@
+use std::marker::PhantomData;
+
+use crate::dbms::{
+    buffer::types::{PageGeneric, ReadOnlyPage, WritablePage},
+    storage::{
+        page::PageError,
+        serialize::{BytesSerialize, SerializeError},
+    },
+};
+
+use super::util::{calculate_bucket_page_layout, BucketPageLayout};
+
+/// Interact with a page as an extendible hash table bucket page.
+pub trait IHashTableBucketPageRead<KeyType: BytesSerialize, ValueType: BytesSerialize> {
+    fn key_at(&self, slot: usize) -> Result<KeyType, HashTableBucketError>;
+    fn value_at(&self, slot: usize) -> Result<ValueType, HashTableBucketError>;
+    fn slot_occupied(&self, slot: usize) -> Result<bool, HashTableBucketError>;
+    fn num_slots(&self) -> usize;
+    /// Number of occupied slots in the bucket
+    fn num_occupied(&self) -> Result<usize, HashTableBucketError>;
+}
+
+/// Interact with a page as an extendible hash table bucket page.
+pub trait IHashTableBucketPageWrite<KeyType: BytesSerialize, ValueType: BytesSerialize>:
+    IHashTableBucketPageRead<KeyType, ValueType>
+{
+    fn put_slot(
+        &mut self,
+        slot: usize,
+        key: KeyType,
+        value: ValueType,
+    ) -> Result<(), HashTableBucketError>;
+    /// Free up a slot so it can be reused
+    fn remove_slot(&mut self, slot: usize) -> Result<(), HashTableBucketError>;
+}
+
+#[derive(Debug)]
+pub enum HashTableBucketError {
+    PageError(PageError),
+    SerializeError(SerializeError),
+    SlotEmpty,
+    SlotOccupied,
+}
+
+impl From<PageError> for HashTableBucketError {
+    fn from(e: PageError) -> Self {
+        HashTableBucketError::PageError(e)
+    }
+}
+
+impl From<SerializeError> for HashTableBucketError {
+    fn from(e: SerializeError) -> Self {
+        HashTableBucketError::SerializeError(e)
+    }
+}
+
+fn bucket_layout<KeyType: BytesSerialize, ValueType: BytesSerialize>(
+    page: &PageGeneric,
+) -> BucketPageLayout {
+    calculate_bucket_page_layout(
+        KeyType::serialized_size() + ValueType::serialized_size(),
+        page.get_page_size().unwrap(),
+    )
+    .unwrap()
+    // TODO: Handle error
+}
+
+fn read_occupied(
+    page: &PageGeneric,
+    layout: &BucketPageLayout,
+    slot: usize,
+) -> Result<bool, HashTableBucketError> {
+    let byte = page.read_data(layout.occupancy_array_start + slot / 8, 1)?[0];
+    Ok((byte >> (slot % 8)) & 1 == 1)
+}
+
+fn read_num_occupied(
+    page: &PageGeneric,
+    layout: &BucketPageLayout,
+) -> Result<usize, HashTableBucketError> {
+    let bytes = page.read_data(layout.occupancy_array_start, layout.max_values.div_ceil(8))?;
+    Ok(bytes.iter().map(|byte| byte.count_ones() as usize).sum())
+}
+
+/// Read an entry's key (at offset 0) or value (at the key's size), checking
+/// the slot is occupied first
+fn read_entry_part<T: BytesSerialize>(
+    page: &PageGeneric,
+    layout: &BucketPageLayout,
+    entry_size: usize,
+    slot: usize,
+    offset: usize,
+) -> Result<T, HashTableBucketError> {
+    if !read_occupied(page, layout, slot)? {
+        return Err(HashTableBucketError::SlotEmpty);
+    }
+    let address = layout.value_array_start + slot * entry_size + offset;
+    let bytes = page.read_data(address, T::serialized_size())?;
+    Ok(T::from_bytes(bytes)?)
+}
+
+pub struct ReadOnlyHashTableBucketPage<'a, KeyType: BytesSerialize, ValueType: BytesSerialize> {
+    page: ReadOnlyPage<'a>,
+    layout: BucketPageLayout,
+
+    _phantom: PhantomData<KeyType>,
+    _phantom2: PhantomData<ValueType>,
+}
+
+impl<'a, KeyType: BytesSerialize, ValueType: BytesSerialize>
+    ReadOnlyHashTableBucketPage<'a, KeyType, ValueType>
+{
+    pub fn new(page: ReadOnlyPage<'a>) -> Self {
+        let layout = bucket_layout::<KeyType, ValueType>(&page);
+        Self {
+            page,
+            layout,
+            _phantom: PhantomData,
+            _phantom2: PhantomData,
+        }
+    }
+}
+
+impl<KeyType: BytesSerialize, ValueType: BytesSerialize>
+    IHashTableBucketPageRead<KeyType, ValueType>
+    for ReadOnlyHashTableBucketPage<'_, KeyType, ValueType>
+{
+    fn key_at(&self, slot: usize) -> Result<KeyType, HashTableBucketError> {
+        let entry_size = KeyType::serialized_size() + ValueType::serialized_size();
+        read_entry_part(&self.page, &self.layout, entry_size, slot, 0)
+    }
+
+    fn value_at(&self, slot: usize) -> Result<ValueType, HashTableBucketError> {
+        let entry_size = KeyType::serialized_size() + ValueType::serialized_size();
+        let offset = KeyType::serialized_size();
+        read_entry_part(&self.page, &self.layout, entry_size, slot, offset)
+    }
+
+    fn slot_occupied(&self, slot: usize) -> Result<bool, HashTableBucketError> {
+        read_occupied(&self.page, &self.layout, slot)
+    }
+
+    fn num_slots(&self) -> usize {
+        self.layout.max_values
+    }
+
+    fn num_occupied(&self) -> Result<usize, HashTableBucketError> {
+        read_num_occupied(&self.page, &self.layout)
+    }
+}
+
+pub struct WritableHashTableBucketPage<'a, KeyType: BytesSerialize, ValueType: BytesSerialize> {
+    page: WritablePage<'a>,
+    layout: BucketPageLayout,
+
+    _phantom: PhantomData<KeyType>,
+    _phantom2: PhantomData<ValueType>,
+}
+
+impl<'a, KeyType: BytesSerialize, ValueType: BytesSerialize>
+    WritableHashTableBucketPage<'a, KeyType, ValueType>
+{
+    pub fn new(page: WritablePage<'a>) -> Self {
+        let layout = bucket_layout::<KeyType, ValueType>(&page);
+        Self {
+            page,
+            layout,
+            _phantom: PhantomData,
+            _phantom2: PhantomData,
+        }
+    }
+
+    fn write_occupied(&mut self, slot: usize, occupied: bool) -> Result<(), HashTableBucketError> {
+        let byte_address = self.layout.occupancy_array_start + slot / 8;
+        let mut byte = self.page.read_data(byte_address, 1)?[0];
+        let bit = 1 << (slot % 8);
+        if occupied {
+            byte |= bit;
+        } else {
+            byte &= !bit;
+        }
+        Ok(self.page.write_data(byte_address, &[byte])?)
+    }
+}
+
+impl<KeyType: BytesSerialize, ValueType: BytesSerialize>
+    IHashTableBucketPageRead<KeyType, ValueType>
+    for WritableHashTableBucketPage<'_, KeyType, ValueType>
+{
+    fn key_at(&self, slot: usize) -> Result<KeyType, HashTableBucketError> {
+        let entry_size = KeyType::serialized_size() + ValueType::serialized_size();
+        read_entry_part(&self.page, &self.layout, entry_size, slot, 0)
+    }
+
+    fn value_at(&self, slot: usize) -> Result<ValueType, HashTableBucketError> {
+        let entry_size = KeyType::serialized_size() + ValueType::serialized_size();
+        let offset = KeyType::serialized_size();
+        read_entry_part(&self.page, &self.layout, entry_size, slot, offset)
+    }
+
+    fn slot_occupied(&self, slot: usize) -> Result<bool, HashTableBucketError> {
+        read_occupied(&self.page, &self.layout, slot)
+    }
+
+    fn num_slots(&self) -> usize {
+        self.layout.max_values
+    }
+
+    fn num_occupied(&self) -> Result<usize, HashTableBucketError> {
+        read_num_occupied(&self.page, &self.layout)
+    }
+}
+
+impl<KeyType: BytesSerialize, ValueType: BytesSerialize>
+    IHashTableBucketPageWrite<KeyType, ValueType>
+    for WritableHashTableBucketPage<'_, KeyType, ValueType>
+{
+    fn put_slot(
+        &mut self,
+        slot: usize,
+        key: KeyType,
+        value: ValueType,
+    ) -> Result<(), HashTableBucketError> {
+        if self.slot_occupied(slot)? {
+            return Err(HashTableBucketError::SlotOccupied);
+        }
+
+        let key_address = self.layout.value_array_start
+            + slot * (KeyType::serialized_size() + ValueType::serialized_size());
+        let value_address = key_address + KeyType::serialized_size();
+        self.page.write_data(key_address, &key.to_bytes()?)?;
+        self.page.write_data(value_address, &value.to_bytes()?)?;
+        self.write_occupied(slot, true)
+    }
+
+    fn remove_slot(&mut self, slot: usize) -> Result<(), HashTableBucketError> {
+        self.write_occupied(slot, false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dbms::buffer::pool_manager::testing::create_testing_pool_manager;
+    use crate::dbms::buffer::pool_manager::IBufferPoolManager;
+    use crate::{tuple, tuple_type};
+
+    use super::*;
+    use rstest::*;
+
+    type TestBucket<'a> = WritableHashTableBucketPage<'a, tuple_type![u32], tuple_type![bool, f64]>;
+
+    #[rstest]
+    fn test_put_and_read_slot() {
+        let pool_manager = create_testing_pool_manager(10);
+        let mut bucket = TestBucket::new(pool_manager.new_page().unwrap());
+
+        bucket.put_slot(3, tuple![1], tuple![true, 1.0]).unwrap();
+
+        assert_eq!(bucket.key_at(3).unwrap(), tuple![1]);
+        assert_eq!(bucket.value_at(3).unwrap(), tuple![true, 1.0]);
+        assert!(bucket.slot_occupied(3).unwrap());
+        assert!(!bucket.slot_occupied(2).unwrap());
+    }
+
+    #[rstest]
+    fn test_put_to_occupied_slot() {
+        let pool_manager = create_testing_pool_manager(10);
+        let mut bucket = TestBucket::new(pool_manager.new_page().unwrap());
+
+        bucket.put_slot(0, tuple![1], tuple![true, 1.0]).unwrap();
+        let result = bucket.put_slot(0, tuple![2], tuple![false, 2.0]);
+
+        assert!(matches!(result, Err(HashTableBucketError::SlotOccupied)));
+        assert_eq!(bucket.key_at(0).unwrap(), tuple![1]);
+    }
+
+    #[rstest]
+    fn test_read_empty_slot() {
+        let pool_manager = create_testing_pool_manager(10);
+        let bucket = TestBucket::new(pool_manager.new_page().unwrap());
+
+        assert!(matches!(
+            bucket.key_at(0),
+            Err(HashTableBucketError::SlotEmpty)
+        ));
+        assert!(matches!(
+            bucket.value_at(0),
+            Err(HashTableBucketError::SlotEmpty)
+        ));
+    }
+
+    #[rstest]
+    fn test_removed_slot_is_reused() {
+        let pool_manager = create_testing_pool_manager(10);
+        let mut bucket = TestBucket::new(pool_manager.new_page().unwrap());
+
+        bucket.put_slot(0, tuple![1], tuple![true, 1.0]).unwrap();
+        bucket.remove_slot(0).unwrap();
+
+        assert!(!bucket.slot_occupied(0).unwrap());
+        assert!(matches!(
+            bucket.key_at(0),
+            Err(HashTableBucketError::SlotEmpty)
+        ));
+
+        bucket.put_slot(0, tuple![2], tuple![false, 2.0]).unwrap();
+        assert_eq!(bucket.key_at(0).unwrap(), tuple![2]);
+        assert_eq!(bucket.value_at(0).unwrap(), tuple![false, 2.0]);
+    }
+
+    #[rstest]
+    fn test_num_occupied() {
+        let pool_manager = create_testing_pool_manager(10);
+        let mut bucket = TestBucket::new(pool_manager.new_page().unwrap());
+        assert_eq!(bucket.num_occupied().unwrap(), 0);
+
+        for slot in [0, 7, 8, 100] {
+            bucket.put_slot(slot, tuple![1], tuple![true, 1.0]).unwrap();
+        }
+        bucket.remove_slot(7).unwrap();
+
+        assert_eq!(bucket.num_occupied().unwrap(), 3);
+    }
+
+    #[rstest]
+    fn test_fill_page() {
+        let pool_manager = create_testing_pool_manager(10);
+        let mut bucket = TestBucket::new(pool_manager.new_page().unwrap());
+        assert_eq!(bucket.num_slots(), 312);
+
+        for i in 0..bucket.num_slots() {
+            bucket
+                .put_slot(i, tuple![i as u32], tuple![true, i as f64 / 3f64])
+                .unwrap();
+        }
+
+        assert_eq!(bucket.num_occupied().unwrap(), bucket.num_slots());
+        for i in 0..bucket.num_slots() {
+            assert_eq!(bucket.key_at(i).unwrap(), tuple![i as u32]);
+            assert_eq!(bucket.value_at(i).unwrap(), tuple![true, i as f64 / 3f64]);
+        }
+    }
+
+    #[rstest]
+    fn test_threaded_read_slots() {
+        let pool_manager = create_testing_pool_manager(100);
+
+        {
+            for i in 0..11 {
+                let mut bucket = TestBucket::new(pool_manager.new_page().unwrap());
+                bucket.put_slot(10, tuple![i], tuple![true, 1.5]).unwrap();
+            }
+        }
+
+        pool_manager.flush_all_pages().unwrap();
+
+        // Relying on the test logic that page IDs in the test pool manager count up from 0
+        let mut read_threads = Vec::new();
+        for i in 0..11 {
+            let buffer_pool_manager = pool_manager.clone();
+            read_threads.push(std::thread::spawn(move || {
+                let bucket =
+                    ReadOnlyHashTableBucketPage::<tuple_type![u32], tuple_type![bool, f64]>::new(
+                        buffer_pool_manager.fetch_page(i).unwrap(),
+                    );
+
+                assert_eq!(bucket.key_at(10).unwrap(), tuple![i as u32]);
+                assert_eq!(bucket.value_at(10).unwrap(), tuple![true, 1.5]);
+                assert_eq!(bucket.num_occupied().unwrap(), 1);
+            }));
+        }
+
+        for thread in read_threads {
+            thread.join().unwrap();
+        }
+    }
+}
@@ -0,0 +1,531 @@
+use crate::dbms::{
+    buffer::types::{PageGeneric, ReadOnlyPage, WritablePage},
+    storage::page::PageError,
+    types::PageId,
+};
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum HashTableDirectoryError {
+    /// Provided page ID is not set
+    NoPageId,
+    /// Directory index is outside the current directory
+    IndexOutOfRange(usize),
+    /// The directory is already as large as the page allows
+    MaxDepthReached,
+    /// The directory is already down to a single entry
+    MinDepthReached,
+    PageError(PageError),
+}
+
+impl From<PageError> for HashTableDirectoryError {
+    fn from(e: PageError) -> Self {
+        HashTableDirectoryError::PageError(e)
+    }
+}
+
+const PAGE_ENTRY_SIZE_BYTES: usize = (PageId::BITS / 8) as usize;
+const U32_SIZE_BYTES: usize = (u32::BITS / 8) as usize;
+const LOCAL_DEPTH_SIZE_BYTES: usize = 1;
+const PAGE_ID_OFFSET_BYTES: usize = 0;
+const GLOBAL_DEPTH_OFFSET_BYTES: usize = PAGE_ID_OFFSET_BYTES + PAGE_ENTRY_SIZE_BYTES;
+const LSN_OFFSET_BYTES: usize = GLOBAL_DEPTH_OFFSET_BYTES + U32_SIZE_BYTES;
+const BUCKET_PAGE_IDS_START_OFFSET_BYTES: usize = LSN_OFFSET_BYTES + U32_SIZE_BYTES;
+
+/// Largest global depth a directory page of the given size has room for, with
+/// a bucket page ID and a local depth per directory entry
+pub fn max_global_depth(page_size: usize) -> u32 {
+    let entry_size = PAGE_ENTRY_SIZE_BYTES + LOCAL_DEPTH_SIZE_BYTES;
+    let mut depth = 0;
+    while BUCKET_PAGE_IDS_START_OFFSET_BYTES + (2 << depth) * entry_size <= page_size {
+        depth += 1;
+    }
+    depth
+}
+
+/// The local depths follow room for as many bucket page IDs as the page can
+/// have directory entries
+fn local_depths_start_offset(page_size: usize) -> usize {
+    BUCKET_PAGE_IDS_START_OFFSET_BYTES + (1 << max_global_depth(page_size)) * PAGE_ENTRY_SIZE_BYTES
+}
+
+/// Interact with a page as an extendible hash table directory page.
+///
+/// The directory has `2^global_depth` entries, each pointing at a bucket page.
+/// A key belongs to the entry given by the low `global_depth` bits of its
+/// hash. Each entry also records the local depth of its bucket: the number of
+/// low hash bits shared by every key in the bucket. A bucket with a local
+/// depth below the global depth is pointed at by more than one entry.
+pub trait IHashTableDirectoryPageRead {
+    /// Get the page ID
+    fn get_page_id(&self) -> Result<PageId, HashTableDirectoryError>;
+    /// Largest global depth the page has room for
+    fn get_max_global_depth(&self) -> Result<u32, HashTableDirectoryError>;
+    /// Number of low hash bits used to pick a directory entry
+    fn get_global_depth(&self) -> Result<u32, HashTableDirectoryError>;
+    /// The log sequence number
+    fn get_lsn(&self) -> Result<u32, HashTableDirectoryError>;
+    /// Get the bucket page ID at the given directory index
+    fn get_bucket_page_id(&self, index: usize) -> Result<PageId, HashTableDirectoryError>;
+    /// Get the local depth of the bucket at the given directory index
+    fn get_local_depth(&self, index: usize) -> Result<u32, HashTableDirectoryError>;
+
+    /// Number of entries in the directory
+    fn size(&self) -> Result<usize, HashTableDirectoryError> {
+        Ok(1 << self.get_global_depth()?)
+    }
+
+    /// Whether the directory can be halved, i.e. no bucket uses every bit of
+    /// the global depth
+    fn can_shrink(&self) -> Result<bool, HashTableDirectoryError> {
+        let global_depth = self.get_global_depth()?;
+        if global_depth == 0 {
+            return Ok(false);
+        }
+        for index in 0..self.size()? {
+            if self.get_local_depth(index)? == global_depth {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+}
+
+/// Interact with a page as an extendible hash table directory page.
+pub trait IHashTableDirectoryPageWrite: IHashTableDirectoryPageRead {
+    /// Set the page ID
+    fn set_page_id(&mut self, page_id: PageId) -> Result<(), HashTableDirectoryError>;
+    /// Set the global depth, without touching any entries
+    fn set_global_depth(&mut self, global_depth: u32) -> Result<(), HashTableDirectoryError>;
+    /// Set the log sequence number
+    fn set_lsn(&mut self, lsn: u32) -> Result<(), HashTableDirectoryError>;
+    /// Set the bucket page ID at the given directory index
+    fn set_bucket_page_id(
+        &mut self,
+        index: usize,
+        page_id: PageId,
+    ) -> Result<(), HashTableDirectoryError>;
+    /// Set the local depth of the bucket at the given directory index
+    fn set_local_depth(&mut self, index: usize, depth: u32) -> Result<(), HashTableDirectoryError>;
+
+    /// Double the directory. Each new entry points at the same bucket as the
+    /// entry it differs from in the new top bit.
+    fn incr_global_depth(&mut self) -> Result<(), HashTableDirectoryError> {
+        let global_depth = self.get_global_depth()?;
+        if global_depth >= self.get_max_global_depth()? {
+            return Err(HashTableDirectoryError::MaxDepthReached);
+        }
+        let size = self.size()?;
+        self.set_global_depth(global_depth + 1)?;
+        for index in 0..size {
+            let bucket_page_id = self.get_bucket_page_id(index)?;
+            let local_depth = self.get_local_depth(index)?;
+            self.set_bucket_page_id(index + size, bucket_page_id)?;
+            self.set_local_depth(index + size, local_depth)?;
+        }
+        Ok(())
+    }
+
+    /// Halve the directory, dropping the top half of the entries.
+    fn decr_global_depth(&mut self) -> Result<(), HashTableDirectoryError> {
+        let global_depth = self.get_global_depth()?;
+        if global_depth == 0 {
+            return Err(HashTableDirectoryError::MinDepthReached);
+        }
+        self.set_global_depth(global_depth - 1)
+    }
+}
+
+fn read_u32_at_offset(
+    page: &PageGeneric,
+    offset_bytes: usize,
+) -> Result<u32, HashTableDirectoryError> {
+    let data = page.read_data(offset_bytes, U32_SIZE_BYTES)?;
+    Ok(u32::from_be_bytes(data.as_slice().try_into().unwrap()))
+}
+
+fn read_page_id_at_offset(
+    page: &PageGeneric,
+    offset_bytes: usize,
+) -> Result<PageId, HashTableDirectoryError> {
+    let data = page.read_data(offset_bytes, PAGE_ENTRY_SIZE_BYTES)?;
+    Ok(PageId::from_be_bytes(data.as_slice().try_into().unwrap()))
+}
+
+fn check_index(page: &PageGeneric, index: usize) -> Result<(), HashTableDirectoryError> {
+    let global_depth = read_u32_at_offset(page, GLOBAL_DEPTH_OFFSET_BYTES)?;
+    if index >= 1 << global_depth {
+        return Err(HashTableDirectoryError::IndexOutOfRange(index));
+    }
+    Ok(())
+}
+
+fn read_bucket_page_id(
+    page: &PageGeneric,
+    index: usize,
+) -> Result<PageId, HashTableDirectoryError> {
+    check_index(page, index)?;
+    read_page_id_at_offset(
+        page,
+        BUCKET_PAGE_IDS_START_OFFSET_BYTES + index * PAGE_ENTRY_SIZE_BYTES,
+    )
+}
+
+fn read_local_depth(page: &PageGeneric, index: usize) -> Result<u32, HashTableDirectoryError> {
+    check_index(page, index)?;
+    let data = page.read_data(
+        local_depths_start_offset(page.get_page_size()?) + index * LOCAL_DEPTH_SIZE_BYTES,
+        LOCAL_DEPTH_SIZE_BYTES,
+    )?;
+    Ok(data[0] as u32)
+}
+
+pub struct ReadOnlyHashTableDirectoryPage<'a> {
+    page: ReadOnlyPage<'a>,
+}
+
+impl<'a> ReadOnlyHashTableDirectoryPage<'a> {
+    pub fn new(page: ReadOnlyPage<'a>) -> Self {
+        Self { page }
+    }
+}
+
+impl IHashTableDirectoryPageRead for ReadOnlyHashTableDirectoryPage<'_> {
+    fn get_page_id(&self) -> Result<PageId, HashTableDirectoryError> {
+        read_page_id_at_offset(&self.page, PAGE_ID_OFFSET_BYTES)
+    }
+
+    fn get_max_global_depth(&self) -> Result<u32, HashTableDirectoryError> {
+        Ok(max_global_depth(self.page.get_page_size()?))
+    }
+
+    fn get_global_depth(&self) -> Result<u32, HashTableDirectoryError> {
+        read_u32_at_offset(&self.page, GLOBAL_DEPTH_OFFSET_BYTES)
+    }
+
+    fn get_lsn(&self) -> Result<u32, HashTableDirectoryError> {
+        read_u32_at_offset(&self.page, LSN_OFFSET_BYTES)
+    }
+
+    fn get_bucket_page_id(&self, index: usize) -> Result<PageId, HashTableDirectoryError> {
+        read_bucket_page_id(&self.page, index)
+    }
+
+    fn get_local_depth(&self, index: usize) -> Result<u32, HashTableDirectoryError> {
+        read_local_depth(&self.page, index)
+    }
+}
+
+pub struct WritableHashTableDirectoryPage<'a> {
+    page: WritablePage<'a>,
+}
+
+impl<'a> WritableHashTableDirectoryPage<'a> {
+    pub fn new(page: WritablePage<'a>) -> Self {
+        Self { page }
+    }
+
+    fn write_at_offset(
+        &mut self,
+        offset_bytes: usize,
+        bytes: &[u8],
+    ) -> Result<(), HashTableDirectoryError> {
+        self.page.write_data(offset_bytes, bytes)?;
+        Ok(())
+    }
+
+    /// Initialize a directory page to contain its page ID, with a global
+    /// depth of 0 and its single entry pointing at the given bucket page
+    pub fn initialize(&mut self, bucket_page_id: PageId) -> Result<(), HashTableDirectoryError> {
+        let page_id = self
+            .page
+            .get_page_id()?
+            .ok_or(HashTableDirectoryError::NoPageId)?;
+        self.set_page_id(page_id)?;
+        self.set_global_depth(0)?;
+        self.set_bucket_page_id(0, bucket_page_id)?;
+        self.set_local_depth(0, 0)
+    }
+}
+
+impl IHashTableDirectoryPageRead for WritableHashTableDirectoryPage<'_> {
+    fn get_page_id(&self) -> Result<PageId, HashTableDirectoryError> {
+        read_page_id_at_offset(&self.page, PAGE_ID_OFFSET_BYTES)
+    }
+
+    fn get_max_global_depth(&self) -> Result<u32, HashTableDirectoryError> {
+        Ok(max_global_depth(self.page.get_page_size()?))
+    }
+
+    fn get_global_depth(&self) -> Result<u32, HashTableDirectoryError> {
+        read_u32_at_offset(&self.page, GLOBAL_DEPTH_OFFSET_BYTES)
+    }
+
+    fn get_lsn(&self) -> Result<u32, HashTableDirectoryError> {
+        read_u32_at_offset(&self.page, LSN_OFFSET_BYTES)
+    }
+
+    fn get_bucket_page_id(&self, index: usize) -> Result<PageId, HashTableDirectoryError> {
+        read_bucket_page_id(&self.page, index)
+    }
+
+    fn get_local_depth(&self, index: usize) -> Result<u32, HashTableDirectoryError> {
+        read_local_depth(&self.page, index)
+    }
+}
+
+impl IHashTableDirectoryPageWrite for WritableHashTableDirectoryPage<'_> {
+    fn set_page_id(&mut self, page_id: PageId) -> Result<(), HashTableDirectoryError> {
+        self.write_at_offset(PAGE_ID_OFFSET_BYTES, &page_id.to_be_bytes())
+    }
+
+    fn set_global_depth(&mut self, global_depth: u32) -> Result<(), HashTableDirectoryError> {
+        if global_depth > self.get_max_global_depth()? {
+            return Err(HashTableDirectoryError::MaxDepthReached);
+        }
+        self.write_at_offset(GLOBAL_DEPTH_OFFSET_BYTES, &global_depth.to_be_bytes())
+    }
+
+    fn set_lsn(&mut self, lsn: u32) -> Result<(), HashTableDirectoryError> {
+        self.write_at_offset(LSN_OFFSET_BYTES, &lsn.to_be_bytes())
+    }
+
+    fn set_bucket_page_id(
+        &mut self,
+        index: usize,
+        page_id: PageId,
+    ) -> Result<(), HashTableDirectoryError> {
+        check_index(&self.page, index)?;
+        self.write_at_offset(
+            BUCKET_PAGE_IDS_START_OFFSET_BYTES + index * PAGE_ENTRY_SIZE_BYTES,
+            &page_id.to_be_bytes(),
+        )
+    }
+
+    fn set_local_depth(&mut self, index: usize, depth: u32) -> Result<(), HashTableDirectoryError> {
+        check_index(&self.page, index)?;
+        let page_size = self.page.get_page_size()?;
+        if depth > max_global_depth(page_size) {
+            return Err(HashTableDirectoryError::MaxDepthReached);
+        }
+        self.page.write_data(
+            local_depths_start_offset(page_size) + index * LOCAL_DEPTH_SIZE_BYTES,
+            &[depth as u8],
+        )?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dbms::buffer::pool_manager::{
+        testing::{create_testing_pool_manager, create_testing_pool_manager_with_page_size},
+        IBufferPoolManager,
+    };
+    use crate::dbms::types::{DEFAULT_PAGE_SIZE, MAX_PAGE_SIZE};
+
+    use super::*;
+    use rstest::*;
+
+    #[rstest]
+    #[case(DEFAULT_PAGE_SIZE, 8)]
+    #[case(MAX_PAGE_SIZE, 12)]
+    fn test_directory_fits_in_page(
+        #[case] page_size: usize,
+        #[case] expected_max_global_depth: u32,
+    ) {
+        assert_eq!(max_global_depth(page_size), expected_max_global_depth);
+        let max_directory_size = 1 << expected_max_global_depth;
+        assert!(local_depths_start_offset(page_size) + max_directory_size <= page_size);
+    }
+
+    #[rstest]
+    fn test_initialize() {
+        let pool_manager = create_testing_pool_manager(10);
+        let page = pool_manager.new_page().unwrap();
+        let mut directory = WritableHashTableDirectoryPage::new(page);
+
+        directory.initialize(123).unwrap();
+
+        assert_eq!(directory.get_page_id().unwrap(), 0);
+        assert_eq!(directory.get_global_depth().unwrap(), 0);
+        assert_eq!(directory.size().unwrap(), 1);
+        assert_eq!(directory.get_bucket_page_id(0).unwrap(), 123);
+        assert_eq!(directory.get_local_depth(0).unwrap(), 0);
+    }
+
+    #[rstest]
+    fn test_set_and_get_lsn() {
+        let pool_manager = create_testing_pool_manager(10);
+        let page = pool_manager.new_page().unwrap();
+        let mut directory = WritableHashTableDirectoryPage::new(page);
+
+        directory.set_lsn(123).unwrap();
+
+        assert_eq!(directory.get_lsn().unwrap(), 123);
+    }
+
+    #[rstest]
+    fn test_set_and_get_entries() {
+        let pool_manager = create_testing_pool_manager(10);
+        let page = pool_manager.new_page().unwrap();
+        let mut directory = WritableHashTableDirectoryPage::new(page);
+
+        directory.set_global_depth(3).unwrap();
+        directory.set_bucket_page_id(5, 123).unwrap();
+        directory.set_local_depth(5, 2).unwrap();
+
+        assert_eq!(directory.size().unwrap(), 8);
+        assert_eq!(directory.get_bucket_page_id(5).unwrap(), 123);
+        assert_eq!(directory.get_local_depth(5).unwrap(), 2);
+    }
+
+    #[rstest]
+    #[case(0, 1)]
+    #[case(2, 4)]
+    #[case(8, 256)]
+    fn test_index_out_of_range(#[case] global_depth: u32, #[case] index: usize) {
+        let pool_manager = create_testing_pool_manager(10);
+        let page = pool_manager.new_page().unwrap();
+        let mut directory = WritableHashTableDirectoryPage::new(page);
+        directory.set_global_depth(global_depth).unwrap();
+
+        let out_of_range = HashTableDirectoryError::IndexOutOfRange;
+        assert_eq!(
+            directory.get_bucket_page_id(index),
+            Err(out_of_range(index))
+        );
+        assert_eq!(directory.get_local_depth(index), Err(out_of_range(index)));
+        assert_eq!(
+            directory.set_bucket_page_id(index, 1),
+            Err(out_of_range(index))
+        );
+        assert_eq!(
+            directory.set_local_depth(index, 1),
+            Err(out_of_range(index))
+        );
+    }
+
+    #[rstest]
+    fn test_incr_global_depth_mirrors_entries() {
+        let pool_manager = create_testing_pool_manager(10);
+        let page = pool_manager.new_page().unwrap();
+        let mut directory = WritableHashTableDirectoryPage::new(page);
+        directory.initialize(10).unwrap();
+        directory.incr_global_depth().unwrap();
+        directory.set_bucket_page_id(1, 11).unwrap();
+        directory.set_local_depth(0, 1).unwrap();
+        directory.set_local_depth(1, 1).unwrap();
+
+        directory.incr_global_depth().unwrap();
+
+        assert_eq!(directory.get_global_depth().unwrap(), 2);
+        let entries = (0..directory.size().unwrap())
+            .map(|i| {
+                (
+                    directory.get_bucket_page_id(i).unwrap(),
+                    directory.get_local_depth(i).unwrap(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(entries, vec![(10, 1), (11, 1), (10, 1), (11, 1)]);
+    }
+
+    #[rstest]
+    #[case(DEFAULT_PAGE_SIZE)]
+    #[case(MAX_PAGE_SIZE)]
+    fn test_incr_global_depth_at_max(#[case] page_size: usize) {
+        let pool_manager = create_testing_pool_manager_with_page_size(10, page_size);
+        let page = pool_manager.new_page().unwrap();
+        let mut directory = WritableHashTableDirectoryPage::new(page);
+        directory.initialize(10).unwrap();
+        directory.set_local_depth(0, 3).unwrap();
+
+        let max_global_depth = max_global_depth(page_size);
+        assert_eq!(directory.get_max_global_depth(), Ok(max_global_depth));
+        for _ in 0..max_global_depth {
+            directory.incr_global_depth().unwrap();
+        }
+
+        let max_directory_size = 1 << max_global_depth;
+        assert_eq!(directory.size().unwrap(), max_directory_size);
+        assert_eq!(directory.get_bucket_page_id(max_directory_size - 1), Ok(10));
+        assert_eq!(directory.get_local_depth(max_directory_size - 1), Ok(3));
+        assert_eq!(
+            directory.incr_global_depth(),
+            Err(HashTableDirectoryError::MaxDepthReached)
+        );
+    }
+
+    #[rstest]
+    fn test_decr_global_depth() {
+        let pool_manager = create_testing_pool_manager(10);
+        let page = pool_manager.new_page().unwrap();
+        let mut directory = WritableHashTableDirectoryPage::new(page);
+        directory.initialize(10).unwrap();
+        directory.incr_global_depth().unwrap();
+
+        directory.decr_global_depth().unwrap();
+
+        assert_eq!(directory.get_global_depth().unwrap(), 0);
+        assert_eq!(
+            directory.decr_global_depth(),
+            Err(HashTableDirectoryError::MinDepthReached)
+        );
+    }
+
+    #[rstest]
+    fn test_can_shrink() {
+        let pool_manager = create_testing_pool_manager(10);
+        let page = pool_manager.new_page().unwrap();
+        let mut directory = WritableHashTableDirectoryPage::new(page);
+        directory.initialize(10).unwrap();
+        assert!(!directory.can_shrink().unwrap());
+
+        // Both entries still point at the one bucket with local depth 0
+        directory.incr_global_depth().unwrap();
+        assert!(directory.can_shrink().unwrap());
+
+        // Split the bucket
+        directory.set_bucket_page_id(1, 11).unwrap();
+        directory.set_local_depth(0, 1).unwrap();
+        directory.set_local_depth(1, 1).unwrap();
+        assert!(!directory.can_shrink().unwrap());
+    }
+
+    #[rstest]
+    fn test_threaded_read_entries() {
+        let pool_manager = create_testing_pool_manager(100);
+
+        {
+            for i in 0..11 {
+                let page = pool_manager.new_page().unwrap();
+                let mut directory = WritableHashTableDirectoryPage::new(page);
+                directory.initialize(i * 10).unwrap();
+                directory.incr_global_depth().unwrap();
+                directory.set_local_depth(1, i as u32 % 5).unwrap();
+            }
+        }
+
+        pool_manager.flush_all_pages().unwrap();
+
+        // Relying on the test logic that page IDs in the test pool manager count up from 0
+        let mut read_threads = Vec::new();
+        for i in 0..11 {
+            let buffer_pool_manager = pool_manager.clone();
+            read_threads.push(std::thread::spawn(move || {
+                let page = buffer_pool_manager.fetch_page(i).unwrap();
+                let directory = ReadOnlyHashTableDirectoryPage::new(page);
+
+                assert_eq!(directory.get_page_id().unwrap(), i);
+                assert_eq!(directory.get_global_depth().unwrap(), 1);
+                assert_eq!(directory.get_bucket_page_id(1).unwrap(), i * 10);
+                assert_eq!(directory.get_local_depth(0).unwrap(), 0);
+                assert_eq!(directory.get_local_depth(1).unwrap(), i as u32 % 5);
+            }));
+        }
+
+        for thread in read_threads {
+            thread.join().unwrap();
+        }
+    }
+}
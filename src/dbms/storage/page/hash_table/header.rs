@@ -1,5 +1,5 @@
 use crate::dbms::{
-    buffer::types::{ReadOnlyPage, WritablePage},
+    buffer::types::{PageGeneric, ReadOnlyPage, WritablePage},
     storage::page::PageError,
     types::PageId,
 };
@@ -18,11 +18,33 @@ impl From<PageError> for HashTableHeaderError {
 }
 
 const PAGE_ENTRY_SIZE_BYTES: usize = (PageId::BITS / 8) as usize;
+const U32_SIZE_BYTES: usize = (u32::BITS / 8) as usize;
 const PAGE_ID_OFFSET_BYTES: usize = 0;
-const SIZE_OFFSET_BYTES: usize = PAGE_ENTRY_SIZE_BYTES;
-const NEXT_IND_OFFSET_BYTES: usize = 2 * PAGE_ENTRY_SIZE_BYTES;
-const LSN_OFFSET_BYTES: usize = 3 * PAGE_ENTRY_SIZE_BYTES;
-const BLOCK_PAGE_IDS_START_OFFSET_BYTES: usize = 4 * PAGE_ENTRY_SIZE_BYTES;
+const SIZE_OFFSET_BYTES: usize = PAGE_ID_OFFSET_BYTES + PAGE_ENTRY_SIZE_BYTES;
+const NEXT_IND_OFFSET_BYTES: usize = SIZE_OFFSET_BYTES + U32_SIZE_BYTES;
+const LSN_OFFSET_BYTES: usize = NEXT_IND_OFFSET_BYTES + U32_SIZE_BYTES;
+const BLOCK_PAGE_IDS_START_OFFSET_BYTES: usize = LSN_OFFSET_BYTES + U32_SIZE_BYTES;
+
+/// Maximum number of block page IDs a header page of the given size can hold
+pub fn max_block_page_ids(page_size: usize) -> usize {
+    (page_size - BLOCK_PAGE_IDS_START_OFFSET_BYTES) / PAGE_ENTRY_SIZE_BYTES
+}
+
+fn read_u32_at_offset(
+    page: &PageGeneric,
+    offset_bytes: usize,
+) -> Result<u32, HashTableHeaderError> {
+    let data = page.read_data(offset_bytes, U32_SIZE_BYTES)?;
+    Ok(u32::from_be_bytes(data.as_slice().try_into().unwrap()))
+}
+
+fn read_page_id_at_offset(
+    page: &PageGeneric,
+    offset_bytes: usize,
+) -> Result<PageId, HashTableHeaderError> {
+    let data = page.read_data(offset_bytes, PAGE_ENTRY_SIZE_BYTES)?;
+    Ok(PageId::from_be_bytes(data.as_slice().try_into().unwrap()))
+}
 
 /// Interact with a page as a hash table header page.
 pub trait IHashTableHeaderPageRead {
@@ -61,32 +83,31 @@ pub struct ReadOnlyHashTableHeaderPage<'a> {
 }
 
 impl<'a> ReadOnlyHashTableHeaderPage<'a> {
-    fn read_single_at_offset(&self, offset_bytes: usize) -> Result<u32, HashTableHeaderError> {
-        let data = self.page.read_data(offset_bytes, PAGE_ENTRY_SIZE_BYTES)?;
-        let result = u32::from_be_bytes(data.as_slice().try_into().unwrap());
-        Ok(result)
+    pub fn new(page: ReadOnlyPage<'a>) -> Self {
+        Self { page }
     }
 }
 
 impl IHashTableHeaderPageRead for ReadOnlyHashTableHeaderPage<'_> {
     fn get_page_id(&self) -> Result<PageId, HashTableHeaderError> {
-        self.read_single_at_offset(PAGE_ID_OFFSET_BYTES)
+        read_page_id_at_offset(&self.page, PAGE_ID_OFFSET_BYTES)
     }
 
     fn get_size(&self) -> Result<u32, HashTableHeaderError> {
-        self.read_single_at_offset(SIZE_OFFSET_BYTES)
+        read_u32_at_offset(&self.page, SIZE_OFFSET_BYTES)
     }
 
     fn get_next_ind(&self) -> Result<u32, HashTableHeaderError> {
-        self.read_single_at_offset(NEXT_IND_OFFSET_BYTES)
+        read_u32_at_offset(&self.page, NEXT_IND_OFFSET_BYTES)
     }
 
     fn get_lsn(&self) -> Result<u32, HashTableHeaderError> {
-        self.read_single_at_offset(LSN_OFFSET_BYTES)
+        read_u32_at_offset(&self.page, LSN_OFFSET_BYTES)
     }
 
     fn get_block_page_id(&self, position: usize) -> Result<PageId, HashTableHeaderError> {
-        self.read_single_at_offset(
+        read_page_id_at_offset(
+            &self.page,
             BLOCK_PAGE_IDS_START_OFFSET_BYTES + position * PAGE_ENTRY_SIZE_BYTES,
         )
     }
@@ -97,24 +118,21 @@ pub struct WritableHashTableHeaderPage<'a> {
 }
 
 impl<'a> WritableHashTableHeaderPage<'a> {
-    fn read_single_at_offset(&self, offset_bytes: usize) -> Result<u32, HashTableHeaderError> {
-        let data = self.page.read_data(offset_bytes, PAGE_ENTRY_SIZE_BYTES)?;
-        let result = u32::from_be_bytes(data.as_slice().try_into().unwrap());
-        Ok(result)
+    pub fn new(page: WritablePage<'a>) -> Self {
+        Self { page }
     }
 
-    fn write_single_at_offset(
+    fn write_at_offset(
         &mut self,
         offset_bytes: usize,
-        value: u32,
+        bytes: &[u8],
     ) -> Result<(), HashTableHeaderError> {
-        self.page.write_data(offset_bytes, &value.to_be_bytes())?;
+        self.page.write_data(offset_bytes, bytes)?;
         Ok(())
     }
 
-    #[allow(dead_code)]
     /// Initialize a header page to contain its page ID
-    fn initialize(&mut self) -> Result<(), HashTableHeaderError> {
+    pub fn initialize(&mut self) -> Result<(), HashTableHeaderError> {
         let page_id = self.page.get_page_id()?;
         match page_id {
             Some(page_id) => {
@@ -128,23 +146,24 @@ impl<'a> WritableHashTableHeaderPage<'a> {
 
 impl IHashTableHeaderPageRead for WritableHashTableHeaderPage<'_> {
     fn get_page_id(&self) -> Result<PageId, HashTableHeaderError> {
-        self.read_single_at_offset(PAGE_ID_OFFSET_BYTES)
+        read_page_id_at_offset(&self.page, PAGE_ID_OFFSET_BYTES)
     }
 
     fn get_size(&self) -> Result<u32, HashTableHeaderError> {
-        self.read_single_at_offset(SIZE_OFFSET_BYTES)
+        read_u32_at_offset(&self.page, SIZE_OFFSET_BYTES)
     }
 
     fn get_next_ind(&self) -> Result<u32, HashTableHeaderError> {
-        self.read_single_at_offset(NEXT_IND_OFFSET_BYTES)
+        read_u32_at_offset(&self.page, NEXT_IND_OFFSET_BYTES)
     }
 
     fn get_lsn(&self) -> Result<u32, HashTableHeaderError> {
-        self.read_single_at_offset(LSN_OFFSET_BYTES)
+        read_u32_at_offset(&self.page, LSN_OFFSET_BYTES)
     }
 
     fn get_block_page_id(&self, position: usize) -> Result<PageId, HashTableHeaderError> {
-        self.read_single_at_offset(
+        read_page_id_at_offset(
+            &self.page,
             BLOCK_PAGE_IDS_START_OFFSET_BYTES + position * PAGE_ENTRY_SIZE_BYTES,
         )
     }
@@ -152,19 +171,19 @@ impl IHashTableHeaderPageRead for WritableHashTableHeaderPage<'_> {
 
 impl IHashTableHeaderPageWrite for WritableHashTableHeaderPage<'_> {
     fn set_page_id(&mut self, page_id: PageId) -> Result<(), HashTableHeaderError> {
-        self.write_single_at_offset(PAGE_ID_OFFSET_BYTES, page_id)
+        self.write_at_offset(PAGE_ID_OFFSET_BYTES, &page_id.to_be_bytes())
     }
 
     fn set_size(&mut self, size: u32) -> Result<(), HashTableHeaderError> {
-        self.write_single_at_offset(SIZE_OFFSET_BYTES, size)
+        self.write_at_offset(SIZE_OFFSET_BYTES, &size.to_be_bytes())
     }
 
     fn set_next_ind(&mut self, next_ind: u32) -> Result<(), HashTableHeaderError> {
-        self.write_single_at_offset(NEXT_IND_OFFSET_BYTES, next_ind)
+        self.write_at_offset(NEXT_IND_OFFSET_BYTES, &next_ind.to_be_bytes())
     }
 
     fn set_lsn(&mut self, lsn: u32) -> Result<(), HashTableHeaderError> {
-        self.write_single_at_offset(LSN_OFFSET_BYTES, lsn)
+        self.write_at_offset(LSN_OFFSET_BYTES, &lsn.to_be_bytes())
     }
 
     fn set_block_page_id(
@@ -172,9 +191,9 @@ impl IHashTableHeaderPageWrite for WritableHashTableHeaderPage<'_> {
         position: usize,
         page_id: PageId,
     ) -> Result<(), HashTableHeaderError> {
-        self.write_single_at_offset(
+        self.write_at_offset(
             BLOCK_PAGE_IDS_START_OFFSET_BYTES + position * PAGE_ENTRY_SIZE_BYTES,
-            page_id,
+            &page_id.to_be_bytes(),
         )
     }
 }
@@ -330,7 +349,7 @@ mod tests {
 
                     let page_size = hash_table_header_page_reader.get_size().unwrap();
 
-                    assert_eq!(page_size, i * 5);
+                    assert_eq!(page_size, i as u32 * 5);
                 }));
             }
         }
@@ -367,7 +386,7 @@ mod tests {
 
                     let page_next_ind = hash_table_header_page_reader.get_next_ind().unwrap();
 
-                    assert_eq!(page_next_ind, i * 5);
+                    assert_eq!(page_next_ind, i as u32 * 5);
                 }));
             }
         }
@@ -404,7 +423,7 @@ mod tests {
 
                     let page_lsn = hash_table_header_page_reader.get_lsn().unwrap();
 
-                    assert_eq!(page_lsn, i * 5);
+                    assert_eq!(page_lsn, i as u32 * 5);
                 }));
             }
         }
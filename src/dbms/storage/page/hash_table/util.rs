@@ -1,5 +1,3 @@
-use crate::dbms::types::PAGE_SIZE;
-
 #[derive(Debug)]
 pub struct PageLayout {
     pub occupancy_array_start: usize,
@@ -14,7 +12,10 @@ pub enum PageLayoutError {
 }
 
 #[allow(dead_code)]
-pub fn calculate_block_page_layout(value_size: usize) -> Result<PageLayout, PageLayoutError> {
+pub fn calculate_block_page_layout(
+    value_size: usize,
+    page_size: usize,
+) -> Result<PageLayout, PageLayoutError> {
     if value_size == 0 {
         return Err(PageLayoutError::BadValueSize(
             "Value size must be greater than 0".to_string(),
@@ -24,13 +25,13 @@ pub fn calculate_block_page_layout(value_size: usize) -> Result<PageLayout, Page
     let byte_size = 8;
 
     // Calculate the size of the bit arrays in bytes, rounded up to the nearest whole byte
-    let bit_array_bytes = |num_values| (num_values + byte_size - 1) / byte_size;
+    let bit_array_bytes = |num_values: usize| num_values.div_ceil(byte_size);
 
     // Calculate how many values can fit into the page with the given value size
     // and the size of the bit arrays. Start with a rough estimate and then decrease it
     // until it fits into the page.
-    let mut max_values = (PAGE_SIZE - 2) / (value_size + 1 / byte_size); // Subtract 2 for initial byte offsets
-    while bit_array_bytes(max_values) * 2 + max_values * value_size > PAGE_SIZE {
+    let mut max_values = (page_size - 2) / (value_size + 1 / byte_size); // Subtract 2 for initial byte offsets
+    while bit_array_bytes(max_values) * 2 + max_values * value_size > page_size {
         max_values -= 1;
     }
 
@@ -38,7 +39,7 @@ pub fn calculate_block_page_layout(value_size: usize) -> Result<PageLayout, Page
     if max_values == 0 {
         return Err(PageLayoutError::BadValueSize(format!(
             "Value size {} is too large for page size {}",
-            value_size, PAGE_SIZE
+            value_size, page_size
         )));
     }
 
@@ -53,32 +54,80 @@ pub fn calculate_block_page_layout(value_size: usize) -> Result<PageLayout, Page
     })
 }
 
+#[derive(Debug)]
+pub struct BucketPageLayout {
+    pub occupancy_array_start: usize,
+    pub value_array_start: usize,
+    pub max_values: usize,
+}
+
+/// Layout of an extendible hash table bucket page. Unlike block pages, bucket
+/// slots are reused once removed, so only an occupancy bit array is needed.
+pub fn calculate_bucket_page_layout(
+    value_size: usize,
+    page_size: usize,
+) -> Result<BucketPageLayout, PageLayoutError> {
+    if value_size == 0 {
+        return Err(PageLayoutError::BadValueSize(
+            "Value size must be greater than 0".to_string(),
+        ));
+    }
+
+    let byte_size = 8;
+    let bit_array_bytes = |num_values: usize| num_values.div_ceil(byte_size);
+
+    // Each value takes its own size plus one bit of the occupancy array
+    let mut max_values = page_size * byte_size / (value_size * byte_size + 1);
+    while bit_array_bytes(max_values) + max_values * value_size > page_size {
+        max_values -= 1;
+    }
+
+    if max_values == 0 {
+        return Err(PageLayoutError::BadValueSize(format!(
+            "Value size {} is too large for page size {}",
+            value_size, page_size
+        )));
+    }
+
+    Ok(BucketPageLayout {
+        occupancy_array_start: 0,
+        value_array_start: bit_array_bytes(max_values),
+        max_values,
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::dbms::types::DEFAULT_PAGE_SIZE;
     use rstest::*;
 
     #[rstest]
-    #[case(32, 0, 16, 32, 127)]
-    #[case(1, 0, 410, 820, 3276)]
-    #[case(10, 0, 50, 100, 399)]
-    #[case(256, 0, 2, 4, 15)]
-    #[case(4094, 0, 1, 2, 1)]
+    #[case(32, 4096, 0, 16, 32, 127)]
+    #[case(1, 4096, 0, 410, 820, 3276)]
+    #[case(10, 4096, 0, 50, 100, 399)]
+    #[case(256, 4096, 0, 2, 4, 15)]
+    #[case(4094, 4096, 0, 1, 2, 1)]
+    #[case(32, 65536, 0, 254, 508, 2032)]
+    #[case(1, 65536, 0, 6554, 13108, 52428)]
+    #[case(256, 65536, 0, 32, 64, 255)]
+    #[case(65534, 65536, 0, 1, 2, 1)]
     fn test_calculate_block_page_layout(
         #[case] value_size: usize,
+        #[case] page_size: usize,
         #[case] exp_occupancy_array_start: usize,
         #[case] exp_readability_array_start: usize,
         #[case] exp_value_array_start: usize,
         #[case] exp_max_values: usize,
     ) {
-        let layout = calculate_block_page_layout(value_size).unwrap();
+        let layout = calculate_block_page_layout(value_size, page_size).unwrap();
         assert_eq!(layout.occupancy_array_start, exp_occupancy_array_start);
         assert_eq!(layout.readability_array_start, exp_readability_array_start);
         assert_eq!(layout.value_array_start, exp_value_array_start);
         assert_eq!(layout.max_values, exp_max_values);
 
         let total_bytes = layout.value_array_start + layout.max_values * value_size;
-        assert!(total_bytes <= PAGE_SIZE);
+        assert!(total_bytes <= page_size);
     }
 
     #[rstest]
@@ -86,24 +135,69 @@ mod tests {
     #[case(4095)]
     #[case(10_000)]
     fn test_calculate_block_page_layout_too_large(#[case] value_size: usize) {
-        let layout = calculate_block_page_layout(value_size);
+        let layout = calculate_block_page_layout(value_size, DEFAULT_PAGE_SIZE);
         assert!(layout.is_err());
         assert_eq!(
             layout.unwrap_err(),
             PageLayoutError::BadValueSize(format!(
                 "Value size {} is too large for page size {}",
-                value_size, PAGE_SIZE
+                value_size, DEFAULT_PAGE_SIZE
             ))
         );
     }
 
     #[rstest]
     fn test_calculate_block_page_layout_size_0() {
-        let layout = calculate_block_page_layout(0);
+        let layout = calculate_block_page_layout(0, DEFAULT_PAGE_SIZE);
         assert!(layout.is_err());
         assert_eq!(
             layout.unwrap_err(),
             PageLayoutError::BadValueSize("Value size must be greater than 0".to_string())
         );
     }
+
+    #[rstest]
+    #[case(16, 4096, 32, 254)]
+    #[case(1, 4096, 455, 3640)]
+    #[case(10, 4096, 51, 404)]
+    #[case(256, 4096, 2, 15)]
+    #[case(4095, 4096, 1, 1)]
+    #[case(16, 65536, 508, 4064)]
+    #[case(1, 65536, 7282, 58254)]
+    #[case(65535, 65536, 1, 1)]
+    fn test_calculate_bucket_page_layout(
+        #[case] value_size: usize,
+        #[case] page_size: usize,
+        #[case] exp_value_array_start: usize,
+        #[case] exp_max_values: usize,
+    ) {
+        let layout = calculate_bucket_page_layout(value_size, page_size).unwrap();
+        assert_eq!(layout.occupancy_array_start, 0);
+        assert_eq!(layout.value_array_start, exp_value_array_start);
+        assert_eq!(layout.max_values, exp_max_values);
+
+        let total_bytes = layout.value_array_start + layout.max_values * value_size;
+        assert!(total_bytes <= page_size);
+    }
+
+    #[rstest]
+    #[case(4096)]
+    #[case(10_000)]
+    fn test_calculate_bucket_page_layout_too_large(#[case] value_size: usize) {
+        assert_eq!(
+            calculate_bucket_page_layout(value_size, DEFAULT_PAGE_SIZE).unwrap_err(),
+            PageLayoutError::BadValueSize(format!(
+                "Value size {} is too large for page size {}",
+                value_size, DEFAULT_PAGE_SIZE
+            ))
+        );
+    }
+
+    #[rstest]
+    fn test_calculate_bucket_page_layout_size_0() {
+        assert_eq!(
+            calculate_bucket_page_layout(0, DEFAULT_PAGE_SIZE).unwrap_err(),
+            PageLayoutError::BadValueSize("Value size must be greater than 0".to_string())
+        );
+    }
 }
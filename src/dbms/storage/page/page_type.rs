@@ -1,4 +1,4 @@
-use crate::dbms::types::{PageData, PageId, PAGE_SIZE};
+use crate::dbms::types::{PageData, PageId};
 
 // TODO: Out of range error
 pub type PageError = ();
@@ -16,6 +16,8 @@ pub trait IPage {
     fn write_data(&mut self, offset: usize, data: &[u8]) -> Result<(), PageError>;
     /// Get the page ID
     fn get_page_id(&self) -> Result<Option<PageId>, PageError>;
+    /// Get the size of the page's data in bytes
+    fn get_page_size(&self) -> Result<usize, PageError>;
     /// Get whether the page is dirty
     fn is_dirty(&self) -> Result<bool, PageError>;
     /// Set the page to dirty
@@ -43,9 +45,9 @@ pub struct Page {
 }
 
 impl Page {
-    pub fn new(page_id: Option<PageId>) -> Page {
+    pub fn new(page_id: Option<PageId>, page_size: usize) -> Page {
         Page {
-            data: [0; PAGE_SIZE],
+            data: vec![0; page_size],
             page_id,
             pin_count: 0,
             is_dirty: false,
@@ -55,7 +57,7 @@ impl Page {
 
 impl IPage for Page {
     fn get_data(&self) -> Result<PageData, PageError> {
-        Ok(self.data)
+        Ok(self.data.clone())
     }
 
     fn read_data(&self, offset: usize, len: usize) -> Result<Vec<u8>, PageError> {
@@ -63,6 +65,7 @@ impl IPage for Page {
     }
 
     fn set_data(&mut self, data: PageData) -> Result<(), PageError> {
+        debug_assert_eq!(data.len(), self.data.len());
         self.data = data;
         self.is_dirty = true;
         Ok(())
@@ -78,6 +81,10 @@ impl IPage for Page {
         Ok(self.page_id)
     }
 
+    fn get_page_size(&self) -> Result<usize, PageError> {
+        Ok(self.data.len())
+    }
+
     fn is_dirty(&self) -> Result<bool, PageError> {
         Ok(self.is_dirty)
     }
@@ -110,13 +117,14 @@ impl IPage for Page {
 
     fn clear(&mut self) -> Result<(), PageError> {
         self.page_id = None;
-        self.data = [0; PAGE_SIZE];
+        self.data.fill(0);
         self.pin_count = 0;
         self.is_dirty = false;
         Ok(())
     }
 
     fn overwrite(&mut self, page_id: Option<PageId>, data: PageData) -> Result<(), PageError> {
+        debug_assert_eq!(data.len(), self.data.len());
         self.page_id = page_id;
         self.data = data;
         self.pin_count = 0;
@@ -126,29 +134,31 @@ impl IPage for Page {
 }
 
 #[cfg(test)]
+#[allow(clippy::bool_assert_comparison)]
 mod tests {
     use super::*;
+    use crate::dbms::types::DEFAULT_PAGE_SIZE;
     use rstest::rstest;
 
     #[rstest]
     fn test_set_and_get_data() {
-        let mut page = Page::new(Some(0));
+        let mut page = Page::new(Some(0), DEFAULT_PAGE_SIZE);
         assert_eq!(page.is_dirty().unwrap(), false);
 
-        let new_data = [1; PAGE_SIZE];
-        let res = page.set_data(new_data);
+        let new_data = vec![1; DEFAULT_PAGE_SIZE];
+        let res = page.set_data(new_data.clone());
         assert_eq!(res, Ok(()));
 
         let data = page.get_data().unwrap();
 
-        assert_eq!(data.len(), PAGE_SIZE);
+        assert_eq!(data.len(), DEFAULT_PAGE_SIZE);
         assert_eq!(data[..], new_data);
         assert_eq!(page.is_dirty().unwrap(), true);
     }
 
     #[rstest]
     fn test_write_data() {
-        let mut page = Page::new(Some(0));
+        let mut page = Page::new(Some(0), DEFAULT_PAGE_SIZE);
         assert_eq!(page.is_dirty().unwrap(), false);
 
         let new_data = [1; 16];
@@ -157,22 +167,35 @@ mod tests {
 
         let data = page.get_data().unwrap();
 
-        assert_eq!(data.len(), PAGE_SIZE);
+        assert_eq!(data.len(), DEFAULT_PAGE_SIZE);
         assert_eq!(data[0..32], [0; 32]);
         assert_eq!(data[32..48], new_data);
-        assert_eq!(data[48..PAGE_SIZE], [0; PAGE_SIZE - 48]);
+        assert_eq!(data[48..DEFAULT_PAGE_SIZE], [0; DEFAULT_PAGE_SIZE - 48]);
         assert_eq!(page.is_dirty().unwrap(), true);
     }
 
+    #[rstest]
+    #[case(DEFAULT_PAGE_SIZE)]
+    #[case(65536)]
+    fn test_page_size(#[case] page_size: usize) {
+        let mut page = Page::new(Some(0), page_size);
+        assert_eq!(page.get_page_size().unwrap(), page_size);
+        assert_eq!(page.get_data().unwrap().len(), page_size);
+
+        page.write_data(page_size - 4, &[1; 4]).unwrap();
+        page.clear().unwrap();
+        assert_eq!(page.get_data().unwrap(), vec![0; page_size]);
+    }
+
     #[rstest]
     fn test_get_page_id() {
-        let page = Page::new(Some(123));
+        let page = Page::new(Some(123), DEFAULT_PAGE_SIZE);
         assert_eq!(page.get_page_id().unwrap(), Some(123));
     }
 
     #[rstest]
     fn test_set_dirty_clean() {
-        let mut page = Page::new(Some(0));
+        let mut page = Page::new(Some(0), DEFAULT_PAGE_SIZE);
         assert_eq!(page.is_dirty().unwrap(), false);
         let res1 = page.set_dirty();
         assert_eq!(res1, Ok(()));
@@ -184,7 +207,7 @@ mod tests {
 
     #[rstest]
     fn test_increase_pin_count() {
-        let mut page = Page::new(Some(0));
+        let mut page = Page::new(Some(0), DEFAULT_PAGE_SIZE);
         assert_eq!(page.get_pin_count().unwrap(), 0);
         let res1 = page.increase_pin_count();
         assert_eq!(res1, Ok(()));
@@ -196,7 +219,7 @@ mod tests {
 
     #[rstest]
     fn test_decrease_pin_count() {
-        let mut page = Page::new(Some(0));
+        let mut page = Page::new(Some(0), DEFAULT_PAGE_SIZE);
         let _ = page.increase_pin_count();
         let _ = page.increase_pin_count();
 
@@ -213,13 +236,13 @@ mod tests {
 
     #[rstest]
     fn test_overwrite() {
-        let mut page = Page::new(Some(0));
+        let mut page = Page::new(Some(0), DEFAULT_PAGE_SIZE);
         let _ = page.increase_pin_count();
         let _ = page.increase_pin_count();
         let _ = page.set_dirty();
 
-        let new_data = [1; PAGE_SIZE];
-        let res = page.overwrite(Some(123), new_data);
+        let new_data = vec![1; DEFAULT_PAGE_SIZE];
+        let res = page.overwrite(Some(123), new_data.clone());
         assert_eq!(res, Ok(()));
 
         assert_eq!(page.get_page_id().unwrap(), Some(123));
@@ -230,7 +253,7 @@ mod tests {
 
     #[rstest]
     fn test_clear() {
-        let mut page = Page::new(Some(0));
+        let mut page = Page::new(Some(0), DEFAULT_PAGE_SIZE);
         let _ = page.increase_pin_count();
         let _ = page.increase_pin_count();
         let _ = page.set_dirty();
@@ -241,6 +264,6 @@ mod tests {
         assert_eq!(page.get_page_id().unwrap(), None);
         assert_eq!(page.get_pin_count().unwrap(), 0);
         assert_eq!(page.is_dirty().unwrap(), false);
-        assert_eq!(page.get_data().unwrap(), [0; PAGE_SIZE]);
+        assert_eq!(page.get_data().unwrap(), [0; DEFAULT_PAGE_SIZE]);
     }
 }
@@ -0,0 +1,3 @@
+pub mod free_space_map;
+pub mod overflow;
+pub mod table_page;
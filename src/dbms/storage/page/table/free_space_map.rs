@@ -0,0 +1,458 @@
+use crate::dbms::{
+    buffer::types::{PageGeneric, ReadOnlyPage, WritablePage},
+    storage::page::PageError,
+    types::{PageId, INVALID_PAGE_ID},
+};
+
+const PAGE_ID_SIZE_BYTES: usize = 8;
+const PAGE_ENTRY_SIZE_BYTES: usize = 4;
+const NEXT_PAGE_ID_OFFSET_BYTES: usize = 0;
+const ENTRY_COUNT_OFFSET_BYTES: usize = PAGE_ID_SIZE_BYTES;
+const ENTRIES_START_OFFSET_BYTES: usize = ENTRY_COUNT_OFFSET_BYTES + PAGE_ENTRY_SIZE_BYTES;
+
+/// Each entry holds a table page's ID and its free space category
+const ENTRY_SIZE_BYTES: usize = PAGE_ID_SIZE_BYTES + 1;
+
+/// Most table pages that one free space map page of the given size can track
+pub const fn free_space_map_page_capacity(page_size: usize) -> usize {
+    (page_size - ENTRIES_START_OFFSET_BYTES) / ENTRY_SIZE_BYTES
+}
+
+/// Free space is tracked in steps of this many bytes, so it fits in one byte
+pub const fn free_space_category_bytes(page_size: usize) -> usize {
+    page_size / 256
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum FreeSpaceMapPageError {
+    EntryOutOfRange(usize),
+    PageError(PageError),
+}
+
+impl From<PageError> for FreeSpaceMapPageError {
+    fn from(e: PageError) -> Self {
+        FreeSpaceMapPageError::PageError(e)
+    }
+}
+
+/// The free space category for a page with this much free space. Rounds down,
+/// so a page always has at least as much room as its category says.
+pub fn free_space_category(free_space: usize, page_size: usize) -> u8 {
+    (free_space / free_space_category_bytes(page_size)).min(u8::MAX as usize) as u8
+}
+
+/// The lowest free space category of a page that's sure to have this much
+/// free space. Rounds up.
+pub fn needed_space_category(needed: usize, page_size: usize) -> u8 {
+    needed
+        .div_ceil(free_space_category_bytes(page_size))
+        .min(u8::MAX as usize) as u8
+}
+
+/// Interact with a page as part of a table's free space map, which records
+/// roughly how much free space each of the table's pages has. The map's pages
+/// form a singly linked chain, and each holds entries for some of the table's
+/// pages in the order they were added to the table.
+///
+/// Free space is recorded as a one byte category rather than an exact count,
+/// which is enough to tell whether a tuple is worth trying to insert into a
+/// page.
+pub trait IFreeSpaceMapPageRead {
+    /// The next page in the map, if there is one
+    fn get_next_page_id(&self) -> Result<Option<PageId>, FreeSpaceMapPageError>;
+    /// The number of table pages tracked by this page
+    fn get_entry_count(&self) -> Result<usize, FreeSpaceMapPageError>;
+    /// The table page and its free space category at an entry
+    fn get_entry(&self, index: usize) -> Result<(PageId, u8), FreeSpaceMapPageError>;
+    /// The entry for a table page, if it's tracked by this page
+    fn find_entry(&self, page_id: PageId) -> Result<Option<usize>, FreeSpaceMapPageError>;
+    /// The first table page tracked by this page with at least the given free
+    /// space category
+    fn find_page_with_category(
+        &self,
+        category: u8,
+    ) -> Result<Option<PageId>, FreeSpaceMapPageError>;
+}
+
+/// Interact with a page as part of a table's free space map.
+pub trait IFreeSpaceMapPageWrite: IFreeSpaceMapPageRead {
+    /// Set the next page in the map
+    fn set_next_page_id(&mut self, page_id: Option<PageId>) -> Result<(), FreeSpaceMapPageError>;
+    /// Start tracking a table page, returning `false` if this page is full
+    fn push_entry(&mut self, page_id: PageId, category: u8) -> Result<bool, FreeSpaceMapPageError>;
+    /// Change the free space category of the table page at an entry
+    fn set_category(&mut self, index: usize, category: u8) -> Result<(), FreeSpaceMapPageError>;
+}
+
+fn entry_address(index: usize) -> usize {
+    ENTRIES_START_OFFSET_BYTES + index * ENTRY_SIZE_BYTES
+}
+
+fn read_u32_at_offset(
+    page: &PageGeneric,
+    offset_bytes: usize,
+) -> Result<u32, FreeSpaceMapPageError> {
+    let data = page.read_data(offset_bytes, PAGE_ENTRY_SIZE_BYTES)?;
+    Ok(u32::from_be_bytes(data.as_slice().try_into().unwrap()))
+}
+
+fn read_page_id_at_offset(
+    page: &PageGeneric,
+    offset_bytes: usize,
+) -> Result<PageId, FreeSpaceMapPageError> {
+    let data = page.read_data(offset_bytes, PAGE_ID_SIZE_BYTES)?;
+    Ok(PageId::from_be_bytes(data.as_slice().try_into().unwrap()))
+}
+
+fn read_next_page_id(page: &PageGeneric) -> Result<Option<PageId>, FreeSpaceMapPageError> {
+    match read_page_id_at_offset(page, NEXT_PAGE_ID_OFFSET_BYTES)? {
+        INVALID_PAGE_ID => Ok(None),
+        page_id => Ok(Some(page_id)),
+    }
+}
+
+fn read_entry_count(page: &PageGeneric) -> Result<usize, FreeSpaceMapPageError> {
+    Ok(read_u32_at_offset(page, ENTRY_COUNT_OFFSET_BYTES)? as usize)
+}
+
+fn read_entries(page: &PageGeneric) -> Result<Vec<(PageId, u8)>, FreeSpaceMapPageError> {
+    let count = read_entry_count(page)?;
+    let data = page.read_data(ENTRIES_START_OFFSET_BYTES, count * ENTRY_SIZE_BYTES)?;
+    Ok(data
+        .chunks(ENTRY_SIZE_BYTES)
+        .map(|entry| {
+            let (page_id, category) = entry.split_at(PAGE_ID_SIZE_BYTES);
+            (
+                PageId::from_be_bytes(page_id.try_into().unwrap()),
+                category[0],
+            )
+        })
+        .collect())
+}
+
+fn read_entry(page: &PageGeneric, index: usize) -> Result<(PageId, u8), FreeSpaceMapPageError> {
+    if index >= read_entry_count(page)? {
+        return Err(FreeSpaceMapPageError::EntryOutOfRange(index));
+    }
+    let address = entry_address(index);
+    let page_id = read_page_id_at_offset(page, address)?;
+    let category = page.read_data(address + PAGE_ID_SIZE_BYTES, 1)?[0];
+    Ok((page_id, category))
+}
+
+fn find_entry(page: &PageGeneric, page_id: PageId) -> Result<Option<usize>, FreeSpaceMapPageError> {
+    Ok(read_entries(page)?
+        .into_iter()
+        .position(|(entry_page_id, _)| entry_page_id == page_id))
+}
+
+fn find_page_with_category(
+    page: &PageGeneric,
+    category: u8,
+) -> Result<Option<PageId>, FreeSpaceMapPageError> {
+    Ok(read_entries(page)?
+        .into_iter()
+        .find(|(_, entry_category)| *entry_category >= category)
+        .map(|(page_id, _)| page_id))
+}
+
+pub struct ReadOnlyFreeSpaceMapPage<'a> {
+    page: ReadOnlyPage<'a>,
+}
+
+impl<'a> ReadOnlyFreeSpaceMapPage<'a> {
+    pub fn new(page: ReadOnlyPage<'a>) -> Self {
+        Self { page }
+    }
+}
+
+impl IFreeSpaceMapPageRead for ReadOnlyFreeSpaceMapPage<'_> {
+    fn get_next_page_id(&self) -> Result<Option<PageId>, FreeSpaceMapPageError> {
+        read_next_page_id(&self.page)
+    }
+
+    fn get_entry_count(&self) -> Result<usize, FreeSpaceMapPageError> {
+        read_entry_count(&self.page)
+    }
+
+    fn get_entry(&self, index: usize) -> Result<(PageId, u8), FreeSpaceMapPageError> {
+        read_entry(&self.page, index)
+    }
+
+    fn find_entry(&self, page_id: PageId) -> Result<Option<usize>, FreeSpaceMapPageError> {
+        find_entry(&self.page, page_id)
+    }
+
+    fn find_page_with_category(
+        &self,
+        category: u8,
+    ) -> Result<Option<PageId>, FreeSpaceMapPageError> {
+        find_page_with_category(&self.page, category)
+    }
+}
+
+pub struct WritableFreeSpaceMapPage<'a> {
+    page: WritablePage<'a>,
+}
+
+impl<'a> WritableFreeSpaceMapPage<'a> {
+    pub fn new(page: WritablePage<'a>) -> Self {
+        Self { page }
+    }
+
+    /// Set up a new page as an empty free space map page, at the end of the
+    /// map.
+    pub fn initialize(page: WritablePage<'a>) -> Result<Self, FreeSpaceMapPageError> {
+        let mut map = Self::new(page);
+        map.set_next_page_id(None)?;
+        map.page
+            .write_data(ENTRY_COUNT_OFFSET_BYTES, &0u32.to_be_bytes())?;
+        Ok(map)
+    }
+}
+
+impl IFreeSpaceMapPageRead for WritableFreeSpaceMapPage<'_> {
+    fn get_next_page_id(&self) -> Result<Option<PageId>, FreeSpaceMapPageError> {
+        read_next_page_id(&self.page)
+    }
+
+    fn get_entry_count(&self) -> Result<usize, FreeSpaceMapPageError> {
+        read_entry_count(&self.page)
+    }
+
+    fn get_entry(&self, index: usize) -> Result<(PageId, u8), FreeSpaceMapPageError> {
+        read_entry(&self.page, index)
+    }
+
+    fn find_entry(&self, page_id: PageId) -> Result<Option<usize>, FreeSpaceMapPageError> {
+        find_entry(&self.page, page_id)
+    }
+
+    fn find_page_with_category(
+        &self,
+        category: u8,
+    ) -> Result<Option<PageId>, FreeSpaceMapPageError> {
+        find_page_with_category(&self.page, category)
+    }
+}
+
+impl IFreeSpaceMapPageWrite for WritableFreeSpaceMapPage<'_> {
+    fn set_next_page_id(&mut self, page_id: Option<PageId>) -> Result<(), FreeSpaceMapPageError> {
+        let page_id = page_id.unwrap_or(INVALID_PAGE_ID);
+        self.page
+            .write_data(NEXT_PAGE_ID_OFFSET_BYTES, &page_id.to_be_bytes())?;
+        Ok(())
+    }
+
+    fn push_entry(&mut self, page_id: PageId, category: u8) -> Result<bool, FreeSpaceMapPageError> {
+        let count = self.get_entry_count()?;
+        if count >= free_space_map_page_capacity(self.page.get_page_size()?) {
+            return Ok(false);
+        }
+
+        let mut entry = page_id.to_be_bytes().to_vec();
+        entry.push(category);
+        self.page.write_data(entry_address(count), &entry)?;
+        self.page
+            .write_data(ENTRY_COUNT_OFFSET_BYTES, &(count as u32 + 1).to_be_bytes())?;
+        Ok(true)
+    }
+
+    fn set_category(&mut self, index: usize, category: u8) -> Result<(), FreeSpaceMapPageError> {
+        if index >= self.get_entry_count()? {
+            return Err(FreeSpaceMapPageError::EntryOutOfRange(index));
+        }
+        self.page
+            .write_data(entry_address(index) + PAGE_ID_SIZE_BYTES, &[category])?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dbms::buffer::pool_manager::{
+        testing::create_testing_pool_manager, IBufferPoolManager,
+    };
+
+    use super::*;
+    use crate::dbms::types::DEFAULT_PAGE_SIZE;
+    use rstest::*;
+
+    const FREE_SPACE_MAP_PAGE_CAPACITY: usize = free_space_map_page_capacity(DEFAULT_PAGE_SIZE);
+
+    #[rstest]
+    fn test_initialize() {
+        let pool_manager = create_testing_pool_manager(10);
+        let page = WritableFreeSpaceMapPage::initialize(pool_manager.new_page().unwrap()).unwrap();
+
+        assert_eq!(page.get_next_page_id().unwrap(), None);
+        assert_eq!(page.get_entry_count().unwrap(), 0);
+        assert_eq!(page.find_page_with_category(0).unwrap(), None);
+    }
+
+    #[rstest]
+    fn test_set_next_page_id() {
+        let pool_manager = create_testing_pool_manager(10);
+        let mut page =
+            WritableFreeSpaceMapPage::initialize(pool_manager.new_page().unwrap()).unwrap();
+
+        page.set_next_page_id(Some(8)).unwrap();
+        assert_eq!(page.get_next_page_id().unwrap(), Some(8));
+        page.set_next_page_id(None).unwrap();
+        assert_eq!(page.get_next_page_id().unwrap(), None);
+    }
+
+    #[rstest]
+    fn test_push_entries() {
+        let pool_manager = create_testing_pool_manager(10);
+        let mut page =
+            WritableFreeSpaceMapPage::initialize(pool_manager.new_page().unwrap()).unwrap();
+
+        assert!(page.push_entry(4, 10).unwrap());
+        assert!(page.push_entry(7, 0).unwrap());
+        assert!(page.push_entry(5, 255).unwrap());
+
+        assert_eq!(page.get_entry_count().unwrap(), 3);
+        assert_eq!(page.get_entry(0).unwrap(), (4, 10));
+        assert_eq!(page.get_entry(1).unwrap(), (7, 0));
+        assert_eq!(page.get_entry(2).unwrap(), (5, 255));
+        assert_eq!(
+            page.get_entry(3),
+            Err(FreeSpaceMapPageError::EntryOutOfRange(3))
+        );
+    }
+
+    #[rstest]
+    fn test_push_entries_until_full() {
+        let pool_manager = create_testing_pool_manager(10);
+        let mut page =
+            WritableFreeSpaceMapPage::initialize(pool_manager.new_page().unwrap()).unwrap();
+
+        for i in 0..FREE_SPACE_MAP_PAGE_CAPACITY {
+            assert!(page.push_entry(i as PageId, (i % 256) as u8).unwrap());
+        }
+        assert!(!page.push_entry(10_000, 1).unwrap());
+
+        assert_eq!(
+            page.get_entry_count().unwrap(),
+            FREE_SPACE_MAP_PAGE_CAPACITY
+        );
+        let last = FREE_SPACE_MAP_PAGE_CAPACITY - 1;
+        assert_eq!(
+            page.get_entry(last).unwrap(),
+            (last as PageId, (last % 256) as u8)
+        );
+    }
+
+    #[rstest]
+    fn test_set_category() {
+        let pool_manager = create_testing_pool_manager(10);
+        let mut page =
+            WritableFreeSpaceMapPage::initialize(pool_manager.new_page().unwrap()).unwrap();
+        page.push_entry(4, 10).unwrap();
+        page.push_entry(7, 0).unwrap();
+
+        page.set_category(1, 200).unwrap();
+
+        assert_eq!(page.get_entry(0).unwrap(), (4, 10));
+        assert_eq!(page.get_entry(1).unwrap(), (7, 200));
+        assert_eq!(
+            page.set_category(2, 1),
+            Err(FreeSpaceMapPageError::EntryOutOfRange(2))
+        );
+    }
+
+    #[rstest]
+    #[case(4, Some(0))]
+    #[case(5, Some(2))]
+    #[case(9, None)]
+    fn test_find_entry(#[case] page_id: PageId, #[case] expected: Option<usize>) {
+        let pool_manager = create_testing_pool_manager(10);
+        let mut page =
+            WritableFreeSpaceMapPage::initialize(pool_manager.new_page().unwrap()).unwrap();
+        for page_id in [4, 7, 5] {
+            page.push_entry(page_id, 0).unwrap();
+        }
+
+        assert_eq!(page.find_entry(page_id).unwrap(), expected);
+    }
+
+    #[rstest]
+    #[case(0, Some(4))]
+    #[case(11, Some(7))]
+    #[case(200, Some(7))]
+    #[case(201, Some(5))]
+    #[case(255, None)]
+    fn test_find_page_with_category(#[case] category: u8, #[case] expected: Option<PageId>) {
+        let pool_manager = create_testing_pool_manager(10);
+        let mut page =
+            WritableFreeSpaceMapPage::initialize(pool_manager.new_page().unwrap()).unwrap();
+        page.push_entry(4, 10).unwrap();
+        page.push_entry(7, 200).unwrap();
+        page.push_entry(5, 254).unwrap();
+
+        assert_eq!(page.find_page_with_category(category).unwrap(), expected);
+    }
+
+    #[rstest]
+    #[case(0, 4096, 0)]
+    #[case(15, 4096, 0)]
+    #[case(16, 4096, 1)]
+    #[case(163, 4096, 10)]
+    #[case(4096, 4096, 255)]
+    #[case(255, 65536, 0)]
+    #[case(256, 65536, 1)]
+    #[case(65536, 65536, 255)]
+    fn test_free_space_category(
+        #[case] free_space: usize,
+        #[case] page_size: usize,
+        #[case] expected: u8,
+    ) {
+        assert_eq!(free_space_category(free_space, page_size), expected);
+    }
+
+    #[rstest]
+    #[case(0, 4096, 0)]
+    #[case(1, 4096, 1)]
+    #[case(16, 4096, 1)]
+    #[case(163, 4096, 11)]
+    #[case(4096, 4096, 255)]
+    #[case(257, 65536, 2)]
+    #[case(65536, 65536, 255)]
+    fn test_needed_space_category(
+        #[case] needed: usize,
+        #[case] page_size: usize,
+        #[case] expected: u8,
+    ) {
+        assert_eq!(needed_space_category(needed, page_size), expected);
+    }
+
+    #[rstest]
+    #[case(4096, 453)]
+    #[case(65536, 7280)]
+    fn test_free_space_map_page_capacity(#[case] page_size: usize, #[case] expected: usize) {
+        assert_eq!(free_space_map_page_capacity(page_size), expected);
+    }
+
+    #[rstest]
+    fn test_read_only_page() {
+        let pool_manager = create_testing_pool_manager(10);
+        let page_id = {
+            let mut page =
+                WritableFreeSpaceMapPage::initialize(pool_manager.new_page().unwrap()).unwrap();
+            page.set_next_page_id(Some(3)).unwrap();
+            page.push_entry(4, 10).unwrap();
+            page.push_entry(7, 200).unwrap();
+            0
+        };
+        pool_manager.unpin_page(page_id, true).unwrap();
+
+        let page = ReadOnlyFreeSpaceMapPage::new(pool_manager.fetch_page(page_id).unwrap());
+
+        assert_eq!(page.get_next_page_id().unwrap(), Some(3));
+        assert_eq!(page.get_entry_count().unwrap(), 2);
+        assert_eq!(page.get_entry(1).unwrap(), (7, 200));
+        assert_eq!(page.find_entry(7).unwrap(), Some(1));
+        assert_eq!(page.find_page_with_category(100).unwrap(), Some(7));
+    }
+}
@@ -0,0 +1,233 @@
+use crate::dbms::{
+    buffer::types::{PageGeneric, ReadOnlyPage, WritablePage},
+    storage::page::PageError,
+    types::{PageId, INVALID_PAGE_ID},
+};
+
+const PAGE_ID_SIZE_BYTES: usize = 8;
+const PAGE_ENTRY_SIZE_BYTES: usize = 4;
+const NEXT_PAGE_ID_OFFSET_BYTES: usize = 0;
+const DATA_SIZE_OFFSET_BYTES: usize = PAGE_ID_SIZE_BYTES;
+const DATA_START_OFFSET_BYTES: usize = DATA_SIZE_OFFSET_BYTES + PAGE_ENTRY_SIZE_BYTES;
+
+/// Most bytes of a tuple that one overflow page of the given size can hold
+pub const fn overflow_page_capacity(page_size: usize) -> usize {
+    page_size - DATA_START_OFFSET_BYTES
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum OverflowPageError {
+    /// More data than fits in the page
+    DataTooLarge(usize),
+    PageError(PageError),
+}
+
+impl From<PageError> for OverflowPageError {
+    fn from(e: PageError) -> Self {
+        OverflowPageError::PageError(e)
+    }
+}
+
+/// Interact with a page as an overflow page, holding part of a tuple too large
+/// to fit in a table page. A tuple's overflow pages form a singly linked
+/// chain, and its bytes are the concatenation of their data in order.
+pub trait IOverflowPageRead {
+    /// The next page in the chain, if there is one
+    fn get_next_page_id(&self) -> Result<Option<PageId>, OverflowPageError>;
+    /// The part of the tuple held by this page
+    fn get_data(&self) -> Result<Vec<u8>, OverflowPageError>;
+}
+
+/// Interact with a page as an overflow page.
+pub trait IOverflowPageWrite: IOverflowPageRead {
+    /// Set the next page in the chain
+    fn set_next_page_id(&mut self, page_id: Option<PageId>) -> Result<(), OverflowPageError>;
+    /// Set the part of the tuple held by this page
+    fn set_data(&mut self, data: &[u8]) -> Result<(), OverflowPageError>;
+}
+
+fn read_u32_at_offset(page: &PageGeneric, offset_bytes: usize) -> Result<u32, OverflowPageError> {
+    let data = page.read_data(offset_bytes, PAGE_ENTRY_SIZE_BYTES)?;
+    Ok(u32::from_be_bytes(data.as_slice().try_into().unwrap()))
+}
+
+fn read_next_page_id(page: &PageGeneric) -> Result<Option<PageId>, OverflowPageError> {
+    let data = page.read_data(NEXT_PAGE_ID_OFFSET_BYTES, PAGE_ID_SIZE_BYTES)?;
+    match PageId::from_be_bytes(data.as_slice().try_into().unwrap()) {
+        INVALID_PAGE_ID => Ok(None),
+        page_id => Ok(Some(page_id)),
+    }
+}
+
+fn read_data(page: &PageGeneric) -> Result<Vec<u8>, OverflowPageError> {
+    let size = read_u32_at_offset(page, DATA_SIZE_OFFSET_BYTES)? as usize;
+    Ok(page.read_data(DATA_START_OFFSET_BYTES, size)?)
+}
+
+pub struct ReadOnlyOverflowPage<'a> {
+    page: ReadOnlyPage<'a>,
+}
+
+impl<'a> ReadOnlyOverflowPage<'a> {
+    pub fn new(page: ReadOnlyPage<'a>) -> Self {
+        Self { page }
+    }
+}
+
+impl IOverflowPageRead for ReadOnlyOverflowPage<'_> {
+    fn get_next_page_id(&self) -> Result<Option<PageId>, OverflowPageError> {
+        read_next_page_id(&self.page)
+    }
+
+    fn get_data(&self) -> Result<Vec<u8>, OverflowPageError> {
+        read_data(&self.page)
+    }
+}
+
+pub struct WritableOverflowPage<'a> {
+    page: WritablePage<'a>,
+}
+
+impl<'a> WritableOverflowPage<'a> {
+    pub fn new(page: WritablePage<'a>) -> Self {
+        Self { page }
+    }
+
+    /// Set up a new page as an overflow page holding some data, at the end
+    /// of its chain.
+    pub fn initialize(page: WritablePage<'a>, data: &[u8]) -> Result<Self, OverflowPageError> {
+        let mut overflow = Self::new(page);
+        overflow.set_next_page_id(None)?;
+        overflow.set_data(data)?;
+        Ok(overflow)
+    }
+}
+
+impl IOverflowPageRead for WritableOverflowPage<'_> {
+    fn get_next_page_id(&self) -> Result<Option<PageId>, OverflowPageError> {
+        read_next_page_id(&self.page)
+    }
+
+    fn get_data(&self) -> Result<Vec<u8>, OverflowPageError> {
+        read_data(&self.page)
+    }
+}
+
+impl IOverflowPageWrite for WritableOverflowPage<'_> {
+    fn set_next_page_id(&mut self, page_id: Option<PageId>) -> Result<(), OverflowPageError> {
+        let page_id = page_id.unwrap_or(INVALID_PAGE_ID);
+        self.page
+            .write_data(NEXT_PAGE_ID_OFFSET_BYTES, &page_id.to_be_bytes())?;
+        Ok(())
+    }
+
+    fn set_data(&mut self, data: &[u8]) -> Result<(), OverflowPageError> {
+        if data.len() > overflow_page_capacity(self.page.get_page_size()?) {
+            return Err(OverflowPageError::DataTooLarge(data.len()));
+        }
+        self.page
+            .write_data(DATA_SIZE_OFFSET_BYTES, &(data.len() as u32).to_be_bytes())?;
+        self.page.write_data(DATA_START_OFFSET_BYTES, data)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dbms::buffer::pool_manager::{
+        testing::{create_testing_pool_manager, create_testing_pool_manager_with_page_size},
+        IBufferPoolManager,
+    };
+
+    use super::*;
+    use crate::dbms::types::DEFAULT_PAGE_SIZE;
+    use rstest::*;
+
+    const OVERFLOW_PAGE_CAPACITY: usize = overflow_page_capacity(DEFAULT_PAGE_SIZE);
+
+    #[rstest]
+    fn test_initialize() {
+        let pool_manager = create_testing_pool_manager(10);
+        let page =
+            WritableOverflowPage::initialize(pool_manager.new_page().unwrap(), b"data").unwrap();
+
+        assert_eq!(page.get_next_page_id().unwrap(), None);
+        assert_eq!(page.get_data().unwrap(), b"data".to_vec());
+    }
+
+    #[rstest]
+    fn test_set_next_page_id() {
+        let pool_manager = create_testing_pool_manager(10);
+        let mut page =
+            WritableOverflowPage::initialize(pool_manager.new_page().unwrap(), b"").unwrap();
+
+        page.set_next_page_id(Some(8)).unwrap();
+        assert_eq!(page.get_next_page_id().unwrap(), Some(8));
+        page.set_next_page_id(None).unwrap();
+        assert_eq!(page.get_next_page_id().unwrap(), None);
+    }
+
+    #[rstest]
+    #[case(0)]
+    #[case(100)]
+    #[case(OVERFLOW_PAGE_CAPACITY)]
+    fn test_set_data(#[case] size: usize) {
+        let pool_manager = create_testing_pool_manager(10);
+        let mut page =
+            WritableOverflowPage::initialize(pool_manager.new_page().unwrap(), b"old data")
+                .unwrap();
+        let data = (0..size).map(|i| i as u8).collect::<Vec<_>>();
+
+        page.set_data(&data).unwrap();
+
+        assert_eq!(page.get_data().unwrap(), data);
+    }
+
+    #[rstest]
+    fn test_set_data_too_large() {
+        let pool_manager = create_testing_pool_manager(10);
+        let mut page =
+            WritableOverflowPage::initialize(pool_manager.new_page().unwrap(), b"data").unwrap();
+
+        assert_eq!(
+            page.set_data(&vec![0u8; OVERFLOW_PAGE_CAPACITY + 1]),
+            Err(OverflowPageError::DataTooLarge(OVERFLOW_PAGE_CAPACITY + 1))
+        );
+        assert_eq!(page.get_data().unwrap(), b"data".to_vec());
+    }
+
+    #[rstest]
+    fn test_large_page() {
+        let page_size = 65536;
+        let pool_manager = create_testing_pool_manager_with_page_size(10, page_size);
+        let mut page =
+            WritableOverflowPage::initialize(pool_manager.new_page().unwrap(), b"").unwrap();
+        let data = vec![4u8; overflow_page_capacity(page_size)];
+
+        page.set_data(&data).unwrap();
+
+        assert_eq!(page.get_data().unwrap(), data);
+        assert_eq!(
+            page.set_data(&vec![0u8; data.len() + 1]),
+            Err(OverflowPageError::DataTooLarge(data.len() + 1))
+        );
+    }
+
+    #[rstest]
+    fn test_read_only_page() {
+        let pool_manager = create_testing_pool_manager(10);
+        let page_id = {
+            let mut page =
+                WritableOverflowPage::initialize(pool_manager.new_page().unwrap(), b"data")
+                    .unwrap();
+            page.set_next_page_id(Some(3)).unwrap();
+            0
+        };
+        pool_manager.unpin_page(page_id, true).unwrap();
+
+        let page = ReadOnlyOverflowPage::new(pool_manager.fetch_page(page_id).unwrap());
+
+        assert_eq!(page.get_next_page_id().unwrap(), Some(3));
+        assert_eq!(page.get_data().unwrap(), b"data".to_vec());
+    }
+}
@@ -0,0 +1,1046 @@
+use crate::dbms::{
+    buffer::types::{PageGeneric, ReadOnlyPage, WritablePage},
+    storage::page::PageError,
+    types::{PageId, INVALID_PAGE_ID},
+};
+
+const PAGE_ID_SIZE_BYTES: usize = 8;
+const PAGE_ENTRY_SIZE_BYTES: usize = 4;
+const PAGE_ID_OFFSET_BYTES: usize = 0;
+const PREV_PAGE_ID_OFFSET_BYTES: usize = PAGE_ID_SIZE_BYTES;
+const NEXT_PAGE_ID_OFFSET_BYTES: usize = 2 * PAGE_ID_SIZE_BYTES;
+const FREE_SPACE_POINTER_OFFSET_BYTES: usize = 3 * PAGE_ID_SIZE_BYTES;
+const SLOT_COUNT_OFFSET_BYTES: usize = FREE_SPACE_POINTER_OFFSET_BYTES + PAGE_ENTRY_SIZE_BYTES;
+const SLOTS_START_OFFSET_BYTES: usize = SLOT_COUNT_OFFSET_BYTES + PAGE_ENTRY_SIZE_BYTES;
+
+/// Each slot holds the offset of its tuple in the page and the tuple's size
+const SLOT_SIZE_BYTES: usize = 2 * PAGE_ENTRY_SIZE_BYTES;
+
+/// Set in a slot's size when its tuple is marked as deleted
+const DELETE_FLAG: u32 = 1 << 31;
+/// Set in a slot's size when it holds a pointer to overflow pages rather than
+/// the tuple itself
+const OVERFLOW_FLAG: u32 = 1 << 30;
+/// An overflow pointer is the first overflow page's ID and the tuple's size
+const OVERFLOW_POINTER_SIZE_BYTES: usize = PAGE_ID_SIZE_BYTES + PAGE_ENTRY_SIZE_BYTES;
+
+/// Largest tuple that fits in an empty page of the given size, alongside its
+/// slot
+pub const fn max_tuple_size(page_size: usize) -> usize {
+    page_size - SLOTS_START_OFFSET_BYTES - SLOT_SIZE_BYTES
+}
+
+/// Space a tuple takes up in the page. Small tuples still take enough for an
+/// overflow pointer, so any tuple can be swapped for one in place.
+fn tuple_space(size: usize) -> usize {
+    size.max(OVERFLOW_POINTER_SIZE_BYTES)
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum TablePageError {
+    /// Provided page ID is not set
+    NoPageId,
+    /// Slot is past the end of the slot directory
+    SlotOutOfRange(usize),
+    /// There isn't enough free space in the page for the tuple
+    NotEnoughSpace,
+    PageError(PageError),
+}
+
+impl From<PageError> for TablePageError {
+    fn from(e: PageError) -> Self {
+        TablePageError::PageError(e)
+    }
+}
+
+/// A tuple as it's stored in a slot: either the tuple itself, or for tuples
+/// too large to fit in a page, a pointer to the chain of overflow pages
+/// holding it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StoredTuple {
+    Inline(Vec<u8>),
+    Overflow { first_page_id: PageId, size: usize },
+}
+
+impl StoredTuple {
+    /// The bytes written to the page for the tuple, and whether they're an
+    /// overflow pointer
+    fn encode(&self) -> (Vec<u8>, bool) {
+        match self {
+            StoredTuple::Inline(tuple) => (tuple.clone(), false),
+            StoredTuple::Overflow {
+                first_page_id,
+                size,
+            } => {
+                let mut bytes = first_page_id.to_be_bytes().to_vec();
+                bytes.extend_from_slice(&(*size as u32).to_be_bytes());
+                (bytes, true)
+            }
+        }
+    }
+
+    /// Free space a page needs to take the tuple into a new slot
+    pub fn space_needed(&self) -> usize {
+        let size = match self {
+            StoredTuple::Inline(tuple) => tuple.len(),
+            StoredTuple::Overflow { .. } => OVERFLOW_POINTER_SIZE_BYTES,
+        };
+        tuple_space(size) + SLOT_SIZE_BYTES
+    }
+
+    fn decode(bytes: Vec<u8>, overflow: bool) -> Self {
+        if !overflow {
+            return StoredTuple::Inline(bytes);
+        }
+        let (page_id_bytes, size_bytes) = bytes.split_at(PAGE_ID_SIZE_BYTES);
+        StoredTuple::Overflow {
+            first_page_id: PageId::from_be_bytes(page_id_bytes.try_into().unwrap()),
+            size: u32::from_be_bytes(size_bytes.try_into().unwrap()) as usize,
+        }
+    }
+}
+
+/// Interact with a page as a slotted table page.
+///
+/// The page starts with a header and a directory of slots, which grows
+/// forward, and the tuples themselves are packed in from the end of the page
+/// backward. The free space pointer marks where the tuple data starts, so the
+/// free space is whatever's left between the two.
+///
+/// A tuple keeps its slot for as long as it's in the page, so the slot number
+/// can be used to refer to it. Deleting a tuple takes two steps: it's marked
+/// as deleted first, which hides it but keeps its data, and then the delete is
+/// applied, which empties the slot so it can be used by a later insert.
+///
+/// The page doesn't manage overflow pages itself, it only stores pointers to
+/// them. It's up to the table to create and free them.
+pub trait ITablePageRead {
+    /// Get the page ID
+    fn get_page_id(&self) -> Result<PageId, TablePageError>;
+    /// The page before this one in the table, if there is one
+    fn get_prev_page_id(&self) -> Result<Option<PageId>, TablePageError>;
+    /// The page after this one in the table, if there is one
+    fn get_next_page_id(&self) -> Result<Option<PageId>, TablePageError>;
+    /// Number of slots in the slot directory, including empty ones
+    fn get_slot_count(&self) -> Result<usize, TablePageError>;
+    /// Bytes free between the slot directory and the tuple data
+    fn get_free_space(&self) -> Result<usize, TablePageError>;
+    /// The tuple in a slot, if the slot holds one that isn't deleted
+    fn get_tuple(&self, slot: usize) -> Result<Option<StoredTuple>, TablePageError>;
+    /// Whether the tuple in a slot is marked as deleted
+    fn is_deleted(&self, slot: usize) -> Result<bool, TablePageError>;
+}
+
+/// Interact with a page as a slotted table page.
+pub trait ITablePageWrite: ITablePageRead {
+    /// Set the page before this one in the table
+    fn set_prev_page_id(&mut self, page_id: Option<PageId>) -> Result<(), TablePageError>;
+    /// Set the page after this one in the table
+    fn set_next_page_id(&mut self, page_id: Option<PageId>) -> Result<(), TablePageError>;
+    /// Add a tuple to the page, returning its slot, or `None` if there isn't
+    /// room for it.
+    fn insert_tuple(&mut self, tuple: &StoredTuple) -> Result<Option<usize>, TablePageError>;
+    /// Replace the tuple in a slot. Returns `false` if the slot doesn't hold a
+    /// tuple or it's marked as deleted. The page is compacted if the new tuple
+    /// won't fit otherwise.
+    fn update_tuple(&mut self, slot: usize, tuple: &StoredTuple) -> Result<bool, TablePageError>;
+    /// Mark the tuple in a slot as deleted. Returns `false` if the slot
+    /// doesn't hold a tuple or it's already marked.
+    fn mark_delete(&mut self, slot: usize) -> Result<bool, TablePageError>;
+    /// Remove the tuple in a slot for good, whether or not it's been marked as
+    /// deleted, and empty the slot. Returns the tuple that was removed, or
+    /// `None` if the slot was already empty.
+    fn apply_delete(&mut self, slot: usize) -> Result<Option<StoredTuple>, TablePageError>;
+    /// Pack the tuples together at the end of the page, so the space left
+    /// behind by updates and deletes is free again. Tuples keep their slots.
+    /// Returns how many bytes of free space were reclaimed.
+    fn compact(&mut self) -> Result<usize, TablePageError>;
+}
+
+/// A slot's entry in the slot directory
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Slot {
+    offset: usize,
+    size: usize,
+    deleted: bool,
+    overflow: bool,
+}
+
+impl Slot {
+    /// Tuple data is always past the slot directory, so no tuple is at offset
+    /// zero
+    const EMPTY: Slot = Slot {
+        offset: 0,
+        size: 0,
+        deleted: false,
+        overflow: false,
+    };
+
+    fn is_empty(&self) -> bool {
+        self.offset == 0
+    }
+}
+
+fn slot_address(slot: usize) -> usize {
+    SLOTS_START_OFFSET_BYTES + slot * SLOT_SIZE_BYTES
+}
+
+fn read_u32_at_offset(page: &PageGeneric, offset_bytes: usize) -> Result<u32, TablePageError> {
+    let data = page.read_data(offset_bytes, PAGE_ENTRY_SIZE_BYTES)?;
+    Ok(u32::from_be_bytes(data.as_slice().try_into().unwrap()))
+}
+
+fn write_u32_at_offset(
+    page: &mut PageGeneric,
+    offset_bytes: usize,
+    value: u32,
+) -> Result<(), TablePageError> {
+    page.write_data(offset_bytes, &value.to_be_bytes())?;
+    Ok(())
+}
+
+fn read_u64_at_offset(page: &PageGeneric, offset_bytes: usize) -> Result<u64, TablePageError> {
+    let data = page.read_data(offset_bytes, PAGE_ID_SIZE_BYTES)?;
+    Ok(u64::from_be_bytes(data.as_slice().try_into().unwrap()))
+}
+
+fn write_u64_at_offset(
+    page: &mut PageGeneric,
+    offset_bytes: usize,
+    value: u64,
+) -> Result<(), TablePageError> {
+    page.write_data(offset_bytes, &value.to_be_bytes())?;
+    Ok(())
+}
+
+fn read_page_id_at_offset(
+    page: &PageGeneric,
+    offset_bytes: usize,
+) -> Result<Option<PageId>, TablePageError> {
+    match read_u64_at_offset(page, offset_bytes)? {
+        INVALID_PAGE_ID => Ok(None),
+        page_id => Ok(Some(page_id)),
+    }
+}
+
+fn write_page_id_at_offset(
+    page: &mut PageGeneric,
+    offset_bytes: usize,
+    page_id: Option<PageId>,
+) -> Result<(), TablePageError> {
+    write_u64_at_offset(page, offset_bytes, page_id.unwrap_or(INVALID_PAGE_ID))
+}
+
+fn read_slot_count(page: &PageGeneric) -> Result<usize, TablePageError> {
+    Ok(read_u32_at_offset(page, SLOT_COUNT_OFFSET_BYTES)? as usize)
+}
+
+fn read_free_space_pointer(page: &PageGeneric) -> Result<usize, TablePageError> {
+    Ok(read_u32_at_offset(page, FREE_SPACE_POINTER_OFFSET_BYTES)? as usize)
+}
+
+fn read_free_space(page: &PageGeneric) -> Result<usize, TablePageError> {
+    Ok(read_free_space_pointer(page)? - slot_address(read_slot_count(page)?))
+}
+
+fn read_slot(page: &PageGeneric, slot: usize) -> Result<Slot, TablePageError> {
+    if slot >= read_slot_count(page)? {
+        return Err(TablePageError::SlotOutOfRange(slot));
+    }
+    let address = slot_address(slot);
+    let offset = read_u32_at_offset(page, address)? as usize;
+    let size = read_u32_at_offset(page, address + PAGE_ENTRY_SIZE_BYTES)?;
+    Ok(Slot {
+        offset,
+        size: (size & !(DELETE_FLAG | OVERFLOW_FLAG)) as usize,
+        deleted: size & DELETE_FLAG != 0,
+        overflow: size & OVERFLOW_FLAG != 0,
+    })
+}
+
+fn read_stored_tuple(page: &PageGeneric, entry: Slot) -> Result<StoredTuple, TablePageError> {
+    let bytes = page.read_data(entry.offset, entry.size)?;
+    Ok(StoredTuple::decode(bytes, entry.overflow))
+}
+
+fn read_tuple(page: &PageGeneric, slot: usize) -> Result<Option<StoredTuple>, TablePageError> {
+    let entry = read_slot(page, slot)?;
+    if entry.is_empty() || entry.deleted {
+        return Ok(None);
+    }
+    Ok(Some(read_stored_tuple(page, entry)?))
+}
+
+pub struct ReadOnlyTablePage<'a> {
+    page: ReadOnlyPage<'a>,
+}
+
+impl<'a> ReadOnlyTablePage<'a> {
+    pub fn new(page: ReadOnlyPage<'a>) -> Self {
+        Self { page }
+    }
+}
+
+impl ITablePageRead for ReadOnlyTablePage<'_> {
+    fn get_page_id(&self) -> Result<PageId, TablePageError> {
+        read_u64_at_offset(&self.page, PAGE_ID_OFFSET_BYTES)
+    }
+
+    fn get_prev_page_id(&self) -> Result<Option<PageId>, TablePageError> {
+        read_page_id_at_offset(&self.page, PREV_PAGE_ID_OFFSET_BYTES)
+    }
+
+    fn get_next_page_id(&self) -> Result<Option<PageId>, TablePageError> {
+        read_page_id_at_offset(&self.page, NEXT_PAGE_ID_OFFSET_BYTES)
+    }
+
+    fn get_slot_count(&self) -> Result<usize, TablePageError> {
+        read_slot_count(&self.page)
+    }
+
+    fn get_free_space(&self) -> Result<usize, TablePageError> {
+        read_free_space(&self.page)
+    }
+
+    fn get_tuple(&self, slot: usize) -> Result<Option<StoredTuple>, TablePageError> {
+        read_tuple(&self.page, slot)
+    }
+
+    fn is_deleted(&self, slot: usize) -> Result<bool, TablePageError> {
+        Ok(read_slot(&self.page, slot)?.deleted)
+    }
+}
+
+pub struct WritableTablePage<'a> {
+    page: WritablePage<'a>,
+}
+
+impl<'a> WritableTablePage<'a> {
+    pub fn new(page: WritablePage<'a>) -> Self {
+        Self { page }
+    }
+
+    /// Set up a new page as an empty table page, following on from the given
+    /// page in the table.
+    pub fn initialize(
+        mut page: WritablePage<'a>,
+        prev_page_id: Option<PageId>,
+    ) -> Result<Self, TablePageError> {
+        let page_id = page.get_page_id()?.ok_or(TablePageError::NoPageId)?;
+        write_u64_at_offset(&mut page, PAGE_ID_OFFSET_BYTES, page_id)?;
+        write_page_id_at_offset(&mut page, PREV_PAGE_ID_OFFSET_BYTES, prev_page_id)?;
+        write_page_id_at_offset(&mut page, NEXT_PAGE_ID_OFFSET_BYTES, None)?;
+        let page_size = page.get_page_size()?;
+        write_u32_at_offset(&mut page, FREE_SPACE_POINTER_OFFSET_BYTES, page_size as u32)?;
+        write_u32_at_offset(&mut page, SLOT_COUNT_OFFSET_BYTES, 0)?;
+        Ok(Self::new(page))
+    }
+
+    fn write_slot(&mut self, slot: usize, entry: Slot) -> Result<(), TablePageError> {
+        let mut size = entry.size as u32;
+        if entry.deleted {
+            size |= DELETE_FLAG;
+        }
+        if entry.overflow {
+            size |= OVERFLOW_FLAG;
+        }
+        let address = slot_address(slot);
+        write_u32_at_offset(&mut self.page, address, entry.offset as u32)?;
+        write_u32_at_offset(&mut self.page, address + PAGE_ENTRY_SIZE_BYTES, size)
+    }
+
+    /// Take space for a tuple from the end of the free space, returning its
+    /// offset
+    fn allocate(&mut self, size: usize) -> Result<usize, TablePageError> {
+        let offset = read_free_space_pointer(&self.page)? - size;
+        write_u32_at_offset(
+            &mut self.page,
+            FREE_SPACE_POINTER_OFFSET_BYTES,
+            offset as u32,
+        )?;
+        Ok(offset)
+    }
+}
+
+impl ITablePageRead for WritableTablePage<'_> {
+    fn get_page_id(&self) -> Result<PageId, TablePageError> {
+        read_u64_at_offset(&self.page, PAGE_ID_OFFSET_BYTES)
+    }
+
+    fn get_prev_page_id(&self) -> Result<Option<PageId>, TablePageError> {
+        read_page_id_at_offset(&self.page, PREV_PAGE_ID_OFFSET_BYTES)
+    }
+
+    fn get_next_page_id(&self) -> Result<Option<PageId>, TablePageError> {
+        read_page_id_at_offset(&self.page, NEXT_PAGE_ID_OFFSET_BYTES)
+    }
+
+    fn get_slot_count(&self) -> Result<usize, TablePageError> {
+        read_slot_count(&self.page)
+    }
+
+    fn get_free_space(&self) -> Result<usize, TablePageError> {
+        read_free_space(&self.page)
+    }
+
+    fn get_tuple(&self, slot: usize) -> Result<Option<StoredTuple>, TablePageError> {
+        read_tuple(&self.page, slot)
+    }
+
+    fn is_deleted(&self, slot: usize) -> Result<bool, TablePageError> {
+        Ok(read_slot(&self.page, slot)?.deleted)
+    }
+}
+
+impl ITablePageWrite for WritableTablePage<'_> {
+    fn set_prev_page_id(&mut self, page_id: Option<PageId>) -> Result<(), TablePageError> {
+        write_page_id_at_offset(&mut self.page, PREV_PAGE_ID_OFFSET_BYTES, page_id)
+    }
+
+    fn set_next_page_id(&mut self, page_id: Option<PageId>) -> Result<(), TablePageError> {
+        write_page_id_at_offset(&mut self.page, NEXT_PAGE_ID_OFFSET_BYTES, page_id)
+    }
+
+    fn insert_tuple(&mut self, tuple: &StoredTuple) -> Result<Option<usize>, TablePageError> {
+        let (tuple, overflow) = tuple.encode();
+
+        // Reuse an empty slot if there is one, otherwise the directory grows
+        let slot_count = self.get_slot_count()?;
+        let empty_slot = (0..slot_count)
+            .map(|slot| Ok((slot, read_slot(&self.page, slot)?)))
+            .collect::<Result<Vec<_>, TablePageError>>()?
+            .into_iter()
+            .find(|(_, entry)| entry.is_empty())
+            .map(|(slot, _)| slot);
+        let needed = match empty_slot {
+            Some(_) => tuple_space(tuple.len()),
+            None => tuple_space(tuple.len()) + SLOT_SIZE_BYTES,
+        };
+        if needed > self.get_free_space()? {
+            return Ok(None);
+        }
+
+        let slot = match empty_slot {
+            Some(slot) => slot,
+            None => {
+                write_u32_at_offset(
+                    &mut self.page,
+                    SLOT_COUNT_OFFSET_BYTES,
+                    (slot_count + 1) as u32,
+                )?;
+                slot_count
+            }
+        };
+        let offset = self.allocate(tuple_space(tuple.len()))?;
+        self.page.write_data(offset, &tuple)?;
+        self.write_slot(
+            slot,
+            Slot {
+                offset,
+                size: tuple.len(),
+                deleted: false,
+                overflow,
+            },
+        )?;
+        Ok(Some(slot))
+    }
+
+    fn update_tuple(&mut self, slot: usize, tuple: &StoredTuple) -> Result<bool, TablePageError> {
+        let entry = read_slot(&self.page, slot)?;
+        if entry.is_empty() || entry.deleted {
+            return Ok(false);
+        }
+        let (tuple, overflow) = tuple.encode();
+
+        // A tuple that's no bigger stays where it is, otherwise it moves to
+        // the free space, compacting the page first if that's what it takes.
+        // Either way the bytes it no longer uses are left unused until the
+        // page is compacted.
+        let space = tuple_space(tuple.len());
+        let old_space = tuple_space(entry.size);
+        let offset = if space <= old_space {
+            entry.offset
+        } else if space <= self.get_free_space()?
+            || (self.compact()? > 0 && space <= self.get_free_space()?)
+        {
+            self.allocate(space)?
+        } else if space <= self.get_free_space()? + old_space {
+            // Only fits without the old tuple, so it's dropped before the
+            // page is compacted again
+            self.write_slot(slot, Slot::EMPTY)?;
+            self.compact()?;
+            self.allocate(space)?
+        } else {
+            return Err(TablePageError::NotEnoughSpace);
+        };
+        self.page.write_data(offset, &tuple)?;
+        self.write_slot(
+            slot,
+            Slot {
+                offset,
+                size: tuple.len(),
+                deleted: false,
+                overflow,
+            },
+        )?;
+        Ok(true)
+    }
+
+    fn mark_delete(&mut self, slot: usize) -> Result<bool, TablePageError> {
+        let entry = read_slot(&self.page, slot)?;
+        if entry.is_empty() || entry.deleted {
+            return Ok(false);
+        }
+        self.write_slot(
+            slot,
+            Slot {
+                deleted: true,
+                ..entry
+            },
+        )?;
+        Ok(true)
+    }
+
+    fn apply_delete(&mut self, slot: usize) -> Result<Option<StoredTuple>, TablePageError> {
+        let entry = read_slot(&self.page, slot)?;
+        if entry.is_empty() {
+            return Ok(None);
+        }
+        let tuple = read_stored_tuple(&self.page, entry)?;
+        // The tuple's bytes are left unused until the page is compacted
+        self.write_slot(slot, Slot::EMPTY)?;
+        Ok(Some(tuple))
+    }
+
+    fn compact(&mut self) -> Result<usize, TablePageError> {
+        let free_space = self.get_free_space()?;
+
+        // Read everything out first, since tuples can overlap where others
+        // are moving to
+        let mut entries = vec![];
+        for slot in 0..self.get_slot_count()? {
+            let entry = read_slot(&self.page, slot)?;
+            if !entry.is_empty() {
+                let bytes = self.page.read_data(entry.offset, entry.size)?;
+                entries.push((slot, entry, bytes));
+            }
+        }
+
+        let mut offset = self.page.get_page_size()?;
+        for (slot, entry, bytes) in entries {
+            offset -= tuple_space(bytes.len());
+            self.page.write_data(offset, &bytes)?;
+            self.write_slot(slot, Slot { offset, ..entry })?;
+        }
+        write_u32_at_offset(
+            &mut self.page,
+            FREE_SPACE_POINTER_OFFSET_BYTES,
+            offset as u32,
+        )?;
+
+        Ok(self.get_free_space()? - free_space)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dbms::buffer::pool_manager::{
+        testing::{create_testing_pool_manager, create_testing_pool_manager_with_page_size},
+        IBufferPoolManager,
+    };
+
+    use super::*;
+    use crate::dbms::types::DEFAULT_PAGE_SIZE;
+    use rstest::*;
+
+    const MAX_TUPLE_SIZE: usize = max_tuple_size(DEFAULT_PAGE_SIZE);
+
+    fn inline(tuple: &[u8]) -> StoredTuple {
+        StoredTuple::Inline(tuple.to_vec())
+    }
+
+    #[rstest]
+    fn test_initialize() {
+        let pool_manager = create_testing_pool_manager(10);
+        let page =
+            WritableTablePage::initialize(pool_manager.new_page().unwrap(), Some(5)).unwrap();
+
+        assert_eq!(page.get_page_id().unwrap(), 0);
+        assert_eq!(page.get_prev_page_id().unwrap(), Some(5));
+        assert_eq!(page.get_next_page_id().unwrap(), None);
+        assert_eq!(page.get_slot_count().unwrap(), 0);
+        assert_eq!(
+            page.get_free_space().unwrap(),
+            MAX_TUPLE_SIZE + SLOT_SIZE_BYTES
+        );
+    }
+
+    #[rstest]
+    fn test_set_page_links() {
+        let pool_manager = create_testing_pool_manager(10);
+        let mut page =
+            WritableTablePage::initialize(pool_manager.new_page().unwrap(), None).unwrap();
+
+        page.set_prev_page_id(Some(3)).unwrap();
+        page.set_next_page_id(Some(7)).unwrap();
+        assert_eq!(page.get_prev_page_id().unwrap(), Some(3));
+        assert_eq!(page.get_next_page_id().unwrap(), Some(7));
+
+        page.set_prev_page_id(None).unwrap();
+        page.set_next_page_id(None).unwrap();
+        assert_eq!(page.get_prev_page_id().unwrap(), None);
+        assert_eq!(page.get_next_page_id().unwrap(), None);
+    }
+
+    #[rstest]
+    fn test_insert_and_get_tuples() {
+        let pool_manager = create_testing_pool_manager(10);
+        let mut page =
+            WritableTablePage::initialize(pool_manager.new_page().unwrap(), None).unwrap();
+
+        assert_eq!(page.insert_tuple(&inline(b"first")).unwrap(), Some(0));
+        assert_eq!(page.insert_tuple(&inline(b"")).unwrap(), Some(1));
+        assert_eq!(
+            page.insert_tuple(&inline(b"the third tuple")).unwrap(),
+            Some(2)
+        );
+
+        assert_eq!(page.get_slot_count().unwrap(), 3);
+        assert_eq!(page.get_tuple(0).unwrap(), Some(inline(b"first")));
+        assert_eq!(
+            page.get_tuple(1).unwrap(),
+            Some(StoredTuple::Inline(vec![]))
+        );
+        assert_eq!(page.get_tuple(2).unwrap(), Some(inline(b"the third tuple")));
+        assert_eq!(
+            page.get_free_space().unwrap(),
+            // The first two are small, but still take room for a pointer
+            MAX_TUPLE_SIZE + SLOT_SIZE_BYTES
+                - 3 * SLOT_SIZE_BYTES
+                - 2 * OVERFLOW_POINTER_SIZE_BYTES
+                - 15
+        );
+    }
+
+    #[rstest]
+    fn test_get_tuple_out_of_range() {
+        let pool_manager = create_testing_pool_manager(10);
+        let mut page =
+            WritableTablePage::initialize(pool_manager.new_page().unwrap(), None).unwrap();
+        page.insert_tuple(&inline(b"tuple")).unwrap();
+
+        assert_eq!(page.get_tuple(1), Err(TablePageError::SlotOutOfRange(1)));
+        assert_eq!(page.mark_delete(1), Err(TablePageError::SlotOutOfRange(1)));
+    }
+
+    #[rstest]
+    fn test_insert_until_full() {
+        let pool_manager = create_testing_pool_manager(10);
+        let mut page =
+            WritableTablePage::initialize(pool_manager.new_page().unwrap(), None).unwrap();
+
+        let tuple = [7u8; 100];
+        let mut inserted = 0;
+        while page.insert_tuple(&inline(&tuple)).unwrap().is_some() {
+            inserted += 1;
+        }
+
+        assert_eq!(
+            inserted,
+            (MAX_TUPLE_SIZE + SLOT_SIZE_BYTES) / (100 + SLOT_SIZE_BYTES)
+        );
+        assert!(page.get_free_space().unwrap() < 100 + SLOT_SIZE_BYTES);
+        // Something small enough still fits
+        assert!(page.insert_tuple(&inline(&[1u8; 4])).unwrap().is_some());
+    }
+
+    #[rstest]
+    #[case(MAX_TUPLE_SIZE, true)]
+    #[case(MAX_TUPLE_SIZE + 1, false)]
+    fn test_insert_largest_tuple(#[case] size: usize, #[case] fits: bool) {
+        let pool_manager = create_testing_pool_manager(10);
+        let mut page =
+            WritableTablePage::initialize(pool_manager.new_page().unwrap(), None).unwrap();
+
+        let tuple = vec![3u8; size];
+        assert_eq!(page.insert_tuple(&inline(&tuple)).unwrap().is_some(), fits);
+        if fits {
+            assert_eq!(page.get_tuple(0).unwrap(), Some(StoredTuple::Inline(tuple)));
+            assert_eq!(page.get_free_space().unwrap(), 0);
+        }
+    }
+
+    #[rstest]
+    #[case(inline(b"tuple"))]
+    #[case(inline(&[0u8; MAX_TUPLE_SIZE]))]
+    #[case(StoredTuple::Overflow { first_page_id: 4, size: 100_000 })]
+    fn test_space_needed(#[case] tuple: StoredTuple) {
+        let pool_manager = create_testing_pool_manager(10);
+        let mut page =
+            WritableTablePage::initialize(pool_manager.new_page().unwrap(), None).unwrap();
+        let free_space = page.get_free_space().unwrap();
+
+        page.insert_tuple(&tuple).unwrap().unwrap();
+
+        assert_eq!(
+            free_space - page.get_free_space().unwrap(),
+            tuple.space_needed()
+        );
+    }
+
+    #[rstest]
+    #[case::smaller(b"tiny".to_vec())]
+    #[case::same_size(b"SECOND".to_vec())]
+    #[case::larger(b"a much longer second tuple".to_vec())]
+    fn test_update_tuple(#[case] new_tuple: Vec<u8>) {
+        let pool_manager = create_testing_pool_manager(10);
+        let mut page =
+            WritableTablePage::initialize(pool_manager.new_page().unwrap(), None).unwrap();
+        page.insert_tuple(&inline(b"first")).unwrap();
+        page.insert_tuple(&inline(b"second")).unwrap();
+        page.insert_tuple(&inline(b"third")).unwrap();
+
+        assert!(page.update_tuple(1, &inline(&new_tuple)).unwrap());
+
+        assert_eq!(page.get_tuple(0).unwrap(), Some(inline(b"first")));
+        assert_eq!(
+            page.get_tuple(1).unwrap(),
+            Some(StoredTuple::Inline(new_tuple))
+        );
+        assert_eq!(page.get_tuple(2).unwrap(), Some(inline(b"third")));
+    }
+
+    #[rstest]
+    fn test_update_tuple_not_enough_space() {
+        let pool_manager = create_testing_pool_manager(10);
+        let mut page =
+            WritableTablePage::initialize(pool_manager.new_page().unwrap(), None).unwrap();
+        page.insert_tuple(&inline(&[1u8; 10])).unwrap();
+        page.insert_tuple(&inline(&vec![2u8; MAX_TUPLE_SIZE - 100]))
+            .unwrap();
+
+        assert_eq!(
+            page.update_tuple(0, &inline(&[3u8; 100])),
+            Err(TablePageError::NotEnoughSpace)
+        );
+        assert_eq!(
+            page.get_tuple(0).unwrap(),
+            Some(StoredTuple::Inline(vec![1u8; 10]))
+        );
+    }
+
+    #[rstest]
+    #[case(vec![])]
+    #[case(b"tiny".to_vec())]
+    fn test_update_to_overflow_pointer_in_full_page(#[case] tuple: Vec<u8>) {
+        let pool_manager = create_testing_pool_manager(10);
+        let mut page =
+            WritableTablePage::initialize(pool_manager.new_page().unwrap(), None).unwrap();
+        while page.insert_tuple(&inline(&tuple)).unwrap().is_some() {}
+
+        let pointer = StoredTuple::Overflow {
+            first_page_id: 4,
+            size: 100_000,
+        };
+        assert!(page.update_tuple(1, &pointer).unwrap());
+
+        assert_eq!(page.get_tuple(1).unwrap(), Some(pointer));
+        assert_eq!(page.get_tuple(0).unwrap(), Some(inline(&tuple)));
+        assert_eq!(page.get_tuple(2).unwrap(), Some(inline(&tuple)));
+    }
+
+    #[rstest]
+    fn test_mark_delete() {
+        let pool_manager = create_testing_pool_manager(10);
+        let mut page =
+            WritableTablePage::initialize(pool_manager.new_page().unwrap(), None).unwrap();
+        page.insert_tuple(&inline(b"first")).unwrap();
+        page.insert_tuple(&inline(b"second")).unwrap();
+
+        assert!(page.mark_delete(0).unwrap());
+
+        assert!(page.is_deleted(0).unwrap());
+        assert!(!page.is_deleted(1).unwrap());
+        assert_eq!(page.get_tuple(0).unwrap(), None);
+        assert_eq!(page.get_tuple(1).unwrap(), Some(inline(b"second")));
+        // Marked tuples can't be marked again or updated
+        assert!(!page.mark_delete(0).unwrap());
+        assert!(!page.update_tuple(0, &inline(b"again")).unwrap());
+        // The slot is still taken
+        assert_eq!(page.insert_tuple(&inline(b"third")).unwrap(), Some(2));
+    }
+
+    #[rstest]
+    #[case::marked(true)]
+    #[case::unmarked(false)]
+    fn test_apply_delete(#[case] mark_first: bool) {
+        let pool_manager = create_testing_pool_manager(10);
+        let mut page =
+            WritableTablePage::initialize(pool_manager.new_page().unwrap(), None).unwrap();
+        page.insert_tuple(&inline(b"first")).unwrap();
+        page.insert_tuple(&inline(b"second")).unwrap();
+        if mark_first {
+            page.mark_delete(0).unwrap();
+        }
+
+        assert_eq!(page.apply_delete(0).unwrap(), Some(inline(b"first")));
+
+        assert!(!page.is_deleted(0).unwrap());
+        assert_eq!(page.get_tuple(0).unwrap(), None);
+        assert_eq!(page.get_tuple(1).unwrap(), Some(inline(b"second")));
+        assert_eq!(page.apply_delete(0).unwrap(), None);
+        assert!(!page.mark_delete(0).unwrap());
+        assert!(!page.update_tuple(0, &inline(b"again")).unwrap());
+    }
+
+    #[rstest]
+    fn test_insert_reuses_empty_slot() {
+        let pool_manager = create_testing_pool_manager(10);
+        let mut page =
+            WritableTablePage::initialize(pool_manager.new_page().unwrap(), None).unwrap();
+        page.insert_tuple(&inline(b"first")).unwrap();
+        page.insert_tuple(&inline(b"second")).unwrap();
+        page.apply_delete(0).unwrap();
+
+        assert_eq!(page.insert_tuple(&inline(b"third")).unwrap(), Some(0));
+
+        assert_eq!(page.get_slot_count().unwrap(), 2);
+        assert_eq!(page.get_tuple(0).unwrap(), Some(inline(b"third")));
+        assert_eq!(page.get_tuple(1).unwrap(), Some(inline(b"second")));
+    }
+
+    #[rstest]
+    fn test_overflow_pointer() {
+        let pool_manager = create_testing_pool_manager(10);
+        let mut page =
+            WritableTablePage::initialize(pool_manager.new_page().unwrap(), None).unwrap();
+        let pointer = StoredTuple::Overflow {
+            first_page_id: 12,
+            size: 100_000,
+        };
+
+        assert_eq!(page.insert_tuple(&inline(b"first")).unwrap(), Some(0));
+        assert_eq!(page.insert_tuple(&pointer).unwrap(), Some(1));
+
+        assert_eq!(page.get_tuple(0).unwrap(), Some(inline(b"first")));
+        assert_eq!(page.get_tuple(1).unwrap(), Some(pointer.clone()));
+        // Only the pointer takes up space in the page
+        assert_eq!(
+            page.get_free_space().unwrap(),
+            MAX_TUPLE_SIZE + SLOT_SIZE_BYTES
+                - 2 * SLOT_SIZE_BYTES
+                - 2 * OVERFLOW_POINTER_SIZE_BYTES
+        );
+
+        // Marking it deleted keeps it a pointer
+        assert!(page.mark_delete(1).unwrap());
+        assert!(page.is_deleted(1).unwrap());
+        assert_eq!(page.apply_delete(1).unwrap(), Some(pointer));
+    }
+
+    #[rstest]
+    fn test_update_between_inline_and_overflow() {
+        let pool_manager = create_testing_pool_manager(10);
+        let mut page =
+            WritableTablePage::initialize(pool_manager.new_page().unwrap(), None).unwrap();
+        let pointer = StoredTuple::Overflow {
+            first_page_id: 3,
+            size: 5000,
+        };
+        page.insert_tuple(&inline(b"first")).unwrap();
+
+        assert!(page.update_tuple(0, &pointer).unwrap());
+        assert_eq!(page.get_tuple(0).unwrap(), Some(pointer));
+
+        assert!(page.update_tuple(0, &inline(b"first again")).unwrap());
+        assert_eq!(page.get_tuple(0).unwrap(), Some(inline(b"first again")));
+    }
+
+    #[rstest]
+    fn test_compact_empty_page() {
+        let pool_manager = create_testing_pool_manager(10);
+        let mut page =
+            WritableTablePage::initialize(pool_manager.new_page().unwrap(), None).unwrap();
+
+        assert_eq!(page.compact().unwrap(), 0);
+        assert_eq!(
+            page.get_free_space().unwrap(),
+            MAX_TUPLE_SIZE + SLOT_SIZE_BYTES
+        );
+    }
+
+    #[rstest]
+    fn test_compact_reclaims_deleted_tuples() {
+        let pool_manager = create_testing_pool_manager(10);
+        let mut page =
+            WritableTablePage::initialize(pool_manager.new_page().unwrap(), None).unwrap();
+        for i in 0..5u8 {
+            page.insert_tuple(&inline(&[i; 100])).unwrap();
+        }
+        page.apply_delete(1).unwrap();
+        page.apply_delete(3).unwrap();
+        let free_space = page.get_free_space().unwrap();
+
+        assert_eq!(page.compact().unwrap(), 200);
+
+        assert_eq!(page.get_free_space().unwrap(), free_space + 200);
+        assert_eq!(page.get_slot_count().unwrap(), 5);
+        for i in [0u8, 2, 4] {
+            assert_eq!(page.get_tuple(i as usize).unwrap(), Some(inline(&[i; 100])));
+        }
+        assert_eq!(page.get_tuple(1).unwrap(), None);
+        assert_eq!(page.get_tuple(3).unwrap(), None);
+        // Nothing more to reclaim
+        assert_eq!(page.compact().unwrap(), 0);
+    }
+
+    #[rstest]
+    fn test_compact_reclaims_updated_tuples() {
+        let pool_manager = create_testing_pool_manager(10);
+        let mut page =
+            WritableTablePage::initialize(pool_manager.new_page().unwrap(), None).unwrap();
+        page.insert_tuple(&inline(&[1u8; 100])).unwrap();
+        page.insert_tuple(&inline(&[2u8; 100])).unwrap();
+        // Shrinks in place, then grows into the free space
+        page.update_tuple(0, &inline(&[3u8; 40])).unwrap();
+        page.update_tuple(1, &inline(&[4u8; 150])).unwrap();
+
+        assert_eq!(page.compact().unwrap(), 60 + 100);
+
+        assert_eq!(page.get_tuple(0).unwrap(), Some(inline(&[3u8; 40])));
+        assert_eq!(page.get_tuple(1).unwrap(), Some(inline(&[4u8; 150])));
+        assert_eq!(
+            page.get_free_space().unwrap(),
+            MAX_TUPLE_SIZE - SLOT_SIZE_BYTES - 40 - 150
+        );
+    }
+
+    #[rstest]
+    fn test_compact_keeps_marked_and_overflow_tuples() {
+        let pool_manager = create_testing_pool_manager(10);
+        let mut page =
+            WritableTablePage::initialize(pool_manager.new_page().unwrap(), None).unwrap();
+        let overflow = StoredTuple::Overflow {
+            first_page_id: 9,
+            size: 10_000,
+        };
+        page.insert_tuple(&inline(b"removed")).unwrap();
+        page.insert_tuple(&inline(b"marked")).unwrap();
+        page.insert_tuple(&overflow).unwrap();
+        page.apply_delete(0).unwrap();
+        page.mark_delete(1).unwrap();
+
+        assert_eq!(page.compact().unwrap(), OVERFLOW_POINTER_SIZE_BYTES);
+
+        assert!(page.is_deleted(1).unwrap());
+        assert_eq!(page.get_tuple(2).unwrap(), Some(overflow));
+        // A marked tuple's data is kept, in case the delete is rolled back
+        assert_eq!(page.apply_delete(1).unwrap(), Some(inline(b"marked")));
+    }
+
+    #[rstest]
+    fn test_compact_makes_room_for_insert() {
+        let pool_manager = create_testing_pool_manager(10);
+        let mut page =
+            WritableTablePage::initialize(pool_manager.new_page().unwrap(), None).unwrap();
+        while page.insert_tuple(&inline(&[7u8; 100])).unwrap().is_some() {}
+        page.apply_delete(0).unwrap();
+        page.apply_delete(1).unwrap();
+        assert_eq!(page.insert_tuple(&inline(&[8u8; 150])).unwrap(), None);
+
+        page.compact().unwrap();
+
+        assert_eq!(page.insert_tuple(&inline(&[8u8; 150])).unwrap(), Some(0));
+        assert_eq!(page.get_tuple(0).unwrap(), Some(inline(&[8u8; 150])));
+        assert_eq!(page.get_tuple(2).unwrap(), Some(inline(&[7u8; 100])));
+    }
+
+    #[rstest]
+    fn test_update_compacts_page() {
+        let pool_manager = create_testing_pool_manager(10);
+        let mut page =
+            WritableTablePage::initialize(pool_manager.new_page().unwrap(), None).unwrap();
+        while page.insert_tuple(&inline(&[7u8; 100])).unwrap().is_some() {}
+        page.apply_delete(0).unwrap();
+        let free_space = page.get_free_space().unwrap();
+
+        assert!(page.update_tuple(1, &inline(&[8u8; 150])).unwrap());
+
+        assert_eq!(page.get_tuple(1).unwrap(), Some(inline(&[8u8; 150])));
+        assert_eq!(page.get_free_space().unwrap(), free_space + 100 - 150);
+    }
+
+    #[rstest]
+    fn test_update_reuses_old_tuple_space() {
+        let pool_manager = create_testing_pool_manager(10);
+        let mut page =
+            WritableTablePage::initialize(pool_manager.new_page().unwrap(), None).unwrap();
+        while page.insert_tuple(&inline(&[7u8; 100])).unwrap().is_some() {}
+        let free_space = page.get_free_space().unwrap();
+
+        // Only fits in the space the old tuple frees up
+        let new_tuple = vec![8u8; 100 + free_space];
+        assert!(page.update_tuple(1, &inline(&new_tuple)).unwrap());
+
+        assert_eq!(page.get_tuple(1).unwrap(), Some(inline(&new_tuple)));
+        assert_eq!(page.get_tuple(0).unwrap(), Some(inline(&[7u8; 100])));
+        assert_eq!(page.get_tuple(2).unwrap(), Some(inline(&[7u8; 100])));
+        assert_eq!(page.get_free_space().unwrap(), 0);
+    }
+
+    #[rstest]
+    #[case(4096, 4056)]
+    #[case(65536, 65496)]
+    fn test_max_tuple_size(#[case] page_size: usize, #[case] expected: usize) {
+        assert_eq!(max_tuple_size(page_size), expected);
+    }
+
+    #[rstest]
+    fn test_large_page() {
+        let page_size = 65536;
+        let pool_manager = create_testing_pool_manager_with_page_size(10, page_size);
+        let mut page =
+            WritableTablePage::initialize(pool_manager.new_page().unwrap(), None).unwrap();
+        assert_eq!(
+            page.get_free_space().unwrap(),
+            max_tuple_size(page_size) + SLOT_SIZE_BYTES
+        );
+
+        let tuple = vec![3u8; max_tuple_size(page_size)];
+        assert_eq!(page.insert_tuple(&inline(&tuple)).unwrap(), Some(0));
+
+        assert_eq!(page.get_tuple(0).unwrap(), Some(inline(&tuple)));
+        assert_eq!(page.get_free_space().unwrap(), 0);
+    }
+
+    #[rstest]
+    fn test_read_only_page() {
+        let pool_manager = create_testing_pool_manager(10);
+        let page_id = {
+            let mut page =
+                WritableTablePage::initialize(pool_manager.new_page().unwrap(), Some(2)).unwrap();
+            page.set_next_page_id(Some(4)).unwrap();
+            page.insert_tuple(&inline(b"first")).unwrap();
+            page.insert_tuple(&inline(b"second")).unwrap();
+            page.mark_delete(1).unwrap();
+            page.get_page_id().unwrap()
+        };
+        pool_manager.unpin_page(page_id, true).unwrap();
+
+        let page = ReadOnlyTablePage::new(pool_manager.fetch_page(page_id).unwrap());
+
+        assert_eq!(page.get_page_id().unwrap(), page_id);
+        assert_eq!(page.get_prev_page_id().unwrap(), Some(2));
+        assert_eq!(page.get_next_page_id().unwrap(), Some(4));
+        assert_eq!(page.get_slot_count().unwrap(), 2);
+        assert_eq!(page.get_tuple(0).unwrap(), Some(inline(b"first")));
+        assert_eq!(page.get_tuple(1).unwrap(), None);
+        assert!(page.is_deleted(1).unwrap());
+        assert_eq!(
+            page.get_free_space().unwrap(),
+            MAX_TUPLE_SIZE + SLOT_SIZE_BYTES
+                - 2 * SLOT_SIZE_BYTES
+                - 2 * OVERFLOW_POINTER_SIZE_BYTES
+        );
+    }
+}
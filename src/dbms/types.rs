@@ -1,5 +1,91 @@
-pub const PAGE_SIZE: usize = 4096;
+/// Smallest page size a database can be created with
+pub const MIN_PAGE_SIZE: usize = 4096;
+/// Largest page size a database can be created with
+pub const MAX_PAGE_SIZE: usize = 65536;
+/// Page size of a database created without asking for one
+pub const DEFAULT_PAGE_SIZE: usize = MIN_PAGE_SIZE;
 
-pub type PageData = [u8; PAGE_SIZE];
+/// Whether a database can be created with the given page size, which has to
+/// be a power of two from `MIN_PAGE_SIZE` to `MAX_PAGE_SIZE`
+pub fn is_valid_page_size(page_size: usize) -> bool {
+    page_size.is_power_of_two() && (MIN_PAGE_SIZE..=MAX_PAGE_SIZE).contains(&page_size)
+}
 
-pub type PageId = u32;
+/// A page's contents, as long as the database's page size
+pub type PageData = Vec<u8>;
+
+pub type PageId = u64;
+
+/// Stored in place of a page ID to mean there's no page, e.g. the root of an
+/// empty tree
+pub const INVALID_PAGE_ID: PageId = PageId::MAX;
+
+/// Identifies one of the files a database's pages are kept in
+pub type FileId = u8;
+
+/// The file every database has, holding pages not put anywhere else and the
+/// log
+pub const SYSTEM_FILE_ID: FileId = 0;
+
+/// A page ID is the ID of the file the page is in, in its top bits, and the
+/// page's number within that file, in the rest. Pages in the system file
+/// have the same ID as their page number.
+pub const PAGE_NUMBER_BITS: u32 = PageId::BITS - FileId::BITS;
+/// The highest page number a file can have. Page numbers past it would
+/// spill over into the file ID.
+pub const MAX_PAGE_NUMBER: PageId = (1 << PAGE_NUMBER_BITS) - 1;
+/// The highest file ID a database can have. File IDs past it would make page
+/// IDs that could be `INVALID_PAGE_ID`.
+pub const MAX_FILE_ID: FileId = FileId::MAX - 1;
+
+/// The ID of the given page of the given file
+pub fn make_page_id(file_id: FileId, page_number: PageId) -> PageId {
+    debug_assert!(page_number <= MAX_PAGE_NUMBER);
+    (PageId::from(file_id) << PAGE_NUMBER_BITS) | page_number
+}
+
+/// The file a page is in
+pub fn page_file_id(page_id: PageId) -> FileId {
+    (page_id >> PAGE_NUMBER_BITS) as FileId
+}
+
+/// A page's number within its file
+pub fn page_number(page_id: PageId) -> PageId {
+    page_id & MAX_PAGE_NUMBER
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+
+    #[rstest]
+    #[case(SYSTEM_FILE_ID, 0, 0)]
+    #[case(SYSTEM_FILE_ID, 12, 12)]
+    #[case(1, 0, 1 << 56)]
+    #[case(3, 7, (3 << 56) | 7)]
+    #[case(MAX_FILE_ID, MAX_PAGE_NUMBER, INVALID_PAGE_ID - (1 << 56))]
+    fn test_page_id_round_trip(
+        #[case] file_id: FileId,
+        #[case] page_number: PageId,
+        #[case] expected: PageId,
+    ) {
+        let page_id = make_page_id(file_id, page_number);
+
+        assert_eq!(page_id, expected);
+        assert_eq!(page_file_id(page_id), file_id);
+        assert_eq!(super::page_number(page_id), page_number);
+    }
+
+    #[rstest]
+    #[case(MIN_PAGE_SIZE, true)]
+    #[case(8192, true)]
+    #[case(MAX_PAGE_SIZE, true)]
+    #[case(0, false)]
+    #[case(2048, false)]
+    #[case(6144, false)]
+    #[case(MAX_PAGE_SIZE * 2, false)]
+    fn test_is_valid_page_size(#[case] page_size: usize, #[case] expected: bool) {
+        assert_eq!(is_valid_page_size(page_size), expected);
+    }
+}
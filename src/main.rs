@@ -1,3 +1,5 @@
+// The DBMS components aren't wired up to the binary yet
+#[allow(dead_code, unused_imports)]
 mod dbms;
 
 fn main() {